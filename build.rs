@@ -0,0 +1,21 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    // `sha256_asm` marks a build hashing with the assembly SHA-256: the `asm` feature on a
+    // target sha2-asm supports, so elsewhere the feature quietly leaves the portable one
+    println!("cargo:rustc-check-cfg=cfg(sha256_asm)");
+    let arch = std::env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+    let os = std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    if std::env::var_os("CARGO_FEATURE_ASM").is_some() && ["x86", "x86_64", "aarch64"].contains(&arch.as_str()) && os != "windows" {
+        println!("cargo:rustc-cfg=sha256_asm");
+    }
+    // The gRPC API is generated from proto/miner.proto; protox compiles it without protoc
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/miner.proto");
+        let descriptors = protox::compile(["proto/miner.proto"], ["proto"])
+            .expect("Failed to compile proto/miner.proto");
+        tonic_prost_build::configure()
+            .compile_fds(descriptors)
+            .expect("Failed to generate the gRPC service");
+    }
+}
@@ -0,0 +1,150 @@
+//! Backing off while someone is using the machine
+//!
+//! Two signs of use are watched: recent keyboard input on a terminal, which is the idle
+//! time `w` shows, and a load average above what the hashing threads account for. While
+//! either is seen, and for `idle` after the last sighting, the miner drops to
+//! `busy_threads`, pausing at 0.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+use tracing::{info, Instrument, info_span};
+
+use crate::control::Control;
+use crate::miner::MiningConfig;
+use crate::tasks::{Stage, Tasks};
+
+/// How often activity is looked for
+pub const ACTIVITY_CHECK_SECS: u64 = 10;
+
+/// Activity-aware mining, as configured in `[activity]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActivitySettings {
+    /// How long the machine must go unused before hashing ramps back up
+    pub idle: Duration,
+    /// Load average beyond the hashing threads' own that counts as use; `None` looks at
+    /// input only. The average takes a minute or so to settle after threads stop.
+    pub max_load: Option<f64>,
+    /// Threads to keep hashing on while the machine is in use; 0 pauses
+    pub busy_threads: usize,
+}
+
+/// One look at the machine
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ActivitySample {
+    /// Time since the last keystroke on any terminal, if there are terminals to look at
+    pub input_idle: Option<Duration>,
+    /// One-minute load average, where the platform has one
+    pub load: Option<f64>,
+}
+
+/// Remembers when the machine was last in use
+#[derive(Debug, Clone, Default)]
+pub struct ActivityTracker {
+    last_used: Option<Instant>,
+}
+
+impl ActivityTracker {
+    /// Take a sample at `now`, with `hashing_threads` running, and say whether the
+    /// machine counts as in use
+    pub fn observe(&mut self, settings: &ActivitySettings, now: Instant, sample: ActivitySample, hashing_threads: usize) -> bool {
+        if let Some(input_idle) = sample.input_idle {
+            let input_at = now.checked_sub(input_idle).unwrap_or(now);
+            self.last_used = Some(self.last_used.map_or(input_at, |last| last.max(input_at)));
+        }
+        let extra_load = sample.load.map(|load| load - hashing_threads as f64);
+        if let (Some(extra_load), Some(max_load)) = (extra_load, settings.max_load) {
+            if extra_load > max_load {
+                self.last_used = Some(now);
+            }
+        }
+        self.last_used.is_some_and(|last| now.duration_since(last) < settings.idle)
+    }
+}
+
+/// The most recent read of a terminal, as `w` works out idle time
+#[cfg(unix)]
+fn input_idle() -> Option<Duration> {
+    use std::os::unix::fs::MetadataExt;
+
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).ok()?.as_secs() as i64;
+    let terminals = std::fs::read_dir("/dev/pts").into_iter().flatten().flatten().map(|entry| entry.path())
+        .chain(std::fs::read_dir("/dev").into_iter().flatten().flatten().map(|entry| entry.path()).filter(|path| {
+            path.file_name().and_then(|name| name.to_str()).is_some_and(|name| {
+                name.strip_prefix("tty").is_some_and(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+            })
+        }));
+    terminals
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|metadata| Duration::from_secs(now.saturating_sub(metadata.atime()).max(0) as u64))
+        .min()
+}
+
+#[cfg(not(unix))]
+fn input_idle() -> Option<Duration> {
+    None
+}
+
+fn load_average() -> Option<f64> {
+    std::fs::read_to_string("/proc/loadavg").ok()?.split_whitespace().next()?.parse().ok()
+}
+
+pub fn sample() -> ActivitySample {
+    ActivitySample { input_idle: input_idle(), load: load_average() }
+}
+
+/// Watch for use of the machine and scale hashing down while it lasts
+pub fn start(settings: ActivitySettings, config: Arc<Mutex<MiningConfig>>, control: Control, tasks: &mut Tasks) {
+    info!(
+        event = "activity_watch",
+        idle_secs = settings.idle.as_secs(),
+        max_load = settings.max_load,
+        busy_threads = settings.busy_threads,
+        "Backing off to {} thread(s) while the machine is in use, until it has been idle {}s",
+        settings.busy_threads,
+        settings.idle.as_secs()
+    );
+    tasks.spawn(Stage::Api, "activity", watch(settings, config, control).instrument(info_span!("activity")));
+}
+
+async fn watch(settings: ActivitySettings, config: Arc<Mutex<MiningConfig>>, control: Control) {
+    let mut tracker = ActivityTracker::default();
+    // The thread count to go back to, while backed off
+    let mut backed_off: Option<usize> = None;
+    let mut interval = tokio::time::interval(Duration::from_secs(ACTIVITY_CHECK_SECS));
+    loop {
+        interval.tick().await;
+        let (threads, paused) = {
+            let config_guard = config.lock().unwrap();
+            (config_guard.threads, config_guard.paused)
+        };
+        let hashing = if paused { 0 } else { threads };
+        let in_use = tracker.observe(&settings, Instant::now(), sample(), hashing);
+        match backed_off {
+            None if in_use && !paused => {
+                if settings.busy_threads == 0 {
+                    control.pause("machine in use");
+                } else if settings.busy_threads < threads {
+                    control.set_threads(settings.busy_threads);
+                } else {
+                    continue;
+                }
+                backed_off = Some(threads);
+                info!(event = "activity_backoff", threads = settings.busy_threads, "Machine in use, hashing on {} thread(s)", settings.busy_threads);
+            }
+            Some(previous) if !in_use => {
+                backed_off = None;
+                if settings.busy_threads == 0 {
+                    // Nothing to resume if someone already did
+                    if !control.resume("machine idle") {
+                        continue;
+                    }
+                } else {
+                    control.set_threads(previous);
+                }
+                info!(event = "activity_resume", threads = previous, "Machine idle for {}s, hashing on {} thread(s)", settings.idle.as_secs(), previous);
+            }
+            _ => {}
+        }
+    }
+}
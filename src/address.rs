@@ -0,0 +1,72 @@
+use std::fmt;
+use std::str::FromStr;
+use anyhow::{Result, bail};
+use bitcoin::address::NetworkUnchecked;
+use bitcoin::{Address, Network};
+
+/// Output type of a mainnet payout address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    /// A future SegWit version, valid but without a named output type yet
+    Witness(u8),
+}
+
+impl fmt::Display for AddressType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AddressType::P2pkh => write!(f, "P2PKH (legacy)"),
+            AddressType::P2sh => write!(f, "P2SH (script hash)"),
+            AddressType::P2wpkh => write!(f, "P2WPKH (native SegWit)"),
+            AddressType::P2wsh => write!(f, "P2WSH (SegWit script hash)"),
+            AddressType::P2tr => write!(f, "P2TR (Taproot)"),
+            AddressType::Witness(version) => write!(f, "SegWit v{}", version),
+        }
+    }
+}
+
+/// Check a mainnet Bitcoin address, including its checksum, and detect its type
+///
+/// Legacy addresses use Base58Check; SegWit v0 addresses use bech32 and v1+ (Taproot)
+/// use bech32m (BIP 173 / BIP 350). Testnet addresses are rejected because rewards
+/// paid to them would be worthless.
+pub fn parse(address: &str) -> Result<AddressType> {
+    let address = decode(address)?;
+    Ok(match address.address_type() {
+        Some(bitcoin::AddressType::P2pkh) => AddressType::P2pkh,
+        Some(bitcoin::AddressType::P2sh) => AddressType::P2sh,
+        Some(bitcoin::AddressType::P2wpkh) => AddressType::P2wpkh,
+        Some(bitcoin::AddressType::P2wsh) => AddressType::P2wsh,
+        Some(bitcoin::AddressType::P2tr) => AddressType::P2tr,
+        // Anything else that decodes is a witness program of some other version
+        _ => match address.script_pubkey().witness_version() {
+            Some(version) => AddressType::Witness(version.to_num()),
+            None => bail!("unsupported address type"),
+        },
+    })
+}
+
+/// The output script that pays `address`, which must pass `parse`
+pub fn script_pubkey(address: &str) -> Result<Vec<u8>> {
+    Ok(decode(address)?.script_pubkey().into_bytes())
+}
+
+/// A mainnet address, with its checksum, case and witness program checked
+fn decode(address: &str) -> Result<Address> {
+    let address = address.trim();
+    if address.is_empty() {
+        bail!("address is empty");
+    }
+    let unchecked = match Address::<NetworkUnchecked>::from_str(address) {
+        Ok(unchecked) => unchecked,
+        Err(e) => bail!("{}", e),
+    };
+    if !unchecked.is_valid_for_network(Network::Bitcoin) {
+        bail!("testnet address; a mainnet address is required");
+    }
+    Ok(unchecked.assume_checked())
+}
@@ -0,0 +1,154 @@
+use std::fmt;
+use std::future::Future;
+use anyhow::{Result, Context};
+use tracing::info;
+
+use crate::exit_code::{ExitCode, fatal};
+use crate::session::{SessionMachine, SessionState};
+use crate::stratum::{MiningJob, StratumClient, StratumError};
+use crate::work::EXTRANONCE2_SIZE_BYTES;
+
+/// Something to hash: a job and the coinbase prefix assigned to this connection
+#[derive(Debug, Clone)]
+pub struct WorkTemplate {
+    pub job: MiningJob,
+    pub extranonce1: String,
+    /// Size of the extranonce2 the pool expects, in bytes
+    pub extranonce2_size: usize,
+}
+
+/// A header that meets the network target, as the pool needs it to rebuild the block
+#[derive(Debug, Clone)]
+pub struct Solution {
+    pub job_id: String,
+    pub extranonce2: String,
+    pub ntime: String,
+    pub nonce: String,
+}
+
+/// The pool's verdict on a solution
+#[derive(Debug, Clone)]
+pub struct SubmitResult {
+    pub accepted: bool,
+    /// Why the pool rejected it, if it said
+    pub reason: Option<String>,
+    /// The reply as received, kept in the block archive
+    pub reply: String,
+}
+
+impl fmt::Display for SubmitResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reply)
+    }
+}
+
+/// Source of work and sink for solutions, one per pool protocol
+///
+/// The mining core only sees this trait, so a protocol is added by implementing it.
+/// Stratum v1 (`StratumV1`) is the only backend so far; Stratum v2 and bitcoind's
+/// getblocktemplate would each be another implementation.
+pub trait PoolBackend: Send {
+    /// Protocol name for logs, e.g. `stratum-v1`
+    fn protocol(&self) -> &'static str;
+
+    /// Wait for the next work template from the pool
+    fn next_work(&mut self) -> impl Future<Output = Result<WorkTemplate>> + Send;
+
+    /// Jobs the pool has sent that `next_work` hasn't returned yet, oldest first
+    fn pending_jobs(&self) -> Vec<MiningJob>;
+
+    /// The share difficulty the pool asked for, if it said
+    fn share_difficulty(&self) -> Option<f64>;
+
+    /// Hand a solution to the pool and wait for its verdict
+    fn submit(&mut self, solution: &Solution) -> impl Future<Output = Result<SubmitResult>> + Send;
+
+    /// Exchange a message with the pool so an idle session isn't dropped, failing if the
+    /// pool doesn't answer
+    fn keepalive(&mut self) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Stratum v1 pool, e.g. solo.ckpool.org
+pub struct StratumV1 {
+    client: StratumClient,
+    user: String,
+    extranonce1: String,
+    extranonce2_size: usize,
+}
+
+impl StratumV1 {
+    /// Connect, subscribe and authorize as `user` (the payout address for solo pools),
+    /// taking `session` from `Connecting` to `Authorized`
+    ///
+    /// Errors are tagged `ExitCode::PoolUnreachable` when the pool can't be reached and
+    /// `ExitCode::AuthorizationRejected` when it refuses `user`.
+    pub async fn connect(pool: &str, user: &str, session: &mut SessionMachine) -> Result<Self> {
+        session.transition(SessionState::Connecting)?;
+        let mut client = session.within(StratumClient::connect(pool)).await
+            .with_context(|| format!("Failed to connect to {}", pool))
+            .map_err(fatal(ExitCode::PoolUnreachable))?;
+
+        info!(event = "subscribing", "Subscribing to mining notifications...");
+        let subscription = session.within(client.subscribe()).await?;
+        info!(event = "subscribed", extranonce1 = %subscription.extranonce1, "Subscription successful");
+        session.transition(SessionState::Subscribed)?;
+
+        let authorization = session.within(client.authorize(user, "password")).await?;
+        if !authorization.accepted() {
+            return Err(StratumError::AuthorizationRejected(authorization.reason()))
+                .map_err(fatal(ExitCode::AuthorizationRejected));
+        }
+        session.transition(SessionState::Authorized)?;
+
+        Ok(Self {
+            client,
+            user: user.to_string(),
+            extranonce1: subscription.extranonce1,
+            // Pools that don't say get the size the local workers use
+            extranonce2_size: match subscription.extranonce2_size {
+                0 => EXTRANONCE2_SIZE_BYTES,
+                size => size as usize,
+            },
+        })
+    }
+}
+
+impl PoolBackend for StratumV1 {
+    fn protocol(&self) -> &'static str {
+        "stratum-v1"
+    }
+
+    async fn next_work(&mut self) -> Result<WorkTemplate> {
+        Ok(WorkTemplate {
+            job: self.client.next_job().await?,
+            extranonce1: self.extranonce1.clone(),
+            extranonce2_size: self.extranonce2_size,
+        })
+    }
+
+    fn pending_jobs(&self) -> Vec<MiningJob> {
+        self.client.queued_jobs()
+    }
+
+    fn share_difficulty(&self) -> Option<f64> {
+        self.client.difficulty()
+    }
+
+    async fn submit(&mut self, solution: &Solution) -> Result<SubmitResult> {
+        let response = self.client
+            .submit(&self.user, &solution.job_id, &solution.extranonce2, &solution.ntime, &solution.nonce)
+            .await?;
+        Ok(SubmitResult {
+            accepted: response.accepted(),
+            reason: Some(response.reason()).filter(|_| !response.error.is_null()),
+            reply: response.to_string(),
+        })
+    }
+
+    /// Stratum v1 has no ping, so authorize the same user again: every pool answers it
+    /// and it changes nothing on the session
+    async fn keepalive(&mut self) -> Result<()> {
+        self.client.authorize(&self.user, "password").await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+//! Pool connection traffic, and keeping it down on metered links
+//!
+//! Every byte written to or read from a Stratum socket is counted. With `low_bandwidth`
+//! set, the miner also cuts the traffic it can do without: the latency pings are spaced
+//! out, the block explorer is asked for the height less often, and the per-job difficulty
+//! cross-check is skipped.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// Seconds between latency pings in low-bandwidth mode; still often enough to keep a
+/// paused session alive
+pub const LOW_BANDWIDTH_PING_INTERVAL_SECS: u64 = 300;
+/// Seconds between block explorer height checks in low-bandwidth mode, where the pool's
+/// jobs are relied on to tell of new blocks
+pub const LOW_BANDWIDTH_HEIGHT_CHECK_SECS: u64 = 300;
+
+static SENT: AtomicU64 = AtomicU64::new(0);
+static RECEIVED: AtomicU64 = AtomicU64::new(0);
+
+/// Count bytes written to a pool connection
+pub fn record_sent(bytes: usize) {
+    SENT.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+/// Count bytes read from a pool connection
+pub fn record_received(bytes: usize) {
+    RECEIVED.fetch_add(bytes as u64, Ordering::Relaxed);
+}
+
+/// Everything sent and received on pool connections since the process started
+pub fn totals() -> Traffic {
+    Traffic {
+        sent_bytes: SENT.load(Ordering::Relaxed),
+        received_bytes: RECEIVED.load(Ordering::Relaxed),
+    }
+}
+
+/// Bytes sent to and received from the pool
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Traffic {
+    pub sent_bytes: u64,
+    pub received_bytes: u64,
+}
+
+impl Traffic {
+    /// The traffic since `earlier` was taken
+    pub fn since(self, earlier: Traffic) -> Traffic {
+        Traffic {
+            sent_bytes: self.sent_bytes.saturating_sub(earlier.sent_bytes),
+            received_bytes: self.received_bytes.saturating_sub(earlier.received_bytes),
+        }
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.sent_bytes + self.received_bytes
+    }
+
+    /// e.g. `12.3 KB sent, 45.6 KB received (1.2 MB/day)`, the daily figure at the rate
+    /// over `elapsed`
+    pub fn summary(&self, elapsed: Duration) -> String {
+        let per_day = self.total_bytes() as f64 * 86_400.0 / elapsed.as_secs_f64().max(1.0);
+        format!(
+            "{} sent, {} received ({}/day)",
+            format_bytes(self.sent_bytes),
+            format_bytes(self.received_bytes),
+            format_bytes(per_day as u64)
+        )
+    }
+}
+
+/// Decimal units, as carriers bill them: `950 B`, `12.3 KB`, `4.56 MB`
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    if value >= 100.0 {
+        format!("{:.0} {}", value, UNITS[unit])
+    } else if value >= 10.0 {
+        format!("{:.1} {}", value, UNITS[unit])
+    } else {
+        format!("{:.2} {}", value, UNITS[unit])
+    }
+}
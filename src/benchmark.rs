@@ -0,0 +1,114 @@
+//! Machine-readable benchmark reports
+//!
+//! `benchmark --report` writes one as JSON, so hash rates can be compared across machines
+//! and builds, and a regression spotted locally without any CI.
+
+use std::path::Path;
+use anyhow::{Result, Context};
+use serde::{Deserialize, Serialize};
+
+use crate::cluster;
+
+/// The machine a benchmark ran on
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HostInfo {
+    pub hostname: String,
+    pub os: String,
+    pub arch: String,
+    /// As `/proc/cpuinfo` names it, where there is one
+    pub cpu_model: Option<String>,
+    pub logical_cpus: usize,
+}
+
+impl HostInfo {
+    pub fn detect() -> Self {
+        let cpu_model = std::fs::read_to_string("/proc/cpuinfo").ok().and_then(|cpuinfo| {
+            // x86 says "model name"; ARM boards say "Hardware" or "Model"
+            ["model name", "Hardware", "Model"].iter().find_map(|key| {
+                cpuinfo.lines().find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    (name.trim() == *key && !value.trim().is_empty()).then(|| value.trim().to_string())
+                })
+            })
+        });
+        Self {
+            hostname: cluster::host_name(),
+            os: std::env::consts::OS.to_string(),
+            arch: std::env::consts::ARCH.to_string(),
+            cpu_model,
+            logical_cpus: std::thread::available_parallelism().map_or(1, |cpus| cpus.get()),
+        }
+    }
+}
+
+/// How steady a series of hash rate samples was
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateSpread {
+    pub mean: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Population standard deviation
+    pub stddev: f64,
+    /// `stddev / mean`, comparable between machines of different speeds
+    pub coefficient_of_variation: f64,
+}
+
+impl RateSpread {
+    /// `None` without samples
+    pub fn of(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+        let variance = samples.iter().map(|sample| (sample - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+        let stddev = variance.sqrt();
+        Some(Self {
+            mean,
+            min: samples.iter().copied().fold(f64::INFINITY, f64::min),
+            max: samples.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            stddev,
+            coefficient_of_variation: if mean > 0.0 { stddev / mean } else { 0.0 },
+        })
+    }
+}
+
+/// Single-thread double SHA-256 rates of the implementations built in
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Sha256Rates {
+    pub portable: f64,
+    /// Only with the `asm` feature on a target it supports
+    pub asm: Option<f64>,
+}
+
+/// Everything one `benchmark` run measured
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkReport {
+    /// Miner version
+    pub version: String,
+    /// Unix time the run finished
+    pub timestamp: u64,
+    pub host: HostInfo,
+    /// The SHA-256 implementation mining uses, `portable` or `asm`
+    pub sha256: String,
+    pub threads: usize,
+    pub duration_secs: f64,
+    pub hashes: u64,
+    pub hashrate: f64,
+    pub hashrate_per_thread: f64,
+    /// Hash rate over each second of the run
+    pub samples: Vec<f64>,
+    pub spread: Option<RateSpread>,
+    pub sha256_rates: Sha256Rates,
+}
+
+impl BenchmarkReport {
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            if !dir.as_os_str().is_empty() {
+                std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+            }
+        }
+        std::fs::write(path, serde_json::to_string_pretty(self)? + "\n")
+            .with_context(|| format!("Failed to write benchmark report {}", path.display()))
+    }
+}
@@ -0,0 +1,166 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use anyhow::Result;
+use serde::Serialize;
+use tracing::info;
+
+use crate::coinbase::{WITNESS_COMMITMENT_PREFIX, WITNESS_RESERVED_VALUE};
+use crate::log_rotation::{RotatingFile, RotationPolicy};
+use crate::paths;
+use crate::stats::format_difficulty;
+use crate::stratum::MiningJob;
+use crate::work::{coinbase_bytes, HashingError};
+
+pub const BLOCKS_LOG_FILE_NAME: &str = "blocks_found.log";
+pub const BLOCKS_JSON_FILE_NAME: &str = "blocks_found.jsonl";
+
+/// A found block put together from the job, as far as the pool's job allows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembledBlock {
+    /// The coinbase transaction, with its witness if it commits to one
+    pub coinbase: Vec<u8>,
+    /// The whole serialized block, ready for `submitblock`, when the coinbase is its only
+    /// transaction; Stratum pools send only the merkle branch of the others
+    pub block: Option<Vec<u8>>,
+}
+
+/// Serialize the block `header` was found for
+pub fn assemble_block(job: &MiningJob, extranonce1: &str, extranonce2: &str, header: &[u8]) -> Result<AssembledBlock, HashingError> {
+    let stripped = coinbase_bytes(job, extranonce1, extranonce2)?;
+    let commits_to_witness = stripped.windows(WITNESS_COMMITMENT_PREFIX.len()).any(|window| window == WITNESS_COMMITMENT_PREFIX);
+    // A block with a witness commitment is only valid if the coinbase carries the
+    // 32-byte witness reserved value, which the pool leaves out of coinb1/coinb2
+    let coinbase = if commits_to_witness && stripped.len() > 8 {
+        let (version, rest) = stripped.split_at(4);
+        let (body, lock_time) = rest.split_at(rest.len() - 4);
+        let mut coinbase = version.to_vec();
+        coinbase.extend_from_slice(&[0x00, 0x01]);
+        coinbase.extend_from_slice(body);
+        coinbase.extend_from_slice(&[0x01, 0x20]);
+        coinbase.extend_from_slice(&WITNESS_RESERVED_VALUE);
+        coinbase.extend_from_slice(lock_time);
+        coinbase
+    } else {
+        stripped
+    };
+    let block = job.merkle_branch.is_empty().then(|| {
+        let mut block = header.to_vec();
+        // One transaction
+        block.push(0x01);
+        block.extend_from_slice(&coinbase);
+        block
+    });
+    Ok(AssembledBlock { coinbase, block })
+}
+
+/// Everything needed to reconstruct and resubmit a found block
+#[derive(Debug, Serialize)]
+pub struct BlockRecord {
+    /// Unix time in seconds
+    pub timestamp: u64,
+    /// `timestamp` in the configured timestamp format
+    pub time: String,
+    pub height: u64,
+    pub address: String,
+    pub hash: String,
+    pub target: String,
+    /// Difficulty the hash reached
+    pub difficulty: f64,
+    pub network_difficulty: f64,
+    pub nonce: String,
+    pub extranonce1: String,
+    pub extranonce2: String,
+    pub merkle_root: String,
+    pub header: String,
+    /// The serialized coinbase transaction
+    pub coinbase: String,
+    /// The whole serialized block, if the pool's job had every transaction in it
+    pub block: Option<String>,
+    pub job: MiningJob,
+    /// `accept`, or `reject:` and the reasons the local pool checks gave
+    pub predicted_response: String,
+    pub pool_response: String,
+}
+
+impl BlockRecord {
+    pub fn to_text(&self) -> String {
+        format!(
+            "[!] VALID BLOCK HASH DISCOVERED!\n\
+            [*] Hash: {}\n\
+            [*] Target: {}\n\
+            [*] Difficulty: {} (network {})\n\
+            [*] Nonce: {}\n\
+            [*] Address: {}\n\
+            [*] Time: {} (unix {})\n\
+            [*] Height: {}\n\
+            [*] Header: {}\n\
+            [*] Merkle root: {}\n\
+            [*] Coinbase: {}\n\
+            [*] Block: {}\n\
+            [*] Extranonce1: {}\n\
+            [*] Extranonce2: {}\n\
+            [*] Job id: {}\n\
+            [*] Prevhash: {}\n\
+            [*] Version: {}\n\
+            [*] nBits: {}\n\
+            [*] nTime: {}\n\
+            [*] Clean jobs: {}\n\
+            [*] Coinb1: {}\n\
+            [*] Coinb2: {}\n\
+            [*] Merkle branch: [{}]\n\
+            [*] Predicted response: {}\n\
+            [*] Pool response: {}\n",
+            self.hash, self.target, format_difficulty(self.difficulty), format_difficulty(self.network_difficulty), self.nonce, self.address, self.time, self.timestamp,
+            self.height, self.header, self.merkle_root, self.coinbase,
+            self.block.as_deref().unwrap_or("not available, the pool holds the other transactions"), self.extranonce1, self.extranonce2,
+            self.job.job_id, self.job.prevhash, self.job.version, self.job.nbits, self.job.ntime,
+            self.job.clean_jobs, self.job.coinb1, self.job.coinb2,
+            self.job.merkle_branch.join(", "), self.predicted_response, self.pool_response
+        )
+    }
+}
+
+/// Where the serialized block with this hash is written
+pub fn block_file(blocks_dir: &Path, hash: &str) -> PathBuf {
+    blocks_dir.join(format!("block_{}.hex", hash))
+}
+
+/// Log block found information to file, as readable text and as a JSON line, and write
+/// the serialized block to its own file when it could be put together
+///
+/// The logs rotate by `rotation` but no rotated file is ever deleted: they may be the only
+/// record of a block.
+pub fn log_block_found(record: &BlockRecord, blocks_dir: &Path, rotation: RotationPolicy) -> Result<()> {
+    paths::ensure_dir(blocks_dir, "blocks_dir")?;
+    
+    let log_file = blocks_dir.join(BLOCKS_LOG_FILE_NAME);
+    let mut file = RotatingFile::open_unpruned(&log_file, rotation)?;
+    
+    // Single write so an entry never straddles a rotation
+    file.write_all(format!("{}\n{}\n", record.to_text(), "=".repeat(80)).as_bytes())?;
+    file.flush()?;
+
+    let json_file = blocks_dir.join(BLOCKS_JSON_FILE_NAME);
+    let mut file = RotatingFile::open_unpruned(&json_file, rotation)?;
+    file.write_all(format!("{}\n", serde_json::to_string(record)?).as_bytes())?;
+    file.flush()?;
+    
+    info!("Block logged to: {} and {}", log_file.display(), json_file.display());
+
+    match &record.block {
+        Some(block) => {
+            let block_file = block_file(blocks_dir, &record.hash);
+            std::fs::write(&block_file, format!("{}\n", block))?;
+            info!(
+                event = "block_archived",
+                path = %block_file.display(),
+                "Full block saved to {}; rebroadcast it with `bitcoin-cli submitblock $(cat {})`", block_file.display(), block_file.display()
+            );
+        }
+        None => info!(
+            event = "block_archived",
+            "The pool's job only carried the merkle branch of the block's other transactions, so the header and coinbase are archived but not the full block"
+        ),
+    }
+    Ok(())
+}
@@ -0,0 +1,162 @@
+//! The bitcoin price, for giving block rewards a fiat value
+//!
+//! Entirely optional: with `[btc_price] source` set, the block-found, session summary and
+//! weekly report notifications give the reward's value in `currency` as well. The price is
+//! fetched when a notification needs it and kept for ten minutes. A failed fetch leaves
+//! the fiat value out rather than holding up the message.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+use tracing::warn;
+
+pub const DEFAULT_CURRENCY: &str = "usd";
+/// How long a fetched price is used before it's fetched again
+pub const PRICE_CACHE_SECS: u64 = 600;
+/// Kept short: the session summary has to go out within the shutdown timeout
+const PRICE_TIMEOUT_SECS: u64 = 5;
+const SATS_PER_BTC: f64 = 100_000_000.0;
+/// The currencies mempool.space quotes
+const MEMPOOL_CURRENCIES: [&str; 7] = ["usd", "eur", "gbp", "cad", "chf", "aud", "jpy"];
+
+/// Where the price comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PriceSource {
+    /// CoinGecko's public simple price API, which quotes most currencies
+    CoinGecko,
+    /// Coinbase's spot price
+    Coinbase,
+    /// mempool.space's price feed, in a handful of currencies
+    Mempool,
+}
+
+impl PriceSource {
+    /// The source called `name`; `None` for an empty name or `none`
+    pub fn parse(name: &str) -> Result<Option<Self>> {
+        let source = match name.trim().to_lowercase().as_str() {
+            "" | "none" => return Ok(None),
+            "coingecko" => PriceSource::CoinGecko,
+            "coinbase" => PriceSource::Coinbase,
+            "mempool" => PriceSource::Mempool,
+            other => bail!("Unknown price source '{}' (expected 'coingecko', 'coinbase' or 'mempool')", other),
+        };
+        Ok(Some(source))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PriceSource::CoinGecko => "coingecko",
+            PriceSource::Coinbase => "coinbase",
+            PriceSource::Mempool => "mempool",
+        }
+    }
+
+    /// The API that gives the price in `currency`
+    pub fn url(&self, currency: &str) -> String {
+        match self {
+            PriceSource::CoinGecko => format!("https://api.coingecko.com/api/v3/simple/price?ids=bitcoin&vs_currencies={}", currency),
+            PriceSource::Coinbase => format!("https://api.coinbase.com/v2/prices/BTC-{}/spot", currency.to_uppercase()),
+            PriceSource::Mempool => "https://mempool.space/api/v1/prices".to_string(),
+        }
+    }
+
+    /// The price of one bitcoin in `currency` from the API's response
+    pub fn parse_price(&self, response: &Value, currency: &str) -> Result<f64> {
+        let price = match self {
+            PriceSource::CoinGecko => response["bitcoin"][currency].as_f64(),
+            // Coinbase quotes the amount as a string
+            PriceSource::Coinbase => response["data"]["amount"].as_str().and_then(|amount| amount.parse().ok()),
+            PriceSource::Mempool => response[currency.to_uppercase()].as_f64(),
+        };
+        match price {
+            Some(price) if price > 0.0 => Ok(price),
+            _ => bail!("{}: no bitcoin price in {}", self.name(), currency.to_uppercase()),
+        }
+    }
+}
+
+/// The price source and currency, as configured in `[btc_price]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PriceSettings {
+    pub source: PriceSource,
+    /// Lowercase currency code, e.g. `usd`
+    pub currency: String,
+}
+
+impl PriceSettings {
+    /// `source` quoting in `currency` (default USD); `None` if no source is set
+    pub fn parse(source: &str, currency: &str) -> Result<Option<Self>> {
+        let Some(source) = PriceSource::parse(source)? else {
+            return Ok(None);
+        };
+        let currency = match currency.trim().to_lowercase() {
+            currency if currency.is_empty() => DEFAULT_CURRENCY.to_string(),
+            currency => currency,
+        };
+        if !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+            bail!("Invalid price currency '{}' (expected a code such as 'usd' or 'eur')", currency);
+        }
+        if source == PriceSource::Mempool && !MEMPOOL_CURRENCIES.contains(&currency.as_str()) {
+            bail!("mempool.space has no {} price (it quotes {})", currency.to_uppercase(), MEMPOOL_CURRENCIES.join(", "));
+        }
+        Ok(Some(Self { source, currency }))
+    }
+}
+
+/// The last price fetched, with the settings it was fetched for
+static CACHE: Mutex<Option<(PriceSettings, f64, Instant)>> = Mutex::new(None);
+
+/// Fetch the price of one bitcoin
+pub async fn fetch(settings: &PriceSettings) -> Result<f64> {
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(PRICE_TIMEOUT_SECS)).build()?;
+    let response: Value = client
+        .get(settings.source.url(&settings.currency))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Failed to fetch the bitcoin price from {}", settings.source.name()))?
+        .json()
+        .await?;
+    settings.source.parse_price(&response, &settings.currency)
+}
+
+/// The price of one bitcoin, fetched at most every `PRICE_CACHE_SECS`; `None` if it
+/// can't be had
+pub async fn current(settings: &PriceSettings) -> Option<f64> {
+    if let Some((cached_for, price, fetched)) = CACHE.lock().unwrap().as_ref() {
+        if cached_for == settings && fetched.elapsed() < Duration::from_secs(PRICE_CACHE_SECS) {
+            return Some(*price);
+        }
+    }
+    match fetch(settings).await {
+        Ok(price) => {
+            *CACHE.lock().unwrap() = Some((settings.clone(), price, Instant::now()));
+            Some(price)
+        }
+        Err(e) => {
+            warn!(event = "price_fetch_failed", source = settings.source.name(), "{:#}", e);
+            None
+        }
+    }
+}
+
+/// What `sats` are worth at the current price, e.g. `≈ 201,234 USD`; `None` if the price
+/// can't be had
+pub async fn value_of(settings: &PriceSettings, sats: u64) -> Option<String> {
+    Some(format_value(sats, current(settings).await?, &settings.currency))
+}
+
+/// `sats` at `price` per bitcoin, e.g. `≈ 201,234 USD`
+pub fn format_value(sats: u64, price: f64, currency: &str) -> String {
+    let value = (sats as f64 / SATS_PER_BTC * price).round() as u64;
+    let digits = value.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    format!("≈ {} {}", grouped, currency.to_uppercase())
+}
@@ -0,0 +1,107 @@
+//! Re-checking a found block before it is submitted
+//!
+//! A block reaches the mining session from the local workers, a LAN miner behind the proxy
+//! or a cluster worker. Each of those paths builds its own header, so a bug in any of them
+//! would only show up as a rejected submit. These checks rebuild what they can from the job
+//! alone and compare it against the candidate.
+
+use bitcoin::block::Header;
+use bitcoin::consensus::deserialize;
+use bitcoin::hashes::Hash;
+
+use crate::hashing::hash_hex;
+use crate::stratum::MiningJob;
+use crate::work::{compute_merkle_root_hex, stratum_prevhash_hex};
+use crate::worker::FoundBlock;
+
+/// How far ahead of the local clock a block's time may be before nodes reject it
+pub const MAX_FUTURE_BLOCK_TIME_SECS: u64 = 2 * 60 * 60;
+
+/// Something wrong with a found block
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CandidateProblem {
+    #[error("header is {0} bytes, not {size}", size = Header::SIZE)]
+    HeaderLength(usize),
+    #[error("header {field} is {found}, the job says {expected}")]
+    HeaderField { field: &'static str, expected: String, found: String },
+    #[error("merkle root is {found}, the coinbase and merkle branch give {expected}")]
+    MerkleRoot { expected: String, found: String },
+    #[error("reported hash {reported} isn't the header's hash {actual}")]
+    HashMismatch { reported: String, actual: String },
+    #[error("hash {hash} is above the target {target} for nbits {nbits}")]
+    AboveTarget { hash: String, target: String, nbits: String },
+    #[error("ntime {ntime} is before the job's ntime {job_ntime}")]
+    NtimeBeforeJob { ntime: u32, job_ntime: u32 },
+    #[error("ntime {ntime} is more than {MAX_FUTURE_BLOCK_TIME_SECS}s ahead of the local clock ({now})")]
+    NtimeInFuture { ntime: u32, now: u64 },
+    #[error("{0}")]
+    Unreadable(String),
+}
+
+/// Everything wrong with `found` as a block for `job`, empty if it would pass
+///
+/// `now` is the local unix time, for the bound on how far ahead ntime may be.
+pub fn check(job: &MiningJob, extranonce1: &str, found: &FoundBlock, now: u64) -> Vec<CandidateProblem> {
+    let mut problems = Vec::new();
+    if found.header.len() != Header::SIZE {
+        problems.push(CandidateProblem::HeaderLength(found.header.len()));
+    }
+    let header: Header = match deserialize(&found.header) {
+        Ok(header) => header,
+        Err(e) => {
+            problems.push(CandidateProblem::Unreadable(format!("can't read the header: {}", e)));
+            return problems;
+        }
+    };
+
+    // The job's fields as they should have gone into the header, and the nonce and ntime as found
+    let fields = [
+        ("version", &job.version, format!("{:08x}", header.version.to_consensus() as u32)),
+        ("prevhash", &job.prevhash, stratum_prevhash_hex(header.prev_blockhash)),
+        ("nbits", &job.nbits, format!("{:08x}", header.bits.to_consensus())),
+        ("ntime", &found.ntime, format!("{:08x}", header.time)),
+        ("nonce", &found.nonce, format!("{:08x}", header.nonce)),
+    ];
+    for (field, expected, found) in fields {
+        if !found.eq_ignore_ascii_case(expected) {
+            problems.push(CandidateProblem::HeaderField { field, expected: expected.to_lowercase(), found });
+        }
+    }
+
+    match compute_merkle_root_hex(job, extranonce1, &found.extranonce2) {
+        Ok(expected) => {
+            for found in [found.merkle_root.clone(), header.merkle_root.to_string()] {
+                if !found.eq_ignore_ascii_case(&expected) {
+                    problems.push(CandidateProblem::MerkleRoot { expected: expected.clone(), found });
+                }
+            }
+        }
+        Err(e) => problems.push(CandidateProblem::Unreadable(format!("can't rebuild the merkle root: {}", e))),
+    }
+
+    let actual = header.block_hash();
+    if actual.to_byte_array()[..] != found.hash[..] {
+        problems.push(CandidateProblem::HashMismatch { reported: hash_hex(&found.hash), actual: actual.to_string() });
+    }
+    // The target comes from the nbits in the header, not the one the workers were given
+    let target = header.target();
+    if !target.is_met_by(actual) {
+        problems.push(CandidateProblem::AboveTarget {
+            hash: actual.to_string(),
+            target: hex::encode(target.to_be_bytes()),
+            nbits: format!("{:08x}", header.bits.to_consensus()),
+        });
+    }
+
+    match u32::from_str_radix(&job.ntime, 16) {
+        Ok(job_ntime) if header.time < job_ntime => {
+            problems.push(CandidateProblem::NtimeBeforeJob { ntime: header.time, job_ntime });
+        }
+        Ok(_) => {}
+        Err(_) => problems.push(CandidateProblem::Unreadable(format!("can't read the job's ntime {}", job.ntime))),
+    }
+    if header.time as u64 > now + MAX_FUTURE_BLOCK_TIME_SECS {
+        problems.push(CandidateProblem::NtimeInFuture { ntime: header.time, now });
+    }
+    problems
+}
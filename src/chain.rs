@@ -0,0 +1,207 @@
+//! The network's chain tip, as the block explorer reports it
+//!
+//! Each new tip the block listener sees is a block someone else found: the race the miner
+//! is in. It is logged as `network_block` with the time since the previous block, emitted
+//! as a `network_block` event and, with `notify_network_blocks`, sent to Telegram. A
+//! steady stream of them also shows the listener is alive.
+//!
+//! The explorer's tips are also held against the block the pool's jobs build on, their
+//! prevhash at the height in their coinbase. A pool that falls more than
+//! `stale_tip_blocks` behind the explorer, or builds on a block the explorer doesn't have,
+//! may be mining a stale or forked chain, where a block found is worth nothing.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// How far the pool's tip may drift from the explorer's before it is reported
+pub const DEFAULT_STALE_TIP_BLOCKS: u64 = 2;
+/// Explorer tips remembered, to look up the block at the pool's tip height
+const NETWORK_TIPS_KEPT: usize = 16;
+
+/// The latest block the block explorer knows of
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainTip {
+    pub height: u64,
+    /// Empty when replaying a recording, which keeps only the height
+    pub hash: String,
+    /// Unix time in the block header, if known
+    pub time: Option<u64>,
+}
+
+/// A block the network found since the last tip seen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewBlock {
+    pub tip: ChainTip,
+    /// Time since the previous block by the header times, when that block was seen too
+    pub interval: Option<Duration>,
+    /// Blocks found in between that were never seen as the tip
+    pub skipped: u64,
+}
+
+/// The tip seen last, to tell a new block from the same one reported again
+#[derive(Debug, Default)]
+pub struct TipTracker {
+    last: Option<ChainTip>,
+}
+
+impl TipTracker {
+    /// Take in the explorer's latest tip, returning the new block if it moved forward
+    ///
+    /// The first tip seen is where the miner joined the race, not a new block. A tip at or
+    /// below the last one is ignored.
+    pub fn observe(&mut self, tip: ChainTip) -> Option<NewBlock> {
+        let Some(last) = &self.last else {
+            self.last = Some(tip);
+            return None;
+        };
+        if tip.height <= last.height {
+            return None;
+        }
+        let skipped = tip.height - last.height - 1;
+        // Header times only roughly increase, so one block can claim to predate the last
+        let interval = match (last.time, tip.time) {
+            (Some(previous), Some(time)) if skipped == 0 => Some(Duration::from_secs(time.saturating_sub(previous))),
+            _ => None,
+        };
+        self.last = Some(tip.clone());
+        Some(NewBlock { tip, interval, skipped })
+    }
+}
+
+/// How the block the pool builds on differs from the explorer's chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TipDivergence {
+    /// The pool builds on an old block of the explorer's chain
+    Stale { pool_height: u64, network_height: u64 },
+    /// The pool builds on a block at `height` that isn't the explorer's block there
+    Forked { height: u64, pool_hash: String, network_hash: String, network_height: u64 },
+}
+
+impl TipDivergence {
+    pub fn pool_height(&self) -> u64 {
+        match self {
+            TipDivergence::Stale { pool_height, .. } => *pool_height,
+            TipDivergence::Forked { height, .. } => *height,
+        }
+    }
+
+    pub fn network_height(&self) -> u64 {
+        match self {
+            TipDivergence::Stale { network_height, .. } | TipDivergence::Forked { network_height, .. } => *network_height,
+        }
+    }
+
+    /// Blocks of the explorer's chain the pool's jobs don't build on
+    pub fn blocks(&self) -> u64 {
+        match self {
+            TipDivergence::Stale { pool_height, network_height } => network_height - pool_height,
+            TipDivergence::Forked { height, network_height, .. } => network_height - height + 1,
+        }
+    }
+}
+
+/// e.g. `the pool builds on block 870000, 3 behind the network's tip at 870003`
+impl fmt::Display for TipDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TipDivergence::Stale { pool_height, network_height } => write!(
+                f,
+                "the pool builds on block {}, {} behind the network's tip at {}",
+                pool_height, network_height - pool_height, network_height
+            ),
+            TipDivergence::Forked { height, pool_hash, network_hash, network_height } => write!(
+                f,
+                "the pool builds on block {} at height {}, but the network's block there is {} (tip at {})",
+                pool_hash, height, network_hash, network_height
+            ),
+        }
+    }
+}
+
+/// A change in whether the pool's tip and the explorer's agree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TipChange {
+    /// They drifted more than the allowed number of blocks apart
+    Diverged(TipDivergence),
+    /// They are back within the allowed number of blocks
+    Converged,
+}
+
+/// The block the pool's jobs build on, held against the explorer's recent tips
+#[derive(Debug, Default)]
+pub struct TipWatch {
+    /// Block hashes by height
+    network: BTreeMap<u64, String>,
+    /// The block the pool's latest job builds on
+    pool: Option<ChainTip>,
+    diverged: bool,
+}
+
+impl TipWatch {
+    /// Take in a tip the explorer reported
+    ///
+    /// A tip without a hash, as replays have, can't be compared and is ignored.
+    pub fn network_tip(&mut self, tip: &ChainTip) {
+        if tip.hash.is_empty() {
+            return;
+        }
+        // A reorg to a lower tip takes the blocks above it away
+        self.network.split_off(&(tip.height + 1));
+        self.network.insert(tip.height, tip.hash.clone());
+        while self.network.len() > NETWORK_TIPS_KEPT {
+            self.network.pop_first();
+        }
+    }
+
+    /// Take in the block the pool's latest job builds on
+    pub fn pool_tip(&mut self, height: u64, hash: String) {
+        self.pool = Some(ChainTip { height, hash, time: None });
+    }
+
+    /// How the pool's tip differs from the explorer's chain right now, if it does
+    pub fn divergence(&self) -> Option<TipDivergence> {
+        let pool = self.pool.as_ref()?;
+        let (&network_height, _) = self.network.last_key_value()?;
+        match self.network.get(&pool.height) {
+            Some(network_hash) if *network_hash != pool.hash => Some(TipDivergence::Forked {
+                height: pool.height,
+                pool_hash: pool.hash.clone(),
+                network_hash: network_hash.clone(),
+                network_height,
+            }),
+            _ if network_height > pool.height => Some(TipDivergence::Stale { pool_height: pool.height, network_height }),
+            _ => None,
+        }
+    }
+
+    /// Compare the tips, returning the change if they just drifted more than `max_blocks`
+    /// apart or came back together
+    ///
+    /// A one-block race is how every new block starts, so only a lasting drift counts.
+    /// A `max_blocks` of 0 turns reporting off.
+    pub fn check(&mut self, max_blocks: u64) -> Option<TipChange> {
+        let divergence = self.divergence().filter(|divergence| max_blocks > 0 && divergence.blocks() > max_blocks);
+        match (divergence, self.diverged) {
+            (Some(divergence), false) => {
+                self.diverged = true;
+                Some(TipChange::Diverged(divergence))
+            }
+            (None, true) => {
+                self.diverged = false;
+                Some(TipChange::Converged)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// e.g. `9m 12s`, `1h 04m`, `45s`
+pub fn format_interval(interval: Duration) -> String {
+    let secs = interval.as_secs();
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m {:02}s", secs / 60, secs % 60),
+        _ => format!("{}h {:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
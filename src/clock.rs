@@ -0,0 +1,118 @@
+//! Wall-clock time and randomness, injectable so tests can pin them down
+//!
+//! Timeouts, intervals and retry delays use tokio's clock instead, which tests control with
+//! `#[tokio::test(start_paused = true)]` and `tokio::time::advance`.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+
+/// Where the miner gets the time of day from
+pub trait Clock: Send + Sync + fmt::Debug {
+    fn now(&self) -> SystemTime;
+
+    /// Whole seconds since the Unix epoch
+    fn unix_secs(&self) -> u64 {
+        self.now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+    }
+}
+
+/// The operating system's clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// A clock that only moves when told to
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<SystemTime>,
+}
+
+impl ManualClock {
+    /// Start at `unix_secs` seconds since the Unix epoch
+    pub fn new(unix_secs: u64) -> Self {
+        Self { now: Mutex::new(UNIX_EPOCH + Duration::from_secs(unix_secs)) }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+
+    pub fn set(&self, now: SystemTime) {
+        *self.now.lock().unwrap() = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> SystemTime {
+        *self.now.lock().unwrap()
+    }
+}
+
+/// A random number generator shared by everything that draws from it
+///
+/// Clones share one generator, so a seeded source hands out the same sequence of numbers
+/// in the same order on every run.
+#[derive(Clone)]
+pub struct RandomSource {
+    rng: Arc<Mutex<StdRng>>,
+}
+
+impl RandomSource {
+    /// Seeded by the operating system
+    pub fn from_entropy() -> Self {
+        Self::from_rng(StdRng::from_entropy())
+    }
+
+    /// The same numbers for the same seed
+    pub fn seeded(seed: u64) -> Self {
+        Self::from_rng(StdRng::seed_from_u64(seed))
+    }
+
+    fn from_rng(rng: StdRng) -> Self {
+        Self { rng: Arc::new(Mutex::new(rng)) }
+    }
+
+    /// A separate generator seeded from this one, for a thread that shouldn't contend for
+    /// the shared one. Forks taken in a fixed order are reproducible.
+    pub fn fork(&self) -> Self {
+        Self::seeded(self.next_u64())
+    }
+
+    pub fn next_u64(&self) -> u64 {
+        self.rng.lock().unwrap().next_u64()
+    }
+
+    pub fn fill(&self, bytes: &mut [u8]) {
+        self.rng.lock().unwrap().fill_bytes(bytes);
+    }
+
+    /// A number in `0..bound`; `bound` must be above zero
+    pub fn below(&self, bound: u64) -> u64 {
+        self.rng.lock().unwrap().gen_range(0..bound)
+    }
+}
+
+impl Default for RandomSource {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+impl fmt::Debug for RandomSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RandomSource")
+    }
+}
+
+/// The clock to use outside tests
+pub fn system() -> Arc<dyn Clock> {
+    Arc::new(SystemClock)
+}
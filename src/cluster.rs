@@ -0,0 +1,1059 @@
+//! Cooperative mining across machines under one payout address
+//!
+//! A coordinator (a miner with `[cluster] listen` set) keeps the one pool connection and
+//! hands out assignments: an extranonce2 and a slice of the nonce range, each handed out
+//! once per job. Workers search their assignment, report what they find and ask for the
+//! next one. Messages are JSON objects tagged by `type`, one per line each way.
+//!
+//! With `[cluster] redis` set, the coordinator also keeps its bookkeeping (how far it got
+//! through the job, the assignments not finished yet and every worker's totals) in Redis,
+//! and picks it up again when it restarts.
+//!
+//! A worker that disconnects, or sends nothing for `WORKER_TIMEOUT_SECS`, is dropped and
+//! the assignments it hadn't finished are handed out again before any new ones, so every
+//! slice of a job gets searched.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{Result, Context, bail};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
+#[cfg(feature = "redis")]
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, error, info, warn, Instrument};
+
+use crate::exit_code::{ExitCode, fatal};
+use crate::hashing::{double_sha256, hash_meets_target};
+use crate::{mdns, proxy, secrets};
+use crate::redis::RedisUrl;
+#[cfg(feature = "redis")]
+use crate::redis::Redis;
+use crate::stats::{format_difficulty, hash_difficulty};
+use crate::stratum::MiningJob;
+use crate::tasks::{Stage, Tasks};
+use crate::work::{compute_merkle_root_hex, create_block_header};
+use crate::worker::{FoundBlock, RangeSearch, RangeWork, SharedWork};
+
+/// Each assignment covers 2^NONCE_RANGE_BITS nonces, a sixteenth of the nonce range
+pub const NONCE_RANGE_BITS: u32 = 28;
+const RANGES_PER_EXTRANONCE2: u64 = 1 << (32 - NONCE_RANGE_BITS);
+/// How long a new connection has to say hello
+const HELLO_TIMEOUT_SECS: u64 = 10;
+/// Longest message line accepted from a peer; the longest real ones, a hello or an
+/// assignment, are well under a kilobyte
+const MAX_LINE_BYTES: u64 = 64 * 1024;
+/// How often a worker reports its hashes to the coordinator
+const WORKER_PROGRESS_INTERVAL_SECS: u64 = 10;
+/// How long the coordinator waits to hear from a worker before dropping it
+const WORKER_TIMEOUT_SECS: u64 = 3 * WORKER_PROGRESS_INTERVAL_SECS;
+/// How long a worker waits before reconnecting to the coordinator
+const WORKER_RECONNECT_DELAY_SECS: u64 = 5;
+/// `--worker` value that finds the coordinator with mDNS
+pub const DISCOVER_COORDINATOR: &str = "auto";
+/// Prefix of the coordinator's keys in Redis
+#[cfg(feature = "redis")]
+const REDIS_KEY_PREFIX: &str = "bitcoin-solo-miner:cluster";
+/// How long one write to Redis may take, connecting included
+#[cfg(feature = "redis")]
+const REDIS_TIMEOUT_SECS: u64 = 5;
+/// How long to wait before retrying a write Redis didn't take
+#[cfg(feature = "redis")]
+const REDIS_RETRY_SECS: u64 = 5;
+
+/// A slice of the search space for one worker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assignment {
+    pub id: u64,
+    pub job: MiningJob,
+    pub extranonce1: String,
+    pub extranonce2: String,
+    /// First nonce to try
+    pub nonce_start: u32,
+    /// Last nonce to try, inclusive
+    pub nonce_end: u32,
+    /// Network target as 64 hex characters
+    pub target: String,
+}
+
+/// What a worker sends the coordinator
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WorkerMessage {
+    /// The first message on a connection
+    Hello {
+        name: String,
+        threads: usize,
+        #[serde(default)]
+        token: Option<String>,
+    },
+    /// The assignment was searched to the end; asks for the next one
+    Finished { assignment: u64 },
+    /// A nonce in the assignment whose header meets the target
+    Found { assignment: u64, nonce: String },
+    /// The assignment can't be searched as sent, e.g. its nonce range is backwards; asks
+    /// for the next one
+    Rejected { assignment: u64, reason: String },
+    /// Hashes since the last report, the current rate and the difficulty of the best
+    /// hash since the last report
+    Progress {
+        hashes: u64,
+        hashrate: f64,
+        #[serde(default)]
+        best_difficulty: f64,
+    },
+}
+
+/// What the coordinator sends a worker
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CoordinatorMessage {
+    Welcome { worker_id: u64 },
+    /// Search this instead of whatever was being searched
+    Work { assignment: Box<Assignment> },
+    /// The connection is refused and about to close
+    Refused { reason: String },
+}
+
+/// A connected worker, as the coordinator sees it
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkerStatus {
+    pub id: u64,
+    pub name: String,
+    pub peer: SocketAddr,
+    pub threads: usize,
+    /// Hashes per second in the worker's last report
+    pub hashrate: f64,
+    pub hashes: u64,
+    /// Assignments searched to the end
+    pub finished: u64,
+    pub best_share_difficulty: f64,
+}
+
+/// The whole cluster at a glance, as reported by the control interfaces
+#[derive(Debug, Clone, Serialize)]
+pub struct ClusterStats {
+    /// This miner's hash rate plus its connected workers' last reports, in h/s
+    pub hashrate: f64,
+    /// Hashes this session by this miner and the connected workers
+    pub hashes: u64,
+    /// Best share difficulty this session anywhere in the cluster
+    pub best_share_difficulty: f64,
+    pub workers: Vec<WorkerStatus>,
+}
+
+impl ClusterStats {
+    /// One line for logs and notifications
+    pub fn summary_line(&self) -> String {
+        format!(
+            "{} worker(s) | {:.0} h/s combined | {} hashes | best share difficulty {}",
+            self.workers.len(),
+            self.hashrate,
+            self.hashes,
+            format_difficulty(self.best_share_difficulty)
+        )
+    }
+}
+
+/// What a worker has done across its connections, by name, kept in Redis if enabled
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorkerTotals {
+    pub hashes: u64,
+    /// Assignments searched to the end
+    pub finished: u64,
+    pub blocks: u64,
+    pub best_share_difficulty: f64,
+}
+
+/// How far the assigner got through a job, as kept in Redis
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Cursor {
+    job_id: String,
+    extranonce1: String,
+    next_id: u64,
+    extranonce2: u128,
+    range: u64,
+    /// Slices waiting to be handed out again, as `(extranonce2, nonce_start)`
+    #[serde(default)]
+    reissue: Vec<(String, u32)>,
+}
+
+/// Hands out the search space of the current job
+///
+/// Assignments take extranonce2 values whose first `proxy::PREFIX_BYTES` bytes are zero,
+/// a prefix the proxy never gives its miners, and walk each one through the nonce range
+/// before moving to the next.
+#[derive(Debug, Default)]
+struct Assigner {
+    work: Option<Arc<SharedWork>>,
+    next_id: u64,
+    extranonce2: u128,
+    range: u64,
+    /// Assignments of the current job not searched to the end yet, with their worker
+    outstanding: HashMap<u64, (u64, Assignment)>,
+    /// Slices of the current job to hand out again before any new one
+    reissue: Vec<(String, u32)>,
+    /// Where the coordinator got to before it restarted, with the assignments it had out
+    restored: Option<(Cursor, Vec<Assignment>)>,
+}
+
+impl Assigner {
+    /// Start over on a new job, or carry on where the last run left off if it is the
+    /// job that run was on
+    fn reset(&mut self, work: Arc<SharedWork>) {
+        self.extranonce2 = 0;
+        self.range = 0;
+        self.outstanding.clear();
+        self.reissue.clear();
+        if let Some((cursor, unfinished)) = self.restored.take() {
+            self.next_id = self.next_id.max(cursor.next_id);
+            if cursor.job_id == work.job.job_id && cursor.extranonce1 == work.extranonce1 {
+                self.extranonce2 = cursor.extranonce2;
+                self.range = cursor.range;
+                self.reissue = cursor.reissue;
+                self.reissue.extend(unfinished.into_iter().map(|assignment| (assignment.extranonce2, assignment.nonce_start)));
+            }
+        }
+        self.work = Some(work);
+    }
+
+    fn cursor(&self) -> Option<Cursor> {
+        let work = self.work.as_ref()?;
+        Some(Cursor {
+            job_id: work.job.job_id.clone(),
+            extranonce1: work.extranonce1.clone(),
+            next_id: self.next_id,
+            extranonce2: self.extranonce2,
+            range: self.range,
+            reissue: self.reissue.clone(),
+        })
+    }
+
+    fn next(&mut self, worker: u64) -> Option<Assignment> {
+        let work = self.work.as_ref()?;
+        let (extranonce2, nonce_start) = match self.reissue.pop() {
+            Some(slice) => slice,
+            None => {
+                let slice = (
+                    format!("{:0width$x}", self.extranonce2, width = work.extranonce2_size * 2),
+                    (self.range << NONCE_RANGE_BITS) as u32,
+                );
+                let free_bytes = work.extranonce2_size.saturating_sub(proxy::PREFIX_BYTES).clamp(1, 8);
+                self.range += 1;
+                if self.range == RANGES_PER_EXTRANONCE2 {
+                    self.range = 0;
+                    self.extranonce2 = (self.extranonce2 + 1) % (1u128 << (8 * free_bytes));
+                }
+                slice
+            }
+        };
+        self.next_id += 1;
+        let assignment = Assignment {
+            id: self.next_id,
+            job: work.job.clone(),
+            extranonce1: work.extranonce1.clone(),
+            extranonce2,
+            nonce_start,
+            nonce_end: nonce_start + ((1u32 << NONCE_RANGE_BITS) - 1),
+            target: hex::encode(&work.target),
+        };
+        self.outstanding.insert(assignment.id, (worker, assignment.clone()));
+        Some(assignment)
+    }
+}
+
+#[derive(Debug, Default)]
+struct ClusterState {
+    assigner: Assigner,
+    workers: BTreeMap<u64, WorkerStatus>,
+    next_worker_id: u64,
+    totals: BTreeMap<String, WorkerTotals>,
+    /// Where changes to the bookkeeping go when it is kept in Redis
+    store: Option<mpsc::UnboundedSender<StoreUpdate>>,
+}
+
+impl ClusterState {
+    fn save(&self, update: impl FnOnce() -> StoreUpdate) {
+        if let Some(store) = &self.store {
+            let _ = store.send(update());
+        }
+    }
+
+    fn update_totals(&mut self, worker: u64, update: impl FnOnce(&mut WorkerTotals)) {
+        let Some(name) = self.workers.get(&worker).map(|status| status.name.clone()) else {
+            return;
+        };
+        let totals = self.totals.entry(name.clone()).or_default();
+        update(totals);
+        let totals = totals.clone();
+        self.save(|| StoreUpdate::Totals { worker: name, totals });
+    }
+}
+
+/// The coordinator's side of the cluster, shared by its connections
+#[derive(Debug, Clone)]
+pub struct Coordinator {
+    addr: SocketAddr,
+    state: Arc<Mutex<ClusterState>>,
+    /// Bumped for every new job, so connections push it to their workers
+    jobs: Arc<watch::Sender<u64>>,
+}
+
+impl Coordinator {
+    /// Where the coordinator is listening, with the port filled in if it was 0
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Start handing out assignments of a new job, replacing every outstanding one
+    pub fn publish(&self, work: Arc<SharedWork>) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.assigner.reset(work);
+            let cursor = state.assigner.cursor();
+            state.save(|| StoreUpdate::Job(cursor.expect("the assigner has work")));
+        }
+        self.jobs.send_modify(|generation| *generation += 1);
+    }
+
+    /// The connected workers, by id
+    pub fn workers(&self) -> Vec<WorkerStatus> {
+        self.state.lock().unwrap().workers.values().cloned().collect()
+    }
+
+    /// Combined figures for this miner and its workers, given this miner's own hash rate,
+    /// hashes and best share difficulty
+    pub fn stats(&self, hashrate: f64, hashes: u64, best_share_difficulty: f64) -> ClusterStats {
+        let workers = self.workers();
+        ClusterStats {
+            hashrate: hashrate + workers.iter().map(|worker| worker.hashrate).sum::<f64>(),
+            hashes: hashes + workers.iter().map(|worker| worker.hashes).sum::<u64>(),
+            best_share_difficulty: workers.iter().map(|worker| worker.best_share_difficulty).fold(best_share_difficulty, f64::max),
+            workers,
+        }
+    }
+
+    /// Every worker that has been connected, by name, with what it has done in total
+    pub fn totals(&self) -> BTreeMap<String, WorkerTotals> {
+        self.state.lock().unwrap().totals.clone()
+    }
+
+    fn join(&self, name: String, peer: SocketAddr, threads: usize) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        state.next_worker_id += 1;
+        let id = state.next_worker_id;
+        state.workers.insert(id, WorkerStatus { id, name, peer, threads, hashrate: 0.0, hashes: 0, finished: 0, best_share_difficulty: 0.0 });
+        id
+    }
+
+    /// Forget a worker, putting the assignments it hadn't finished back to be handed out
+    /// again; returns its status and how many assignments were put back
+    fn leave(&self, worker: u64) -> (Option<WorkerStatus>, usize) {
+        let mut state = self.state.lock().unwrap();
+        let mut dropped: Vec<u64> = state.assigner.outstanding.iter()
+            .filter(|(_, (owner, _))| *owner == worker)
+            .map(|(id, _)| *id)
+            .collect();
+        // Oldest last, so it is the first handed out again
+        dropped.sort_unstable_by(|a, b| b.cmp(a));
+        for id in &dropped {
+            if let Some((_, assignment)) = state.assigner.outstanding.remove(id) {
+                state.assigner.reissue.push((assignment.extranonce2, assignment.nonce_start));
+            }
+            state.save(|| StoreUpdate::Done(*id));
+        }
+        if !dropped.is_empty() {
+            let cursor = state.assigner.cursor().expect("outstanding assignments have work");
+            state.save(|| StoreUpdate::Cursor(cursor));
+        }
+        (state.workers.remove(&worker), dropped.len())
+    }
+
+    fn assign(&self, worker: u64) -> Option<Assignment> {
+        let mut state = self.state.lock().unwrap();
+        let assignment = state.assigner.next(worker)?;
+        let cursor = state.assigner.cursor().expect("the assigner has work");
+        let name = state.workers.get(&worker).map(|status| status.name.clone()).unwrap_or_default();
+        state.save(|| StoreUpdate::Assigned { cursor, worker: name, assignment: Box::new(assignment.clone()) });
+        Some(assignment)
+    }
+
+    fn finish(&self, worker: u64, assignment: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.assigner.outstanding.get(&assignment).is_some_and(|(owner, _)| *owner == worker) {
+            state.assigner.outstanding.remove(&assignment);
+            state.save(|| StoreUpdate::Done(assignment));
+            if let Some(status) = state.workers.get_mut(&worker) {
+                status.finished += 1;
+            }
+            state.update_totals(worker, |totals| totals.finished += 1);
+        }
+    }
+
+    /// Drop an assignment its worker can't search, without counting it as searched
+    fn reject(&self, worker: u64, assignment: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.assigner.outstanding.get(&assignment).is_some_and(|(owner, _)| *owner == worker) {
+            state.assigner.outstanding.remove(&assignment);
+            state.save(|| StoreUpdate::Done(assignment));
+        }
+    }
+
+    fn progress(&self, worker: u64, hashes: u64, hashrate: f64, best_difficulty: f64) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(status) = state.workers.get_mut(&worker) {
+            status.hashes += hashes;
+            status.hashrate = hashrate;
+            status.best_share_difficulty = status.best_share_difficulty.max(best_difficulty);
+        }
+        state.update_totals(worker, |totals| {
+            totals.hashes += hashes;
+            totals.best_share_difficulty = totals.best_share_difficulty.max(best_difficulty);
+        });
+    }
+
+    /// Check a reported block and hand it to the mining session if it is one
+    fn found(&self, worker: u64, assignment: u64, nonce: &str) -> Result<(), &'static str> {
+        let (work, assignment) = {
+            let state = self.state.lock().unwrap();
+            let Some((owner, assignment)) = state.assigner.outstanding.get(&assignment) else {
+                return Err("not an assignment of the current job");
+            };
+            if *owner != worker {
+                return Err("assigned to another worker");
+            }
+            (state.assigner.work.clone().ok_or("no current job")?, assignment.clone())
+        };
+        let in_range = u32::from_str_radix(nonce, 16)
+            .is_ok_and(|value| nonce.len() == 8 && (assignment.nonce_start..=assignment.nonce_end).contains(&value));
+        if !in_range {
+            return Err("nonce outside the assignment");
+        }
+        let job = &work.job;
+        let merkle_root = compute_merkle_root_hex(job, &work.extranonce1, &assignment.extranonce2).map_err(|_| "bad job")?;
+        let header = create_block_header(&job.version, &job.prevhash, &merkle_root, &job.nbits, &job.ntime, nonce).map_err(|_| "bad job")?;
+        let hash = double_sha256(&header);
+        if !hash_meets_target(&hash, &work.target) {
+            return Err("hash doesn't meet the target");
+        }
+        let found = FoundBlock {
+            nonce: nonce.to_string(),
+            extranonce2: assignment.extranonce2,
+            ntime: job.ntime.clone(),
+            merkle_root,
+            header,
+            hash,
+        };
+        work.found.send(Ok(found)).map_err(|_| "the job has ended")?;
+        self.state.lock().unwrap().update_totals(worker, |totals| totals.blocks += 1);
+        Ok(())
+    }
+}
+
+/// Accept cluster workers on `listen` until the mining stage stops
+///
+/// With a `token`, workers must present it in their hello. With `redis`, the bookkeeping a
+/// previous run left there is picked up and kept up to date. Fails if the port can't be
+/// opened or Redis can't be read.
+pub async fn start(listen: SocketAddr, token: Option<String>, redis: Option<RedisUrl>, tasks: &mut Tasks) -> Result<Coordinator> {
+    let listener = tokio::net::TcpListener::bind(listen).await
+        .with_context(|| format!("Failed to listen for cluster workers on {}", listen))?;
+    let addr = listener.local_addr()?;
+    if token.is_none() && !addr.ip().is_loopback() {
+        warn!(event = "cluster_unauthenticated", "Cluster coordinator on {} accepts workers from the network without a token", addr);
+    }
+    info!(event = "cluster_listening", listen = %addr, "Cluster coordinator listening on {}", addr);
+
+    let mut state = ClusterState::default();
+    if let Some(url) = redis {
+        restore_from_redis(url, &mut state, tasks).await?;
+    }
+
+    let (jobs, _) = watch::channel(0);
+    let coordinator = Coordinator { addr, state: Arc::new(Mutex::new(state)), jobs: Arc::new(jobs) };
+    let accepting = coordinator.clone();
+    tasks.spawn_with(Stage::Mining, "cluster", |cancel| async move {
+        loop {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = cancel.cancelled() => return,
+            };
+            match accepted {
+                Ok((stream, peer)) => {
+                    let connection = serve(stream, peer, token.clone(), accepting.clone());
+                    tokio::spawn(cancel.child_token().run_until_cancelled_owned(connection).in_current_span());
+                }
+                Err(e) => {
+                    error!("Cluster coordinator stopped: {}", e);
+                    return;
+                }
+            }
+        }
+    });
+    Ok(coordinator)
+}
+
+/// Pick up the bookkeeping a previous run left in Redis, and keep it there from now on
+#[cfg(feature = "redis")]
+async fn restore_from_redis(url: RedisUrl, state: &mut ClusterState, tasks: &mut Tasks) -> Result<()> {
+    let mut connection = Redis::connect(&url).await?;
+    let saved = load(&mut connection).await.with_context(|| format!("Failed to read the cluster state from Redis at {}", url))?;
+    info!(
+        event = "cluster_restored",
+        redis = %url,
+        workers = saved.totals.len(),
+        unfinished = saved.assignments.len(),
+        "Keeping cluster state in Redis at {}; restored {} worker(s) and {} unfinished assignment(s)", url, saved.totals.len(), saved.assignments.len()
+    );
+    state.totals = saved.totals;
+    state.assigner.restored = saved.cursor.map(|cursor| (cursor, saved.assignments));
+    let (store, updates) = mpsc::unbounded_channel();
+    state.store = Some(store);
+    tasks.spawn_with(Stage::Mining, "cluster-redis", |cancel| keep_in_redis(url, connection, updates, cancel));
+    Ok(())
+}
+
+#[cfg(not(feature = "redis"))]
+async fn restore_from_redis(_url: RedisUrl, _state: &mut ClusterState, _tasks: &mut Tasks) -> Result<()> {
+    anyhow::bail!("Keeping the cluster state in Redis requires building with `--features redis`")
+}
+
+/// A change to the coordinator's bookkeeping, on its way to Redis
+#[derive(Debug)]
+#[cfg_attr(not(feature = "redis"), allow(dead_code))]
+enum StoreUpdate {
+    /// A new job, which voids every earlier assignment
+    Job(Cursor),
+    Assigned { cursor: Cursor, worker: String, assignment: Box<Assignment> },
+    /// An assignment was finished or put back when its worker left
+    Done(u64),
+    /// Slices were put back to be handed out again
+    Cursor(Cursor),
+    Totals { worker: String, totals: WorkerTotals },
+}
+
+/// An unfinished assignment as kept in Redis
+#[cfg(feature = "redis")]
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedAssignment {
+    worker: String,
+    assignment: Assignment,
+}
+
+/// What a previous run left in Redis
+#[cfg(feature = "redis")]
+#[derive(Debug, Default)]
+struct Saved {
+    cursor: Option<Cursor>,
+    assignments: Vec<Assignment>,
+    totals: BTreeMap<String, WorkerTotals>,
+}
+
+#[cfg(feature = "redis")]
+fn redis_key(name: &str) -> String {
+    format!("{}:{}", REDIS_KEY_PREFIX, name)
+}
+
+/// Read the bookkeeping a previous run left, skipping entries that don't parse
+#[cfg(feature = "redis")]
+async fn load(redis: &mut Redis) -> Result<Saved> {
+    let mut saved = Saved::default();
+    if let Some(cursor) = redis.get(&redis_key("cursor")).await? {
+        saved.cursor = serde_json::from_str(&cursor).ok();
+    }
+    let mut assignments: Vec<Assignment> = redis.hgetall(&redis_key("assignments")).await?
+        .into_iter()
+        .filter_map(|(_, value)| serde_json::from_str::<SavedAssignment>(&value).ok())
+        .map(|saved| saved.assignment)
+        .collect();
+    assignments.sort_by_key(|assignment| std::cmp::Reverse(assignment.id));
+    saved.assignments = assignments;
+    for (worker, value) in redis.hgetall(&redis_key("workers")).await? {
+        if let Ok(totals) = serde_json::from_str(&value) {
+            saved.totals.insert(worker, totals);
+        }
+    }
+    Ok(saved)
+}
+
+#[cfg(feature = "redis")]
+async fn apply(redis: &mut Redis, update: &StoreUpdate) -> Result<()> {
+    match update {
+        StoreUpdate::Job(cursor) => {
+            redis.set(&redis_key("cursor"), &serde_json::to_string(cursor)?).await?;
+            redis.del(&redis_key("assignments")).await
+        }
+        StoreUpdate::Assigned { cursor, worker, assignment } => {
+            let saved = SavedAssignment { worker: worker.clone(), assignment: (**assignment).clone() };
+            redis.hset(&redis_key("assignments"), &assignment.id.to_string(), &serde_json::to_string(&saved)?).await?;
+            redis.set(&redis_key("cursor"), &serde_json::to_string(cursor)?).await
+        }
+        StoreUpdate::Done(assignment) => redis.hdel(&redis_key("assignments"), &assignment.to_string()).await,
+        StoreUpdate::Cursor(cursor) => redis.set(&redis_key("cursor"), &serde_json::to_string(cursor)?).await,
+        StoreUpdate::Totals { worker, totals } => {
+            redis.hset(&redis_key("workers"), worker, &serde_json::to_string(totals)?).await
+        }
+    }
+}
+
+/// Write one update, reconnecting first if the last write failed
+#[cfg(feature = "redis")]
+async fn write(url: &RedisUrl, redis: &mut Option<Redis>, update: &StoreUpdate) -> Result<()> {
+    let written = tokio::time::timeout(Duration::from_secs(REDIS_TIMEOUT_SECS), async {
+        if redis.is_none() {
+            *redis = Some(Redis::connect(url).await?);
+        }
+        apply(redis.as_mut().expect("connected above"), update).await
+    }).await;
+    let result = written.unwrap_or_else(|_| Err(anyhow::anyhow!("Redis didn't answer within {}s", REDIS_TIMEOUT_SECS)));
+    if result.is_err() {
+        *redis = None;
+    }
+    result
+}
+
+/// Write the coordinator's updates to Redis in order, retrying each until Redis takes
+/// it, and flush what is queued once cancelled
+#[cfg(feature = "redis")]
+async fn keep_in_redis(url: RedisUrl, connection: Redis, mut updates: mpsc::UnboundedReceiver<StoreUpdate>, cancel: CancellationToken) {
+    let mut redis = Some(connection);
+    let mut failing = false;
+    let mut unwritten = 0;
+    'updates: loop {
+        let update = tokio::select! {
+            update = updates.recv() => update,
+            _ = cancel.cancelled() => break,
+        };
+        let Some(update) = update else {
+            return;
+        };
+        loop {
+            match write(&url, &mut redis, &update).await {
+                Ok(()) => {
+                    if failing {
+                        info!(event = "redis_recovered", redis = %url, "Writing the cluster state to Redis at {} again", url);
+                        failing = false;
+                    }
+                    break;
+                }
+                Err(e) => {
+                    if !failing {
+                        warn!(event = "redis_unavailable", redis = %url, "Can't write the cluster state to Redis at {}: {:#}; retrying every {}s", url, e, REDIS_RETRY_SECS);
+                        failing = true;
+                    }
+                    tokio::select! {
+                        _ = tokio::time::sleep(Duration::from_secs(REDIS_RETRY_SECS)) => {}
+                        _ = cancel.cancelled() => {
+                            unwritten += 1;
+                            break 'updates;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    while let Ok(update) = updates.try_recv() {
+        if failing || write(&url, &mut redis, &update).await.is_err() {
+            failing = true;
+            unwritten += 1;
+        }
+    }
+    if unwritten > 0 {
+        warn!(event = "redis_unwritten", redis = %url, unwritten, "{} cluster state update(s) didn't reach Redis at {}", unwritten, url);
+    }
+}
+
+/// Write one message and its newline
+async fn write_message(writer: &mut OwnedWriteHalf, message: &impl Serialize) -> Result<()> {
+    let line = serde_json::to_string(message)?;
+    writer.write_all(format!("{}\n", line).as_bytes()).await?;
+    Ok(())
+}
+
+async fn send(writer: &mut OwnedWriteHalf, message: &CoordinatorMessage) -> bool {
+    write_message(writer, message).await.is_ok()
+}
+
+/// Read one message line of at most `MAX_LINE_BYTES`, `None` at the end of the stream
+///
+/// A longer line is an error rather than something to buffer, so a peer can't grow
+/// memory without bound by never sending a newline.
+async fn next_line(reader: &mut (impl AsyncBufRead + Unpin)) -> std::io::Result<Option<String>> {
+    let mut line = String::new();
+    let read = reader.take(MAX_LINE_BYTES + 1).read_line(&mut line).await?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if read as u64 > MAX_LINE_BYTES {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("line longer than {} bytes", MAX_LINE_BYTES)));
+    }
+    let trimmed = line.trim_end_matches(['\n', '\r']).len();
+    line.truncate(trimmed);
+    Ok(Some(line))
+}
+
+/// Talk to one worker from its hello until it disconnects
+async fn serve(stream: TcpStream, peer: SocketAddr, token: Option<String>, coordinator: Coordinator) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader);
+
+    let hello = tokio::time::timeout(Duration::from_secs(HELLO_TIMEOUT_SECS), next_line(&mut lines)).await;
+    let line = match hello {
+        Ok(Ok(Some(line))) => line,
+        Ok(Err(e)) => {
+            warn!(event = "cluster_invalid_message", peer = %peer, error = %e, "Dropping cluster connection from {}: {}", peer, e);
+            return;
+        }
+        _ => {
+            debug!(peer = %peer, "Cluster connection closed before saying hello");
+            return;
+        }
+    };
+    let (name, threads) = match serde_json::from_str(&line) {
+        Ok(WorkerMessage::Hello { name, threads, token: given }) => {
+            let accepted = match (&token, &given) {
+                (Some(token), Some(given)) => secrets::constant_time_eq(token.as_bytes(), given.as_bytes()),
+                (Some(_), None) => false,
+                (None, _) => true,
+            };
+            if !accepted {
+                warn!(event = "cluster_refused", peer = %peer, name = %name, "Refused cluster worker {} from {}: wrong token", name, peer);
+                send(&mut writer, &CoordinatorMessage::Refused { reason: "missing or wrong token".to_string() }).await;
+                return;
+            }
+            (name, threads)
+        }
+        _ => {
+            send(&mut writer, &CoordinatorMessage::Refused { reason: "expected a hello".to_string() }).await;
+            return;
+        }
+    };
+    let worker_id = coordinator.join(name.clone(), peer, threads);
+    info!(event = "cluster_worker_joined", worker = %name, worker_id, peer = %peer, threads, "Cluster worker {} joined from {} with {} thread(s)", name, peer, threads);
+
+    let mut jobs = coordinator.jobs.subscribe();
+    jobs.borrow_and_update();
+    let mut current = coordinator.assign(worker_id);
+    let timeout = Duration::from_secs(WORKER_TIMEOUT_SECS);
+    let mut last_heard = Instant::now();
+    let mut connected = send(&mut writer, &CoordinatorMessage::Welcome { worker_id }).await;
+    if let Some(assignment) = current.clone() {
+        connected = connected && send(&mut writer, &CoordinatorMessage::Work { assignment: Box::new(assignment) }).await;
+    }
+    while connected {
+        let next = tokio::select! {
+            line = next_line(&mut lines) => {
+                let line = match line {
+                    Ok(Some(line)) => line,
+                    Ok(None) => break,
+                    Err(e) => {
+                        warn!(event = "cluster_invalid_message", worker = %name, error = %e, "Dropping cluster worker {}: {}", name, e);
+                        break;
+                    }
+                };
+                last_heard = Instant::now();
+                match serde_json::from_str(&line) {
+                    Ok(WorkerMessage::Finished { assignment }) => {
+                        coordinator.finish(worker_id, assignment);
+                        // A finish for an assignment already replaced by a new job's asks for nothing
+                        current.as_ref().is_some_and(|current| current.id == assignment)
+                    }
+                    Ok(WorkerMessage::Rejected { assignment, reason }) => {
+                        warn!(event = "cluster_assignment_rejected", worker = %name, assignment, reason = %reason, "Cluster worker {} rejected assignment {}: {}", name, assignment, reason);
+                        coordinator.reject(worker_id, assignment);
+                        current.as_ref().is_some_and(|current| current.id == assignment)
+                    }
+                    Ok(WorkerMessage::Found { assignment, nonce }) => {
+                        match coordinator.found(worker_id, assignment, &nonce) {
+                            Ok(()) => warn!(event = "cluster_block_found", worker = %name, nonce = %nonce, "Cluster worker {} found a block", name),
+                            Err(problem) => warn!(event = "cluster_bad_solution", worker = %name, assignment, nonce = %nonce, "Ignoring a solution from cluster worker {}: {}", name, problem),
+                        }
+                        false
+                    }
+                    Ok(WorkerMessage::Progress { hashes, hashrate, best_difficulty }) => {
+                        coordinator.progress(worker_id, hashes, hashrate, best_difficulty);
+                        false
+                    }
+                    Ok(WorkerMessage::Hello { .. }) | Err(_) => {
+                        warn!(event = "cluster_invalid_message", worker = %name, message = %line, "Unexpected message from cluster worker {}: {}", name, line);
+                        break;
+                    }
+                }
+            }
+            changed = jobs.changed() => {
+                if changed.is_err() {
+                    break;
+                }
+                jobs.borrow_and_update();
+                true
+            }
+            _ = tokio::time::sleep_until(last_heard + timeout) => {
+                warn!(event = "cluster_worker_timeout", worker = %name, worker_id, "Cluster worker {} sent nothing for {}s, dropping it", name, WORKER_TIMEOUT_SECS);
+                break;
+            }
+        };
+        if next {
+            current = coordinator.assign(worker_id);
+            if let Some(assignment) = current.clone() {
+                connected = send(&mut writer, &CoordinatorMessage::Work { assignment: Box::new(assignment) }).await;
+            }
+        }
+    }
+
+    let (status, reissued) = coordinator.leave(worker_id);
+    if let Some(status) = status {
+        info!(
+            event = "cluster_worker_left",
+            worker = %name,
+            worker_id,
+            hashes = status.hashes,
+            finished = status.finished,
+            reissued,
+            "Cluster worker {} left after searching {} assignment(s); {} unfinished will be handed out again", name, status.finished, reissued
+        );
+    }
+}
+
+/// How a cluster worker reaches its coordinator
+#[derive(Debug, Clone)]
+pub struct WorkerOptions {
+    /// The coordinator's `host:port`, or `DISCOVER_COORDINATOR` to look for one with mDNS
+    /// before every connection
+    pub coordinator: String,
+    /// Shown in the coordinator's logs
+    pub name: String,
+    pub threads: usize,
+    pub token: Option<String>,
+}
+
+/// This machine's host name, or `worker` if it can't be found
+pub fn host_name() -> String {
+    std::fs::read_to_string("/etc/hostname").ok()
+        .map(|name| name.trim().to_string())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "worker".to_string())
+}
+
+/// Search assignments from the coordinator until `shutdown` resolves, reconnecting whenever
+/// the connection drops
+///
+/// Fails only when the coordinator refuses the worker, tagged `ExitCode::AuthorizationRejected`.
+pub async fn run_worker(options: WorkerOptions, shutdown: impl Future<Output = &'static str>) -> Result<()> {
+    info!(
+        event = "startup",
+        coordinator = %options.coordinator,
+        worker = %options.name,
+        threads = options.threads,
+        "Cluster worker {} with {} thread(s) for coordinator {}", options.name, options.threads, options.coordinator
+    );
+    tokio::pin!(shutdown);
+    loop {
+        let session = tokio::select! {
+            signal = &mut shutdown => {
+                warn!(event = "shutdown", signal, "Received {}, shutting down", signal);
+                return Ok(());
+            }
+            session = work_for(&options) => session,
+        };
+        if ExitCode::of(&session) == ExitCode::AuthorizationRejected {
+            return Err(session);
+        }
+        warn!(event = "coordinator_lost", "Lost the coordinator: {:#}; reconnecting in {}s", session, WORKER_RECONNECT_DELAY_SECS);
+        tokio::select! {
+            signal = &mut shutdown => {
+                warn!(event = "shutdown", signal, "Received {}, shutting down", signal);
+                return Ok(());
+            }
+            _ = tokio::time::sleep(Duration::from_secs(WORKER_RECONNECT_DELAY_SECS)) => {}
+        }
+    }
+}
+
+/// The assignment being searched
+struct Search {
+    assignment: u64,
+    search: RangeSearch,
+    results: mpsc::UnboundedReceiver<Result<Option<FoundBlock>>>,
+    /// Threads that have searched their part without a find
+    exhausted: usize,
+    /// Hashes already reported
+    counted: u64,
+}
+
+impl Search {
+    fn start(assignment: Assignment, threads: usize) -> Result<Self> {
+        let target = hex::decode(&assignment.target).context("Invalid target in assignment")?;
+        if target.len() != 32 {
+            bail!("Target {} in assignment isn't 32 bytes", assignment.target);
+        }
+        let work = Arc::new(RangeWork {
+            target,
+            job: assignment.job,
+            extranonce1: assignment.extranonce1,
+            extranonce2: assignment.extranonce2,
+            nonce_start: assignment.nonce_start,
+            nonce_end: assignment.nonce_end,
+        });
+        let (results_tx, results) = mpsc::unbounded_channel();
+        let search = RangeSearch::start(threads, work, results_tx)?;
+        Ok(Self { assignment: assignment.id, search, results, exhausted: 0, counted: 0 })
+    }
+
+    /// Hashes since the last call
+    fn new_hashes(&mut self) -> u64 {
+        let total = self.search.progress().hashes();
+        let new = total - self.counted;
+        self.counted = total;
+        new
+    }
+
+    /// Difficulty of the best hash since the last call, or 0
+    fn best_difficulty(&self) -> f64 {
+        self.search.progress().take_best_hash().map_or(0.0, |hash| hash_difficulty(&hash))
+    }
+
+    /// Stop the threads, returning the hashes and best difficulty not reported yet
+    fn stop(self) -> (u64, f64) {
+        let best = self.best_difficulty();
+        (self.search.join().hashes() - self.counted, best)
+    }
+}
+
+/// One connection to the coordinator, returning why it ended
+async fn work_for(options: &WorkerOptions) -> anyhow::Error {
+    let coordinator = if options.coordinator == DISCOVER_COORDINATOR {
+        match mdns::find_coordinator().await {
+            Ok(found) => {
+                info!(event = "coordinator_discovered", name = %found.name, coordinator = %found.addr, "Found cluster coordinator {} at {}", found.name, found.addr);
+                found.addr.to_string()
+            }
+            Err(e) => return e,
+        }
+    } else {
+        options.coordinator.clone()
+    };
+    let stream = match TcpStream::connect(&coordinator).await {
+        Ok(stream) => stream,
+        Err(e) => return anyhow::Error::from(e).context(format!("Failed to connect to {}", coordinator)),
+    };
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader);
+    let hello = WorkerMessage::Hello { name: options.name.clone(), threads: options.threads, token: options.token.clone() };
+    if let Err(e) = write_message(&mut writer, &hello).await {
+        return e;
+    }
+
+    let mut search: Option<Search> = None;
+    let mut unreported = 0u64;
+    let mut unreported_best = 0f64;
+    let mut last_report = Instant::now();
+    let report_interval = Duration::from_secs(WORKER_PROGRESS_INTERVAL_SECS);
+    let mut report = tokio::time::interval_at(Instant::now() + report_interval, report_interval);
+    loop {
+        let results = async {
+            match search.as_mut() {
+                Some(search) => search.results.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            line = next_line(&mut lines) => {
+                let line = match line {
+                    Ok(Some(line)) => line,
+                    Ok(None) => return anyhow::anyhow!("Connection closed by coordinator"),
+                    Err(e) => return e.into(),
+                };
+                match serde_json::from_str(&line) {
+                    Ok(CoordinatorMessage::Welcome { worker_id }) => {
+                        info!(event = "coordinator_connected", worker_id, "Joined the cluster at {} as worker {}", coordinator, worker_id);
+                    }
+                    Ok(CoordinatorMessage::Work { assignment }) => {
+                        if let Some(previous) = search.take() {
+                            let (hashes, best) = previous.stop();
+                            unreported += hashes;
+                            unreported_best = unreported_best.max(best);
+                        }
+                        info!(
+                            event = "assignment",
+                            assignment = assignment.id,
+                            job_id = %assignment.job.job_id,
+                            extranonce2 = %assignment.extranonce2,
+                            "Searching nonces {:08x}-{:08x} of extranonce2 {}", assignment.nonce_start, assignment.nonce_end, assignment.extranonce2
+                        );
+                        let id = assignment.id;
+                        match Search::start(*assignment, options.threads) {
+                            Ok(started) => search = Some(started),
+                            // Searching it anyway could hash forever without a chance of a find
+                            Err(e) => {
+                                warn!(event = "assignment_rejected", assignment = id, "Rejecting assignment {}: {:#}", id, e);
+                                let rejected = WorkerMessage::Rejected { assignment: id, reason: format!("{:#}", e) };
+                                if let Err(e) = write_message(&mut writer, &rejected).await {
+                                    return e;
+                                }
+                            }
+                        }
+                    }
+                    Ok(CoordinatorMessage::Refused { reason }) => {
+                        return fatal(ExitCode::AuthorizationRejected)(anyhow::anyhow!("Coordinator refused this worker: {}", reason));
+                    }
+                    Err(_) => warn!(event = "coordinator_invalid_message", message = %line, "Unexpected message from the coordinator: {}", line),
+                }
+            }
+            result = results => {
+                let Some(current) = search.as_mut() else {
+                    continue;
+                };
+                let assignment = current.assignment;
+                let message = match result {
+                    Some(Ok(Some(found))) => {
+                        warn!(event = "block_found", assignment, nonce = %found.nonce, hash = %hex::encode(&found.hash), "Found a block, reporting it to the coordinator");
+                        Some(WorkerMessage::Found { assignment, nonce: found.nonce })
+                    }
+                    Some(Ok(None)) => {
+                        current.exhausted += 1;
+                        (current.exhausted == current.search.threads()).then_some(WorkerMessage::Finished { assignment })
+                    }
+                    // A job that can't be hashed is given back as searched, to get the next one
+                    Some(Err(e)) => {
+                        warn!(event = "assignment_failed", assignment, "Can't search assignment {}: {:#}", assignment, e);
+                        Some(WorkerMessage::Finished { assignment })
+                    }
+                    None => None,
+                };
+                if let Some(message) = message {
+                    if matches!(message, WorkerMessage::Finished { .. }) {
+                        if let Some(finished) = search.take() {
+                            let (hashes, best) = finished.stop();
+                            unreported += hashes;
+                            unreported_best = unreported_best.max(best);
+                        }
+                    }
+                    if let Err(e) = write_message(&mut writer, &message).await {
+                        return e;
+                    }
+                }
+            }
+            _ = report.tick() => {
+                let hashes = unreported + search.as_mut().map_or(0, Search::new_hashes);
+                let best_difficulty = unreported_best.max(search.as_ref().map_or(0.0, Search::best_difficulty));
+                unreported = 0;
+                unreported_best = 0.0;
+                let hashrate = hashes as f64 / last_report.elapsed().as_secs_f64().max(f64::EPSILON);
+                last_report = Instant::now();
+                info!(event = "hashrate", hashrate = hashrate as u64, hashes, "Hash rate: {:>8} h/s | Hashes: {:>12}", hashrate as u64, hashes);
+                if let Err(e) = write_message(&mut writer, &WorkerMessage::Progress { hashes, hashrate, best_difficulty }).await {
+                    return e;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+//! Reading the coinbase transaction a pool's job builds
+//!
+//! Stratum pools send the coinbase split around the extranonces (`coinb1`, `coinb2`); its
+//! outputs are all in `coinb2`, so they don't depend on the extranonce2 a worker picks.
+
+use anyhow::{bail, Context, Result};
+use bitcoin::hashes::Hash;
+use bitcoin::{merkle_tree, Block, WitnessMerkleNode, Wtxid};
+
+use crate::stratum::MiningJob;
+
+/// Output script start of a segwit witness commitment: OP_RETURN, a 36-byte push, aa21a9ed
+pub const WITNESS_COMMITMENT_PREFIX: [u8; 6] = [0x6a, 0x24, 0xaa, 0x21, 0xa9, 0xed];
+/// The witness reserved value a found block's coinbase gets
+pub const WITNESS_RESERVED_VALUE: [u8; 32] = [0; 32];
+
+/// One output of the coinbase
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxOut {
+    /// In satoshis
+    pub value: u64,
+    pub script_pubkey: Vec<u8>,
+}
+
+/// What a block found on a job would pay out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Reward {
+    /// Subsidy plus the template's fees, across every output, in satoshis
+    pub total: u64,
+    /// The part of `total` paid to the payout address
+    pub to_address: u64,
+}
+
+impl Reward {
+    /// Add up the outputs, counting those with `script_pubkey` as paying the address
+    pub fn of(outputs: &[TxOut], script_pubkey: &[u8]) -> Self {
+        outputs.iter().fold(Reward::default(), |reward, output| Reward {
+            total: reward.total.saturating_add(output.value),
+            to_address: if output.script_pubkey == script_pubkey {
+                reward.to_address.saturating_add(output.value)
+            } else {
+                reward.to_address
+            },
+        })
+    }
+}
+
+/// Satoshis as bitcoin with all eight decimals, e.g. `3.12500000 BTC`
+pub fn format_btc(sats: u64) -> String {
+    format!("{}.{:08} BTC", sats / 100_000_000, sats % 100_000_000)
+}
+
+/// The outputs of the job's coinbase, with extranonce2 filled with zeros
+pub fn outputs(job: &MiningJob, extranonce1: &str, extranonce2_size: usize) -> Result<Vec<TxOut>> {
+    let coinbase = hex::decode(format!("{}{}{}{}", job.coinb1, extranonce1, "00".repeat(extranonce2_size), job.coinb2))
+        .context("Invalid coinbase hex")?;
+    parse_outputs(&coinbase)
+}
+
+/// How the witness commitment in a job's coinbase checks out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WitnessCheck {
+    /// The coinbase commits to no witness data
+    Absent,
+    /// The commitment is the one the block's transactions give
+    Matches,
+    /// The job has other transactions, which Stratum doesn't send, so their witnesses can't
+    /// be hashed
+    Unverifiable,
+    Mismatch { expected: String, found: String },
+}
+
+/// Check the job's witness commitment against the one its block would need
+///
+/// BIP141 counts the last output that looks like a commitment. Only a coinbase-only block
+/// can be checked: its witness root is the coinbase's all-zero wtxid alone.
+pub fn check_witness_commitment(job: &MiningJob, extranonce1: &str, extranonce2_size: usize) -> Result<WitnessCheck> {
+    let found = outputs(job, extranonce1, extranonce2_size)?
+        .into_iter()
+        .rev()
+        .find(|output| output.script_pubkey.len() >= 38 && output.script_pubkey.starts_with(&WITNESS_COMMITMENT_PREFIX));
+    let Some(found) = found else {
+        return Ok(WitnessCheck::Absent);
+    };
+    if !job.merkle_branch.is_empty() {
+        return Ok(WitnessCheck::Unverifiable);
+    }
+    let witness_root = merkle_tree::calculate_root([Wtxid::all_zeros()].into_iter())
+        .map(|root| WitnessMerkleNode::from_byte_array(root.to_byte_array()))
+        .expect("one leaf");
+    let expected = Block::compute_witness_commitment(&witness_root, &WITNESS_RESERVED_VALUE).to_byte_array();
+    let found = &found.script_pubkey[6..38];
+    if found == expected {
+        Ok(WitnessCheck::Matches)
+    } else {
+        Ok(WitnessCheck::Mismatch { expected: hex::encode(expected), found: hex::encode(found) })
+    }
+}
+
+/// The height of the block a job builds, from the BIP34 push that starts its coinbase script
+///
+/// The push is in `coinb1`, ahead of the extranonces, so no extranonce is needed.
+pub fn height(job: &MiningJob) -> Result<u64> {
+    let coinb1 = hex::decode(&job.coinb1).context("Invalid coinbase hex")?;
+    let mut reader = Reader { bytes: &coinb1, position: 0 };
+    reader.take(4)?;
+    if reader.compact_size()? != 1 {
+        bail!("coinbase doesn't have exactly one input");
+    }
+    reader.take(36)?;
+    reader.compact_size()?;
+    let push = reader.take(1)?[0] as usize;
+    if !(1..=8).contains(&push) {
+        bail!("coinbase script doesn't start with a height push");
+    }
+    let mut height = [0u8; 8];
+    height[..push].copy_from_slice(reader.take(push)?);
+    Ok(u64::from_le_bytes(height))
+}
+
+/// The outputs of a serialized transaction without witness data
+pub fn parse_outputs(transaction: &[u8]) -> Result<Vec<TxOut>> {
+    let mut reader = Reader { bytes: transaction, position: 0 };
+    reader.take(4)?;
+    let inputs = reader.compact_size()?;
+    for _ in 0..inputs {
+        // Previous output, then the script and the sequence
+        reader.take(36)?;
+        let script_length = reader.compact_size()?;
+        reader.take(script_length)?;
+        reader.take(4)?;
+    }
+    let count = reader.compact_size()?;
+    let mut outputs = Vec::new();
+    for _ in 0..count {
+        let value = u64::from_le_bytes(reader.take(8)?.try_into().expect("took 8 bytes"));
+        let script_length = reader.compact_size()?;
+        outputs.push(TxOut { value, script_pubkey: reader.take(script_length)?.to_vec() });
+    }
+    // Only the lock time may follow
+    if transaction.len() - reader.position != 4 {
+        bail!("coinbase doesn't end with a lock time after its outputs");
+    }
+    Ok(outputs)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8]> {
+        let Some(taken) = self.bytes.get(self.position..self.position.saturating_add(length)) else {
+            bail!("coinbase ends early at byte {}", self.bytes.len());
+        };
+        self.position += length;
+        Ok(taken)
+    }
+
+    fn compact_size(&mut self) -> Result<usize> {
+        let value = match self.take(1)?[0] {
+            0xfd => u16::from_le_bytes(self.take(2)?.try_into().expect("took 2 bytes")) as u64,
+            0xfe => u32::from_le_bytes(self.take(4)?.try_into().expect("took 4 bytes")) as u64,
+            0xff => u64::from_le_bytes(self.take(8)?.try_into().expect("took 8 bytes")),
+            small => small as u64,
+        };
+        usize::try_from(value).context("coinbase length out of range")
+    }
+}
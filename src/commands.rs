@@ -0,0 +1,550 @@
+use std::future::Future;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use anyhow::{Result, Context, anyhow, bail};
+
+use bitcoin_solo_miner::{address, config_file, console, logging, presets, self_test};
+use bitcoin_solo_miner::benchmark::{BenchmarkReport, HostInfo, RateSpread, Sha256Rates};
+use bitcoin_solo_miner::clock::RandomSource;
+use bitcoin_solo_miner::cluster::{self, WorkerOptions};
+use bitcoin_solo_miner::config::{DEFAULT_POOL_ADDRESS, LoadedConfig};
+use bitcoin_solo_miner::events::OutputMode;
+use bitcoin_solo_miner::exit_code::{fatal, ExitCode};
+use bitcoin_solo_miner::fleet::{self, FleetOptions};
+use bitcoin_solo_miner::hashing::{self, SHA256_IMPL};
+use bitcoin_solo_miner::logging::Verbosity;
+use bitcoin_solo_miner::notify::TELEGRAM_API;
+use bitcoin_solo_miner::stats::LifetimeStats;
+use bitcoin_solo_miner::stratum::MiningJob;
+use bitcoin_solo_miner::work::{HeaderTemplate, EXTRANONCE2_SIZE_BYTES};
+use bitcoin_solo_miner::worker::{JobWork, WorkerPool};
+
+// Per-check network timeout for `config-check`
+const CONFIG_CHECK_TIMEOUT_SECS: u64 = 10;
+// Typical merkle branch depth for a full block template
+const BENCHMARK_MERKLE_DEPTH: usize = 12;
+/// Longest each SHA-256 implementation is timed for in the benchmark's comparison
+const SHA256_COMPARISON_SECS: u64 = 5;
+/// Shown by `--print-config` in place of a secret
+const REDACTED: &str = "<redacted>";
+
+/// Hash a synthetic job on `threads` threads for `duration` and report the rate, also
+/// writing it as JSON to `report` if given
+pub fn benchmark(threads: usize, duration: Duration, report: Option<&Path>) -> Result<()> {
+    let job = benchmark_job();
+    let work = Arc::new(JobWork {
+        template: HeaderTemplate::new(&job, "f000000f", EXTRANONCE2_SIZE_BYTES)?,
+        job,
+        // An all-zero target can't be met, so the workers hash for the full duration
+        target: vec![0u8; 32],
+        rng: RandomSource::from_entropy(),
+    });
+    let (found_tx, _found_rx) = tokio::sync::mpsc::unbounded_channel();
+
+    println!("Benchmarking {} thread(s) for {}s...", threads, duration.as_secs());
+    let started = Instant::now();
+    let workers = WorkerPool::start(threads, work, found_tx)?;
+    // The rate over each second, to show how steady it was
+    let mut samples = Vec::new();
+    let (mut sampled_at, mut sampled_hashes) = (started, 0);
+    while started.elapsed() < duration {
+        std::thread::sleep(Duration::from_secs(1).min(duration.saturating_sub(started.elapsed())));
+        let hashes = workers.progress().hashes();
+        let interval = sampled_at.elapsed().as_secs_f64();
+        if interval >= 0.5 {
+            samples.push((hashes - sampled_hashes) as f64 / interval);
+        }
+        (sampled_at, sampled_hashes) = (Instant::now(), hashes);
+    }
+    let progress = workers.join();
+    let elapsed = started.elapsed().as_secs_f64();
+
+    let hashes = progress.hashes();
+    let hash_rate = hashes as f64 / elapsed;
+    println!(
+        "{} hashes in {:.1}s: {:.0} h/s ({:.0} h/s per thread)",
+        hashes,
+        elapsed,
+        hash_rate,
+        hash_rate / threads as f64
+    );
+    let spread = RateSpread::of(&samples);
+    if let Some(spread) = &spread {
+        println!(
+            "Per second: {:.0} to {:.0} h/s, standard deviation {:.0} h/s ({:.1}%)",
+            spread.min,
+            spread.max,
+            spread.stddev,
+            spread.coefficient_of_variation * 100.0
+        );
+    }
+    let sha256_rates = compare_sha256(duration.min(Duration::from_secs(SHA256_COMPARISON_SECS)));
+
+    if let Some(path) = report {
+        let report = BenchmarkReport {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            timestamp: chrono::Utc::now().timestamp().max(0) as u64,
+            host: HostInfo::detect(),
+            sha256: SHA256_IMPL.to_string(),
+            threads,
+            duration_secs: elapsed,
+            hashes,
+            hashrate: hash_rate,
+            hashrate_per_thread: hash_rate / threads as f64,
+            samples,
+            spread,
+            sha256_rates,
+        };
+        report.save(path)?;
+        println!("Report written to {}", path.display());
+    }
+    Ok(())
+}
+
+/// Double SHA-256s of an 80-byte header per second on one thread with `hash`
+fn sha256_rate(hash: fn(&[u8]) -> Vec<u8>, duration: Duration) -> f64 {
+    let mut header = [0u8; 80];
+    let started = Instant::now();
+    let mut hashes = 0u64;
+    while started.elapsed() < duration {
+        for nonce in 0..1024u32 {
+            header[76..].copy_from_slice(&nonce.to_le_bytes());
+            std::hint::black_box(hash(std::hint::black_box(&header)));
+        }
+        hashes += 1024;
+    }
+    hashes as f64 / started.elapsed().as_secs_f64()
+}
+
+/// Time the assembly SHA-256 against the portable one, when both are built in
+fn compare_sha256(duration: Duration) -> Sha256Rates {
+    #[cfg(sha256_asm)]
+    {
+        println!("Comparing SHA-256 implementations on one thread for {}s each...", duration.as_secs());
+        let portable = sha256_rate(hashing::double_sha256_portable, duration);
+        let asm = sha256_rate(hashing::double_sha256_asm, duration);
+        println!(
+            "asm {:.0} h/s vs portable {:.0} h/s: {:+.1}% (mining uses {})",
+            asm,
+            portable,
+            (asm / portable - 1.0) * 100.0,
+            SHA256_IMPL
+        );
+        Sha256Rates { portable, asm: Some(asm) }
+    }
+    #[cfg(not(sha256_asm))]
+    {
+        let portable = sha256_rate(hashing::double_sha256_portable, duration.min(Duration::from_secs(1)));
+        println!(
+            "SHA-256: {} ({:.0} h/s on one thread); build with `--features asm` to compare the assembly implementation",
+            SHA256_IMPL, portable
+        );
+        Sha256Rates { portable, asm: None }
+    }
+}
+
+/// Search assignments from the cluster coordinator at `coordinator` until `shutdown`,
+/// logging to the console only
+pub async fn worker(coordinator: &str, config: &LoadedConfig, output: OutputMode, no_color: bool, shutdown: impl Future<Output = &'static str>) -> Result<()> {
+    let color = console::use_color(no_color, output);
+    logging::init(config.log_format, config.verbosity, output, color, &config.timestamps, None, None, None);
+    self_test::run().await
+        .context("Hashing self-test failed, refusing to mine")
+        .map_err(fatal(ExitCode::SelfTestFailed))?;
+    let options = WorkerOptions {
+        coordinator: coordinator.to_string(),
+        name: cluster::host_name(),
+        threads: config.threads,
+        token: config.cluster_token.clone(),
+    };
+    cluster::run_worker(options, shutdown).await
+}
+
+/// Watch the configured fleet until `shutdown`, logging to the console only
+pub async fn fleet(config: &LoadedConfig, output: OutputMode, no_color: bool, shutdown: impl Future<Output = &'static str>) -> Result<()> {
+    let color = console::use_color(no_color, output);
+    logging::init(config.log_format, config.verbosity, output, color, &config.timestamps, None, None, None);
+    let options = FleetOptions {
+        instances: config.fleet_instances.clone(),
+        token: config.fleet_token.clone(),
+        alert_after: Duration::from_secs(config.fleet_alert_minutes * 60),
+        summary_every: (config.fleet_summary_minutes > 0).then(|| Duration::from_secs(config.fleet_summary_minutes * 60)),
+        telegram: config.telegram.clone(),
+    };
+    fleet::run(options, shutdown).await
+}
+
+fn benchmark_job() -> MiningJob {
+    MiningJob {
+        job_id: "benchmark".to_string(),
+        prevhash: "00".repeat(32),
+        coinb1: "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff20".to_string(),
+        coinb2: "ffffffff0100f2052a010000001976a914000000000000000000000000000000000000000088ac00000000".to_string(),
+        merkle_branch: (0..BENCHMARK_MERKLE_DEPTH).map(|i| format!("{:02x}", i).repeat(32)).collect(),
+        version: "20000000".to_string(),
+        nbits: "1d00ffff".to_string(),
+        ntime: "5f5e1000".to_string(),
+        clean_jobs: true,
+    }
+}
+
+/// Check an address, failing if it doesn't look valid
+pub fn validate_address(address: &str) -> Result<()> {
+    if address.is_empty() {
+        bail!("No address given and none configured");
+    }
+    let address_type = address::parse(address)
+        .with_context(|| format!("{} is not a valid Bitcoin address", address))?;
+    println!("{} is a valid {} address", address, address_type);
+    Ok(())
+}
+
+/// Validate every setting against the outside world and print a pass/fail report
+///
+/// Checks the address checksum, that the pool accepts TCP connections, that the
+/// Telegram bot token is accepted by `getMe` and that the log directory, block archive and
+/// state file locations are writable.
+/// Nothing is mined; the command fails if any check fails.
+pub async fn config_check(config: &LoadedConfig) -> Result<()> {
+    let mut failures = 0;
+    let mut report = |ok: bool, item: &str, detail: String| {
+        println!("[{}] {}: {}", if ok { "ok" } else { "FAIL" }, item, detail);
+        if !ok {
+            failures += 1;
+        }
+    };
+
+    match &config.config_path {
+        Some(path) => report(true, "config file", match &config.profile {
+            Some(profile) => format!("{} (profile {})", path.display(), profile),
+            None => path.display().to_string(),
+        }),
+        None => report(true, "config file", "none (environment and defaults only)".to_string()),
+    }
+
+    if config.address.is_empty() {
+        report(false, "address", "not configured".to_string());
+    } else if config.payout_addresses.is_empty() {
+        check_address(&mut report, "address", &config.address, None);
+    } else {
+        for payout in &config.payout_addresses {
+            check_address(&mut report, "payout address", &payout.address, Some(payout.weight));
+        }
+        println!("[ok] address rotation: {:?}", config.address_rotation);
+    }
+
+    // A preset is checked by the address it stands for
+    let pool = presets::address(&config.pool);
+    match check_pool(&pool).await {
+        Ok(elapsed) => report(true, "pool", format!("{} reachable in {} ms", pool, elapsed.as_millis())),
+        Err(e) => report(false, "pool", format!("{}: {:#}", pool, e)),
+    }
+
+    println!("[ok] threads: {}", config.threads);
+    println!("[ok] verbosity: {}", config.verbosity.name());
+    println!("[ok] log format: {:?}", config.log_format);
+
+    let state_dir = config.state_file.parent().unwrap_or(Path::new("."));
+    for (item, dir) in [("log directory", &config.log_dir), ("block archive", &config.blocks_dir)] {
+        match check_dir(dir) {
+            Ok(()) => report(true, item, format!("{} (writable)", dir.display())),
+            Err(e) => report(false, item, format!("{}: {:#}", dir.display(), e)),
+        }
+    }
+    match check_dir(state_dir) {
+        Ok(()) => report(true, "state file", format!("{} (directory writable)", config.state_file.display())),
+        Err(e) => report(false, "state file", format!("{}: {:#}", config.state_file.display(), e)),
+    }
+
+    match &config.telegram {
+        None => println!("[ok] telegram: disabled"),
+        Some(telegram) => match check_telegram(&telegram.bot_token).await {
+            Ok(bot) => report(true, "telegram", format!("bot @{}, notifying user {}", bot, telegram.user_id)),
+            Err(e) => report(false, "telegram", format!("{:#}", e)),
+        },
+    }
+
+    println!("[ok] OTLP endpoint: {}", config.otlp_endpoint.as_deref().unwrap_or("disabled"));
+    println!("[ok] error reporting: {}", if config.sentry_dsn.is_some() { "configured" } else { "disabled" });
+    println!("[ok] system log: {}", config.system_log.map(|kind| format!("{:?}", kind)).unwrap_or_else(|| "disabled".to_string()));
+
+    if failures > 0 {
+        bail!("Configuration check failed ({} problem(s))", failures);
+    }
+    println!("Configuration OK");
+    Ok(())
+}
+
+fn check_address(report: &mut impl FnMut(bool, &str, String), item: &str, candidate: &str, weight: Option<u32>) {
+    let weight = weight.map(|w| format!(", weight {}", w)).unwrap_or_default();
+    match address::parse(candidate) {
+        Ok(address_type) => report(true, item, format!("{} ({}, checksum valid{})", candidate, address_type, weight)),
+        Err(e) => report(false, item, format!("{}: {}", candidate, e)),
+    }
+}
+
+/// Open (and immediately close) a TCP connection to the pool
+async fn check_pool(pool: &str) -> Result<Duration> {
+    let started = Instant::now();
+    tokio::time::timeout(Duration::from_secs(CONFIG_CHECK_TIMEOUT_SECS), tokio::net::TcpStream::connect(pool))
+        .await
+        .context("connection timed out")?
+        .context("connection failed")?;
+    Ok(started.elapsed())
+}
+
+/// Create a directory if needed and prove a file can be written there
+fn check_dir(dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir).context("cannot create directory")?;
+    let probe = dir.join(".write_test");
+    std::fs::write(&probe, b"").context("directory is not writable")?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+/// Ask Telegram who the bot token belongs to, returning the bot's username
+async fn check_telegram(bot_token: &str) -> Result<String> {
+    let response: serde_json::Value = reqwest::Client::new()
+        .get(format!("{}{}/getMe", TELEGRAM_API, bot_token))
+        .timeout(Duration::from_secs(CONFIG_CHECK_TIMEOUT_SECS))
+        .send()
+        .await
+        // reqwest nests the same cause several times; the innermost one is enough
+        .map_err(|e| anyhow!("Telegram API unreachable: {}", anyhow::Error::from(e).root_cause()))?
+        .json()
+        .await
+        .context("unexpected Telegram API response")?;
+    if response["ok"].as_bool() != Some(true) {
+        bail!(
+            "bot token rejected: {}",
+            response["description"].as_str().unwrap_or("unknown error")
+        );
+    }
+    Ok(response["result"]["username"].as_str().unwrap_or("unknown").to_string())
+}
+
+/// Print the lifetime stats from the state file as JSON
+pub fn export_stats(state_file: &Path) -> Result<()> {
+    let stats = LifetimeStats::load(state_file)?;
+    println!("{}", serde_json::to_string_pretty(&stats)?);
+    Ok(())
+}
+
+/// Print the environment variable that overrides each config file setting
+pub fn env_vars() -> Result<()> {
+    for (section, key) in config_file::SETTINGS {
+        println!("{:<36} [{}] {}", config_file::env_var_name(section, key), section, key);
+    }
+    Ok(())
+}
+
+/// Print the effective configuration as a config file, with secrets redacted
+///
+/// Every setting is shown, defaults included, so the output answers "where did this value
+/// come from?" when compared against the config file and environment.
+pub fn print_config(config: &LoadedConfig, dry_run: bool) {
+    let quote = |value: &str| toml::Value::String(value.to_string()).to_string();
+    let redact = |secret: Option<&str>| match secret {
+        Some(secret) if !secret.is_empty() => quote(REDACTED),
+        _ => quote(""),
+    };
+    let path = |path: &Path| quote(&path.display().to_string());
+
+    println!("# Effective configuration: defaults, config file, environment and command line merged");
+    println!("# Config file: {}", config.config_path.as_deref().map_or("none".to_string(), |p| p.display().to_string()));
+    println!("# Profile: {}", config.profile.as_deref().unwrap_or("none"));
+    if dry_run {
+        println!("# Dry run: solutions won't be submitted");
+    }
+
+    let payout_addresses = config.payout_addresses.iter()
+        .map(|payout| format!("{}:{}", payout.address, payout.weight))
+        .collect::<Vec<_>>()
+        .join(", ");
+    println!("\n[miner]");
+    println!("wallet_address = {}", quote(&config.address));
+    println!("payout_addresses = {}", quote(&payout_addresses));
+    println!("address_rotation = {}", quote(config.address_rotation.name()));
+    println!("payout_check = {}", quote(config.payout_check.name()));
+    println!("pool = {}", quote(&config.pool));
+    println!("threads = {}", config.threads);
+    println!("verbosity = {}", quote(config.verbosity.name()));
+    println!("log_format = {}", quote(config.log_format.name()));
+    println!("watch_config = {}", config.watch_config);
+    println!("low_bandwidth = {}", config.low_bandwidth);
+    println!("submit_timeout = {}", config.submit_policy.timeout.as_secs());
+    println!("submit_retries = {}", config.submit_policy.retries);
+    println!("difficulty_change_factor = {}", config.difficulty_change_factor);
+    println!("stale_tip_blocks = {}", config.stale_tip_blocks);
+    println!("prompt_timeout = {}", config.prompt_timeout.map_or(0, |timeout| timeout.as_secs()));
+
+    let telegram = config.telegram.as_ref();
+    println!("\n[telegram]");
+    println!("bot_token = {}", redact(telegram.map(|t| t.bot_token.as_str())));
+    println!("user_id = {}", quote(telegram.map_or("", |t| t.user_id.as_str())));
+    println!("notify_summary = {}", telegram.is_some_and(|t| t.notify_summary));
+    println!("notify_weekly = {}", telegram.is_some_and(|t| t.notify_weekly));
+    println!("commands = {}", telegram.is_some_and(|t| t.commands));
+    println!("notify_pool_messages = {}", telegram.is_some_and(|t| t.notify_pool_messages));
+    println!("notify_network_blocks = {}", telegram.is_some_and(|t| t.notify_network_blocks));
+
+    println!("\n[logging]");
+    println!("log_dir = {}", path(&config.log_dir));
+    println!("main_log = {}", config.main_log);
+    println!("max_size_mb = {}", config.log_rotation.max_bytes / (1024 * 1024));
+    println!("rotate_daily = {}", config.log_rotation.daily);
+    println!("max_files = {}", config.log_rotation.max_files);
+    println!("system_log = {}", quote(config.system_log.map_or("none", |log| log.name())));
+    println!("timestamp_format = {}", quote(config.timestamps.pattern()));
+    println!("timezone = {}", quote(&config.timestamps.zone().name()));
+
+    println!("\n[paths]");
+    println!("state_file = {}", path(&config.state_file));
+    println!("blocks_dir = {}", path(&config.blocks_dir));
+
+    println!("\n[hooks]");
+    println!("on_start = {}", quote(config.hooks.on_start.as_deref().unwrap_or("")));
+    println!("on_block_found = {}", quote(config.hooks.on_block_found.as_deref().unwrap_or("")));
+    println!("on_disconnect = {}", quote(config.hooks.on_disconnect.as_deref().unwrap_or("")));
+
+    println!("\n[scripting]");
+    println!("script = {}", quote(&config.script.as_deref().map_or(String::new(), |p| p.display().to_string())));
+    println!("tick_secs = {}", config.script_tick.as_secs());
+
+    println!("\n[api]");
+    println!("grpc_listen = {}", quote(&config.grpc_listen.map_or(String::new(), |listen| listen.to_string())));
+    println!("control_socket = {}", quote(&config.control_socket.as_ref().map_or(String::new(), |socket| socket.to_string())));
+    println!("token = {}", redact(config.api_token.as_deref()));
+
+    println!("\n[proxy]");
+    println!("listen = {}", quote(&config.proxy_listen.map_or(String::new(), |listen| listen.to_string())));
+    println!("difficulty = {}", config.proxy_difficulty);
+
+    println!("\n[cluster]");
+    println!("listen = {}", quote(&config.cluster_listen.map_or(String::new(), |listen| listen.to_string())));
+    println!("token = {}", redact(config.cluster_token.as_deref()));
+    println!("redis = {}", redact(config.cluster_redis.as_ref().map(|_| REDACTED)));
+    println!("mdns = {}", config.cluster_mdns);
+
+    println!("\n[fleet]");
+    let instances: Vec<String> = config.fleet_instances.iter().map(|instance| format!("{}={}", instance.name, instance.addr)).collect();
+    println!("instances = {}", quote(&instances.join(",")));
+    println!("token = {}", redact(config.fleet_token.as_deref()));
+    println!("alert_minutes = {}", config.fleet_alert_minutes);
+    println!("summary_minutes = {}", config.fleet_summary_minutes);
+
+    println!("\n[telemetry]");
+    println!("otlp_endpoint = {}", quote(config.otlp_endpoint.as_deref().unwrap_or("")));
+
+    println!("\n[error_reporting]");
+    println!("sentry_dsn = {}", redact(config.sentry_dsn.as_deref()));
+}
+
+/// Walk through the essential settings and write them to a new config file
+pub fn setup(path: &Path, force: bool) -> Result<()> {
+    if !atty::is(atty::Stream::Stdin) {
+        bail!("setup is interactive; run it from a terminal");
+    }
+    println!("Bitcoin Solo Miner setup - press Enter to accept the [default]\n");
+    if path.exists() && !force && !confirm(&format!("{} already exists. Overwrite it?", path.display()), false)? {
+        bail!("Setup cancelled, {} left unchanged", path.display());
+    }
+
+    let address = loop {
+        let address = prompt("Bitcoin address for mining rewards", None)?;
+        match address::parse(&address) {
+            Ok(address_type) => {
+                println!("  {} address", address_type);
+                break address;
+            }
+            Err(e) => println!("  '{}' is not a valid Bitcoin address ({}), try again", address, e),
+        }
+    };
+
+    let pool = prompt("Pool (host:port, or ckpool for the closest ckpool server)", Some(DEFAULT_POOL_ADDRESS))?;
+
+    let cpus = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let threads = loop {
+        let answer = prompt(&format!("Hashing threads (this machine has {} CPUs)", cpus), Some(&cpus.to_string()))?;
+        match answer.parse::<usize>() {
+            Ok(threads) if threads >= 1 => break threads,
+            _ => println!("  Enter a whole number of at least 1"),
+        }
+    };
+
+    let verbosity = loop {
+        let answer = prompt("Verbosity (silent, summary, normal, verbose, trace)", Some(Verbosity::default().name()))?;
+        match Verbosity::parse(&answer) {
+            Ok(verbosity) => break verbosity,
+            Err(e) => println!("  {}", e),
+        }
+    };
+
+    let telegram = if confirm("Send Telegram notifications?", false)? {
+        println!("  Create a bot with @BotFather and get your user id from @userinfobot");
+        let bot_token = prompt("Telegram bot token", None)?;
+        let user_id = prompt("Telegram user id", None)?;
+        Some((bot_token, user_id))
+    } else {
+        None
+    };
+
+    let quote = |value: &str| toml::Value::String(value.to_string()).to_string();
+    let mut contents = format!(
+        "# Written by `bitcoin-solo-miner setup`\n\
+        # Environment variables and command-line flags override these values.\n\
+        \n\
+        [miner]\n\
+        wallet_address = {}\n\
+        pool = {}\n\
+        threads = {}\n\
+        verbosity = \"{}\"\n\
+        log_format = \"text\"\n",
+        quote(&address),
+        quote(&pool),
+        threads,
+        verbosity.name()
+    );
+    match telegram {
+        Some((bot_token, user_id)) => contents.push_str(&format!(
+            "\n[telegram]\nbot_token = {}\nuser_id = {}\nnotify_summary = true\n",
+            quote(&bot_token),
+            quote(&user_id)
+        )),
+        None => contents.push_str("\n# [telegram]\n# bot_token = \"\"\n# user_id = \"\"\n"),
+    }
+
+    std::fs::write(path, contents)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    println!("\nConfiguration written to {}", path.display());
+    println!("Start mining with: bitcoin-solo-miner --config {}", path.display());
+    Ok(())
+}
+
+/// Ask a question on stdin, returning the default for an empty answer
+fn prompt(question: &str, default: Option<&str>) -> Result<String> {
+    loop {
+        match default {
+            Some(default) => print!("{} [{}]: ", question, default),
+            None => print!("{}: ", question),
+        }
+        std::io::stdout().flush()?;
+        let mut input = String::new();
+        if std::io::stdin().read_line(&mut input)? == 0 {
+            bail!("Setup cancelled (end of input)");
+        }
+        let answer = input.trim();
+        match (answer.is_empty(), default) {
+            (false, _) => return Ok(answer.to_string()),
+            (true, Some(default)) => return Ok(default.to_string()),
+            (true, None) => println!("  A value is required"),
+        }
+    }
+}
+
+fn confirm(question: &str, default: bool) -> Result<bool> {
+    let answer = prompt(&format!("{} (y/n)", question), Some(if default { "y" } else { "n" }))?;
+    Ok(matches!(answer.to_lowercase().as_str(), "y" | "yes"))
+}
@@ -0,0 +1,600 @@
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use anyhow::{Result, Context, anyhow};
+
+use crate::activity::ActivitySettings;
+use crate::btc_price::PriceSettings;
+use crate::chain::DEFAULT_STALE_TIP_BLOCKS;
+use crate::config_file::FileConfig;
+use crate::electricity::{ElectricitySettings, PriceProvider};
+use crate::error::Recovery;
+use crate::failover::{self, FailoverSettings, DEFAULT_BLACKLIST_AFTER, DEFAULT_BLACKLIST_MINUTES};
+use crate::fleet::FleetInstance;
+use crate::hooks::Hooks;
+use crate::log_rotation::RotationPolicy;
+use crate::logging::{LogFormat, Verbosity};
+use crate::notify::TelegramConfig;
+use crate::payout::{self, AddressRotation, PayoutAddress, PayoutCheck};
+use crate::power::{PowerSettings, PowerSource};
+use crate::quiet::{self, QuietSchedule};
+use crate::redis::RedisUrl;
+use crate::rpc::ControlSocket;
+use crate::solar::{SolarSettings, DEFAULT_SOLAR_POLL_SECS};
+use crate::submit::{SubmitPolicy, DEFAULT_SUBMIT_RETRIES, DEFAULT_SUBMIT_TIMEOUT_SECS};
+use crate::system_log::SystemLog;
+use crate::timestamp::{TimeZone, Timestamps};
+use crate::vardiff::DEFAULT_DIFFICULTY_CHANGE_FACTOR;
+use crate::{paths, secrets};
+
+pub const DEFAULT_POOL_ADDRESS: &str = "solo.ckpool.org:3333";
+/// Config file written by `setup` and the first-run template
+pub const SETUP_CONFIG_FILE_NAME: &str = "config.toml";
+pub const PROFILE_ENV_VAR: &str = "MINER_PROFILE";
+pub const STATE_FILE_NAME: &str = "miner_state.json";
+pub const DEFAULT_SCRIPT_TICK_SECS: u64 = 60;
+pub const DEFAULT_PROXY_DIFFICULTY: u64 = 1024;
+pub const DEFAULT_FLEET_ALERT_MINUTES: u64 = 20;
+pub const DEFAULT_FLEET_SUMMARY_MINUTES: u64 = 60;
+/// Commented config file written on a non-interactive first run
+const CONFIG_TEMPLATE: &str = include_str!("config_template.toml");
+
+/// Settings given on the command line, which take precedence over everything else
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    pub address: Option<String>,
+    pub pool: Option<String>,
+    pub threads: Option<usize>,
+    pub verbosity: Option<Verbosity>,
+    /// Deprecated: the same as `Verbosity::Summary` when `verbosity` isn't set
+    pub quiet: bool,
+    /// Config file to read instead of looking for config.toml/config.ini
+    pub config: Option<PathBuf>,
+    pub profile: Option<String>,
+    pub prompt_timeout: Option<u64>,
+}
+
+/// Settings resolved from the command line, environment variables and the config file
+#[derive(Debug, Clone)]
+pub struct LoadedConfig {
+    pub address: String,
+    /// Addresses to rotate between; empty means just `address`
+    pub payout_addresses: Vec<PayoutAddress>,
+    pub address_rotation: AddressRotation,
+    /// What to do when a job's coinbase doesn't pay the address
+    pub payout_check: PayoutCheck,
+    pub pool: String,
+    pub threads: usize,
+    pub verbosity: Verbosity,
+    pub telegram: Option<TelegramConfig>,
+    pub log_format: LogFormat,
+    pub otlp_endpoint: Option<String>,
+    pub sentry_dsn: Option<String>,
+    pub main_log: bool,
+    pub log_dir: PathBuf,
+    pub log_rotation: RotationPolicy,
+    pub system_log: Option<SystemLog>,
+    pub state_file: PathBuf,
+    pub blocks_dir: PathBuf,
+    pub timestamps: Timestamps,
+    pub config_path: Option<PathBuf>,
+    /// Config file profile in use
+    pub profile: Option<String>,
+    pub watch_config: bool,
+    /// How long the address prompt waits for input; `None` waits forever
+    pub prompt_timeout: Option<Duration>,
+    /// Cut optional network traffic, for metered connections
+    pub low_bandwidth: bool,
+    /// How long to wait for the pool to answer a submit, and how often to resend it
+    pub submit_policy: SubmitPolicy,
+    /// Report share difficulty changes by more than this factor; 1 or less never does
+    pub difficulty_change_factor: f64,
+    /// Report the pool's tip drifting more than this many blocks from the explorer's; 0 never does
+    pub stale_tip_blocks: u64,
+    pub hooks: Hooks,
+    /// Rhai script to run alongside the miner
+    pub script: Option<PathBuf>,
+    /// Interval of the script's `on_tick()`
+    pub script_tick: Duration,
+    /// Where the gRPC API listens, if enabled
+    pub grpc_listen: Option<SocketAddr>,
+    /// Where the JSON-RPC control socket listens, if enabled
+    pub control_socket: Option<ControlSocket>,
+    /// Bearer token the APIs require
+    pub api_token: Option<String>,
+    /// Where the Stratum proxy accepts LAN miners, if enabled
+    pub proxy_listen: Option<SocketAddr>,
+    /// Share difficulty the proxy sets for its miners
+    pub proxy_difficulty: u64,
+    /// Where the cluster coordinator accepts workers, if enabled
+    pub cluster_listen: Option<SocketAddr>,
+    /// Secret cluster workers must present
+    pub cluster_token: Option<String>,
+    /// Redis server keeping the coordinator's state, if enabled
+    pub cluster_redis: Option<RedisUrl>,
+    /// Advertise the coordinator with mDNS, for workers started with `--worker auto`
+    pub cluster_mdns: bool,
+    /// Miners the `fleet` command watches
+    pub fleet_instances: Vec<FleetInstance>,
+    /// API token of the watched miners
+    pub fleet_token: Option<String>,
+    pub fleet_alert_minutes: u64,
+    /// 0 means no summaries
+    pub fleet_summary_minutes: u64,
+    /// Price-aware mining, if a provider is configured
+    pub electricity: Option<ElectricitySettings>,
+    /// Thread scaling to surplus solar power, if an endpoint is configured
+    pub solar: Option<SolarSettings>,
+    /// Backing off while the machine is in use, if enabled
+    pub activity: Option<ActivitySettings>,
+    /// Daily windows in which hashing pauses, if any
+    pub quiet: Option<QuietSchedule>,
+    /// Power use and cost accounting, if a draw per thread or RAPL is configured
+    pub power: Option<PowerSettings>,
+    /// Backup pools, if any are configured
+    pub failover: Option<FailoverSettings>,
+    /// Where the bitcoin price for fiat reward values comes from, if anywhere
+    pub btc_price: Option<PriceSettings>,
+}
+
+/// Why the configuration couldn't be loaded
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("Config file not found: {}", .0.display())]
+    NotFound(PathBuf),
+    #[error("Profile '{0}' requested but no config file was found")]
+    ProfileWithoutFile(String),
+    /// A `file:`, `cmd:` or `keyring:` secret reference couldn't be resolved
+    #[error("Failed to resolve the {setting}")]
+    Secret {
+        setting: &'static str,
+        #[source]
+        source: anyhow::Error,
+    },
+    /// A setting in the file, environment or flags has an unusable value
+    #[error(transparent)]
+    Invalid(#[from] anyhow::Error),
+}
+
+impl ConfigError {
+    /// The same settings fail the same way on every attempt
+    pub fn recovery(&self) -> Recovery {
+        Recovery::Abort
+    }
+}
+
+/// Load configuration from command-line flags, environment variables and the config file
+/// Precedence: command line > environment variables > config file
+pub fn load_config(overrides: &ConfigOverrides) -> Result<LoadedConfig, ConfigError> {
+    // Defaults, overridden by the config file, then the environment
+    let mut address = String::new();
+    let mut payout_addresses = String::new();
+    let mut address_rotation = String::new();
+    let mut payout_check = String::new();
+    let mut pool = String::new();
+    let mut threads = 1;
+    let mut quiet_mode = false;
+    let mut verbosity = String::new();
+    let mut telegram_token = String::new();
+
+    let mut telegram_user_id = String::new();
+    let mut telegram_notify_summary = true;
+    let mut telegram_notify_weekly = true;
+    let mut telegram_commands = false;
+    let mut telegram_notify_pool_messages = false;
+    let mut telegram_notify_network_blocks = false;
+    let mut log_format = String::new();
+    let mut otlp_endpoint = String::new();
+    let mut sentry_dsn = String::new();
+    let mut main_log = false;
+    let mut log_dir = String::new();
+    let mut log_rotation = RotationPolicy::default();
+    let mut system_log = String::new();
+    let mut timestamp_format = String::new();
+    let mut timezone = String::new();
+    let mut state_file = String::new();
+    let mut blocks_dir = String::new();
+    let mut watch_config = true;
+    let mut prompt_timeout = 0;
+    let mut low_bandwidth = false;
+    let mut submit_timeout = DEFAULT_SUBMIT_TIMEOUT_SECS;
+    let mut submit_retries = DEFAULT_SUBMIT_RETRIES as u64;
+    let mut difficulty_change_factor = DEFAULT_DIFFICULTY_CHANGE_FACTOR;
+    let mut stale_tip_blocks = DEFAULT_STALE_TIP_BLOCKS;
+    
+    // An explicit --config must exist; the default config files are optional
+    let config_path = match &overrides.config {
+        Some(path) => {
+            if !path.exists() {
+                return Err(ConfigError::NotFound(path.clone()));
+            }
+            Some(path.clone())
+        }
+        None => FileConfig::find_default(),
+    };
+    let file = match &config_path {
+        Some(config_path) => FileConfig::load(config_path)?,
+        None => FileConfig::default(),
+    };
+    let profile = overrides.profile.clone()
+        .or_else(|| std::env::var(PROFILE_ENV_VAR).ok())
+        .filter(|name| !name.is_empty());
+    let file = match &profile {
+        Some(name) if config_path.is_none() => return Err(ConfigError::ProfileWithoutFile(name.clone())),
+        Some(name) => file.with_profile(name)?,
+        None => file,
+    };
+    // MINER_* variables win over the older unprefixed names
+    let file = file.overlay(legacy_env_config()).overlay(FileConfig::from_env()?);
+    let miner = file.miner;
+    if let Some(value) = miner.wallet_address {
+        address = value;
+    }
+    if let Some(value) = miner.payout_addresses {
+        payout_addresses = value;
+    }
+    if let Some(value) = miner.address_rotation {
+        address_rotation = value;
+    }
+    if let Some(value) = miner.payout_check {
+        payout_check = value;
+    }
+    if let Some(value) = miner.pool {
+        pool = value;
+    }
+    if let Some(value) = miner.threads {
+        threads = value;
+    }
+    if let Some(value) = miner.quiet_mode {
+        quiet_mode = value;
+    }
+    if let Some(value) = miner.verbosity {
+        verbosity = value;
+    }
+    if let Some(value) = miner.log_format {
+        log_format = value;
+    }
+    if let Some(value) = miner.watch_config {
+        watch_config = value;
+    }
+    if let Some(value) = miner.prompt_timeout {
+        prompt_timeout = value;
+    }
+    if let Some(value) = miner.low_bandwidth {
+        low_bandwidth = value;
+    }
+    if let Some(value) = miner.submit_timeout {
+        submit_timeout = value;
+    }
+    if let Some(value) = miner.submit_retries {
+        submit_retries = value;
+    }
+    if let Some(value) = miner.difficulty_change_factor {
+        difficulty_change_factor = value;
+    }
+    if let Some(value) = miner.stale_tip_blocks {
+        stale_tip_blocks = value;
+    }
+    if let Some(value) = file.telegram.bot_token {
+        telegram_token = value;
+    }
+    if let Some(value) = file.telegram.user_id {
+        telegram_user_id = value;
+    }
+    if let Some(value) = file.telegram.notify_summary {
+        telegram_notify_summary = value;
+    }
+    if let Some(value) = file.telegram.notify_weekly {
+        telegram_notify_weekly = value;
+    }
+    if let Some(value) = file.telegram.commands {
+        telegram_commands = value;
+    }
+    if let Some(value) = file.telegram.notify_pool_messages {
+        telegram_notify_pool_messages = value;
+    }
+    if let Some(value) = file.telegram.notify_network_blocks {
+        telegram_notify_network_blocks = value;
+    }
+    if let Some(value) = file.telemetry.otlp_endpoint {
+        otlp_endpoint = value;
+    }
+    if let Some(value) = file.error_reporting.sentry_dsn {
+        sentry_dsn = value;
+    }
+    let logging = file.logging;
+    if let Some(value) = logging.main_log {
+        main_log = value;
+    }
+    if let Some(value) = logging.log_dir {
+        log_dir = value;
+    }
+    if let Some(size_mb) = logging.max_size_mb {
+        log_rotation.max_bytes = size_mb * 1024 * 1024;
+    }
+    if let Some(daily) = logging.rotate_daily {
+        log_rotation.daily = daily;
+    }
+    if let Some(max_files) = logging.max_files {
+        log_rotation.max_files = max_files;
+    }
+    if let Some(value) = logging.system_log {
+        system_log = value;
+    }
+    if let Some(value) = logging.timestamp_format {
+        timestamp_format = value;
+    }
+    if let Some(value) = logging.timezone {
+        timezone = value;
+    }
+    if let Some(value) = file.paths.state_file {
+        state_file = value;
+    }
+    if let Some(value) = file.paths.blocks_dir {
+        blocks_dir = value;
+    }
+    let script = file.scripting.script.filter(|s| !s.is_empty()).map(PathBuf::from);
+    let script_tick = Duration::from_secs(file.scripting.tick_secs.unwrap_or(DEFAULT_SCRIPT_TICK_SECS).max(1));
+    let grpc_listen = file.api.grpc_listen
+        .filter(|listen| !listen.is_empty())
+        .map(|listen| listen.parse::<SocketAddr>().with_context(|| format!("Invalid grpc_listen '{}', expected host:port", listen)))
+        .transpose()?;
+    let control_socket = file.api.control_socket.filter(|socket| !socket.is_empty()).map(|socket| ControlSocket::parse(&socket));
+    let api_token = file.api.token.unwrap_or_default();
+    let proxy_listen = file.proxy.listen
+        .filter(|listen| !listen.is_empty())
+        .map(|listen| listen.parse::<SocketAddr>().with_context(|| format!("Invalid proxy listen '{}', expected host:port", listen)))
+        .transpose()?;
+    let proxy_difficulty = file.proxy.difficulty.unwrap_or(DEFAULT_PROXY_DIFFICULTY).max(1);
+    let cluster_listen = file.cluster.listen
+        .filter(|listen| !listen.is_empty())
+        .map(|listen| listen.parse::<SocketAddr>().with_context(|| format!("Invalid cluster listen '{}', expected host:port", listen)))
+        .transpose()?;
+    let cluster_token = file.cluster.token.unwrap_or_default();
+    let cluster_redis = file.cluster.redis.unwrap_or_default();
+    let cluster_mdns = file.cluster.mdns.unwrap_or(false);
+    let fleet_instances = FleetInstance::parse_list(&file.fleet.instances.unwrap_or_default())?;
+    let fleet_token = file.fleet.token.unwrap_or_default();
+    let fleet_alert_minutes = file.fleet.alert_minutes.unwrap_or(DEFAULT_FLEET_ALERT_MINUTES).max(1);
+    let fleet_summary_minutes = file.fleet.summary_minutes.unwrap_or(DEFAULT_FLEET_SUMMARY_MINUTES);
+    let electricity_provider = file.electricity.provider.unwrap_or_default();
+    let electricity_token = file.electricity.token.unwrap_or_default();
+    let electricity_tariff = file.electricity.tariff.unwrap_or_default();
+    let electricity_country = file.electricity.country.unwrap_or_default();
+    let electricity_max_price = file.electricity.max_price;
+    let electricity_watts = file.electricity.watts.unwrap_or(0);
+    let solar_url = file.solar.url.filter(|url| !url.is_empty());
+    let solar_field = file.solar.field.filter(|field| !field.is_empty());
+    let solar_watts_per_thread = file.solar.watts_per_thread;
+    let solar_max_threads = file.solar.max_threads;
+    let solar_poll = Duration::from_secs(file.solar.poll_secs.unwrap_or(DEFAULT_SOLAR_POLL_SECS).max(1));
+    let activity = file.activity.idle_minutes.map(|idle_minutes| ActivitySettings {
+        idle: Duration::from_secs(idle_minutes * 60),
+        max_load: file.activity.max_load,
+        busy_threads: file.activity.busy_threads.unwrap_or(0),
+    });
+    let quiet_windows = quiet::parse_windows(&file.quiet.windows.unwrap_or_default())?;
+    let quiet = if quiet_windows.is_empty() {
+        None
+    } else {
+        Some(QuietSchedule {
+            windows: quiet_windows,
+            zone: TimeZone::parse(file.quiet.timezone.as_deref().unwrap_or("local"))?,
+        })
+    };
+    let power_source = match (file.power.rapl.unwrap_or(false), file.power.watts_per_thread) {
+        (true, _) => Some(PowerSource::Rapl),
+        (false, Some(watts_per_thread)) => Some(PowerSource::Estimate { watts_per_thread: watts_per_thread.max(0.0) }),
+        (false, None) => None,
+    };
+    let power = power_source.map(|source| PowerSettings { source, price: file.power.price });
+    let btc_price = PriceSettings::parse(&file.btc_price.source.unwrap_or_default(), &file.btc_price.currency.unwrap_or_default())?;
+    let failover_pools = failover::parse_pools(&file.failover.pools.unwrap_or_default());
+    let failover = (!failover_pools.is_empty()).then(|| FailoverSettings {
+        pools: failover_pools,
+        blacklist_after: file.failover.blacklist_after.unwrap_or(DEFAULT_BLACKLIST_AFTER as u64) as u32,
+        cooldown: Duration::from_secs(file.failover.blacklist_minutes.unwrap_or(DEFAULT_BLACKLIST_MINUTES) * 60),
+    });
+    let hooks = Hooks {
+        on_start: file.hooks.on_start,
+        on_block_found: file.hooks.on_block_found,
+        on_disconnect: file.hooks.on_disconnect,
+    };
+    
+    // Command-line flags override everything; --address also replaces a configured address list
+    if let Some(cli_address) = &overrides.address {
+        address = cli_address.clone();
+        payout_addresses.clear();
+    }
+    let payout_addresses = payout::parse_addresses(&payout_addresses)?;
+    if let Some(first) = payout_addresses.first() {
+        address = first.address.clone();
+    }
+
+    if let Some(cli_pool) = &overrides.pool {
+        pool = cli_pool.clone();
+    }
+
+    if let Some(cli_threads) = overrides.threads {
+        threads = cli_threads;
+    }
+
+    if let Some(cli_prompt_timeout) = overrides.prompt_timeout {
+        prompt_timeout = cli_prompt_timeout;
+    }
+
+    let solar = match solar_url {
+        Some(url) => Some(SolarSettings {
+            url,
+            field: solar_field,
+            watts_per_thread: solar_watts_per_thread.context("Solar scaling needs solar watts_per_thread")?,
+            max_threads: solar_max_threads.unwrap_or(threads).max(1),
+            poll: solar_poll,
+        }),
+        None => None,
+    };
+
+    // An explicit verbosity wins over the older quiet_mode flag
+    let verbosity = match overrides.verbosity {
+        Some(level) => level,
+        None if overrides.quiet => Verbosity::Summary,
+        None if verbosity.is_empty() && quiet_mode => Verbosity::Summary,
+        None => Verbosity::parse(&verbosity)?,
+    };
+    
+    // Secrets may be file:, cmd: or keyring: references rather than the values themselves
+    let secret = |setting, value: &str| secrets::resolve(value).map_err(|source| ConfigError::Secret { setting, source });
+    let telegram_token = secret("Telegram bot token", &telegram_token)?;
+    let sentry_dsn = secret("Sentry DSN", &sentry_dsn)?;
+    let api_token = secret("API token", &api_token)?;
+    let cluster_token = secret("cluster token", &cluster_token)?;
+    let cluster_redis = Some(secret("cluster Redis URL", &cluster_redis)?)
+        .filter(|url| !url.is_empty())
+        .map(|url| RedisUrl::parse(&url))
+        .transpose()?;
+    let fleet_token = secret("fleet token", &fleet_token)?;
+    let electricity_token = secret("electricity token", &electricity_token)?;
+    let electricity = match PriceProvider::parse(&electricity_provider, &electricity_token, &electricity_tariff, &electricity_country)? {
+        Some(provider) => Some(ElectricitySettings {
+            provider,
+            max_price: electricity_max_price.context("Price-aware mining needs electricity max_price")?,
+            watts: electricity_watts,
+        }),
+        None => None,
+    };
+
+    // Create telegram config if both token and user_id are available
+    let telegram = if !telegram_token.is_empty() && !telegram_user_id.is_empty() {
+        Some(TelegramConfig {
+            bot_token: telegram_token,
+            user_id: telegram_user_id,
+            notify_summary: telegram_notify_summary,
+            notify_weekly: telegram_notify_weekly,
+            commands: telegram_commands,
+            notify_pool_messages: telegram_notify_pool_messages,
+            notify_network_blocks: telegram_notify_network_blocks,
+        })
+    } else {
+        None
+    };
+    
+    // The state file and block archive live in the log directory unless placed elsewhere
+    let log_dir = if log_dir.is_empty() { paths::default_log_dir() } else { PathBuf::from(log_dir) };
+    let state_file = if state_file.is_empty() { log_dir.join(STATE_FILE_NAME) } else { PathBuf::from(state_file) };
+    let blocks_dir = if blocks_dir.is_empty() { log_dir.clone() } else { PathBuf::from(blocks_dir) };
+
+    Ok(LoadedConfig {
+        address,
+        payout_addresses,
+        address_rotation: AddressRotation::parse(&address_rotation)?,
+        payout_check: PayoutCheck::parse(&payout_check)?,
+        pool: if pool.is_empty() { DEFAULT_POOL_ADDRESS.to_string() } else { pool },
+        threads: threads.max(1),
+        verbosity,
+        telegram,
+        log_format: LogFormat::parse(&log_format)?,
+        otlp_endpoint: Some(otlp_endpoint).filter(|e| !e.is_empty()),
+        sentry_dsn: Some(sentry_dsn).filter(|d| !d.is_empty()),
+        main_log,
+        log_dir,
+        log_rotation,
+        system_log: SystemLog::parse(&system_log)?,
+        state_file,
+        blocks_dir,
+        timestamps: Timestamps::new(&timestamp_format, TimeZone::parse(&timezone)?)?,
+        config_path,
+        profile,
+        watch_config,
+        prompt_timeout: Some(Duration::from_secs(prompt_timeout)).filter(|t| !t.is_zero()),
+        low_bandwidth,
+        submit_policy: SubmitPolicy {
+            // A submit has to be given some time to be answered
+            timeout: Duration::from_secs(submit_timeout.max(1)),
+            retries: submit_retries.min(u32::MAX as u64) as u32,
+        },
+        difficulty_change_factor,
+        stale_tip_blocks,
+        hooks,
+        script,
+        script_tick,
+        grpc_listen,
+        control_socket,
+        api_token: Some(api_token).filter(|t| !t.is_empty()),
+        proxy_listen,
+        proxy_difficulty,
+        cluster_listen,
+        cluster_token: Some(cluster_token).filter(|t| !t.is_empty()),
+        cluster_redis,
+        cluster_mdns,
+        fleet_instances,
+        fleet_token: Some(fleet_token).filter(|t| !t.is_empty()),
+        fleet_alert_minutes,
+        fleet_summary_minutes,
+        electricity,
+        solar,
+        activity,
+        quiet,
+        power,
+        failover,
+        btc_price,
+    })
+}
+
+/// Settings from the original unprefixed environment variables (`BTC_ADDRESS`, ...)
+///
+/// Kept for existing deployments; flags are 1/0 and unparseable values are ignored.
+fn legacy_env_config() -> FileConfig {
+    let var = |name: &str| std::env::var(name).ok();
+    // Secrets can also come from a file named by NAME_FILE, resolved like a `file:` value
+    let secret = |name: &str| var(name).or_else(|| var(&format!("{}_FILE", name)).map(|path| format!("file:{}", path)));
+    let flag = |name: &str| var(name).and_then(|v| v.parse::<u32>().ok()).map(|v| v == 1);
+    let mut config = FileConfig::default();
+
+    config.miner.wallet_address = var("BTC_ADDRESS");
+    config.miner.pool = var("POOL_ADDRESS");
+    config.miner.threads = var("MINING_THREADS").and_then(|v| v.parse().ok());
+    config.miner.quiet_mode = flag("QUIET_MODE");
+    config.miner.log_format = var("LOG_FORMAT");
+    config.miner.watch_config = flag("WATCH_CONFIG");
+    config.miner.prompt_timeout = var("PROMPT_TIMEOUT").and_then(|v| v.parse().ok());
+    config.telegram.bot_token = secret("TELEGRAM_BOT_TOKEN");
+    config.telegram.user_id = var("TELEGRAM_USER_ID");
+    config.telegram.notify_summary = flag("TELEGRAM_NOTIFY_SUMMARY");
+    config.telemetry.otlp_endpoint = var("OTEL_EXPORTER_OTLP_ENDPOINT");
+    config.error_reporting.sentry_dsn = secret("SENTRY_DSN");
+    config.logging.main_log = flag("MAIN_LOG");
+    config.logging.log_dir = var("LOG_DIR");
+    config.logging.max_size_mb = var("LOG_MAX_SIZE_MB").and_then(|v| v.parse().ok());
+    config.logging.rotate_daily = flag("LOG_ROTATE_DAILY");
+    config.logging.max_files = var("LOG_MAX_FILES").and_then(|v| v.parse().ok());
+    config.logging.system_log = var("SYSTEM_LOG");
+    config.logging.timestamp_format = var("LOG_TIMESTAMP_FORMAT");
+    config.logging.timezone = var("LOG_TIMEZONE");
+    config.paths.state_file = var("STATE_FILE");
+    config.paths.blocks_dir = var("BLOCKS_DIR");
+    config
+}
+
+/// Write the commented config template where the miner looks for its config: the working
+/// directory or, if that isn't writable, the platform config directory
+///
+/// An existing file is never overwritten.
+pub fn write_config_template() -> Result<PathBuf> {
+    let candidates = std::iter::once(PathBuf::from(SETUP_CONFIG_FILE_NAME))
+        .chain(paths::config_dir().map(|dir| dir.join(SETUP_CONFIG_FILE_NAME)));
+    let mut last_error = None;
+    for path in candidates {
+        let result = match path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            Some(dir) => std::fs::create_dir_all(dir),
+            None => Ok(()),
+        }
+        .and_then(|_| std::fs::OpenOptions::new().write(true).create_new(true).open(&path))
+        .and_then(|mut file| file.write_all(CONFIG_TEMPLATE.as_bytes()));
+        match result {
+            Ok(()) => return Ok(std::path::absolute(&path).unwrap_or(path)),
+            Err(e) => last_error = Some(anyhow::Error::from(e).context(format!("Failed to write {}", path.display()))),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow!("no location for a config file")))
+}
@@ -0,0 +1,610 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context, bail};
+use configparser::ini::Ini;
+use serde::Deserialize;
+
+/// Config files looked for when `--config` isn't given, in order, first in the working
+/// directory and then in the platform config directory
+#[cfg(feature = "yaml")]
+const DEFAULT_CONFIG_FILES: &[&str] = &["config.toml", "config.yaml", "config.yml", "config.ini"];
+#[cfg(not(feature = "yaml"))]
+const DEFAULT_CONFIG_FILES: &[&str] = &["config.toml", "config.ini"];
+/// Prefix of the environment variables that mirror the config file
+const ENV_PREFIX: &str = "MINER_";
+
+/// Settings read from a config file (config.toml, config.yaml or config.ini)
+///
+/// Every field is optional; anything left unset falls back to environment variables
+/// and defaults. TOML and YAML files reject unknown keys so typos don't go unnoticed.
+/// Named profiles (`[profile.<name>.<section>]`) hold settings that replace the
+/// top-level ones when selected with `--profile`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub miner: MinerSection,
+    pub telegram: TelegramSection,
+    pub telemetry: TelemetrySection,
+    pub error_reporting: ErrorReportingSection,
+    pub logging: LoggingSection,
+    pub paths: PathsSection,
+    pub hooks: HooksSection,
+    pub scripting: ScriptingSection,
+    pub api: ApiSection,
+    pub proxy: ProxySection,
+    pub cluster: ClusterSection,
+    pub fleet: FleetSection,
+    pub electricity: ElectricitySection,
+    pub solar: SolarSection,
+    pub activity: ActivitySection,
+    pub quiet: QuietSection,
+    pub power: PowerSection,
+    pub failover: FailoverSection,
+    pub btc_price: BtcPriceSection,
+    pub profile: BTreeMap<String, FileConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MinerSection {
+    pub wallet_address: Option<String>,
+    /// Comma-separated `address[:weight]` list, used instead of `wallet_address`
+    pub payout_addresses: Option<String>,
+    pub address_rotation: Option<String>,
+    /// What to do when a job's coinbase doesn't pay the address: refuse, warn or off
+    pub payout_check: Option<String>,
+    pub pool: Option<String>,
+    pub threads: Option<usize>,
+    /// Deprecated: `true` is the same as `verbosity = "summary"`
+    pub quiet_mode: Option<bool>,
+    pub verbosity: Option<String>,
+    pub log_format: Option<String>,
+    pub watch_config: Option<bool>,
+    /// Seconds to wait at the address prompt before giving up (0 = wait forever)
+    pub prompt_timeout: Option<u64>,
+    /// Cut optional network traffic, for metered connections
+    pub low_bandwidth: Option<bool>,
+    /// Seconds to wait for the pool to answer a submitted solution
+    pub submit_timeout: Option<u64>,
+    /// Times an unanswered submit is sent again
+    pub submit_retries: Option<u64>,
+    /// Report share difficulty changes by more than this factor (0 = never)
+    pub difficulty_change_factor: Option<f64>,
+    /// Report the pool's tip drifting more than this many blocks from the explorer's (0 = never)
+    pub stale_tip_blocks: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelegramSection {
+    pub bot_token: Option<String>,
+    pub user_id: Option<String>,
+    pub notify_summary: Option<bool>,
+    pub notify_weekly: Option<bool>,
+    /// Accept bot commands from `user_id`
+    pub commands: Option<bool>,
+    /// Forward the pool operator's announcements
+    pub notify_pool_messages: Option<bool>,
+    pub notify_network_blocks: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetrySection {
+    pub otlp_endpoint: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErrorReportingSection {
+    pub sentry_dsn: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingSection {
+    pub main_log: Option<bool>,
+    pub log_dir: Option<String>,
+    pub max_size_mb: Option<u64>,
+    pub rotate_daily: Option<bool>,
+    pub max_files: Option<usize>,
+    pub system_log: Option<String>,
+    pub timestamp_format: Option<String>,
+    pub timezone: Option<String>,
+}
+
+/// Locations of the persistent files; both default to the log directory
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PathsSection {
+    /// Lifetime stats and rotation state
+    pub state_file: Option<String>,
+    /// Directory of the found-block archive (`blocks_found.log` and `.jsonl`)
+    pub blocks_dir: Option<String>,
+}
+
+/// Commands run on miner events (see `hooks::run`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HooksSection {
+    pub on_start: Option<String>,
+    pub on_block_found: Option<String>,
+    pub on_disconnect: Option<String>,
+}
+
+/// Rhai script with callbacks for events and ticks (see `scripting::start`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScriptingSection {
+    pub script: Option<String>,
+    /// Seconds between `on_tick()` calls
+    pub tick_secs: Option<u64>,
+}
+
+/// Remote control interfaces, all off unless given an address
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiSection {
+    /// `host:port` for the gRPC API
+    pub grpc_listen: Option<String>,
+    /// Socket path, or `host:port`, for the JSON-RPC control socket
+    pub control_socket: Option<String>,
+    /// Bearer token required by the APIs; may be a file:, cmd: or keyring: reference
+    pub token: Option<String>,
+}
+
+/// Local Stratum server for other miners on the LAN, off unless given an address
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxySection {
+    /// `host:port` to accept miners on
+    pub listen: Option<String>,
+    /// Share difficulty set for downstream miners
+    pub difficulty: Option<u64>,
+}
+
+/// Cooperative mining with other machines (see `cluster::start`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClusterSection {
+    /// `host:port` to accept cluster workers on
+    pub listen: Option<String>,
+    /// Shared secret workers must present; may be a file:, cmd: or keyring: reference
+    pub token: Option<String>,
+    /// `redis://` URL to keep the coordinator's state in; may be a file:, cmd: or keyring: reference
+    pub redis: Option<String>,
+    /// Advertise the coordinator on the local network with mDNS
+    pub mdns: Option<bool>,
+}
+
+/// The miners the `fleet` command watches
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FleetSection {
+    /// Comma-separated `name=host:port` list of control sockets
+    pub instances: Option<String>,
+    /// The instances' API token; may be a file:, cmd: or keyring: reference
+    pub token: Option<String>,
+    /// Minutes an instance can be silent or without a pool before an alert
+    pub alert_minutes: Option<u64>,
+    /// Minutes between combined summaries; 0 turns them off
+    pub summary_minutes: Option<u64>,
+}
+
+/// Mining only while a dynamic electricity tariff is cheap (see `electricity::start`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ElectricitySection {
+    /// tibber, octopus or awattar; unset turns price-aware mining off
+    pub provider: Option<String>,
+    /// Tibber API token; may be a file:, cmd: or keyring: reference
+    pub token: Option<String>,
+    /// Octopus tariff code, e.g. E-1R-AGILE-24-10-01-C
+    pub tariff: Option<String>,
+    /// aWATTar market: de or at
+    pub country: Option<String>,
+    /// Highest price per kWh, in the tariff's currency, to hash at
+    pub max_price: Option<f64>,
+    /// Power drawn while hashing, for the savings figures
+    pub watts: Option<u64>,
+}
+
+/// Hashing on surplus solar power (see `solar::start`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SolarSection {
+    /// HTTP endpoint reporting the surplus in watts; unset turns surplus scaling off
+    pub url: Option<String>,
+    /// JSON pointer to the surplus in the endpoint's answer, e.g. /grid/export_w
+    pub field: Option<String>,
+    pub watts_per_thread: Option<u64>,
+    /// Threads to run with surplus to spare; defaults to [miner] threads
+    pub max_threads: Option<usize>,
+    pub poll_secs: Option<u64>,
+}
+
+/// Backing off while the machine is in use (see `activity::start`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ActivitySection {
+    /// Minutes without use before hashing ramps back up; unset turns the watch off
+    pub idle_minutes: Option<u64>,
+    /// Load average beyond the hashing threads that counts as use
+    pub max_load: Option<f64>,
+    /// Threads to keep while in use; 0 pauses
+    pub busy_threads: Option<usize>,
+}
+
+/// Daily quiet windows (see `quiet::start`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct QuietSection {
+    /// Comma-separated `HH:MM-HH:MM` windows
+    pub windows: Option<String>,
+    /// Zone the windows are in: `local` (default), `utc` or an offset like `+02:00`
+    pub timezone: Option<String>,
+}
+
+/// Power use and cost accounting (see `power::start`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PowerSection {
+    /// Estimated draw of one hashing thread
+    pub watts_per_thread: Option<f64>,
+    /// Measure with the CPU's RAPL counters instead of estimating
+    pub rapl: Option<bool>,
+    /// Electricity price per kWh
+    pub price: Option<f64>,
+}
+
+/// Backup pools and blacklisting the failing ones (see `failover::PoolList`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FailoverSection {
+    /// Comma-separated `host:port` pools to try after `[miner] pool`
+    pub pools: Option<String>,
+    /// Failed sessions in a row that blacklist a pool; 0 never does
+    pub blacklist_after: Option<u64>,
+    /// Minutes a blacklisted pool is skipped
+    pub blacklist_minutes: Option<u64>,
+}
+
+/// The bitcoin price for fiat reward values (see `btc_price`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BtcPriceSection {
+    /// coingecko, coinbase or mempool; unset leaves fiat values out
+    pub source: Option<String>,
+    /// Currency code, e.g. usd or eur
+    pub currency: Option<String>,
+}
+
+/// Lists every config file field once and derives the environment variable mapping
+/// from it. Each section is built with a struct literal, so a field added to a section
+/// without being listed here fails to compile.
+macro_rules! config_fields {
+    ($($section:ident: $section_type:ident { $($field:ident),* $(,)? }),* $(,)?) => {
+        /// Every config file setting as `(section, key)`, in file order
+        pub const SETTINGS: &[(&str, &str)] = &[$($((stringify!($section), stringify!($field)),)*)*];
+
+        impl FileConfig {
+            /// Read every setting from its `MINER_*` environment variable
+            pub fn from_env() -> Result<Self> {
+                Ok(Self {
+                    $($section: $section_type {
+                        $($field: env_value(stringify!($section), stringify!($field))?,)*
+                    },)*
+                    profile: BTreeMap::new(),
+                })
+            }
+
+            /// Settings from `other` where set, otherwise from `self`; the profiles of `self` are kept
+            pub fn overlay(self, other: Self) -> Self {
+                Self {
+                    $($section: $section_type {
+                        $($field: other.$section.$field.or(self.$section.$field),)*
+                    },)*
+                    profile: self.profile,
+                }
+            }
+        }
+    };
+}
+
+config_fields! {
+    miner: MinerSection { wallet_address, payout_addresses, address_rotation, payout_check, pool, threads, quiet_mode, verbosity, log_format, watch_config, prompt_timeout, low_bandwidth, submit_timeout, submit_retries, difficulty_change_factor, stale_tip_blocks },
+    telegram: TelegramSection { bot_token, user_id, notify_summary, notify_weekly, commands, notify_pool_messages, notify_network_blocks },
+    telemetry: TelemetrySection { otlp_endpoint },
+    error_reporting: ErrorReportingSection { sentry_dsn },
+    logging: LoggingSection { main_log, log_dir, max_size_mb, rotate_daily, max_files, system_log, timestamp_format, timezone },
+    paths: PathsSection { state_file, blocks_dir },
+    hooks: HooksSection { on_start, on_block_found, on_disconnect },
+    scripting: ScriptingSection { script, tick_secs },
+    api: ApiSection { grpc_listen, control_socket, token },
+    proxy: ProxySection { listen, difficulty },
+    cluster: ClusterSection { listen, token, redis, mdns },
+    fleet: FleetSection { instances, token, alert_minutes, summary_minutes },
+    electricity: ElectricitySection { provider, token, tariff, country, max_price, watts },
+    solar: SolarSection { url, field, watts_per_thread, max_threads, poll_secs },
+    activity: ActivitySection { idle_minutes, max_load, busy_threads },
+    quiet: QuietSection { windows, timezone },
+    power: PowerSection { watts_per_thread, rapl, price },
+    failover: FailoverSection { pools, blacklist_after, blacklist_minutes },
+    btc_price: BtcPriceSection { source, currency },
+}
+
+/// Environment variable for a setting: `MINER_<KEY>` for the `[miner]` section,
+/// `MINER_<SECTION>_<KEY>` for the others
+pub fn env_var_name(section: &str, key: &str) -> String {
+    if section == "miner" {
+        format!("{}{}", ENV_PREFIX, key.to_uppercase())
+    } else {
+        format!("{}{}_{}", ENV_PREFIX, section.to_uppercase(), key.to_uppercase())
+    }
+}
+
+/// Value of a setting's environment variable, or of the file named by `<NAME>_FILE`
+/// (the Docker secrets convention)
+fn env_value<T: EnvValue>(section: &str, key: &str) -> Result<Option<T>> {
+    let name = env_var_name(section, key);
+    let value = match std::env::var(&name) {
+        Ok(value) if !value.is_empty() => value,
+        _ => match std::env::var(format!("{}_FILE", name)) {
+            Ok(path) if !path.is_empty() => crate::secrets::read_file(Path::new(&path))?,
+            _ => return Ok(None),
+        },
+    };
+    T::parse(&value)
+        .map(Some)
+        .with_context(|| format!("Invalid {}", name))
+}
+
+/// A setting type that can be read from an environment variable
+trait EnvValue: Sized {
+    fn parse(value: &str) -> Result<Self>;
+}
+
+impl EnvValue for String {
+    fn parse(value: &str) -> Result<Self> {
+        Ok(value.to_string())
+    }
+}
+
+impl EnvValue for bool {
+    fn parse(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(true),
+            "0" | "false" | "no" | "off" => Ok(false),
+            _ => bail!("expected 1/0 or true/false"),
+        }
+    }
+}
+
+impl EnvValue for usize {
+    fn parse(value: &str) -> Result<Self> {
+        Ok(value.parse()?)
+    }
+}
+
+impl EnvValue for u64 {
+    fn parse(value: &str) -> Result<Self> {
+        Ok(value.parse()?)
+    }
+}
+
+impl EnvValue for f64 {
+    fn parse(value: &str) -> Result<Self> {
+        Ok(value.parse()?)
+    }
+}
+
+impl FileConfig {
+    /// First default config file present in the working directory or, failing that,
+    /// the platform config directory (see `paths::config_dir`)
+    pub fn find_default() -> Option<PathBuf> {
+        let in_dir = |dir: PathBuf| DEFAULT_CONFIG_FILES.iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.exists());
+        in_dir(PathBuf::new()).or_else(|| crate::paths::config_dir().and_then(in_dir))
+    }
+
+    /// Parse a config file, choosing the format from its extension
+    pub fn load(path: &Path) -> Result<Self> {
+        let extension = path.extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        match extension.as_str() {
+            "toml" => toml::from_str(&data)
+                .with_context(|| format!("Invalid config file {}", path.display())),
+            "yaml" | "yml" => Self::from_yaml(&data)
+                .with_context(|| format!("Invalid config file {}", path.display())),
+            _ => Self::from_ini(&data)
+                .with_context(|| format!("Invalid config file {}", path.display())),
+        }
+    }
+
+    #[cfg(feature = "yaml")]
+    fn from_yaml(data: &str) -> Result<Self> {
+        // An empty document means "no settings", not an error
+        if data.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        Ok(serde_yaml::from_str(data)?)
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    fn from_yaml(_data: &str) -> Result<Self> {
+        bail!("YAML config files require building with `--features yaml`")
+    }
+
+    /// Settings of the named profile on top of the top-level ones
+    pub fn with_profile(mut self, name: &str) -> Result<Self> {
+        let Some(profile) = self.profile.remove(name) else {
+            let available = self.profile.keys().map(String::as_str).collect::<Vec<_>>();
+            if available.is_empty() {
+                bail!("Profile '{}' not found: the config file defines no profiles", name);
+            }
+            bail!("Profile '{}' not found (available: {})", name, available.join(", "));
+        };
+        Ok(self.overlay(profile))
+    }
+
+    /// Read the classic config.ini, where flags are written as 0/1 and profiles are
+    /// `[profile.<name>.<section>]` sections
+    fn from_ini(data: &str) -> Result<Self> {
+        let mut ini = Ini::new();
+        if let Err(e) = ini.read(data.to_string()) {
+            bail!("{}", e);
+        }
+        let mut config = Self::from_ini_sections(&ini, "")?;
+        for section in ini.sections() {
+            let Some((name, _)) = section.strip_prefix("profile.").and_then(|rest| rest.rsplit_once('.')) else {
+                continue;
+            };
+            if !config.profile.contains_key(name) {
+                let profile = Self::from_ini_sections(&ini, &format!("profile.{}.", name))?;
+                config.profile.insert(name.to_string(), profile);
+            }
+        }
+        Ok(config)
+    }
+
+    /// Read the sections named `<prefix><section>`
+    fn from_ini_sections(ini: &Ini, prefix: &str) -> Result<Self> {
+        let section_name = |section: &str| format!("{}{}", prefix, section);
+        let get = |section: &str, key: &str| ini.get(&section_name(section), key).filter(|v| !v.is_empty());
+        let get_uint = |section: &str, key: &str| -> Result<Option<u64>> {
+            ini.getuint(&section_name(section), key)
+                .map_err(|e| anyhow::anyhow!("[{}] {}: {}", section_name(section), key, e))
+        };
+        let get_flag = |section: &str, key: &str| -> Result<Option<bool>> {
+            Ok(get_uint(section, key)?.map(|v| v == 1))
+        };
+        let get_float = |section: &str, key: &str| -> Result<Option<f64>> {
+            ini.getfloat(&section_name(section), key)
+                .map_err(|e| anyhow::anyhow!("[{}] {}: {}", section_name(section), key, e))
+        };
+
+        Ok(Self {
+            miner: MinerSection {
+                wallet_address: get("miner", "wallet_address"),
+                payout_addresses: get("miner", "payout_addresses"),
+                address_rotation: get("miner", "address_rotation"),
+                payout_check: get("miner", "payout_check"),
+                pool: get("miner", "pool"),
+                threads: get_uint("miner", "threads")?.map(|v| v as usize),
+                quiet_mode: get_flag("miner", "quiet_mode")?,
+                verbosity: get("miner", "verbosity"),
+                log_format: get("miner", "log_format"),
+                watch_config: get_flag("miner", "watch_config")?,
+                prompt_timeout: get_uint("miner", "prompt_timeout")?,
+                low_bandwidth: get_flag("miner", "low_bandwidth")?,
+                submit_timeout: get_uint("miner", "submit_timeout")?,
+                submit_retries: get_uint("miner", "submit_retries")?,
+                difficulty_change_factor: get_float("miner", "difficulty_change_factor")?,
+                stale_tip_blocks: get_uint("miner", "stale_tip_blocks")?,
+            },
+            telegram: TelegramSection {
+                bot_token: get("telegram", "bot_token"),
+                user_id: get("telegram", "user_id"),
+                notify_summary: get_flag("telegram", "notify_summary")?,
+                notify_weekly: get_flag("telegram", "notify_weekly")?,
+                commands: get_flag("telegram", "commands")?,
+                notify_pool_messages: get_flag("telegram", "notify_pool_messages")?,
+                notify_network_blocks: get_flag("telegram", "notify_network_blocks")?,
+            },
+            telemetry: TelemetrySection {
+                otlp_endpoint: get("telemetry", "otlp_endpoint"),
+            },
+            error_reporting: ErrorReportingSection {
+                sentry_dsn: get("error_reporting", "sentry_dsn"),
+            },
+            logging: LoggingSection {
+                main_log: get_flag("logging", "main_log")?,
+                log_dir: get("logging", "log_dir"),
+                max_size_mb: get_uint("logging", "max_size_mb")?,
+                rotate_daily: get_flag("logging", "rotate_daily")?,
+                max_files: get_uint("logging", "max_files")?.map(|v| v as usize),
+                system_log: get("logging", "system_log"),
+                timestamp_format: get("logging", "timestamp_format"),
+                timezone: get("logging", "timezone"),
+            },
+            paths: PathsSection {
+                state_file: get("paths", "state_file"),
+                blocks_dir: get("paths", "blocks_dir"),
+            },
+            hooks: HooksSection {
+                on_start: get("hooks", "on_start"),
+                on_block_found: get("hooks", "on_block_found"),
+                on_disconnect: get("hooks", "on_disconnect"),
+            },
+            scripting: ScriptingSection {
+                script: get("scripting", "script"),
+                tick_secs: get_uint("scripting", "tick_secs")?,
+            },
+            api: ApiSection {
+                grpc_listen: get("api", "grpc_listen"),
+                control_socket: get("api", "control_socket"),
+                token: get("api", "token"),
+            },
+            proxy: ProxySection {
+                listen: get("proxy", "listen"),
+                difficulty: get_uint("proxy", "difficulty")?,
+            },
+            cluster: ClusterSection {
+                listen: get("cluster", "listen"),
+                token: get("cluster", "token"),
+                redis: get("cluster", "redis"),
+                mdns: get_flag("cluster", "mdns")?,
+            },
+            fleet: FleetSection {
+                instances: get("fleet", "instances"),
+                token: get("fleet", "token"),
+                alert_minutes: get_uint("fleet", "alert_minutes")?,
+                summary_minutes: get_uint("fleet", "summary_minutes")?,
+            },
+            electricity: ElectricitySection {
+                provider: get("electricity", "provider"),
+                token: get("electricity", "token"),
+                tariff: get("electricity", "tariff"),
+                country: get("electricity", "country"),
+                max_price: get_float("electricity", "max_price")?,
+                watts: get_uint("electricity", "watts")?,
+            },
+            solar: SolarSection {
+                url: get("solar", "url"),
+                field: get("solar", "field"),
+                watts_per_thread: get_uint("solar", "watts_per_thread")?,
+                max_threads: get_uint("solar", "max_threads")?.map(|v| v as usize),
+                poll_secs: get_uint("solar", "poll_secs")?,
+            },
+            activity: ActivitySection {
+                idle_minutes: get_uint("activity", "idle_minutes")?,
+                max_load: get_float("activity", "max_load")?,
+                busy_threads: get_uint("activity", "busy_threads")?.map(|v| v as usize),
+            },
+            quiet: QuietSection {
+                windows: get("quiet", "windows"),
+                timezone: get("quiet", "timezone"),
+            },
+            power: PowerSection {
+                watts_per_thread: get_float("power", "watts_per_thread")?,
+                rapl: get_flag("power", "rapl")?,
+                price: get_float("power", "price")?,
+            },
+            failover: FailoverSection {
+                pools: get("failover", "pools"),
+                blacklist_after: get_uint("failover", "blacklist_after")?,
+                blacklist_minutes: get_uint("failover", "blacklist_minutes")?,
+            },
+            btc_price: BtcPriceSection {
+                source: get("btc_price", "source"),
+                currency: get("btc_price", "currency"),
+            },
+            profile: BTreeMap::new(),
+        })
+    }
+}
@@ -0,0 +1,169 @@
+use std::fmt;
+use std::sync::mpsc;
+use std::time::Duration;
+use anyhow::Result;
+use nu_ansi_term::{Color, Style};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::events::OutputMode;
+use crate::timestamp::Timestamps;
+
+/// Width of the event name column
+const EVENT_COLUMN_WIDTH: usize = 16;
+const BANNER_WIDTH: usize = 72;
+
+/// Whether the console log should use ANSI colors
+///
+/// Colors are off with `--no-color`, when `NO_COLOR` is set (https://no-color.org) and
+/// when the console stream is redirected to a file or pipe.
+pub fn use_color(no_color: bool, output: OutputMode) -> bool {
+    if no_color || std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        return false;
+    }
+    let stream = match output {
+        OutputMode::Text => atty::Stream::Stdout,
+        OutputMode::Ndjson => atty::Stream::Stderr,
+    };
+    if !atty::is(stream) {
+        return false;
+    }
+    // Older Windows consoles need virtual terminal processing switched on first
+    #[cfg(windows)]
+    return nu_ansi_term::enable_ansi_support().is_ok();
+    #[cfg(not(windows))]
+    true
+}
+
+/// Read a line from stdin, giving up after `timeout` (`None` waits forever)
+///
+/// Returns `None` on timeout. The read happens on its own thread, which is left blocked
+/// on stdin if nobody answers; callers are expected to exit in that case.
+pub fn read_line(timeout: Option<Duration>) -> Result<Option<String>> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut input = String::new();
+        let _ = tx.send(std::io::stdin().read_line(&mut input).map(|_| input));
+    });
+    let input = match timeout {
+        Some(timeout) => match rx.recv_timeout(timeout) {
+            Ok(input) => input,
+            Err(_) => return Ok(None),
+        },
+        None => rx.recv()?,
+    };
+    Ok(Some(input?))
+}
+
+/// Text console layout: timestamp, level and event name in aligned columns, then the
+/// message and the remaining fields
+///
+/// With colors enabled, levels are colored, hash rate lines are green, errors are red and
+/// a found block is framed as a highlighted banner. The banner frame is kept without colors.
+pub struct ConsoleFormat {
+    timestamps: Timestamps,
+}
+
+impl ConsoleFormat {
+    pub fn new(timestamps: Timestamps) -> Self {
+        Self { timestamps }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for ConsoleFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let color = writer.has_ansi_escapes();
+        let paint = |style: Style, text: &str| -> String {
+            if color { style.paint(text).to_string() } else { text.to_string() }
+        };
+
+        let mut fields = EventFields::default();
+        event.record(&mut fields);
+        let level = *event.metadata().level();
+        let event_name = fields.event.as_deref().unwrap_or("");
+
+        let level_style = match level {
+            Level::ERROR => Color::Red.bold(),
+            Level::WARN => Color::Yellow.bold(),
+            Level::INFO => Color::Green.normal(),
+            Level::DEBUG => Color::Blue.normal(),
+            Level::TRACE => Color::Purple.normal(),
+        };
+        let message_style = match (level, event_name) {
+            (Level::ERROR, _) => Color::Red.normal(),
+            (_, "block_found") => Color::Yellow.bold(),
+            (_, "hashrate") => Color::Green.normal(),
+            (Level::WARN, _) => Color::Yellow.normal(),
+            _ => Style::new(),
+        };
+        let dimmed = Style::new().dimmed();
+
+        write!(writer, "{} ", paint(dimmed, &self.timestamps.now()))?;
+        write!(writer, "{} ", paint(level_style, &format!("{:>5}", level)))?;
+        write!(writer, "{} ", paint(dimmed, &format!("{:<width$}", event_name, width = EVENT_COLUMN_WIDTH)))?;
+
+        if event_name == "block_found" {
+            let frame = "=".repeat(BANNER_WIDTH);
+            writeln!(writer, "{}", paint(message_style, &frame))?;
+            for line in fields.message.lines() {
+                writeln!(writer, "{}", paint(message_style, line))?;
+            }
+            write!(writer, "{}", paint(message_style, &frame))?;
+        } else {
+            write!(writer, "{}", paint(message_style, &fields.message))?;
+        }
+
+        let mut context = String::new();
+        if let Some(scope) = ctx.event_scope() {
+            for span in scope.from_root() {
+                context.push_str(span.name());
+                if let Some(span_fields) = span.extensions().get::<FormattedFields<N>>() {
+                    if !span_fields.is_empty() {
+                        context.push_str(&format!("{{{}}}", span_fields));
+                    }
+                }
+                context.push(' ');
+            }
+        }
+        for (name, value) in &fields.other {
+            context.push_str(&format!("{}={} ", name, value));
+        }
+        if !context.is_empty() {
+            write!(writer, "  {}", paint(dimmed, context.trim_end()))?;
+        }
+        writeln!(writer)
+    }
+}
+
+/// Splits an event into its message, its `event` name and everything else
+#[derive(Default)]
+struct EventFields {
+    message: String,
+    event: Option<String>,
+    other: Vec<(&'static str, String)>,
+}
+
+impl Visit for EventFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            "event" => self.event = Some(value.to_string()),
+            name => self.other.push((name, format!("{:?}", value))),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{:?}", value),
+            "event" => self.event = Some(format!("{:?}", value)),
+            name => self.other.push((name, format!("{:?}", value))),
+        }
+    }
+}
@@ -0,0 +1,324 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::Result;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::cluster::ClusterStats;
+use crate::events::{self, MinerEvent};
+use crate::latency::LatencyStats;
+use crate::logging::Verbosity;
+use crate::miner::MiningConfig;
+use crate::notify::{get_telegram_updates, html_escape, send_telegram_message};
+use crate::quiet::{self, QuietSchedule};
+use crate::reload::ConfigReloader;
+use crate::sensors::SensorReadings;
+use crate::stats::{format_closest_call, format_difficulty, SessionSummary};
+use crate::timestamp::TimeZone;
+
+/// How long `getUpdates` waits for a bot message before asking again
+const TELEGRAM_POLL_TIMEOUT_SECS: u64 = 30;
+/// How often to check whether Telegram commands were turned on, and to retry a failed poll
+const TELEGRAM_IDLE_SECS: u64 = 10;
+
+/// What a running miner is doing, as reported by the control interfaces
+#[derive(Debug, Clone, Serialize)]
+pub struct Status {
+    pub version: String,
+    pub pool: String,
+    pub address: String,
+    pub connected: bool,
+    /// Pool session state, e.g. `working` or `degraded`
+    pub state: String,
+    pub paused: bool,
+    pub dry_run: bool,
+    pub threads: usize,
+    pub height: u64,
+    /// Latest hashrate sample in h/s
+    pub hashrate: u64,
+    pub verbosity: String,
+    /// Latest hardware sensor readings
+    pub sensors: SensorReadings,
+    /// Round trips to the pool this session
+    pub pool_latency: LatencyStats,
+    pub session: SessionSummary,
+    /// The whole cluster, when this miner is a cluster coordinator
+    pub cluster: Option<ClusterStats>,
+}
+
+/// Operations on a running miner, shared by every remote control interface so they all
+/// behave the same
+#[derive(Clone)]
+pub struct Control {
+    config: Arc<Mutex<MiningConfig>>,
+    reloader: Arc<ConfigReloader>,
+}
+
+impl Control {
+    pub fn new(config: Arc<Mutex<MiningConfig>>, reloader: Arc<ConfigReloader>) -> Self {
+        Self { config, reloader }
+    }
+
+    pub fn status(&self) -> Status {
+        let config_guard = self.config.lock().unwrap();
+        let hashrate = if config_guard.paused { 0 } else { config_guard.hashrate_history.latest().unwrap_or(0) };
+        let session = config_guard.stats.session_summary();
+        let cluster = config_guard.coordinator.as_ref()
+            .map(|coordinator| coordinator.stats(hashrate as f64, session.hashes, session.best_share_difficulty));
+        Status {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            pool: config_guard.pool.clone(),
+            address: config_guard.address.clone(),
+            connected: config_guard.stats.is_connected(),
+            state: config_guard.session_state.name().to_string(),
+            paused: config_guard.paused,
+            dry_run: config_guard.dry_run,
+            threads: config_guard.threads,
+            height: config_guard.current_height,
+            hashrate,
+            verbosity: config_guard.verbosity.name().to_string(),
+            sensors: config_guard.stats.sensors(),
+            pool_latency: config_guard.stats.pool_latency(),
+            session,
+            cluster,
+        }
+    }
+
+    /// Stop hashing while staying connected; false if already paused
+    pub fn pause(&self, source: &str) -> bool {
+        self.set_paused(true, source)
+    }
+
+    /// Start hashing again; false if not paused
+    pub fn resume(&self, source: &str) -> bool {
+        self.set_paused(false, source)
+    }
+
+    fn set_paused(&self, paused: bool, source: &str) -> bool {
+        let changed = {
+            let mut config_guard = self.config.lock().unwrap();
+            let changed = config_guard.paused != paused;
+            config_guard.paused = paused;
+            changed
+        };
+        if changed {
+            info!(event = "pause_requested", paused, source, "{} requested by {}", if paused { "Pause" } else { "Resume" }, source);
+            events::emit(if paused { MinerEvent::Paused } else { MinerEvent::Resumed });
+        }
+        changed
+    }
+
+    /// Drop the pool session and reconnect, picking up the next payout address
+    pub fn restart(&self, source: &str) {
+        self.config.lock().unwrap().restart_requested = true;
+        info!(event = "restart_requested", source, "Session restart requested by {}", source);
+    }
+
+    pub fn set_threads(&self, threads: usize) {
+        self.reloader.set_threads(threads);
+    }
+
+    pub fn set_verbosity(&self, verbosity: Verbosity) -> Result<()> {
+        self.reloader.set_verbosity(verbosity)
+    }
+
+    /// Make `pool` the primary pool and reconnect to it
+    pub fn set_pool(&self, pool: &str) {
+        self.reloader.set_pool(pool);
+    }
+
+    /// Replace the quiet windows, or turn them off with `None`
+    pub fn set_quiet(&self, quiet: Option<QuietSchedule>) {
+        self.reloader.set_quiet(quiet);
+    }
+
+    /// The quiet windows in use
+    pub fn quiet(&self) -> Option<QuietSchedule> {
+        self.reloader.quiet()
+    }
+
+    /// Re-read the configuration, as on SIGHUP
+    pub fn reload(&self) {
+        self.reloader.reload();
+    }
+
+    /// Pause on SIGUSR1 and resume on SIGUSR2
+    #[cfg(unix)]
+    pub async fn pause_on_signals(&self) -> Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut pauses = signal(SignalKind::user_defined1())?;
+        let mut resumes = signal(SignalKind::user_defined2())?;
+        loop {
+            tokio::select! {
+                Some(()) = pauses.recv() => self.pause("SIGUSR1"),
+                Some(()) = resumes.recv() => self.resume("SIGUSR2"),
+                else => return Ok(()),
+            };
+        }
+    }
+
+    /// Answer commands sent to the Telegram bot for as long as `[telegram] commands` is on
+    ///
+    /// Only messages from the configured user count, and only those sent after the miner
+    /// started, so a `/pause` left unread from an earlier run isn't acted on.
+    pub async fn telegram_commands(&self) {
+        let started = unix_now();
+        let mut offset = 0;
+        loop {
+            let telegram = self.config.lock().unwrap().telegram.clone().filter(|t| t.commands);
+            let Some(telegram) = telegram else {
+                tokio::time::sleep(Duration::from_secs(TELEGRAM_IDLE_SECS)).await;
+                continue;
+            };
+            let updates = match get_telegram_updates(&telegram, offset, Duration::from_secs(TELEGRAM_POLL_TIMEOUT_SECS)).await {
+                Ok(updates) => updates,
+                Err(e) => {
+                    warn!("Failed to fetch Telegram commands: {}", e);
+                    tokio::time::sleep(Duration::from_secs(TELEGRAM_IDLE_SECS)).await;
+                    continue;
+                }
+            };
+            for update in updates {
+                offset = update.update_id + 1;
+                if update.date < started || update.text.is_empty() {
+                    continue;
+                }
+                // Anyone can find and message a bot, or add it to a group
+                if !telegram.accepts_command(&update) {
+                    warn!(event = "telegram_command_ignored", chat_id = %update.chat_id, sender_id = %update.sender_id, "Ignoring Telegram message from user {} in chat {}", update.sender_id, update.chat_id);
+                    continue;
+                }
+                let reply = self.telegram_command(&update.text);
+                if let Err(e) = send_telegram_message(&telegram, &reply).await {
+                    warn!("Failed to answer Telegram command: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Run one bot command, returning the reply
+    fn telegram_command(&self, text: &str) -> String {
+        // Commands picked from the menu in a group arrive as `/pause@bot_name`
+        let (command, argument) = text.trim().split_once(char::is_whitespace).unwrap_or((text.trim(), ""));
+        let command = command.split('@').next().unwrap_or_default();
+        let argument = argument.trim();
+        match command {
+            "/status" => {
+                let status = self.status();
+                let mut reply = format!(
+                    "📊 <b>Miner Status</b>\n\n\
+                    State: {}\n\
+                    Session: <code>{}</code>\n\
+                    Pool: <code>{}</code>\n\
+                    Height: <code>{}</code>\n\
+                    Threads: <code>{}</code>\n\
+                    Hashrate: <code>{} h/s</code>\n\
+                    Hashes: <code>{}</code>\n\
+                    Best share difficulty: <code>{}</code>\n\
+                    Closest call: <code>{}</code>",
+                    if status.paused { "⏸ paused" } else if status.connected { "⛏ mining" } else { "🔌 disconnected" },
+                    status.state,
+                    status.pool,
+                    status.height,
+                    status.threads,
+                    status.hashrate,
+                    status.session.hashes,
+                    format_difficulty(status.session.best_share_difficulty),
+                    format_closest_call(status.session.closest_call)
+                );
+                if let Some(energy) = status.session.energy_line() {
+                    reply.push_str(&format!("\nEnergy: <code>{}</code>", energy));
+                }
+                if let Some(latency) = status.pool_latency.summary() {
+                    reply.push_str(&format!("\nPool latency: <code>{}</code>", latency));
+                }
+                reply.push_str(&format!("\nPool traffic: <code>{}</code>", status.session.traffic_line()));
+                if let Some(sensors) = status.sensors.summary() {
+                    reply.push_str(&format!("\nSensors: <code>{}</code>", sensors));
+                }
+                if let Some(cluster) = &status.cluster {
+                    reply.push_str(&cluster_lines(cluster));
+                }
+                reply
+            }
+            "/pause" if self.pause("telegram") => "⏸ Hashing paused, the pool connection stays up".to_string(),
+            "/pause" => "Already paused".to_string(),
+            "/resume" if self.resume("telegram") => "▶️ Hashing resumed".to_string(),
+            "/resume" => "Not paused".to_string(),
+            "/restart" => {
+                self.restart("telegram");
+                "🔄 Reconnecting to the pool".to_string()
+            }
+            "/setthreads" => {
+                // More threads than the machine runs at once only slow each other down
+                let max_threads = std::thread::available_parallelism().map_or(1, |cpus| cpus.get());
+                match argument.parse::<usize>() {
+                    Ok(threads) if (1..=max_threads).contains(&threads) => {
+                        self.set_threads(threads);
+                        info!(event = "config_updated", source = "telegram", threads, "Threads set to {} over Telegram", threads);
+                        format!("🧵 Hashing on <code>{}</code> thread(s)", threads)
+                    }
+                    _ => format!("Usage: /setthreads &lt;count&gt;, from 1 to {} on this machine", max_threads),
+                }
+            }
+            "/setpool" if !argument.is_empty() && !argument.contains(char::is_whitespace) => {
+                self.set_pool(argument);
+                info!(event = "config_updated", source = "telegram", pool = argument, "Pool set to {} over Telegram", argument);
+                format!("🔄 Reconnecting to <code>{}</code>", html_escape(argument))
+            }
+            "/setpool" => "Usage: /setpool &lt;host:port or preset&gt;".to_string(),
+            "/setquiet" if argument == "off" => {
+                self.set_quiet(None);
+                info!(event = "config_updated", source = "telegram", "Quiet windows turned off over Telegram");
+                "🔊 Quiet windows off".to_string()
+            }
+            "/setquiet" => match quiet::parse_windows(argument) {
+                Ok(windows) if !windows.is_empty() => {
+                    // Keep the configured time zone, if there is one
+                    let zone = self.quiet().map_or(TimeZone::Local, |schedule| schedule.zone);
+                    let schedule = QuietSchedule { windows, zone };
+                    info!(event = "config_updated", source = "telegram", quiet = %schedule, "Quiet windows set to {} over Telegram", schedule);
+                    let reply = format!("🔇 Quiet windows: <code>{}</code>", schedule);
+                    self.set_quiet(Some(schedule));
+                    reply
+                }
+                Ok(_) => "Usage: /setquiet &lt;HH:MM-HH:MM, ...&gt; or /setquiet off".to_string(),
+                Err(e) => format!("{}\nUsage: /setquiet &lt;HH:MM-HH:MM, ...&gt; or /setquiet off", html_escape(&format!("{:#}", e))),
+            },
+            _ => "Commands: /status, /pause, /resume, /restart, /setthreads, /setpool, /setquiet".to_string(),
+        }
+    }
+}
+
+/// The cluster's figures and one line per worker, for Telegram messages
+pub fn cluster_lines(cluster: &ClusterStats) -> String {
+    let mut lines = format!(
+        "\n\n🖧 <b>Cluster</b>\n\
+        Workers: <code>{}</code>\n\
+        Combined hashrate: <code>{:.0} h/s</code>\n\
+        Hashes: <code>{}</code>\n\
+        Best share difficulty: <code>{}</code>",
+        cluster.workers.len(),
+        cluster.hashrate,
+        cluster.hashes,
+        format_difficulty(cluster.best_share_difficulty)
+    );
+    for worker in &cluster.workers {
+        lines.push_str(&format!(
+            "\n• {}: <code>{:.0} h/s</code>, <code>{}</code> hashes, <code>{}</code> assignment(s)",
+            html_escape(&worker.name),
+            worker.hashrate,
+            worker.hashes,
+            worker.finished
+        ));
+    }
+    lines
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
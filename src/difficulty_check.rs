@@ -0,0 +1,45 @@
+//! Cross-checking the difficulty a pool's work implies against other sources
+//!
+//! A misconfigured pool, or a hijacked DNS name pointing the miner at someone else's
+//! server, can hand out work for the wrong chain or with a made-up target. The job's
+//! nbits is compared with the network difficulty an independent block explorer reports,
+//! and the pool's `mining.set_difficulty` with both.
+
+use crate::stats::format_difficulty;
+
+/// How far apart, as a fraction, the job's and the explorer's network difficulty may be
+///
+/// Explorers round what they report; the only honest disagreement beyond that is the
+/// explorer still being a block behind just after a retarget.
+pub const NETWORK_DIFFICULTY_TOLERANCE: f64 = 0.05;
+
+/// A difficulty that doesn't agree with the others
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum DifficultyMismatch {
+    #[error("job nbits give network difficulty {}, but the block explorer reports {}", format_difficulty(*job), format_difficulty(*explorer))]
+    Network { job: f64, explorer: f64 },
+    #[error("pool share difficulty {} is above the network difficulty {}, so a block could be rejected as a low share", format_difficulty(*pool), format_difficulty(*network))]
+    PoolAboveNetwork { pool: f64, network: f64 },
+    #[error("pool share difficulty {0} isn't a positive number")]
+    InvalidPool(f64),
+}
+
+/// Everything inconsistent between the job's network difficulty and the other sources,
+/// empty if they agree
+///
+/// `explorer` is the independent network difficulty and `pool` the pool's share
+/// difficulty; either is skipped when unknown.
+pub fn cross_check(job: f64, explorer: Option<f64>, pool: Option<f64>) -> Vec<DifficultyMismatch> {
+    let mut mismatches = Vec::new();
+    if let Some(explorer) = explorer {
+        if (job - explorer).abs() > explorer * NETWORK_DIFFICULTY_TOLERANCE {
+            mismatches.push(DifficultyMismatch::Network { job, explorer });
+        }
+    }
+    match pool {
+        Some(pool) if !pool.is_finite() || pool <= 0.0 => mismatches.push(DifficultyMismatch::InvalidPool(pool)),
+        Some(pool) if pool > job => mismatches.push(DifficultyMismatch::PoolAboveNetwork { pool, network: job }),
+        _ => {}
+    }
+    mismatches
+}
@@ -0,0 +1,315 @@
+//! Mining only while electricity is cheap
+//!
+//! Dynamic tariffs (Tibber, Octopus Agile, aWATTar) publish their prices up to a day
+//! ahead. The schedule is fetched from the provider and hashing is paused for every slot
+//! priced above `max_price`, with the pool session kept up so mining resumes as soon as
+//! the price drops. Prices are per kWh in the tariff's currency.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{Result, Context, bail};
+use chrono::DateTime;
+use serde_json::{json, Value};
+use tracing::{info, warn, Instrument, info_span};
+
+use crate::control::Control;
+use crate::miner::MiningConfig;
+use crate::tasks::{Stage, Tasks};
+
+const TIBBER_API: &str = "https://api.tibber.com/v1-beta/gql";
+const TIBBER_QUERY: &str = "{ viewer { homes { currentSubscription { priceInfo { today { total startsAt } tomorrow { total startsAt } } } } } }";
+const OCTOPUS_API: &str = "https://api.octopus.energy/v1/products";
+/// How often the price of the current slot is looked at
+pub const PRICE_CHECK_INTERVAL_SECS: u64 = 60;
+/// How often the schedule is fetched again, picking up the next day's prices
+pub const PRICE_REFRESH_SECS: u64 = 60 * 60;
+/// Length given to a slot whose end the provider doesn't say
+const DEFAULT_SLOT_SECS: u64 = 60 * 60;
+/// How far ahead a pause's savings are projected
+const SAVINGS_HORIZON_SECS: u64 = 24 * 60 * 60;
+
+/// Where the prices come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PriceProvider {
+    /// Tibber's API, with the account's token
+    Tibber { token: String },
+    /// Octopus Energy's public unit rates for a tariff code such as `E-1R-AGILE-24-10-01-C`
+    Octopus { tariff: String },
+    /// aWATTar's day-ahead market prices for `de` or `at`
+    Awattar { country: String },
+}
+
+impl PriceProvider {
+    /// The provider named by `name` with the setting it needs; `None` for an empty name
+    pub fn parse(name: &str, token: &str, tariff: &str, country: &str) -> Result<Option<Self>> {
+        let tariff = tariff.trim();
+        let provider = match name.trim().to_lowercase().as_str() {
+            "" | "none" => return Ok(None),
+            "tibber" if token.is_empty() => bail!("The tibber electricity provider needs a token"),
+            "tibber" => PriceProvider::Tibber { token: token.to_string() },
+            "octopus" if tariff.len() < 8 => bail!("The octopus electricity provider needs a tariff code, e.g. E-1R-AGILE-24-10-01-C"),
+            "octopus" => PriceProvider::Octopus { tariff: tariff.to_uppercase() },
+            "awattar" => match country.trim().to_lowercase().as_str() {
+                "" | "de" => PriceProvider::Awattar { country: "de".to_string() },
+                "at" => PriceProvider::Awattar { country: "at".to_string() },
+                other => bail!("Unknown aWATTar country '{}' (expected 'de' or 'at')", other),
+            },
+            other => bail!("Unknown electricity provider '{}' (expected 'tibber', 'octopus' or 'awattar')", other),
+        };
+        Ok(Some(provider))
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PriceProvider::Tibber { .. } => "tibber",
+            PriceProvider::Octopus { .. } => "octopus",
+            PriceProvider::Awattar { .. } => "awattar",
+        }
+    }
+}
+
+/// Price-aware mining, as configured in `[electricity]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElectricitySettings {
+    pub provider: PriceProvider,
+    /// Hashing pauses while the price per kWh is above this
+    pub max_price: f64,
+    /// What the machine draws while hashing, for the savings figures; 0 if unknown
+    pub watts: u64,
+}
+
+/// The price of electricity over one stretch of time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PriceSlot {
+    /// Unix time the price starts applying
+    pub start: u64,
+    /// Unix time it stops applying
+    pub end: u64,
+    /// Per kWh, in the tariff's currency
+    pub price: f64,
+}
+
+fn unix_time(value: &Value) -> Result<u64> {
+    let text = value.as_str().context("missing time")?;
+    let time = DateTime::parse_from_rfc3339(text).with_context(|| format!("invalid time '{}'", text))?;
+    u64::try_from(time.timestamp()).with_context(|| format!("time '{}' before 1970", text))
+}
+
+/// Slots from start times and prices, each ending where the next begins
+fn slots_from_starts(mut starts: Vec<(u64, f64)>) -> Vec<PriceSlot> {
+    starts.sort_by_key(|(start, _)| *start);
+    let ends: Vec<u64> = starts.iter().skip(1).map(|(start, _)| *start).collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &(start, price))| PriceSlot { start, end: ends.get(i).copied().unwrap_or(start + DEFAULT_SLOT_SECS), price })
+        .collect()
+}
+
+/// Prices from Tibber's `priceInfo` query, today's and tomorrow's once published
+pub fn parse_tibber(response: &Value) -> Result<Vec<PriceSlot>> {
+    if let Some(error) = response["errors"][0]["message"].as_str() {
+        bail!("Tibber: {}", error);
+    }
+    let price_info = &response["data"]["viewer"]["homes"][0]["currentSubscription"]["priceInfo"];
+    if price_info.is_null() {
+        bail!("Tibber: no home with a subscription on this account");
+    }
+    let mut starts = Vec::new();
+    for day in ["today", "tomorrow"] {
+        for entry in price_info[day].as_array().into_iter().flatten() {
+            let price = entry["total"].as_f64().context("Tibber: missing total")?;
+            starts.push((unix_time(&entry["startsAt"]).context("Tibber: startsAt")?, price));
+        }
+    }
+    Ok(slots_from_starts(starts))
+}
+
+/// Prices from Octopus' `standard-unit-rates`, which are in pence
+pub fn parse_octopus(response: &Value) -> Result<Vec<PriceSlot>> {
+    let results = response["results"].as_array().context("Octopus: missing results")?;
+    let mut slots = results
+        .iter()
+        .map(|rate| {
+            Ok(PriceSlot {
+                start: unix_time(&rate["valid_from"]).context("Octopus: valid_from")?,
+                // Fixed rates run until further notice
+                end: match &rate["valid_to"] {
+                    Value::Null => u64::MAX,
+                    valid_to => unix_time(valid_to).context("Octopus: valid_to")?,
+                },
+                price: rate["value_inc_vat"].as_f64().context("Octopus: missing value_inc_vat")? / 100.0,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    slots.sort_by_key(|slot| slot.start);
+    Ok(slots)
+}
+
+/// Prices from aWATTar's `marketdata`, which are per MWh with times in milliseconds
+pub fn parse_awattar(response: &Value) -> Result<Vec<PriceSlot>> {
+    let data = response["data"].as_array().context("aWATTar: missing data")?;
+    data.iter()
+        .map(|entry| {
+            Ok(PriceSlot {
+                start: entry["start_timestamp"].as_u64().context("aWATTar: missing start_timestamp")? / 1000,
+                end: entry["end_timestamp"].as_u64().context("aWATTar: missing end_timestamp")? / 1000,
+                price: entry["marketprice"].as_f64().context("aWATTar: missing marketprice")? / 1000.0,
+            })
+        })
+        .collect()
+}
+
+/// The provider's current price schedule
+pub async fn fetch(provider: &PriceProvider) -> Result<Vec<PriceSlot>> {
+    let client = reqwest::Client::new();
+    match provider {
+        PriceProvider::Tibber { token } => {
+            let response: Value = client
+                .post(TIBBER_API)
+                .bearer_auth(token)
+                .json(&json!({ "query": TIBBER_QUERY }))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+            parse_tibber(&response)
+        }
+        PriceProvider::Octopus { tariff } => {
+            // E-1R-AGILE-24-10-01-C belongs to the product AGILE-24-10-01
+            let product = &tariff[5..tariff.len() - 2];
+            let url = format!("{}/{}/electricity-tariffs/{}/standard-unit-rates/", OCTOPUS_API, product, tariff);
+            let response: Value = client.get(url).send().await?.error_for_status()?.json().await?;
+            parse_octopus(&response)
+        }
+        PriceProvider::Awattar { country } => {
+            let url = format!("https://api.awattar.{}/v1/marketdata", country);
+            let response: Value = client.get(url).send().await?.error_for_status()?.json().await?;
+            parse_awattar(&response)
+        }
+    }
+}
+
+/// Whether to hash right now, going by the price schedule
+#[derive(Debug, Clone, PartialEq)]
+pub enum PriceDecision {
+    Mine { price: f64 },
+    Pause {
+        price: f64,
+        /// When the price next drops to `max_price` or below, if the schedule goes that far
+        until: Option<u64>,
+        /// What hashing until then, or for the next day at most, would have cost; 0
+        /// without a power figure
+        projected_savings: f64,
+    },
+    /// The schedule doesn't cover `now`
+    Unknown,
+}
+
+/// Cost of drawing `watts` for `secs` at `price` per kWh
+pub fn energy_cost(watts: u64, secs: u64, price: f64) -> f64 {
+    watts as f64 / 1000.0 * secs as f64 / 3600.0 * price
+}
+
+/// Decide from the schedule whether to hash at `now`
+pub fn decide(slots: &[PriceSlot], now: u64, max_price: f64, watts: u64) -> PriceDecision {
+    let Some(current) = slots.iter().find(|slot| slot.start <= now && now < slot.end) else {
+        return PriceDecision::Unknown;
+    };
+    if current.price <= max_price {
+        return PriceDecision::Mine { price: current.price };
+    }
+    let mut until = None;
+    let mut projected_savings = 0.0;
+    let horizon = now + SAVINGS_HORIZON_SECS;
+    for slot in slots.iter().filter(|slot| slot.end > now && slot.start < horizon) {
+        if slot.price <= max_price {
+            until = Some(slot.start.max(now));
+            break;
+        }
+        projected_savings += energy_cost(watts, slot.end.min(horizon) - slot.start.max(now), slot.price);
+    }
+    PriceDecision::Pause { price: current.price, until, projected_savings }
+}
+
+/// Watch the price and pause or resume hashing as it crosses `max_price`
+pub fn start(settings: ElectricitySettings, config: Arc<Mutex<MiningConfig>>, control: Control, tasks: &mut Tasks) {
+    info!(
+        event = "electricity_pricing",
+        provider = settings.provider.name(),
+        max_price = settings.max_price,
+        "Mining only while electricity costs {} per kWh or less ({})",
+        settings.max_price,
+        settings.provider.name()
+    );
+    tasks.spawn(Stage::Api, "electricity", watch(settings, config, control).instrument(info_span!("electricity")));
+}
+
+async fn watch(settings: ElectricitySettings, config: Arc<Mutex<MiningConfig>>, control: Control) {
+    let mut slots = Vec::new();
+    let mut fetched_at = None::<u64>;
+    // Whether the current pause is ours, so a pause from the user is never lifted here
+    let mut price_paused = false;
+    let mut interval = tokio::time::interval(Duration::from_secs(PRICE_CHECK_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        let now = config.lock().unwrap().clock.unix_secs();
+        if fetched_at.is_none_or(|fetched| now >= fetched + PRICE_REFRESH_SECS) {
+            match fetch(&settings.provider).await {
+                Ok(fetched) => {
+                    slots = fetched;
+                    fetched_at = Some(now);
+                }
+                Err(e) => warn!(event = "electricity_price_failed", "Failed to fetch electricity prices: {:#}", e),
+            }
+        }
+
+        match decide(&slots, now, settings.max_price, settings.watts) {
+            PriceDecision::Pause { price, until, projected_savings } => {
+                if price_paused {
+                    if config.lock().unwrap().paused {
+                        let saved = energy_cost(settings.watts, PRICE_CHECK_INTERVAL_SECS, price);
+                        config.lock().unwrap().stats.record_price_pause(PRICE_CHECK_INTERVAL_SECS, saved);
+                    } else {
+                        // Resumed by hand: leave it to the user until the price drops again
+                        price_paused = false;
+                    }
+                } else if control.pause("electricity price") {
+                    price_paused = true;
+                    let until_text = until.map_or("beyond the published prices".to_string(), |until| {
+                        format!("in {} min", until.saturating_sub(now).div_ceil(60))
+                    });
+                    info!(
+                        event = "price_pause",
+                        price,
+                        max_price = settings.max_price,
+                        until,
+                        projected_savings,
+                        "Electricity costs {} per kWh, above {}: pausing hashing, cheaper {}, saving about {:.2}",
+                        price,
+                        settings.max_price,
+                        until_text,
+                        projected_savings
+                    );
+                }
+            }
+            decision @ (PriceDecision::Mine { .. } | PriceDecision::Unknown) if price_paused => {
+                price_paused = false;
+                control.resume("electricity price");
+                match decision {
+                    PriceDecision::Mine { price } => info!(
+                        event = "price_resume",
+                        price,
+                        max_price = settings.max_price,
+                        "Electricity costs {} per kWh, at or below {}: resuming hashing",
+                        price,
+                        settings.max_price
+                    ),
+                    _ => warn!(event = "price_resume", "No electricity price known for now: resuming hashing"),
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use crate::config::ConfigError;
+use crate::exit_code::ExitCode;
+use crate::notify::NotifyError;
+use crate::session::SessionError;
+use crate::stratum::StratumError;
+use crate::work::HashingError;
+
+/// What the supervisor should do after an error, decided from the error's type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// The operation failed but the connection is fine; try again
+    Retry,
+    /// The connection is unusable; reconnect after a delay
+    Reconnect,
+    /// Trying again won't help; stop the miner
+    Abort,
+}
+
+impl Recovery {
+    /// Recovery for an error: errors tagged with an exit code abort (except an unreachable
+    /// pool, which the supervisor retries for a while), otherwise the first typed error in
+    /// the chain decides, and anything unrecognised gets a reconnect
+    pub fn of(error: &anyhow::Error) -> Self {
+        match ExitCode::of(error) {
+            ExitCode::Failure | ExitCode::PoolUnreachable => {}
+            _ => return Recovery::Abort,
+        }
+        error.chain()
+            .find_map(|e| {
+                if let Some(e) = e.downcast_ref::<StratumError>() {
+                    Some(e.recovery())
+                } else if let Some(e) = e.downcast_ref::<HashingError>() {
+                    Some(e.recovery())
+                } else if let Some(e) = e.downcast_ref::<ConfigError>() {
+                    Some(e.recovery())
+                } else if let Some(e) = e.downcast_ref::<SessionError>() {
+                    Some(e.recovery())
+                } else {
+                    e.downcast_ref::<NotifyError>().map(NotifyError::recovery)
+                }
+            })
+            .unwrap_or(Recovery::Reconnect)
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde_json::{json, Value};
+use rand::Rng;
+use tracing::warn;
+use anyhow::{Result, Context, bail};
+
+use crate::exit_code;
+
+const SENTRY_CLIENT: &str = concat!("bitcoin-solo-miner/", env!("CARGO_PKG_VERSION"));
+const RELEASE: &str = concat!("bitcoin-solo-miner@", env!("CARGO_PKG_VERSION"));
+const REPORT_TIMEOUT_SECS: u64 = 5;
+// Report a failure once it repeats this many times in a row, then every REPEATED_REPORT_EVERY
+const REPEATED_FAILURE_THRESHOLD: u32 = 5;
+const REPEATED_REPORT_EVERY: u32 = 100;
+
+/// Parsed Sentry DSN (`https://<public_key>@<host>/<project_id>`)
+#[derive(Debug, Clone)]
+struct SentryDsn {
+    store_url: String,
+    public_key: String,
+}
+
+impl SentryDsn {
+    fn parse(dsn: &str) -> Result<Self> {
+        let (scheme, rest) = dsn.trim().split_once("://")
+            .context("Sentry DSN must start with http:// or https://")?;
+        let (public_key, rest) = rest.split_once('@')
+            .context("Sentry DSN is missing the public key")?;
+        let (host, project_id) = rest.rsplit_once('/')
+            .context("Sentry DSN is missing the project id")?;
+        let public_key = public_key.split(':').next().unwrap_or_default();
+        if public_key.is_empty() || host.is_empty() || project_id.is_empty() {
+            bail!("Invalid Sentry DSN: {}", dsn);
+        }
+        Ok(Self {
+            store_url: format!("{}://{}/api/{}/store/", scheme, host, project_id),
+            public_key: public_key.to_string(),
+        })
+    }
+
+    fn auth_header(&self) -> String {
+        format!(
+            "Sentry sentry_version=7, sentry_key={}, sentry_client={}",
+            self.public_key, SENTRY_CLIENT
+        )
+    }
+}
+
+/// Context attached to every report
+#[derive(Debug, Default)]
+struct ReportContext {
+    pool: String,
+    backend: String,
+    job_id: Option<String>,
+}
+
+struct Reporter {
+    dsn: SentryDsn,
+    context: Mutex<ReportContext>,
+    consecutive_failures: Mutex<HashMap<&'static str, u32>>,
+}
+
+static REPORTER: OnceLock<Reporter> = OnceLock::new();
+
+/// Enable error reporting to a Sentry (or Sentry-compatible) DSN and install the panic hook
+pub fn init(dsn: &str, pool: &str, backend: &str) -> Result<()> {
+    let reporter = Reporter {
+        dsn: SentryDsn::parse(dsn)?,
+        context: Mutex::new(ReportContext {
+            pool: pool.to_string(),
+            backend: backend.to_string(),
+            job_id: None,
+        }),
+        consecutive_failures: Mutex::new(HashMap::new()),
+    };
+    if REPORTER.set(reporter).is_err() {
+        bail!("Error reporting already initialized");
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let payload = info.payload();
+        let message = payload.downcast_ref::<&str>().map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string());
+        let location = info.location()
+            .map(|l| format!("{}:{}", l.file(), l.line()))
+            .unwrap_or_default();
+        let thread = std::thread::current().name().unwrap_or("unnamed").to_string();
+        // A worker under `supervise` is restarted, so its panic isn't the end of the miner
+        let recovered = exit_code::catching_panics();
+
+        let level = if recovered { "error" } else { "fatal" };
+        let Some(event) = build_event(level, "panic", &format!("panic: {}", message), json!({
+            "location": location,
+            "thread": thread,
+            "recovered": recovered,
+        })) else {
+            return;
+        };
+
+        if recovered {
+            // Don't hold up the restart on Sentry
+            std::thread::spawn(move || send_blocking(&event));
+            return;
+        }
+        // The process may be about to abort, so deliver synchronously from a fresh thread
+        // (a blocking client can't run on a tokio worker)
+        let sender = std::thread::spawn(move || send_blocking(&event));
+        let _ = sender.join();
+    }));
+
+    Ok(())
+}
+
+/// Update the job id attached to reports
+pub fn set_job(job_id: Option<&str>) {
+    if let Some(reporter) = REPORTER.get() {
+        reporter.context.lock().unwrap().job_id = job_id.map(str::to_string);
+    }
+}
+
+/// Record a failure of `kind`; repeated failures are reported once they cross the threshold
+pub fn record_failure(kind: &'static str, error: &anyhow::Error) {
+    let Some(reporter) = REPORTER.get() else {
+        return;
+    };
+    let count = {
+        let mut failures = reporter.consecutive_failures.lock().unwrap();
+        let count = failures.entry(kind).or_insert(0);
+        *count += 1;
+        *count
+    };
+    let should_report = count == REPEATED_FAILURE_THRESHOLD
+        || (count > REPEATED_FAILURE_THRESHOLD && count % REPEATED_REPORT_EVERY == 0);
+    if !should_report {
+        return;
+    }
+
+    let chain: Vec<String> = error.chain().map(|cause| cause.to_string()).collect();
+    let message = format!("{} failed {} times in a row: {}", kind, count, error);
+    let Some(event) = build_event("error", kind, &message, json!({
+        "consecutive_failures": count,
+        "error_chain": chain,
+    })) else {
+        return;
+    };
+
+    if let Ok(handle) = tokio::runtime::Handle::try_current() {
+        handle.spawn(async move {
+            if let Err(e) = send(&event).await {
+                warn!("Failed to send error report: {}", e);
+            }
+        });
+    }
+}
+
+/// Reset the consecutive failure count of `kind` after it succeeds
+pub fn record_success(kind: &'static str) {
+    if let Some(reporter) = REPORTER.get() {
+        reporter.consecutive_failures.lock().unwrap().remove(kind);
+    }
+}
+
+fn build_event(level: &str, kind: &str, message: &str, extra: Value) -> Option<Value> {
+    let reporter = REPORTER.get()?;
+    let context = reporter.context.lock().ok()?;
+    let event_id: [u8; 16] = rand::thread_rng().gen();
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or_default();
+    Some(json!({
+        "event_id": hex::encode(event_id),
+        "timestamp": timestamp,
+        "level": level,
+        "platform": "other",
+        "logger": "bitcoin-solo-miner",
+        "release": RELEASE,
+        "message": { "formatted": message },
+        "tags": {
+            "kind": kind,
+            "pool": context.pool,
+            "backend": context.backend,
+            "job_id": context.job_id.clone().unwrap_or_default(),
+        },
+        "extra": extra,
+    }))
+}
+
+async fn send(event: &Value) -> Result<()> {
+    let reporter = REPORTER.get().context("Error reporting not initialized")?;
+    let response = reqwest::Client::new()
+        .post(&reporter.dsn.store_url)
+        .header("X-Sentry-Auth", reporter.dsn.auth_header())
+        .timeout(Duration::from_secs(REPORT_TIMEOUT_SECS))
+        .json(event)
+        .send()
+        .await
+        .context("Failed to send error report")?;
+    if !response.status().is_success() {
+        bail!("Error report rejected: {}", response.status());
+    }
+    Ok(())
+}
+
+fn send_blocking(event: &Value) {
+    let Some(reporter) = REPORTER.get() else {
+        return;
+    };
+    let _ = reqwest::blocking::Client::new()
+        .post(&reporter.dsn.store_url)
+        .header("X-Sentry-Auth", reporter.dsn.auth_header())
+        .timeout(Duration::from_secs(REPORT_TIMEOUT_SECS))
+        .json(event)
+        .send();
+}
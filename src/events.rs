@@ -0,0 +1,237 @@
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use serde_json::{json, Value};
+use anyhow::{Result, bail};
+use tokio::sync::broadcast;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+use crate::stats::{JobStats, SessionSummary};
+use crate::weekly::WeeklyStats;
+
+/// Events a slow subscriber can fall behind by before it starts missing them
+const EVENT_BUS_CAPACITY: usize = 1024;
+
+/// Where runtime events go
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Human-readable log output only
+    #[default]
+    Text,
+    /// One JSON event per line on stdout; logs move to stderr
+    Ndjson,
+}
+
+impl OutputMode {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "text" => Ok(OutputMode::Text),
+            "ndjson" => Ok(OutputMode::Ndjson),
+            other => bail!("Unknown output mode '{}' (expected 'text' or 'ndjson')", other),
+        }
+    }
+}
+
+/// Significant runtime events, serialized as `{"event": "<name>", "timestamp": ..., ...fields}`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum MinerEvent {
+    Started {
+        version: String,
+        pool: String,
+        addresses: Vec<String>,
+        dry_run: bool,
+    },
+    Connected {
+        pool: String,
+    },
+    Disconnected {
+        pool: String,
+        error: Option<String>,
+    },
+    /// The pool's share difficulty moved by more than `difficulty_change_factor`
+    ShareDifficultyChanged {
+        from: f64,
+        to: f64,
+    },
+    /// The block the pool's jobs build on drifted more than `stale_tip_blocks` from the
+    /// explorer's chain
+    ChainTipDiverged {
+        pool_height: u64,
+        network_height: u64,
+        /// Blocks of the explorer's chain the pool's jobs don't build on
+        blocks: u64,
+        /// Whether the pool builds on a block the explorer doesn't have, rather than an old one
+        forked: bool,
+    },
+    /// The pool operator sent a `client.show_message` announcement
+    PoolMessage {
+        message: String,
+    },
+    /// The network found a block, as the block explorer reports it
+    NetworkBlock {
+        height: u64,
+        hash: String,
+        /// Time since the previous block by the header times, if that block was seen too
+        interval_secs: Option<u64>,
+    },
+    /// A pool failed too many sessions in a row and is skipped until its cooldown ends
+    PoolBlacklisted {
+        pool: String,
+        failures: u32,
+        cooldown_secs: u64,
+    },
+    JobReceived {
+        job_id: String,
+        height: u64,
+        nbits: String,
+        ntime: String,
+        clean_jobs: bool,
+        /// Difficulty of the job's nbits target
+        network_difficulty: f64,
+        /// Subsidy plus fees in the coinbase, in satoshis, when it could be read
+        reward_sats: Option<u64>,
+        /// The part of `reward_sats` paid to the payout address
+        payout_sats: Option<u64>,
+    },
+    HashrateSample {
+        hashrate: u64,
+        hashes: u64,
+        interval_secs: f64,
+    },
+    BlockFound {
+        job_id: String,
+        hash: String,
+        target: String,
+        nonce: String,
+        address: String,
+        network_difficulty: f64,
+        /// Difficulty the block's hash reached, at least `network_difficulty`
+        hash_difficulty: f64,
+        reward_sats: Option<u64>,
+        payout_sats: Option<u64>,
+    },
+    ShareSubmitted {
+        job_id: String,
+        extranonce2: String,
+        ntime: String,
+        nonce: String,
+        /// Difficulty the submitted hash reached
+        difficulty: f64,
+        /// First reason the local pool checks expected a reject, `None` if they expected
+        /// the pool to accept it
+        predicted_rejection: Option<String>,
+    },
+    ShareAccepted {
+        job_id: String,
+        accepted: bool,
+        error: Option<String>,
+        /// Time from sending mining.submit to the pool's reply
+        round_trip_secs: f64,
+    },
+    /// The pool never answered a submitted solution, however often it was sent
+    SubmitUnacknowledged {
+        job_id: String,
+        nonce: String,
+        attempts: u32,
+        /// How long each attempt waited for a reply
+        timeout_secs: u64,
+    },
+    /// A hashing thread panicked and was started again on the same work
+    WorkerRestarted {
+        worker: usize,
+        job_id: String,
+        panic: String,
+        /// Times this worker has been restarted on this job
+        restarts: u32,
+    },
+    JobFinished(JobStats),
+    SessionSummary(SessionSummary),
+    /// The figures for a week, once it is over
+    WeeklyReport(WeeklyStats),
+    Paused,
+    Resumed,
+}
+
+impl MinerEvent {
+    /// The event as a JSON object, stamped with the current unix time
+    pub fn to_json(&self) -> Value {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs_f64())
+            .unwrap_or_default();
+        let mut value = json!(self);
+        value["timestamp"] = json!(timestamp);
+        value
+    }
+}
+
+static OUTPUT_MODE: OnceLock<OutputMode> = OnceLock::new();
+static BUS: OnceLock<broadcast::Sender<MinerEvent>> = OnceLock::new();
+
+pub fn init(mode: OutputMode) {
+    let _ = OUTPUT_MODE.set(mode);
+}
+
+pub fn output_mode() -> OutputMode {
+    OUTPUT_MODE.get().copied().unwrap_or(OutputMode::Text)
+}
+
+fn bus() -> &'static broadcast::Sender<MinerEvent> {
+    BUS.get_or_init(|| broadcast::channel(EVENT_BUS_CAPACITY).0)
+}
+
+/// Publish an event to every subscriber (and print it as a JSON line in ndjson mode)
+///
+/// The ndjson line is written right away rather than by a subscriber task, so the
+/// events leading up to an exit are never lost.
+pub fn emit(event: MinerEvent) {
+    if output_mode() == OutputMode::Ndjson {
+        println!("{}", event.to_json());
+    }
+    // Nobody listening is fine
+    let _ = bus().send(event);
+}
+
+/// Receive every event published from now on
+pub fn subscribe() -> broadcast::Receiver<MinerEvent> {
+    bus().subscribe()
+}
+
+/// The next event for a subscriber, or `None` once the bus is gone
+///
+/// A subscriber that falls more than `EVENT_BUS_CAPACITY` events behind skips the ones
+/// it missed with a warning rather than stalling the publishers.
+pub async fn next(events: &mut broadcast::Receiver<MinerEvent>) -> Option<MinerEvent> {
+    loop {
+        match events.recv().await {
+            Ok(event) => return Some(event),
+            Err(broadcast::error::RecvError::Lagged(missed)) => {
+                warn!(event = "events_lagged", missed, "Event subscriber fell behind, {} events skipped", missed);
+            }
+            Err(broadcast::error::RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Like `next`, but `None` once `cancel` fires and the events published before then have
+/// all been received, so a subscriber that stops on shutdown still sees the last ones
+pub async fn next_until(events: &mut broadcast::Receiver<MinerEvent>, cancel: &CancellationToken) -> Option<MinerEvent> {
+    if !cancel.is_cancelled() {
+        tokio::select! {
+            biased;
+            event = next(events) => return event,
+            _ = cancel.cancelled() => {}
+        }
+    }
+    loop {
+        match events.try_recv() {
+            Ok(event) => return Some(event),
+            Err(broadcast::error::TryRecvError::Lagged(missed)) => {
+                warn!(event = "events_lagged", missed, "Event subscriber fell behind, {} events skipped", missed);
+            }
+            Err(_) => return None,
+        }
+    }
+}
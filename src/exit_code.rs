@@ -0,0 +1,107 @@
+use std::cell::Cell;
+use std::error::Error;
+use std::fmt;
+use std::panic::AssertUnwindSafe;
+
+/// Process exit codes, one per kind of fatal condition, so wrapper scripts and service
+/// managers can react to each (e.g. restart on `PoolUnreachable`, alert on `InvalidConfig`)
+///
+/// 2 is left to clap, which uses it for command-line usage errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ExitCode {
+    Success = 0,
+    /// Any error without a more specific code
+    Failure = 1,
+    /// The config file, environment or flags are invalid, or `config-check` failed
+    InvalidConfig = 3,
+    /// The payout address is missing or fails validation
+    InvalidAddress = 4,
+    /// The pool couldn't be reached before the first successful connection
+    PoolUnreachable = 5,
+    /// The pool rejected `mining.authorize`, or a cluster coordinator refused this worker
+    AuthorizationRejected = 6,
+    /// The startup self-test couldn't reproduce a known block, so hashing can't be trusted
+    SelfTestFailed = 7,
+    /// The miner panicked (the same code Rust uses for a panic on the main thread)
+    Panic = 101,
+}
+
+impl From<ExitCode> for std::process::ExitCode {
+    fn from(code: ExitCode) -> Self {
+        std::process::ExitCode::from(code as u8)
+    }
+}
+
+impl ExitCode {
+    /// Exit code for an error: the code of the first `Fatal` in its chain, else `Failure`
+    pub fn of(error: &anyhow::Error) -> Self {
+        error.chain()
+            .find_map(|e| e.downcast_ref::<Fatal>())
+            .map_or(ExitCode::Failure, |fatal| fatal.code)
+    }
+}
+
+/// An error that ends the process with a specific exit code
+///
+/// Displays as the wrapped error, so tagging an error doesn't change its message.
+#[derive(Debug)]
+pub struct Fatal {
+    code: ExitCode,
+    error: anyhow::Error,
+}
+
+impl fmt::Display for Fatal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.error)
+    }
+}
+
+impl Error for Fatal {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.error.source()
+    }
+}
+
+/// Tag an error with an exit code, for use with `map_err`
+pub fn fatal<E: Into<anyhow::Error>>(code: ExitCode) -> impl FnOnce(E) -> anyhow::Error {
+    move |error| Fatal { code, error: error.into() }.into()
+}
+
+thread_local! {
+    /// Set while the thread runs inside `catch_panic`
+    static CATCHING_PANICS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Exit with `ExitCode::Panic` after a panic anywhere in the process, once the hooks
+/// installed so far (the panic message, error reporting) have run
+///
+/// Without this a panic in a background task or worker thread would leave a crippled
+/// miner running. Panics inside `catch_panic` are left to its caller to recover from.
+pub fn exit_on_panic() {
+    let previous_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous_hook(info);
+        if !CATCHING_PANICS.get() {
+            std::process::exit(ExitCode::Panic as i32);
+        }
+    }));
+}
+
+/// Whether a panic on this thread now would be caught by `catch_panic` and recovered from
+pub fn catching_panics() -> bool {
+    CATCHING_PANICS.get()
+}
+
+/// Run `f`, returning a panic in it as an `Err` with the panic's message rather than
+/// exiting the process
+pub fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    let outer = CATCHING_PANICS.replace(true);
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING_PANICS.set(outer);
+    result.map_err(|payload| {
+        payload.downcast_ref::<&str>().map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string())
+    })
+}
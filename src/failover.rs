@@ -0,0 +1,170 @@
+//! Backup pools, and setting aside the ones that keep failing
+//!
+//! With `[failover] pools` configured, each session goes to the first pool in order (the
+//! primary, then the backups) that isn't blacklisted. A pool whose sessions fail
+//! `blacklist_after` times in a row, never getting as far as hashing a job or having every
+//! submit rejected, is blacklisted for a cooldown. The miner then settles on a backup that
+//! works instead of flapping between broken endpoints.
+
+use std::time::{Duration, Instant};
+
+/// Consecutive failed sessions that blacklist a pool
+pub const DEFAULT_BLACKLIST_AFTER: u32 = 3;
+pub const DEFAULT_BLACKLIST_MINUTES: u64 = 10;
+
+/// Backup pools, as configured in `[failover]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailoverSettings {
+    /// Tried in order after the primary pool
+    pub pools: Vec<String>,
+    /// Consecutive failed sessions that blacklist a pool; 0 never does
+    pub blacklist_after: u32,
+    /// How long a blacklisted pool is skipped
+    pub cooldown: Duration,
+}
+
+/// Comma-separated `host:port` pools, skipping empty entries
+pub fn parse_pools(value: &str) -> Vec<String> {
+    value.split(',').map(str::trim).filter(|pool| !pool.is_empty()).map(str::to_string).collect()
+}
+
+/// How a session went, as far as its pool is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionOutcome {
+    /// Hashed a job, and the pool accepted a share or wasn't sent any
+    Worked,
+    /// Never got as far as hashing: the connection, subscribe or authorize failed, or no
+    /// job came
+    NeverWorked,
+    /// Every share submitted was rejected
+    RejectedEverything,
+}
+
+/// A pool that just got blacklisted
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blacklisting {
+    pub pool: String,
+    /// Failed sessions in a row that got it blacklisted
+    pub failures: u32,
+    pub cooldown: Duration,
+}
+
+#[derive(Debug)]
+struct PoolEntry {
+    pool: String,
+    /// Failed sessions in a row
+    failures: u32,
+    blacklisted_until: Option<Instant>,
+}
+
+/// The pools to mine on, in order of preference, and which of them are blacklisted
+#[derive(Debug)]
+pub struct PoolList {
+    entries: Vec<PoolEntry>,
+    blacklist_after: u32,
+    cooldown: Duration,
+    current: usize,
+}
+
+impl PoolList {
+    /// `primary`, followed by the backups if failover is configured
+    pub fn new(primary: String, failover: Option<&FailoverSettings>) -> Self {
+        let backups = failover.map(|settings| settings.pools.clone()).unwrap_or_default();
+        let mut pools = vec![primary];
+        for pool in backups {
+            if !pools.contains(&pool) {
+                pools.push(pool);
+            }
+        }
+        Self {
+            entries: pools
+                .into_iter()
+                .map(|pool| PoolEntry { pool, failures: 0, blacklisted_until: None })
+                .collect(),
+            blacklist_after: failover.map_or(0, |settings| settings.blacklist_after),
+            cooldown: failover.map_or(Duration::ZERO, |settings| settings.cooldown),
+            current: 0,
+        }
+    }
+
+    /// Whether a failing pool can be blacklisted for another to take over
+    pub fn can_fail_over(&self) -> bool {
+        self.entries.len() > 1 && self.blacklist_after > 0
+    }
+
+    /// The pool the last `select` picked
+    pub fn current(&self) -> &str {
+        &self.entries[self.current].pool
+    }
+
+    /// Lift the blacklistings whose cooldown has ended by `now`, returning those pools
+    ///
+    /// A pool back from its cooldown is on probation: its next failure blacklists it again.
+    pub fn release_expired(&mut self, now: Instant) -> Vec<String> {
+        let mut released = Vec::new();
+        for entry in &mut self.entries {
+            if entry.blacklisted_until.is_some_and(|until| until <= now) {
+                entry.blacklisted_until = None;
+                entry.failures = self.blacklist_after.saturating_sub(1);
+                released.push(entry.pool.clone());
+            }
+        }
+        released
+    }
+
+    /// Pick the pool for the next session: the first that isn't blacklisted, or with every
+    /// pool blacklisted, the one whose cooldown ends first
+    pub fn select(&mut self) -> &str {
+        self.current = self.entries
+            .iter()
+            .position(|entry| entry.blacklisted_until.is_none())
+            .or_else(|| {
+                self.entries
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, entry)| entry.blacklisted_until)
+                    .map(|(index, _)| index)
+            })
+            .unwrap_or(0);
+        self.current()
+    }
+
+    /// Put `primary` in place of the primary pool, with a clean record
+    ///
+    /// A backup that is the same pool moves up rather than being tried twice.
+    pub fn set_primary(&mut self, primary: String) {
+        self.entries.remove(0);
+        self.entries.retain(|entry| entry.pool != primary);
+        self.entries.insert(0, PoolEntry { pool: primary, failures: 0, blacklisted_until: None });
+        self.current = 0;
+    }
+
+    /// Whether every pool is blacklisted
+    pub fn all_blacklisted(&self) -> bool {
+        self.entries.iter().all(|entry| entry.blacklisted_until.is_some())
+    }
+
+    /// Count how a session on the current pool went, returning the blacklisting if this
+    /// failure got the pool blacklisted
+    ///
+    /// A lone pool, or any pool with `blacklist_after` 0, is never blacklisted.
+    pub fn record(&mut self, outcome: SessionOutcome, now: Instant) -> Option<Blacklisting> {
+        let blacklist_after = self.blacklist_after;
+        let cooldown = self.cooldown;
+        let can_blacklist = self.can_fail_over();
+        let entry = &mut self.entries[self.current];
+        if outcome == SessionOutcome::Worked {
+            // A blacklisted pool only gets a session when every pool is blacklisted, and
+            // one that works there has earned its place back
+            entry.failures = 0;
+            entry.blacklisted_until = None;
+            return None;
+        }
+        entry.failures += 1;
+        if !can_blacklist || entry.failures < blacklist_after || entry.blacklisted_until.is_some() {
+            return None;
+        }
+        entry.blacklisted_until = Some(now + cooldown);
+        Some(Blacklisting { pool: entry.pool.clone(), failures: entry.failures, cooldown })
+    }
+}
@@ -0,0 +1,341 @@
+//! Watching several miners from one place
+//!
+//! Fleet mode polls the `status` method of each instance's JSON-RPC control socket, logs
+//! the combined hash rate and raises an alert, in the log and on Telegram, when an
+//! instance stops answering or loses its pool for too long, and again when it recovers.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::exit_code::{ExitCode, fatal};
+use crate::logging;
+use crate::notify::{html_escape, send_telegram_message, TelegramConfig};
+use crate::sensors::SensorReadings;
+use crate::stats::format_difficulty;
+
+/// How often every instance is asked for its status
+const POLL_INTERVAL_SECS: u64 = 30;
+/// How long an instance has to answer
+const POLL_TIMEOUT_SECS: u64 = 10;
+
+/// A miner to watch: `name=host:port`, or just `host:port` to go by the address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FleetInstance {
+    pub name: String,
+    /// The instance's control socket, `host:port`
+    pub addr: String,
+}
+
+impl FleetInstance {
+    /// Parse a comma-separated list of instances
+    pub fn parse_list(list: &str) -> Result<Vec<Self>> {
+        list.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (name, addr) = match entry.split_once('=') {
+                    Some((name, addr)) => (name.trim(), addr.trim()),
+                    None => (entry, entry),
+                };
+                if name.is_empty() || !addr.contains(':') {
+                    bail!("Invalid fleet instance '{}', expected name=host:port or host:port", entry);
+                }
+                Ok(Self { name: name.to_string(), addr: addr.to_string() })
+            })
+            .collect()
+    }
+}
+
+/// The parts of an instance's `status` reply the fleet uses
+///
+/// Missing fields are left at their defaults, so instances running other versions can
+/// still be watched.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct InstanceStatus {
+    pub version: String,
+    pub connected: bool,
+    pub paused: bool,
+    pub threads: usize,
+    pub height: u64,
+    pub hashrate: u64,
+    pub sensors: SensorReadings,
+    pub session: InstanceSession,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct InstanceSession {
+    pub hashes: u64,
+    pub shares: u64,
+    pub best_share_difficulty: f64,
+}
+
+/// Ask the control socket at `addr` for its status
+pub async fn query_status(addr: &str, token: Option<&str>) -> Result<InstanceStatus> {
+    let query = async {
+        let stream = TcpStream::connect(addr).await?;
+        let (reader, mut writer) = stream.into_split();
+        let params = match token {
+            Some(token) => json!({"token": token}),
+            None => json!({}),
+        };
+        let request = json!({"jsonrpc": "2.0", "id": 1, "method": "status", "params": params});
+        writer.write_all(format!("{}\n", request).as_bytes()).await?;
+        let line = BufReader::new(reader).lines().next_line().await?
+            .ok_or_else(|| anyhow!("connection closed"))?;
+        let mut response: Value = serde_json::from_str(&line)?;
+        if let Some(message) = response["error"]["message"].as_str() {
+            bail!("{}", message);
+        }
+        Ok(serde_json::from_value(response["result"].take())?)
+    };
+    tokio::time::timeout(Duration::from_secs(POLL_TIMEOUT_SECS), query).await
+        .unwrap_or_else(|_| Err(anyhow!("no answer within {}s", POLL_TIMEOUT_SECS)))
+}
+
+/// What is wrong with an instance
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Problem {
+    /// Its control socket doesn't answer
+    Silent,
+    /// It answers but isn't connected to its pool, and isn't paused
+    NoPool,
+}
+
+#[derive(Debug)]
+struct Watched {
+    instance: FleetInstance,
+    /// The last status it answered with
+    status: Option<InstanceStatus>,
+    last_seen: Option<Instant>,
+    last_error: Option<String>,
+    /// The current problem and when it started
+    problem: Option<(Problem, Instant)>,
+    /// Whether the current problem has been alerted on yet
+    alerted: bool,
+}
+
+/// Every watched instance and what it was last seen doing
+#[derive(Debug)]
+pub struct Fleet {
+    watched: Vec<Watched>,
+    /// How long a problem lasts before it is alerted on
+    alert_after: Duration,
+    started: Instant,
+}
+
+impl Fleet {
+    pub fn new(instances: Vec<FleetInstance>, alert_after: Duration, now: Instant) -> Self {
+        let watched = instances.into_iter()
+            .map(|instance| Watched { instance, status: None, last_seen: None, last_error: None, problem: None, alerted: false })
+            .collect();
+        Self { watched, alert_after, started: now }
+    }
+
+    pub fn instances(&self) -> impl Iterator<Item = &FleetInstance> {
+        self.watched.iter().map(|watched| &watched.instance)
+    }
+
+    /// Take one poll's results, in instance order, returning the alerts they raise
+    pub fn update(&mut self, results: Vec<Result<InstanceStatus>>, now: Instant) -> Vec<String> {
+        let mut alerts = Vec::new();
+        for (watched, result) in self.watched.iter_mut().zip(results) {
+            let problem = match &result {
+                Err(_) => Some(Problem::Silent),
+                Ok(status) if !status.connected && !status.paused => Some(Problem::NoPool),
+                Ok(_) => None,
+            };
+            let name = &watched.instance.name;
+            if watched.problem.map(|(current, _)| current) != problem {
+                if let Some((ended, since)) = watched.problem.filter(|_| watched.alerted) {
+                    let minutes = minutes(now - since);
+                    alerts.push(match ended {
+                        Problem::Silent => format!("{} is back after {} minutes", name, minutes),
+                        Problem::NoPool => format!("{} is connected to its pool again after {} minutes", name, minutes),
+                    });
+                }
+                // Silence started at the last answer, not at the first poll that went unanswered
+                let since = match problem {
+                    Some(Problem::Silent) => watched.last_seen.unwrap_or(self.started),
+                    _ => now,
+                };
+                watched.problem = problem.map(|problem| (problem, since));
+                watched.alerted = false;
+            }
+            if let Some((problem, since)) = watched.problem {
+                if !watched.alerted && now - since >= self.alert_after {
+                    watched.alerted = true;
+                    let minutes = minutes(now - since);
+                    alerts.push(match (problem, &result) {
+                        (Problem::Silent, Err(e)) => format!("{} silent for {} minutes ({:#})", name, minutes, e),
+                        (Problem::Silent, Ok(_)) => format!("{} silent for {} minutes", name, minutes),
+                        (Problem::NoPool, _) => format!("{} has had no pool connection for {} minutes", name, minutes),
+                    });
+                }
+            }
+            match result {
+                Ok(status) => {
+                    watched.status = Some(status);
+                    watched.last_seen = Some(now);
+                    watched.last_error = None;
+                }
+                Err(e) => watched.last_error = Some(format!("{:#}", e)),
+            }
+        }
+        alerts
+    }
+
+    /// Instances answering and mining, out of all of them
+    pub fn mining(&self) -> usize {
+        self.watched.iter().filter(|watched| watched.problem.is_none() && watched.status.as_ref().is_some_and(|status| !status.paused)).count()
+    }
+
+    /// Combined hash rate of the instances answering, in h/s
+    pub fn hashrate(&self) -> u64 {
+        self.answering().map(|status| status.hashrate).sum()
+    }
+
+    fn answering(&self) -> impl Iterator<Item = &InstanceStatus> {
+        self.watched.iter()
+            .filter(|watched| !matches!(watched.problem, Some((Problem::Silent, _))))
+            .filter_map(|watched| watched.status.as_ref())
+    }
+
+    /// One line for the whole fleet, then one per instance
+    pub fn summary(&self, now: Instant) -> String {
+        let hashes: u64 = self.answering().map(|status| status.session.hashes).sum();
+        let shares: u64 = self.answering().map(|status| status.session.shares).sum();
+        let best = self.answering().map(|status| status.session.best_share_difficulty).fold(0.0, f64::max);
+        let mut summary = format!(
+            "{}/{} mining | {} h/s | {} hashes | {} shares | best share difficulty {}",
+            self.mining(), self.watched.len(), self.hashrate(), hashes, shares, format_difficulty(best)
+        );
+        for watched in &self.watched {
+            let state = match (&watched.status, watched.problem) {
+                (_, Some((Problem::Silent, since))) => format!(
+                    "silent for {} minutes{}",
+                    minutes(now - since),
+                    watched.last_error.as_ref().map_or(String::new(), |e| format!(" ({})", e))
+                ),
+                (Some(status), Some((Problem::NoPool, _))) => format!("no pool | height {}", status.height),
+                (Some(status), None) if status.paused => "paused".to_string(),
+                (Some(status), None) => format!(
+                    "{} h/s | {} threads | height {}{}",
+                    status.hashrate,
+                    status.threads,
+                    status.height,
+                    status.sensors.cpu_temp_c.map_or(String::new(), |temp| format!(" | CPU {:.1}°C", temp))
+                ),
+                (None, _) => "not seen yet".to_string(),
+            };
+            summary.push_str(&format!("\n  {}: {}", watched.instance.name, state));
+        }
+        summary
+    }
+}
+
+fn minutes(duration: Duration) -> u64 {
+    duration.as_secs() / 60
+}
+
+/// How fleet mode watches its instances
+#[derive(Debug, Clone)]
+pub struct FleetOptions {
+    pub instances: Vec<FleetInstance>,
+    /// The instances' API token
+    pub token: Option<String>,
+    /// How long an instance can be silent or without a pool before an alert
+    pub alert_after: Duration,
+    /// How often to log (and send) the combined summary, if at all
+    pub summary_every: Option<Duration>,
+    pub telegram: Option<TelegramConfig>,
+}
+
+/// Poll the instances until `shutdown` resolves, logging their combined state and alerting
+/// on problems
+///
+/// Fails if there are no instances to watch, tagged `ExitCode::InvalidConfig`.
+pub async fn run(options: FleetOptions, shutdown: impl Future<Output = &'static str>) -> Result<()> {
+    if options.instances.is_empty() {
+        return Err(fatal(ExitCode::InvalidConfig)(anyhow!("No instances to watch; set [fleet] instances")));
+    }
+    info!(
+        event = "startup",
+        instances = options.instances.len(),
+        "Watching {} miner(s): {}",
+        options.instances.len(),
+        options.instances.iter().map(|instance| format!("{} ({})", instance.name, instance.addr)).collect::<Vec<_>>().join(", ")
+    );
+    let mut fleet = Fleet::new(options.instances.clone(), options.alert_after, Instant::now());
+    let mut polls = tokio::time::interval(Duration::from_secs(POLL_INTERVAL_SECS));
+    polls.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut summaries = options.summary_every.map(|every| tokio::time::interval_at(tokio::time::Instant::now() + every, every));
+    tokio::pin!(shutdown);
+    loop {
+        let summary_due = async {
+            match summaries.as_mut() {
+                Some(summaries) => summaries.tick().await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            signal = &mut shutdown => {
+                warn!(event = "shutdown", signal, "Received {}, shutting down", signal);
+                return Ok(());
+            }
+            _ = polls.tick() => {
+                let results = poll(&fleet, options.token.as_deref()).await;
+                for alert in fleet.update(results, Instant::now()) {
+                    warn!(target: logging::SUMMARY_TARGET, event = "fleet_alert", "{}", alert);
+                    notify(&options.telegram, format!("⚠️ <b>Fleet</b>\n\n{}", html_escape(&alert)));
+                }
+                info!(
+                    event = "fleet_status",
+                    mining = fleet.mining(),
+                    instances = options.instances.len(),
+                    hashrate = fleet.hashrate(),
+                    "Fleet: {}/{} mining | {} h/s", fleet.mining(), options.instances.len(), fleet.hashrate()
+                );
+            }
+            _ = summary_due => {
+                let summary = fleet.summary(Instant::now());
+                info!(target: logging::SUMMARY_TARGET, event = "fleet_summary", "Fleet summary: {}", summary);
+                notify(&options.telegram, format!("📊 <b>Fleet Summary</b>\n\n<pre>{}</pre>", html_escape(&summary)));
+            }
+        }
+    }
+}
+
+/// Ask every instance for its status at once, returning the results in instance order
+async fn poll(fleet: &Fleet, token: Option<&str>) -> Vec<Result<InstanceStatus>> {
+    let mut queries = JoinSet::new();
+    for (index, instance) in fleet.instances().enumerate() {
+        let addr = instance.addr.clone();
+        let token = token.map(str::to_string);
+        queries.spawn(async move { (index, query_status(&addr, token.as_deref()).await) });
+    }
+    let mut results: Vec<Result<InstanceStatus>> = fleet.instances().map(|_| Err(anyhow!("not polled"))).collect();
+    while let Some(Ok((index, result))) = queries.join_next().await {
+        results[index] = result;
+    }
+    results
+}
+
+/// Send a Telegram message in the background, if Telegram is set up
+fn notify(telegram: &Option<TelegramConfig>, message: String) {
+    if let Some(telegram) = telegram.clone() {
+        tokio::spawn(async move {
+            if let Err(e) = send_telegram_message(&telegram, &message).await {
+                warn!("Failed to send Telegram fleet message: {}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,234 @@
+use std::net::SocketAddr;
+use anyhow::Result;
+
+use crate::control::Control;
+use crate::tasks::Tasks;
+
+#[cfg(feature = "grpc")]
+use std::pin::Pin;
+#[cfg(feature = "grpc")]
+use anyhow::Context;
+#[cfg(feature = "grpc")]
+use tokio::sync::mpsc::error::TrySendError;
+#[cfg(feature = "grpc")]
+use tokio_stream::{Stream, StreamExt};
+#[cfg(feature = "grpc")]
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+#[cfg(feature = "grpc")]
+use tokio_util::sync::CancellationToken;
+#[cfg(feature = "grpc")]
+use tonic::{Request, Response};
+#[cfg(feature = "grpc")]
+use tracing::{error, info, warn, Instrument};
+
+#[cfg(feature = "grpc")]
+use crate::events;
+#[cfg(feature = "grpc")]
+use crate::logging::Verbosity;
+#[cfg(feature = "grpc")]
+use crate::tasks::Stage;
+
+/// Types and service generated from `proto/miner.proto`
+#[cfg(feature = "grpc")]
+#[allow(clippy::all)]
+pub mod proto {
+    tonic::include_proto!("miner.v1");
+}
+
+#[cfg(feature = "grpc")]
+use proto::miner_server::{Miner, MinerServer};
+
+/// Events an event stream can fall behind by before it starts skipping them
+#[cfg(feature = "grpc")]
+const EVENT_STREAM_BUFFER: usize = 1024;
+
+#[cfg(feature = "grpc")]
+struct MinerService {
+    control: Control,
+    /// Ends the event streams, which would otherwise keep the server from shutting down
+    cancel: CancellationToken,
+}
+
+#[cfg(feature = "grpc")]
+#[tonic::async_trait]
+impl Miner for MinerService {
+    async fn get_status(&self, _request: Request<proto::GetStatusRequest>) -> Result<Response<proto::Status>, tonic::Status> {
+        Ok(Response::new(self.status()))
+    }
+
+    async fn pause(&self, _request: Request<proto::PauseRequest>) -> Result<Response<proto::PauseResponse>, tonic::Status> {
+        let changed = self.control.pause("grpc");
+        Ok(Response::new(proto::PauseResponse { changed, status: Some(self.status()) }))
+    }
+
+    async fn resume(&self, _request: Request<proto::ResumeRequest>) -> Result<Response<proto::PauseResponse>, tonic::Status> {
+        let changed = self.control.resume("grpc");
+        Ok(Response::new(proto::PauseResponse { changed, status: Some(self.status()) }))
+    }
+
+    async fn restart(&self, _request: Request<proto::RestartRequest>) -> Result<Response<proto::Status>, tonic::Status> {
+        self.control.restart("grpc");
+        Ok(Response::new(self.status()))
+    }
+
+    async fn update_config(&self, request: Request<proto::UpdateConfigRequest>) -> Result<Response<proto::Status>, tonic::Status> {
+        let update = request.into_inner();
+        // Validate everything before applying anything
+        let verbosity = update.verbosity.as_deref()
+            .map(Verbosity::parse)
+            .transpose()
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+        if update.threads == Some(0) {
+            return Err(tonic::Status::invalid_argument("threads must be at least 1"));
+        }
+        if let Some(threads) = update.threads {
+            self.control.set_threads(threads as usize);
+        }
+        if let Some(verbosity) = verbosity {
+            self.control.set_verbosity(verbosity).map_err(|e| tonic::Status::internal(e.to_string()))?;
+        }
+        info!(event = "config_updated", source = "grpc", threads = ?update.threads, verbosity = ?update.verbosity, "Configuration updated over gRPC");
+        Ok(Response::new(self.status()))
+    }
+
+    async fn reload_config(&self, _request: Request<proto::ReloadConfigRequest>) -> Result<Response<proto::Status>, tonic::Status> {
+        self.control.reload();
+        Ok(Response::new(self.status()))
+    }
+
+    type StreamEventsStream = Pin<Box<dyn Stream<Item = Result<proto::Event, tonic::Status>> + Send>>;
+
+    async fn stream_events(&self, request: Request<proto::StreamEventsRequest>) -> Result<Response<Self::StreamEventsStream>, tonic::Status> {
+        let wanted = request.into_inner().events;
+        // A client too slow to keep up skips what it missed rather than holding up the bus
+        let (events_tx, events_rx) = tokio::sync::mpsc::channel(EVENT_STREAM_BUFFER);
+        let mut subscription = events::subscribe();
+        tokio::spawn(self.cancel.clone().run_until_cancelled_owned(async move {
+            while let Some(event) = events::next(&mut subscription).await {
+                if let Err(TrySendError::Closed(_)) = events_tx.try_send(event) {
+                    return;
+                }
+            }
+        }).in_current_span());
+        let stream = ReceiverStream::new(events_rx).filter_map(move |event| {
+            let event = event.to_json();
+            let name = event["event"].as_str().unwrap_or_default().to_string();
+            if !wanted.is_empty() && !wanted.contains(&name) {
+                return None;
+            }
+            Some(Ok(proto::Event {
+                event: name,
+                timestamp: event["timestamp"].as_f64().unwrap_or_default(),
+                json: event.to_string(),
+            }))
+        });
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+#[cfg(feature = "grpc")]
+impl MinerService {
+    fn status(&self) -> proto::Status {
+        let status = self.control.status();
+        let session = status.session;
+        proto::Status {
+            version: status.version,
+            pool: status.pool,
+            address: status.address,
+            connected: status.connected,
+            state: status.state,
+            paused: status.paused,
+            dry_run: status.dry_run,
+            threads: status.threads as u32,
+            height: status.height,
+            hashrate: status.hashrate,
+            verbosity: status.verbosity,
+            session: Some(proto::Session {
+                duration_secs: session.duration_secs,
+                hashes: session.hashes,
+                average_hashrate: session.average_hashrate,
+                shares: session.shares,
+                best_share_difficulty: session.best_share_difficulty,
+                closest_call: session.closest_call,
+                reconnects: session.reconnects,
+                jobs: session.jobs,
+                average_job_secs: session.average_job_secs,
+                peak_cpu_temp_c: session.peak_cpu_temp_c,
+                energy_kwh: session.energy_kwh,
+                energy_cost: session.energy_cost,
+                pool_bytes_sent: session.pool_traffic.sent_bytes,
+                pool_bytes_received: session.pool_traffic.received_bytes,
+            }),
+            sensors: Some(proto::Sensors {
+                cpu_temp_c: status.sensors.cpu_temp_c,
+                fan_rpm: status.sensors.fan_rpm,
+                cpu_mhz: status.sensors.cpu_mhz,
+            }),
+            pool_latency: Some(proto::PoolLatency {
+                last_ms: status.pool_latency.last_ms,
+                recent_ms: status.pool_latency.recent_ms,
+                min_ms: status.pool_latency.min_ms,
+                max_ms: status.pool_latency.max_ms,
+                samples: status.pool_latency.samples,
+                degraded: status.pool_latency.degraded,
+            }),
+            cluster: status.cluster.map(|cluster| proto::Cluster {
+                hashrate: cluster.hashrate,
+                hashes: cluster.hashes,
+                best_share_difficulty: cluster.best_share_difficulty,
+                workers: cluster.workers.into_iter().map(|worker| proto::ClusterWorker {
+                    id: worker.id,
+                    name: worker.name,
+                    peer: worker.peer.to_string(),
+                    threads: worker.threads as u32,
+                    hashrate: worker.hashrate,
+                    hashes: worker.hashes,
+                    finished: worker.finished,
+                    best_share_difficulty: worker.best_share_difficulty,
+                }).collect(),
+            }),
+        }
+    }
+}
+
+/// Serve the gRPC API on `listen` until the API stage stops
+///
+/// With a `token`, every call must carry an `authorization: Bearer <token>` header.
+/// Fails if the address can't be bound.
+#[cfg(feature = "grpc")]
+pub async fn start(listen: SocketAddr, token: Option<String>, control: Control, tasks: &mut Tasks) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(listen).await
+        .with_context(|| format!("Failed to listen for gRPC on {}", listen))?;
+    if token.is_none() && !listen.ip().is_loopback() {
+        warn!(event = "api_unauthenticated", "gRPC API on {} accepts calls from the network without a token", listen);
+    }
+    info!(event = "grpc_listening", listen = %listen, "gRPC API listening on {}", listen);
+
+    let expected = token.map(|token| format!("Bearer {}", token));
+    let check_token = move |request: Request<()>| -> Result<Request<()>, tonic::Status> {
+        let Some(expected) = &expected else {
+            return Ok(request);
+        };
+        match request.metadata().get("authorization").and_then(|value| value.to_str().ok()) {
+            Some(given) if given == expected => Ok(request),
+            _ => Err(tonic::Status::unauthenticated("missing or wrong API token")),
+        }
+    };
+    let service = MinerServer::with_interceptor(MinerService { control, cancel: tasks.token(Stage::Api) }, check_token);
+    tasks.spawn_with(Stage::Api, "grpc", |cancel| async move {
+        // Open event streams end with the server
+        let served = tonic::transport::Server::builder()
+            .add_service(service)
+            .serve_with_incoming_shutdown(TcpListenerStream::new(listener), cancel.cancelled_owned())
+            .await;
+        if let Err(e) = served {
+            error!("gRPC API stopped: {}", e);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "grpc"))]
+pub async fn start(_listen: SocketAddr, _token: Option<String>, _control: Control, _tasks: &mut Tasks) -> Result<()> {
+    anyhow::bail!("The gRPC API requires building with `--features grpc`")
+}
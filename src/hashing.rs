@@ -0,0 +1,112 @@
+use bitcoin::hashes::Hash;
+use bitcoin::{BlockHash, Target};
+use sha2::{Sha256, Digest};
+
+/// The double SHA-256 the miner hashes with: `asm` when built with the `asm` feature on a
+/// target it supports, otherwise `portable`
+pub const SHA256_IMPL: &str = if cfg!(sha256_asm) { "asm" } else { "portable" };
+
+/// The hashing backend on this machine, as metrics and error reports name it
+///
+/// `cpu-asm` with the assembly SHA-256. Otherwise `sha2` picks at runtime: `cpu-sha` where
+/// the CPU has SHA extensions, `cpu-portable` where it doesn't.
+pub fn backend() -> &'static str {
+    if cfg!(sha256_asm) {
+        "cpu-asm"
+    } else if cpu_has_sha_extensions() {
+        "cpu-sha"
+    } else {
+        "cpu-portable"
+    }
+}
+
+/// Whether `sha2` hashes with the CPU's SHA instructions, by the same checks it makes
+fn cpu_has_sha_extensions() -> bool {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        std::arch::is_x86_feature_detected!("sha")
+            && std::arch::is_x86_feature_detected!("sse2")
+            && std::arch::is_x86_feature_detected!("ssse3")
+            && std::arch::is_x86_feature_detected!("sse4.1")
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        std::arch::is_aarch64_feature_detected!("sha2")
+    }
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        false
+    }
+}
+
+/// Double SHA256 hash
+pub fn double_sha256(data: &[u8]) -> Vec<u8> {
+    #[cfg(sha256_asm)]
+    return double_sha256_asm(data);
+    #[cfg(not(sha256_asm))]
+    double_sha256_portable(data)
+}
+
+/// Double SHA256 with the `sha2` crate, which uses the CPU's SHA extensions where it has them
+pub fn double_sha256_portable(data: &[u8]) -> Vec<u8> {
+    let first_hash = Sha256::digest(data);
+    let second_hash = Sha256::digest(first_hash);
+    second_hash.to_vec()
+}
+
+/// Double SHA256 with the assembly compression function from `sha2-asm`
+#[cfg(sha256_asm)]
+pub fn double_sha256_asm(data: &[u8]) -> Vec<u8> {
+    sha256_asm(&sha256_asm(data)).to_vec()
+}
+
+#[cfg(sha256_asm)]
+fn sha256_asm(data: &[u8]) -> [u8; 32] {
+    const INITIAL_STATE: [u32; 8] = [
+        0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+    ];
+    let mut state = INITIAL_STATE;
+    let mut blocks = data.chunks_exact(64);
+    for block in &mut blocks {
+        sha2_asm::compress256(&mut state, &[block.try_into().expect("64-byte chunk")]);
+    }
+    // The rest, a 1 bit, zeros and the length in bits fill one or two final blocks
+    let rest = blocks.remainder();
+    let mut tail = [0u8; 128];
+    tail[..rest.len()].copy_from_slice(rest);
+    tail[rest.len()] = 0x80;
+    let tail_len = if rest.len() < 56 { 64 } else { 128 };
+    tail[tail_len - 8..tail_len].copy_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+    for block in tail[..tail_len].chunks_exact(64) {
+        sha2_asm::compress256(&mut state, &[block.try_into().expect("64-byte chunk")]);
+    }
+    let mut hash = [0u8; 32];
+    for (bytes, word) in hash.chunks_exact_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    hash
+}
+
+fn block_hash(hash: &[u8]) -> Option<BlockHash> {
+    Some(BlockHash::from_byte_array(hash.try_into().ok()?))
+}
+
+/// Whether a header hash (as `double_sha256` returns it) meets a 32-byte big-endian target
+///
+/// The hash is a little-endian number, so it is compared from its last byte down.
+pub fn hash_meets_target(hash: &[u8], target: &[u8]) -> bool {
+    let (Some(hash), Ok(target)) = (block_hash(hash), <[u8; 32]>::try_from(target)) else {
+        return false;
+    };
+    Target::from_be_bytes(target).is_met_by(hash)
+}
+
+/// Whether `hash` is a lower number than `than`, both as `double_sha256` returns them
+pub fn hash_below(hash: &[u8], than: &[u8]) -> bool {
+    hash.iter().rev().lt(than.iter().rev())
+}
+
+/// A header hash the way block explorers and nodes show it
+pub fn hash_hex(hash: &[u8]) -> String {
+    block_hash(hash).map_or_else(|| hex::encode(hash), |hash| hash.to_string())
+}
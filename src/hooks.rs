@@ -0,0 +1,104 @@
+use std::process::Stdio;
+use std::time::Duration;
+use anyhow::{Result, Context, bail};
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::debug;
+
+use crate::events::MinerEvent;
+
+/// How long a hook may run before it's killed
+const HOOK_TIMEOUT_SECS: u64 = 60;
+/// Prefix of the environment variables carrying the event's fields
+const HOOK_ENV_PREFIX: &str = "HOOK_";
+
+/// User commands run when something happens, for integrations we don't ship a notifier for
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Hooks {
+    pub on_start: Option<String>,
+    pub on_block_found: Option<String>,
+    pub on_disconnect: Option<String>,
+}
+
+impl Hooks {
+    /// The hook configured for an event, as `(name, command)`
+    pub fn for_event(&self, event: &MinerEvent) -> Option<(&'static str, &str)> {
+        let (name, command) = match event {
+            MinerEvent::Started { .. } => ("on_start", &self.on_start),
+            MinerEvent::BlockFound { .. } => ("on_block_found", &self.on_block_found),
+            MinerEvent::Disconnected { .. } => ("on_disconnect", &self.on_disconnect),
+            _ => return None,
+        };
+        command.as_deref().map(|command| (name, command))
+    }
+}
+
+/// Run a hook through the shell with the event on stdin as JSON (the same object
+/// `--output ndjson` prints) and each field in a `HOOK_<FIELD>` environment variable
+///
+/// Output goes to the debug log rather than the console so it can't corrupt the ndjson
+/// stream. Fails if the command exits non-zero or runs longer than `HOOK_TIMEOUT_SECS`.
+pub async fn run(command: &str, event: &MinerEvent) -> Result<()> {
+    let event = event.to_json();
+    let mut child = shell(command)
+        .envs(env_vars(&event))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("Failed to run '{}'", command))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that ignores stdin may exit before reading it
+        let _ = stdin.write_all(format!("{}\n", event).as_bytes()).await;
+    }
+    let output = tokio::time::timeout(Duration::from_secs(HOOK_TIMEOUT_SECS), child.wait_with_output())
+        .await
+        .with_context(|| format!("'{}' still running after {}s, killed", command, HOOK_TIMEOUT_SECS))??;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !stdout.trim().is_empty() {
+        debug!(event = "hook_output", "{}", stdout.trim());
+    }
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        match stderr.trim() {
+            "" => bail!("'{}' failed ({})", command, output.status),
+            stderr => bail!("'{}' failed ({}): {}", command, output.status, stderr),
+        }
+    }
+    Ok(())
+}
+
+fn shell(command: &str) -> Command {
+    #[cfg(windows)]
+    let mut shell = Command::new("cmd");
+    #[cfg(windows)]
+    shell.args(["/C", command]);
+    #[cfg(not(windows))]
+    let mut shell = Command::new("sh");
+    #[cfg(not(windows))]
+    shell.args(["-c", command]);
+    shell
+}
+
+/// `HOOK_EVENT=block_found`, `HOOK_NONCE=...`: lists are comma-separated, nested objects JSON
+fn env_vars(event: &Value) -> Vec<(String, String)> {
+    let Some(fields) = event.as_object() else {
+        return Vec::new();
+    };
+    fields.iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::Null => String::new(),
+                Value::String(s) => s.clone(),
+                Value::Array(items) => items.iter()
+                    .map(|item| item.as_str().map_or_else(|| item.to_string(), str::to_string))
+                    .collect::<Vec<_>>()
+                    .join(","),
+                other => other.to_string(),
+            };
+            (format!("{}{}", HOOK_ENV_PREFIX, key.to_uppercase()), value)
+        })
+        .collect()
+}
@@ -0,0 +1,101 @@
+//! Pool round-trip latency
+//!
+//! Every submit and a periodic ping are timed from the request going out to the pool's
+//! reply. A block that reaches the pool after the network has moved on is stale and lost,
+//! so a link that gets much slower than it has been is worth a warning.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+
+/// How often the pool is pinged to measure the round trip (and keep a paused session alive)
+pub const PING_INTERVAL_SECS: u64 = 60;
+/// Round trips the recent latency is averaged over
+const RECENT_SAMPLES: usize = 5;
+/// Recent latency this many times the best recent latency seen is degraded...
+const DEGRADED_FACTOR: f64 = 3.0;
+/// ...as long as it's also at least this many milliseconds, so a fast link getting a
+/// little slower isn't flagged
+const DEGRADED_MIN_MS: f64 = 250.0;
+
+/// Round trips to the pool so far this session, in milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LatencyStats {
+    pub last_ms: Option<f64>,
+    /// Over the last few round trips
+    pub recent_ms: Option<f64>,
+    pub min_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+    pub samples: u64,
+    /// Whether the recent latency is well above what the link has managed before
+    pub degraded: bool,
+}
+
+impl LatencyStats {
+    /// e.g. `42 ms (recent 45 ms, 31-120 ms)`, or `None` before the first round trip
+    pub fn summary(&self) -> Option<String> {
+        let last = self.last_ms?;
+        let mut line = format!("{:.0} ms", last);
+        if let (Some(recent), Some(min), Some(max)) = (self.recent_ms, self.min_ms, self.max_ms) {
+            line.push_str(&format!(" (recent {:.0} ms, {:.0}-{:.0} ms)", recent, min, max));
+        }
+        if self.degraded {
+            line.push_str(", degraded");
+        }
+        Some(line)
+    }
+}
+
+/// A change in whether the pool link is degraded
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LatencyChange {
+    Degraded { recent_ms: f64, baseline_ms: f64 },
+    Recovered { recent_ms: f64, baseline_ms: f64 },
+}
+
+/// Keeps the round trips and notices when they degrade
+#[derive(Debug, Default)]
+pub struct LatencyTracker {
+    recent: VecDeque<f64>,
+    /// Lowest recent average seen, once there were enough round trips for one
+    baseline_ms: Option<f64>,
+    stats: LatencyStats,
+}
+
+impl LatencyTracker {
+    /// Count a round trip, returning whether the link just became degraded or recovered
+    pub fn record(&mut self, round_trip: Duration) -> Option<LatencyChange> {
+        let ms = round_trip.as_secs_f64() * 1000.0;
+        if self.recent.len() == RECENT_SAMPLES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(ms);
+        let recent_ms = self.recent.iter().sum::<f64>() / self.recent.len() as f64;
+        self.stats.last_ms = Some(ms);
+        self.stats.recent_ms = Some(recent_ms);
+        self.stats.min_ms = Some(self.stats.min_ms.map_or(ms, |min| min.min(ms)));
+        self.stats.max_ms = Some(self.stats.max_ms.map_or(ms, |max| max.max(ms)));
+        self.stats.samples += 1;
+
+        if self.recent.len() < RECENT_SAMPLES {
+            return None;
+        }
+        let baseline_ms = self.baseline_ms.map_or(recent_ms, |baseline| baseline.min(recent_ms));
+        self.baseline_ms = Some(baseline_ms);
+        let degraded = recent_ms >= DEGRADED_MIN_MS && recent_ms >= baseline_ms * DEGRADED_FACTOR;
+        if degraded == self.stats.degraded {
+            return None;
+        }
+        self.stats.degraded = degraded;
+        Some(if degraded {
+            LatencyChange::Degraded { recent_ms, baseline_ms }
+        } else {
+            LatencyChange::Recovered { recent_ms, baseline_ms }
+        })
+    }
+
+    pub fn stats(&self) -> LatencyStats {
+        self.stats
+    }
+}
@@ -0,0 +1,71 @@
+//! Bitcoin solo miner: Stratum pool connection, block header construction, multi-threaded
+//! hashing and the notification, logging and stats plumbing around them.
+//!
+//! The `bitcoin-solo-miner` binary is a thin command-line front end over [`miner::mine`].
+
+pub mod activity;
+pub mod address;
+pub mod backend;
+pub mod bandwidth;
+pub mod benchmark;
+pub mod block_archive;
+pub mod btc_price;
+pub mod candidate;
+pub mod chain;
+pub mod clock;
+pub mod cluster;
+pub mod coinbase;
+pub mod config;
+pub mod config_file;
+pub mod console;
+pub mod control;
+pub mod difficulty_check;
+pub mod electricity;
+pub mod error;
+pub mod error_report;
+pub mod events;
+pub mod exit_code;
+pub mod failover;
+pub mod fleet;
+pub mod grpc;
+pub mod hashing;
+pub mod hooks;
+pub mod latency;
+pub mod log_rotation;
+pub mod logging;
+pub mod mdns;
+pub mod miner;
+pub mod notify;
+pub mod paths;
+pub mod payout;
+pub mod pool_check;
+pub mod power;
+pub mod prefetch;
+pub mod presets;
+pub mod proxy;
+pub mod quiet;
+pub mod recording;
+pub mod redis;
+pub mod reload;
+pub mod rpc;
+pub mod scripting;
+pub mod sd_notify;
+pub mod secrets;
+pub mod self_test;
+pub mod sensors;
+pub mod session;
+pub mod solar;
+pub mod sparkline;
+pub mod stats;
+pub mod stratum;
+pub mod submit;
+pub mod system_log;
+pub mod tasks;
+pub mod telemetry;
+#[cfg(feature = "testutil")]
+pub mod testutil;
+pub mod timestamp;
+pub mod vardiff;
+pub mod weekly;
+pub mod work;
+pub mod worker;
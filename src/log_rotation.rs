@@ -0,0 +1,216 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECS_PER_DAY: u64 = 86_400;
+
+/// When to rotate a log file and how many rotated files to keep
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RotationPolicy {
+    /// Rotate once the file would grow past this many bytes (0 = no size limit)
+    pub max_bytes: u64,
+    /// Rotate when the UTC date changes
+    pub daily: bool,
+    /// Number of rotated files to keep (`file.log.1` is the newest)
+    pub max_files: usize,
+}
+
+impl Default for RotationPolicy {
+    fn default() -> Self {
+        Self {
+            max_bytes: 10 * 1024 * 1024,
+            daily: false,
+            max_files: 5,
+        }
+    }
+}
+
+/// Append-only log file that rotates itself according to a `RotationPolicy`
+#[derive(Debug)]
+pub struct RotatingFile {
+    path: PathBuf,
+    policy: RotationPolicy,
+    /// Delete rotated files past `max_files`
+    prune: bool,
+    file: File,
+    size: u64,
+    opened_day: u64,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, policy: RotationPolicy) -> io::Result<Self> {
+        Self::open_with(path, policy, true)
+    }
+
+    /// A log that rotates like `open` but never deletes a rotated file, for records such as
+    /// the found blocks that must outlive `max_files`
+    pub fn open_unpruned(path: &Path, policy: RotationPolicy) -> io::Result<Self> {
+        Self::open_with(path, policy, false)
+    }
+
+    fn open_with(path: &Path, policy: RotationPolicy, prune: bool) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let metadata = file.metadata()?;
+        // An existing file keeps the day it was last written, so a restart on a later day rotates it
+        let opened_day = metadata.modified().map(day_of).unwrap_or_else(|_| today());
+        Ok(Self {
+            path: path.to_path_buf(),
+            policy,
+            prune,
+            file,
+            size: metadata.len(),
+            opened_day,
+        })
+    }
+
+    fn needs_rotation(&self, incoming: usize) -> bool {
+        let too_big = self.policy.max_bytes > 0
+            && self.size > 0
+            && self.size + incoming as u64 > self.policy.max_bytes;
+        let new_day = self.policy.daily && self.size > 0 && today() != self.opened_day;
+        too_big || new_day
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_os_string();
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        if !self.prune {
+            let kept = (1..).take_while(|index| self.rotated_path(*index).exists()).count();
+            for index in (1..=kept).rev() {
+                std::fs::rename(self.rotated_path(index), self.rotated_path(index + 1))?;
+            }
+            std::fs::rename(&self.path, self.rotated_path(1))?;
+        } else if self.policy.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let oldest = self.rotated_path(self.policy.max_files);
+            if oldest.exists() {
+                std::fs::remove_file(&oldest)?;
+            }
+            for index in (1..self.policy.max_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    std::fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.rotated_path(1))?;
+        }
+        self.file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = 0;
+        self.opened_day = today();
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.needs_rotation(buf.len()) {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn day_of(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() / SECS_PER_DAY)
+        .unwrap_or(0)
+}
+
+fn today() -> u64 {
+    day_of(SystemTime::now())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory of its own for `name`
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("miner-log-rotation-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn read(path: &Path) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    /// Write `lines` 10-byte lines to `file`, a new one each time the 25-byte limit is hit
+    fn write_lines(file: &mut RotatingFile, lines: usize) {
+        for line in 0..lines {
+            // One write per line, as the loggers do, so no line straddles a rotation
+            file.write_all(format!("line {:04}\n", line).as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+    }
+
+    const SMALL: RotationPolicy = RotationPolicy { max_bytes: 25, daily: false, max_files: 2 };
+
+    #[test]
+    fn a_file_rotates_before_it_would_pass_the_size_limit() {
+        let dir = scratch_dir("size");
+        let path = dir.join("miner.log");
+        let mut file = RotatingFile::open(&path, SMALL).unwrap();
+        write_lines(&mut file, 3);
+        assert_eq!(read(&path), "line 0002\n");
+        assert_eq!(read(&dir.join("miner.log.1")), "line 0000\nline 0001\n");
+
+        // A restart picks up the size already on disk
+        let mut file = RotatingFile::open(&path, SMALL).unwrap();
+        file.write_all(b"restarted\n").unwrap();
+        file.write_all(b"rotated\n").unwrap();
+        assert_eq!(read(&path), "rotated\n");
+        assert_eq!(read(&dir.join("miner.log.1")), "line 0002\nrestarted\n");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_the_newest_max_files_rotated_files_are_kept() {
+        let dir = scratch_dir("prune");
+        let path = dir.join("miner.log");
+        let mut file = RotatingFile::open(&path, SMALL).unwrap();
+        // Four rotations
+        write_lines(&mut file, 10);
+        assert_eq!(read(&path), "line 0008\nline 0009\n");
+        assert_eq!(read(&dir.join("miner.log.1")), "line 0006\nline 0007\n");
+        assert_eq!(read(&dir.join("miner.log.2")), "line 0004\nline 0005\n");
+        assert!(!dir.join("miner.log.3").exists());
+
+        // Without any rotated files, a full file is just started over
+        let path = dir.join("nothing_kept.log");
+        let mut file = RotatingFile::open(&path, RotationPolicy { max_files: 0, ..SMALL }).unwrap();
+        write_lines(&mut file, 3);
+        assert_eq!(read(&path), "line 0002\n");
+        assert!(!dir.join("nothing_kept.log.1").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn an_unpruned_file_keeps_every_rotated_file() {
+        let dir = scratch_dir("unpruned");
+        let path = dir.join("blocks_found.log");
+        let mut file = RotatingFile::open_unpruned(&path, SMALL).unwrap();
+        write_lines(&mut file, 10);
+        assert_eq!(read(&path), "line 0008\nline 0009\n");
+        for (index, first) in [(1, 6), (2, 4), (3, 2), (4, 0)] {
+            let rotated = read(&dir.join(format!("blocks_found.log.{}", index)));
+            assert_eq!(rotated, format!("line {:04}\nline {:04}\n", first, first + 1));
+        }
+        assert!(!dir.join("blocks_found.log.5").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
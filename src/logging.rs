@@ -0,0 +1,245 @@
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::reload;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, Registry};
+use anyhow::{Result, bail};
+
+use std::sync::Mutex;
+
+use crate::console::ConsoleFormat;
+use crate::events::OutputMode;
+use crate::log_rotation::RotatingFile;
+use crate::system_log::SystemLogWriter;
+use crate::telemetry::Telemetry;
+use crate::timestamp::Timestamps;
+
+/// Output format for log events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl LogFormat {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => bail!("Unknown log_format '{}' (expected 'text' or 'json')", other),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        }
+    }
+}
+
+/// How much the miner reports, from least to most
+///
+/// Applies to the console, the main log file, the system log and Telegram notifications.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Errors only; block finds are still written to the block log and notified
+    Silent,
+    /// Warnings, block finds and run summaries (what quiet mode used to show)
+    Summary,
+    /// Everything informational, including the hashrate (the default)
+    #[default]
+    Normal,
+    /// Debug detail from the miner, such as raw pool messages
+    Verbose,
+    /// Everything the miner can log
+    Trace,
+}
+
+impl Verbosity {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "silent" => Ok(Verbosity::Silent),
+            "summary" => Ok(Verbosity::Summary),
+            "" | "normal" => Ok(Verbosity::Normal),
+            "verbose" => Ok(Verbosity::Verbose),
+            "trace" => Ok(Verbosity::Trace),
+            other => bail!("Unknown verbosity '{}' (expected silent, summary, normal, verbose or trace)", other),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Verbosity::Silent => "silent",
+            Verbosity::Summary => "summary",
+            Verbosity::Normal => "normal",
+            Verbosity::Verbose => "verbose",
+            Verbosity::Trace => "trace",
+        }
+    }
+}
+
+/// Target for events shown below the normal verbosity: summaries at `summary`, block finds
+/// (logged at warn) even at `silent`
+pub const SUMMARY_TARGET: &str = "summary";
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Handle for changing the log level of every output after startup
+#[derive(Clone)]
+pub struct LogLevel {
+    handles: Vec<reload::Handle<EnvFilter, Registry>>,
+}
+
+impl LogLevel {
+    /// Change the verbosity of every output, keeping any `RUST_LOG` directives
+    pub fn set_verbosity(&self, verbosity: Verbosity) -> Result<()> {
+        for handle in &self.handles {
+            handle.reload(level_filter(verbosity))?;
+        }
+        Ok(())
+    }
+}
+
+fn level_filter(verbosity: Verbosity) -> EnvFilter {
+    let default_level = match verbosity {
+        Verbosity::Silent => LevelFilter::ERROR,
+        Verbosity::Summary => LevelFilter::WARN,
+        Verbosity::Normal | Verbosity::Verbose | Verbosity::Trace => LevelFilter::INFO,
+    };
+    let filter = EnvFilter::builder()
+        .with_default_directive(default_level.into())
+        .from_env_lossy();
+    // Extra detail is limited to the miner itself; dependencies stay at info
+    let directive = match verbosity {
+        Verbosity::Silent => Some(format!("{}=warn", SUMMARY_TARGET)),
+        Verbosity::Summary => Some(format!("{}=info", SUMMARY_TARGET)),
+        Verbosity::Verbose => Some(format!("{}=debug", env!("CARGO_CRATE_NAME"))),
+        Verbosity::Trace => Some(format!("{}=trace", env!("CARGO_CRATE_NAME"))),
+        Verbosity::Normal => None,
+    };
+    match directive.and_then(|d| d.parse().ok()) {
+        Some(directive) => filter.add_directive(directive),
+        None => filter,
+    }
+}
+
+/// Install the global tracing subscriber
+///
+/// The verbosity sets the level of every output (see `Verbosity`). `RUST_LOG` directives (e.g. `bitcoin_solo_miner=debug,reqwest=warn`) are layered on top
+/// for per-target filtering. When OTLP export is enabled, spans are exported at info level
+/// regardless of verbosity. Text console output uses the aligned `ConsoleFormat`, colored
+/// when `color` is set. Console and main log lines are stamped with `timestamps`. The
+/// optional main log file receives the same events without ANSI colors. In ndjson output mode the console log moves to stderr
+/// so stdout carries only the event stream. With a system log configured, events are also
+/// sent to journald or syslog at their matching priority; the host log adds its own timestamps.
+/// The returned `LogLevel` changes the verbosity of every output without reinstalling anything.
+#[allow(clippy::too_many_arguments)]
+pub fn init(
+    format: LogFormat,
+    verbosity: Verbosity,
+    output: OutputMode,
+    color: bool,
+    timestamps: &Timestamps,
+    main_log: Option<RotatingFile>,
+    system_log: Option<SystemLogWriter>,
+    telemetry: Option<&Telemetry>,
+) -> LogLevel {
+    let mut handles = Vec::new();
+    let mut filter = || {
+        let (filter, handle) = reload::Layer::new(level_filter(verbosity));
+        handles.push(handle);
+        filter
+    };
+
+    let console_writer = move || -> Box<dyn std::io::Write> {
+        match output {
+            OutputMode::Text => Box::new(std::io::stdout()),
+            OutputMode::Ndjson => Box::new(std::io::stderr()),
+        }
+    };
+    let console_layer = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .event_format(ConsoleFormat::new(timestamps.clone()))
+            .with_ansi(color)
+            .with_writer(console_writer)
+            .with_filter(filter())
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_timer(timestamps.clone())
+            .with_writer(console_writer)
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_filter(filter())
+            .boxed(),
+    };
+
+    let file_layer = main_log.map(|file| {
+        let writer = Mutex::new(file);
+        match format {
+            LogFormat::Text => tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_target(false)
+                .with_timer(timestamps.clone())
+                .with_writer(writer)
+                .with_filter(filter())
+                .boxed(),
+            LogFormat::Json => tracing_subscriber::fmt::layer()
+                .json()
+                .with_timer(timestamps.clone())
+                .flatten_event(true)
+                .with_current_span(true)
+                .with_span_list(false)
+                .with_writer(writer)
+                .with_filter(filter())
+                .boxed(),
+        }
+    });
+
+    let system_log_layer = system_log.map(|writer| match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer()
+            .without_time()
+            .with_ansi(false)
+            .with_target(false)
+            .with_level(false)
+            .with_writer(writer)
+            .with_filter(filter())
+            .boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .without_time()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_writer(writer)
+            .with_filter(filter())
+            .boxed(),
+    });
+
+    #[cfg(feature = "otel")]
+    let otel_layer = telemetry.map(|telemetry| {
+        tracing_opentelemetry::layer()
+            .with_tracer(telemetry.tracer())
+            .with_filter(LevelFilter::INFO)
+            .boxed()
+    });
+    #[cfg(not(feature = "otel"))]
+    let otel_layer: Option<BoxedLayer> = {
+        let _ = telemetry;
+        None
+    };
+
+    let layers: Vec<BoxedLayer> = std::iter::once(console_layer)
+        .chain(file_layer)
+        .chain(system_log_layer)
+        .chain(otel_layer)
+        .collect();
+    tracing_subscriber::registry()
+        .with(layers)
+        .init();
+
+    LogLevel { handles }
+}
@@ -0,0 +1,324 @@
+//! Finding the cluster coordinator on the local network with multicast DNS
+//!
+//! A coordinator with `[cluster] mdns` on answers DNS-SD queries for `SERVICE_TYPE` with
+//! the port it listens on; a worker started with `--worker auto` asks for it. Only the
+//! bits of DNS the two need are implemented, over IPv4.
+
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::time::Duration;
+use anyhow::{bail, Context, Result};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+use crate::tasks::{Stage, Tasks};
+
+/// The DNS-SD service coordinators advertise
+pub const SERVICE_TYPE: &str = "_btc-solo-cluster._tcp.local";
+const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+const MDNS_PORT: u16 = 5353;
+/// How long a worker waits for coordinators to answer
+pub const DISCOVERY_TIMEOUT_SECS: u64 = 3;
+/// How long answers may be cached, in seconds
+const RECORD_TTL: u32 = 120;
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_SRV: u16 = 33;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+/// Set on records only this host answers for, so caches replace rather than add to them
+const CACHE_FLUSH: u16 = 0x8000;
+
+/// What a coordinator advertises
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Advert {
+    /// Instance name, usually the host name
+    pub name: String,
+    pub port: u16,
+}
+
+/// A coordinator that answered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Discovered {
+    pub name: String,
+    pub addr: SocketAddr,
+}
+
+/// A DNS query for the service, sent from an ephemeral port (a one-shot query)
+pub fn query() -> Vec<u8> {
+    let mut packet = header(0, 0, 1, 0, 0);
+    put_name(&mut packet, SERVICE_TYPE);
+    put_u16(&mut packet, TYPE_PTR);
+    put_u16(&mut packet, CLASS_IN);
+    packet
+}
+
+/// Whether `packet` is a query for the service
+fn asks_for_service(packet: &[u8]) -> bool {
+    let (Some(flags), Some(questions)) = (read_u16(packet, 2), read_u16(packet, 4)) else {
+        return false;
+    };
+    let mut offset = 12;
+    for _ in 0..questions {
+        let Some((name, next)) = read_name(packet, offset) else {
+            return false;
+        };
+        let Some(kind) = read_u16(packet, next) else {
+            return false;
+        };
+        if flags & 0x8000 == 0 && name.eq_ignore_ascii_case(SERVICE_TYPE) && (kind == TYPE_PTR || kind == TYPE_ANY) {
+            return true;
+        }
+        offset = next + 4;
+    }
+    false
+}
+
+/// The answer to `query` if it asks for the service, advertising `host` as the address
+pub fn answer(query: &[u8], advert: &Advert, host: Ipv4Addr) -> Option<Vec<u8>> {
+    if !asks_for_service(query) {
+        return None;
+    }
+    let id = read_u16(query, 0)?;
+    let instance = format!("{}.{}", label(&advert.name), SERVICE_TYPE);
+    let target = format!("{}.local", label(&advert.name));
+    let mut packet = header(id, 0x8400, 0, 1, 3);
+    record(&mut packet, SERVICE_TYPE, TYPE_PTR, CLASS_IN, |rdata| put_name(rdata, &instance));
+    record(&mut packet, &instance, TYPE_SRV, CLASS_IN | CACHE_FLUSH, |rdata| {
+        put_u16(rdata, 0);
+        put_u16(rdata, 0);
+        put_u16(rdata, advert.port);
+        put_name(rdata, &target);
+    });
+    record(&mut packet, &instance, TYPE_TXT, CLASS_IN | CACHE_FLUSH, |rdata| {
+        rdata.push(3);
+        rdata.extend_from_slice(b"v=1");
+    });
+    record(&mut packet, &target, TYPE_A, CLASS_IN | CACHE_FLUSH, |rdata| rdata.extend_from_slice(&host.octets()));
+    Some(packet)
+}
+
+/// The coordinators in an answer, using `source` when it doesn't give an address
+pub fn parse_answer(packet: &[u8], source: SocketAddr) -> Vec<Discovered> {
+    parse_records(packet, source).unwrap_or_default()
+}
+
+fn parse_records(packet: &[u8], source: SocketAddr) -> Option<Vec<Discovered>> {
+    let flags = read_u16(packet, 2)?;
+    if flags & 0x8000 == 0 {
+        return None;
+    }
+    let questions = read_u16(packet, 4)?;
+    let records = read_u16(packet, 6)? as usize + read_u16(packet, 8)? as usize + read_u16(packet, 10)? as usize;
+    let mut offset = 12;
+    for _ in 0..questions {
+        offset = read_name(packet, offset)?.1 + 4;
+    }
+    let mut instances = Vec::new();
+    let mut services = HashMap::new();
+    let mut hosts = HashMap::new();
+    for _ in 0..records {
+        let (name, next) = read_name(packet, offset)?;
+        let kind = read_u16(packet, next)?;
+        let length = read_u16(packet, next + 8)? as usize;
+        let rdata = next + 10;
+        offset = rdata + length;
+        if offset > packet.len() {
+            return None;
+        }
+        match kind {
+            TYPE_PTR if name.eq_ignore_ascii_case(SERVICE_TYPE) => instances.push(read_name(packet, rdata)?.0),
+            TYPE_SRV => {
+                let port = read_u16(packet, rdata + 4)?;
+                services.insert(name.to_ascii_lowercase(), (port, read_name(packet, rdata + 6)?.0.to_ascii_lowercase()));
+            }
+            TYPE_A if length == 4 => {
+                hosts.insert(name.to_ascii_lowercase(), Ipv4Addr::new(packet[rdata], packet[rdata + 1], packet[rdata + 2], packet[rdata + 3]));
+            }
+            _ => {}
+        }
+    }
+    Some(instances.into_iter()
+        .filter_map(|instance| {
+            let (port, target) = services.get(&instance.to_ascii_lowercase())?;
+            let ip = hosts.get(target).copied().map_or(source.ip(), Into::into);
+            let name = instance.strip_suffix(&format!(".{}", SERVICE_TYPE)).unwrap_or(&instance).to_string();
+            Some(Discovered { name, addr: SocketAddr::new(ip, *port) })
+        })
+        .collect())
+}
+
+/// Answer queries for the service until the mining stage stops
+///
+/// Fails if the mDNS port can't be opened.
+pub fn advertise(advert: Advert, tasks: &mut Tasks) -> Result<()> {
+    let socket = UdpSocket::from_std(bind_mdns()?.into())?;
+    info!(event = "mdns_advertising", service = SERVICE_TYPE, name = %advert.name, port = advert.port, "Advertising the cluster coordinator on the local network as {}", advert.name);
+    tasks.spawn_with(Stage::Mining, "mdns", |cancel| async move {
+        let mut buffer = [0u8; 1500];
+        loop {
+            let received = tokio::select! {
+                received = socket.recv_from(&mut buffer) => received,
+                _ = cancel.cancelled() => return,
+            };
+            let (length, source) = match received {
+                Ok(received) => received,
+                Err(e) => {
+                    warn!("mDNS responder stopped: {}", e);
+                    return;
+                }
+            };
+            let SocketAddr::V4(source) = source else {
+                continue;
+            };
+            if !asks_for_service(&buffer[..length]) {
+                continue;
+            }
+            let Some(host) = local_address_for(*source.ip()).await else {
+                continue;
+            };
+            let Some(reply) = answer(&buffer[..length], &advert, host) else {
+                continue;
+            };
+            // One-shot queries from other ports get a unicast answer, as RFC 6762 asks
+            let destination = if source.port() == MDNS_PORT { SocketAddrV4::new(MDNS_GROUP, MDNS_PORT) } else { source };
+            debug!(peer = %source, "Answering an mDNS query for the cluster");
+            if let Err(e) = socket.send_to(&reply, destination).await {
+                debug!("Failed to answer an mDNS query: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Ask the local network for coordinators, returning those that answer within `wait`
+pub async fn discover(wait: Duration) -> Result<Vec<Discovered>> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+    socket.set_multicast_loop_v4(true)?;
+    socket.send_to(&query(), (MDNS_GROUP, MDNS_PORT)).await
+        .context("Failed to send an mDNS query")?;
+    let mut found: Vec<Discovered> = Vec::new();
+    let mut buffer = [0u8; 1500];
+    let deadline = tokio::time::Instant::now() + wait;
+    while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv_from(&mut buffer)).await {
+        let (length, source) = received?;
+        for coordinator in parse_answer(&buffer[..length], source) {
+            if !found.contains(&coordinator) {
+                found.push(coordinator);
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// The first coordinator on the local network, failing if none answers
+pub async fn find_coordinator() -> Result<Discovered> {
+    let mut found = discover(Duration::from_secs(DISCOVERY_TIMEOUT_SECS)).await?;
+    if found.is_empty() {
+        bail!("No cluster coordinator answered on the local network within {}s", DISCOVERY_TIMEOUT_SECS);
+    }
+    if found.len() > 1 {
+        let others: Vec<String> = found[1..].iter().map(|other| format!("{} ({})", other.name, other.addr)).collect();
+        warn!(event = "mdns_several_coordinators", "Several cluster coordinators answered; using {} and ignoring {}", found[0].name, others.join(", "));
+    }
+    Ok(found.remove(0))
+}
+
+/// A socket on the mDNS port that other responders on this host can share
+fn bind_mdns() -> Result<Socket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(all(unix, not(any(target_os = "solaris", target_os = "illumos"))))]
+    socket.set_reuse_port(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, MDNS_PORT).into())
+        .with_context(|| format!("Failed to open the mDNS port {}", MDNS_PORT))?;
+    socket.join_multicast_v4(&MDNS_GROUP, &Ipv4Addr::UNSPECIFIED)
+        .context("Failed to join the mDNS multicast group")?;
+    socket.set_multicast_loop_v4(true)?;
+    Ok(socket)
+}
+
+/// This host's address on the interface that reaches `peer`
+async fn local_address_for(peer: Ipv4Addr) -> Option<Ipv4Addr> {
+    let probe = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await.ok()?;
+    probe.connect((peer, MDNS_PORT)).await.ok()?;
+    match probe.local_addr().ok()? {
+        SocketAddr::V4(local) => Some(*local.ip()),
+        SocketAddr::V6(_) => None,
+    }
+}
+
+/// `name` as one DNS label: dots become dashes and it is cut to 63 bytes
+fn label(name: &str) -> String {
+    let mut label: String = name.chars().map(|c| if c == '.' { '-' } else { c }).collect();
+    while label.len() > 63 {
+        label.pop();
+    }
+    label
+}
+
+fn header(id: u16, flags: u16, questions: u16, answers: u16, additional: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(512);
+    for value in [id, flags, questions, answers, 0, additional] {
+        put_u16(&mut packet, value);
+    }
+    packet
+}
+
+fn record(packet: &mut Vec<u8>, name: &str, kind: u16, class: u16, rdata: impl FnOnce(&mut Vec<u8>)) {
+    put_name(packet, name);
+    put_u16(packet, kind);
+    put_u16(packet, class);
+    packet.extend_from_slice(&RECORD_TTL.to_be_bytes());
+    let mut data = Vec::new();
+    rdata(&mut data);
+    put_u16(packet, data.len() as u16);
+    packet.extend_from_slice(&data);
+}
+
+fn put_u16(packet: &mut Vec<u8>, value: u16) {
+    packet.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_name(packet: &mut Vec<u8>, name: &str) {
+    for part in name.split('.').filter(|part| !part.is_empty()) {
+        packet.push(part.len() as u8);
+        packet.extend_from_slice(part.as_bytes());
+    }
+    packet.push(0);
+}
+
+fn read_u16(packet: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes([*packet.get(offset)?, *packet.get(offset + 1)?]))
+}
+
+/// A possibly compressed name at `offset`, and the offset just past it
+fn read_name(packet: &[u8], offset: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut position = offset;
+    let mut end = None;
+    // Each pointer must go backwards, so a malicious packet can't loop
+    let mut limit = offset;
+    loop {
+        let length = *packet.get(position)? as usize;
+        if length & 0xc0 == 0xc0 {
+            let target = ((length & 0x3f) << 8) | *packet.get(position + 1)? as usize;
+            if target >= limit {
+                return None;
+            }
+            end.get_or_insert(position + 2);
+            position = target;
+            limit = target;
+        } else if length == 0 {
+            return Some((labels.join("."), end.unwrap_or(position + 1)));
+        } else {
+            let bytes = packet.get(position + 1..position + 1 + length)?;
+            labels.push(String::from_utf8_lossy(bytes).into_owned());
+            position += 1 + length;
+        }
+    }
+}
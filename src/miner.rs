@@ -0,0 +1,2035 @@
+use std::future::Future;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{Result, Context, anyhow};
+use serde_json::Value;
+use tokio::sync::broadcast;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, info, error, warn, info_span, Instrument};
+
+use crate::backend::{PoolBackend, Solution, StratumV1, SubmitResult, WorkTemplate};
+use crate::bandwidth::{LOW_BANDWIDTH_HEIGHT_CHECK_SECS, LOW_BANDWIDTH_PING_INTERVAL_SECS};
+use crate::block_archive::{self, BlockRecord};
+use crate::btc_price::{self, PriceSettings};
+use crate::candidate;
+use crate::chain::{format_interval, ChainTip, NewBlock, TipChange, TipDivergence, TipTracker, TipWatch, DEFAULT_STALE_TIP_BLOCKS};
+use crate::clock::{self, Clock, RandomSource};
+use crate::cluster::Coordinator;
+use crate::coinbase::{self, Reward, WitnessCheck, format_btc};
+use crate::config::{self, ConfigOverrides, LoadedConfig};
+use crate::control::{self, Control};
+use crate::difficulty_check;
+use crate::error::Recovery;
+use crate::events::{self, MinerEvent, OutputMode};
+use crate::exit_code::{self, ExitCode, fatal};
+use crate::failover::{FailoverSettings, PoolList};
+use crate::hashing::{self, hash_hex, SHA256_IMPL};
+use crate::hooks::{self, Hooks};
+use crate::latency::{LatencyChange, PING_INTERVAL_SECS};
+use crate::log_rotation::{RotatingFile, RotationPolicy};
+use crate::logging::{self, LogFormat, Verbosity};
+use crate::notify::{TelegramConfig, html_escape, send_telegram_message};
+use crate::payout::{PayoutAddress, PayoutCheck, PayoutRotation};
+use crate::pool_check::{self, PoolRejection, PoolState, SubmittedShares};
+use crate::prefetch::PreparedJob;
+use crate::proxy::Proxy;
+use crate::quiet::QuietSchedule;
+use crate::recording::{self, EntryKind};
+use crate::reload::ConfigReloader;
+use crate::session::{SessionMachine, SessionState};
+use crate::sparkline::HashrateHistory;
+use crate::stats::{JobStats, LifetimeStats, MinerStats, format_closest_call, format_difficulty, hash_difficulty};
+use crate::stratum::MiningJob;
+use crate::submit::{self, SubmitPolicy};
+use crate::system_log::SystemLogWriter;
+use crate::tasks::{Stage, Tasks};
+use crate::telemetry::{self, Telemetry};
+use crate::timestamp::Timestamps;
+use crate::vardiff::{DifficultyChange, DifficultyWatch, DEFAULT_DIFFICULTY_CHANGE_FACTOR};
+use crate::weekly::{WeeklyStats, WEEKLY_CHECK_INTERVAL_SECS};
+use crate::work::{stratum_prevhash, HashingError};
+use crate::worker::{FoundBlock, JobWork, SharedWork, WorkerPool};
+use crate::{activity, address, cluster, console, electricity, error_report, grpc, mdns, paths, power, presets, proxy, quiet, rpc, scripting, sd_notify, self_test, sensors, solar};
+
+const CREDITS: &str = r#"
+Bitcoin Solo Miner - Rust Edition
+Credits: x.com/hey_itsmyturn | t.me/itsthealephyouknowfromtwitter
+"#;
+
+const BLOCKCHAIN_API: &str = "https://blockchain.info/latestblock";
+const DIFFICULTY_API: &str = "https://blockchain.info/q/getdifficulty";
+const HASH_RATE_LOG_INTERVAL_SECS: u64 = 5;
+const BLOCK_HEIGHT_CHECK_INTERVAL_SECS: u64 = 40;
+const MINING_RESTART_DELAY_MS: u64 = 100;
+const WORKER_POLL_INTERVAL_MS: u64 = 100;
+// How long to wait for the pool to answer a keepalive
+const KEEPALIVE_RESPONSE_TIMEOUT_SECS: u64 = 30;
+const MAIN_LOG_FILE_NAME: &str = "miner.log";
+const STATS_SAVE_INTERVAL_SECS: u64 = 60;
+const SHUTDOWN_TIMEOUT_SECS: u64 = 10;
+// Give up with ExitCode::PoolUnreachable if the pool can't be reached for this long at startup
+const STARTUP_CONNECT_TIMEOUT_SECS: u64 = 60;
+const SPARKLINE_SAMPLES: usize = 24;
+
+/// How `mine` runs, beyond the configuration itself
+#[derive(Debug, Clone, Default)]
+pub struct RunOptions {
+    /// Command-line settings, reapplied whenever the config file is reloaded
+    pub overrides: ConfigOverrides,
+    pub output: OutputMode,
+    /// Disable colored console output
+    pub no_color: bool,
+    /// Detect blocks but never submit them
+    pub dry_run: bool,
+    /// Record pool traffic to this file
+    pub record: Option<PathBuf>,
+    /// Replay this recording instead of connecting to the pool
+    pub replay: Option<PathBuf>,
+}
+
+
+/// Settings and counters shared by the mining session and its background tasks
+#[derive(Debug)]
+pub struct MiningConfig {
+    pub address: String,
+    pub pool: String,
+    pub threads: usize,
+    pub current_height: u64,
+    pub telegram: Option<TelegramConfig>,
+    pub stats: MinerStats,
+    pub blocks_dir: PathBuf,
+    pub state_file: PathBuf,
+    pub log_rotation: RotationPolicy,
+    pub hashrate_history: HashrateHistory,
+    pub console_sparkline: bool,
+    /// Detect blocks but never submit them
+    pub dry_run: bool,
+    /// Decides which Telegram notifications are sent
+    pub verbosity: Verbosity,
+    pub timestamps: Timestamps,
+    pub hooks: Hooks,
+    /// Hashing stopped on request; the pool session stays up
+    pub paused: bool,
+    /// Drop the pool session and reconnect once the current job notices
+    pub restart_requested: bool,
+    pub session_state: SessionState,
+    /// Time of day for block timestamps; replaced in tests
+    pub clock: Arc<dyn Clock>,
+    /// Randomness for extranonce2 selection; seeded in tests
+    pub rng: RandomSource,
+    /// Stratum server sharing each job with LAN miners, if enabled
+    pub proxy: Option<Proxy>,
+    /// Hands out slices of each job to cluster workers, if enabled
+    pub coordinator: Option<Coordinator>,
+    /// What to do when a job's coinbase doesn't pay `address`
+    pub payout_check: PayoutCheck,
+    /// Shares already sent to the pool, to predict duplicate rejects
+    pub submitted_shares: SubmittedShares,
+    /// The network difficulty the block explorer reported, and the height it was fetched at
+    pub explorer_difficulty: Option<(u64, f64)>,
+    /// Cut optional network traffic (see `bandwidth`)
+    pub low_bandwidth: bool,
+    /// How long to wait for the pool to answer a submit, and how often to resend it
+    pub submit_policy: SubmitPolicy,
+    /// Report share difficulty changes by more than this factor
+    pub difficulty_change_factor: f64,
+    /// The pool's share difficulty, watched for big changes
+    pub share_difficulty: DifficultyWatch,
+    /// Report the pool's tip drifting more than this many blocks from the explorer's
+    pub stale_tip_blocks: u64,
+    /// The block the pool builds on, held against the explorer's tips
+    pub tip_watch: TipWatch,
+    /// Where the bitcoin price for fiat reward values comes from, if anywhere
+    pub btc_price: Option<PriceSettings>,
+    /// The reward of the last job, for the summaries
+    pub last_reward: Option<Reward>,
+    /// Daily windows when hashing pauses; changeable at runtime
+    pub quiet: Option<QuietSchedule>,
+    /// A new primary pool for the next session, asked for at runtime
+    pub requested_pool: Option<String>,
+}
+
+impl MiningConfig {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        address: String,
+        pool: String,
+        threads: usize,
+        telegram: Option<TelegramConfig>,
+        stats: MinerStats,
+        blocks_dir: PathBuf,
+        state_file: PathBuf,
+        log_rotation: RotationPolicy,
+        console_sparkline: bool,
+        dry_run: bool,
+        verbosity: Verbosity,
+        timestamps: Timestamps,
+        hooks: Hooks,
+    ) -> Self {
+        Self {
+            address,
+            pool,
+            threads,
+            current_height: 0,
+            telegram,
+            stats,
+            blocks_dir,
+            state_file,
+            log_rotation,
+            hashrate_history: HashrateHistory::new(SPARKLINE_SAMPLES),
+            console_sparkline,
+            dry_run,
+            verbosity,
+            timestamps,
+            hooks,
+            paused: false,
+            restart_requested: false,
+            session_state: SessionState::Disconnected,
+            clock: clock::system(),
+            rng: RandomSource::from_entropy(),
+            proxy: None,
+            coordinator: None,
+            payout_check: PayoutCheck::default(),
+            submitted_shares: SubmittedShares::default(),
+            explorer_difficulty: None,
+            low_bandwidth: false,
+            submit_policy: SubmitPolicy::default(),
+            difficulty_change_factor: DEFAULT_DIFFICULTY_CHANGE_FACTOR,
+            share_difficulty: DifficultyWatch::default(),
+            stale_tip_blocks: DEFAULT_STALE_TIP_BLOCKS,
+            tip_watch: TipWatch::default(),
+            btc_price: None,
+            last_reward: None,
+            quiet: None,
+            requested_pool: None,
+        }
+    }
+}
+
+/// Get current Bitcoin blockchain height
+async fn get_current_block_height() -> Result<u64> {
+    Ok(get_chain_tip().await?.height)
+}
+
+/// Get the latest block from the block explorer
+async fn get_chain_tip() -> Result<ChainTip> {
+    if let Some(height) = recording::replayed_height() {
+        return Ok(ChainTip { height, hash: String::new(), time: None });
+    }
+    let response = reqwest::get(BLOCKCHAIN_API)
+        .await
+        .context("Failed to fetch blockchain height")?;
+    let data: Value = response.json().await?;
+    let height = data["height"].as_u64().unwrap_or(0);
+    recording::record(EntryKind::Height { height });
+    Ok(ChainTip {
+        height,
+        hash: data["hash"].as_str().unwrap_or_default().to_string(),
+        time: data["time"].as_u64(),
+    })
+}
+
+/// Get the network difficulty from the block explorer, independently of the pool
+async fn get_network_difficulty() -> Result<f64> {
+    let response = reqwest::get(DIFFICULTY_API)
+        .await
+        .context("Failed to fetch network difficulty")?;
+    let text = response.text().await?;
+    text.trim().parse().with_context(|| format!("Unexpected network difficulty '{}'", text.trim()))
+}
+
+/// The block explorer's network difficulty at `height`, fetched once per height
+///
+/// `None` when replaying a recording, whose work needn't be for the live network, or
+/// when the explorer can't be reached.
+async fn explorer_difficulty(config: &Arc<Mutex<MiningConfig>>, height: u64) -> Option<f64> {
+    if recording::replayed_height().is_some() {
+        return None;
+    }
+    if config.lock().unwrap().low_bandwidth {
+        debug!(event = "difficulty_check_skipped", "Low-bandwidth mode: not cross-checking the network difficulty");
+        return None;
+    }
+    if let Some((fetched_at, difficulty)) = config.lock().unwrap().explorer_difficulty {
+        if fetched_at == height {
+            return Some(difficulty);
+        }
+    }
+    match get_network_difficulty().await {
+        Ok(difficulty) => {
+            config.lock().unwrap().explorer_difficulty = Some((height, difficulty));
+            Some(difficulty)
+        }
+        Err(e) => {
+            debug!(event = "difficulty_check_skipped", "Can't cross-check the network difficulty: {:#}", e);
+            None
+        }
+    }
+}
+
+/// Compare the job's network difficulty with the explorer's and the pool's share
+/// difficulty, warning loudly if they don't agree
+///
+/// Mining goes on: a mismatch is as likely a lagging explorer as a bad pool, and the
+/// operator is better placed to tell which.
+fn report_difficulty_mismatches(job_difficulty: f64, explorer: Option<f64>, pool: Option<f64>) {
+    for mismatch in difficulty_check::cross_check(job_difficulty, explorer, pool) {
+        error!(
+            event = "difficulty_mismatch",
+            job_difficulty,
+            explorer_difficulty = explorer,
+            pool_difficulty = pool,
+            "Difficulty mismatch: {}. The pool may be misconfigured, or not the pool it claims to be",
+            mismatch
+        );
+    }
+}
+
+/// Warn of a share difficulty far from the one the pool set before
+fn report_share_difficulty_change(config: &Arc<Mutex<MiningConfig>>, difficulty: f64) {
+    let change = {
+        let mut config_guard = config.lock().unwrap();
+        let factor = config_guard.difficulty_change_factor;
+        config_guard.share_difficulty.observe(difficulty, factor)
+    };
+    let Some(change) = change else {
+        return;
+    };
+    warn!(
+        event = "share_difficulty_changed",
+        from = change.from,
+        to = change.to,
+        "The pool changed the share difficulty {}: the hash rate changed a lot, or the connection has trouble",
+        change
+    );
+    events::emit(MinerEvent::ShareDifficultyChanged { from: change.from, to: change.to });
+}
+
+/// Note the block a job builds on, from its prevhash and the height in its coinbase
+fn observe_pool_tip(config: &Arc<Mutex<MiningConfig>>, job: &MiningJob) {
+    let tip = coinbase::height(job).and_then(|height| {
+        let hash = stratum_prevhash(&job.prevhash)?;
+        Ok((height.checked_sub(1).context("job builds the genesis block")?, hash.to_string()))
+    });
+    match tip {
+        Ok((height, hash)) => {
+            config.lock().unwrap().tip_watch.pool_tip(height, hash);
+            report_tip_change(config);
+        }
+        Err(e) => debug!("Can't tell which block the job builds on: {:#}", e),
+    }
+}
+
+/// Warn when the pool's tip drifts too far from the explorer's, and say when they agree again
+///
+/// Mining goes on either way: the explorer is as likely to be wrong as the pool.
+fn report_tip_change(config: &Arc<Mutex<MiningConfig>>) {
+    let change = {
+        let mut config_guard = config.lock().unwrap();
+        let max_blocks = config_guard.stale_tip_blocks;
+        config_guard.tip_watch.check(max_blocks)
+    };
+    match change {
+        Some(TipChange::Diverged(divergence)) => {
+            warn!(
+                event = "chain_tip_diverged",
+                pool_height = divergence.pool_height(),
+                network_height = divergence.network_height(),
+                blocks = divergence.blocks(),
+                "The pool's chain tip differs from the block explorer's: {}. The pool may be mining a stale or forked chain",
+                divergence
+            );
+            events::emit(MinerEvent::ChainTipDiverged {
+                pool_height: divergence.pool_height(),
+                network_height: divergence.network_height(),
+                blocks: divergence.blocks(),
+                forked: matches!(divergence, TipDivergence::Forked { .. }),
+            });
+        }
+        Some(TipChange::Converged) => {
+            info!(event = "chain_tip_converged", "The pool builds on the block explorer's chain tip again");
+        }
+        None => {}
+    }
+}
+
+/// Write a found block to the blocks log, warning (not failing) if that isn't possible
+fn log_block_record(config: &Arc<Mutex<MiningConfig>>, record: BlockRecord) {
+    let (blocks_dir, rotation) = {
+        let config_guard = config.lock().unwrap();
+        (config_guard.blocks_dir.clone(), config_guard.log_rotation)
+    };
+    if let Err(e) = block_archive::log_block_found(&record, &blocks_dir, rotation) {
+        warn!("Failed to log block to file: {:#}", e);
+    }
+}
+
+/// Make sure a block found on the job `reward` was read from would pay `address`, as `check` says
+///
+/// A pool that was given a mistyped address, or that pays out some other way, would take
+/// the whole reward. With `PayoutCheck::Refuse` that stops the miner, tagged
+/// `ExitCode::InvalidAddress`.
+fn check_payout(reward: &Result<Reward>, address: &str, check: PayoutCheck) -> Result<()> {
+    if check == PayoutCheck::Off {
+        return Ok(());
+    }
+    match reward {
+        Ok(reward) if reward.to_address > 0 => {
+            debug!(event = "payout_verified", address = %address, "The job's coinbase pays {}", address);
+            Ok(())
+        }
+        Ok(_) if check == PayoutCheck::Refuse => {
+            Err(anyhow!("The pool's coinbase has no output paying {}; a block found on it would pay someone else (set payout_check = \"warn\" or \"off\" to mine anyway)", address))
+                .map_err(fatal(ExitCode::InvalidAddress))
+        }
+        Ok(_) => {
+            warn!(event = "payout_mismatch", address = %address, "The pool's coinbase has no output paying {}; a block found on this job would pay someone else", address);
+            Ok(())
+        }
+        // A coinbase this can't read says nothing about who it pays
+        Err(e) => {
+            warn!(event = "payout_unverified", address = %address, "Can't check that the pool's coinbase pays {}: {:#}", address, e);
+            Ok(())
+        }
+    }
+}
+
+/// Refuse a job whose coinbase commits to the wrong witness data, as nodes would reject
+/// any block found on it
+fn check_witness_commitment(check: Result<WitnessCheck>) -> Result<()> {
+    match check {
+        Ok(WitnessCheck::Mismatch { expected, found }) => Err(HashingError::WitnessCommitment { expected, found }.into()),
+        Ok(WitnessCheck::Matches) => {
+            debug!(event = "witness_commitment_verified", "The job's witness commitment matches its block");
+            Ok(())
+        }
+        Ok(WitnessCheck::Absent | WitnessCheck::Unverifiable) => Ok(()),
+        Err(e) => {
+            debug!(event = "witness_commitment_unverified", "Can't read the job's witness commitment: {:#}", e);
+            Ok(())
+        }
+    }
+}
+
+/// Re-check a found block against its job, logging what's wrong with it if anything is
+///
+/// The block is submitted either way: the pool has the final say, and holding back a block
+/// over a bug in these checks would be worse than a rejected submit.
+fn report_candidate_problems(job: &MiningJob, extranonce1: &str, found: &FoundBlock, now: u64) {
+    let problems = candidate::check(job, extranonce1, found, now);
+    if problems.is_empty() {
+        debug!(event = "candidate_verified", nonce = %found.nonce, "Found block passes the pre-submission checks");
+        return;
+    }
+    let list: Vec<String> = problems.iter().map(|problem| format!("  - {}", problem)).collect();
+    error!(
+        event = "candidate_check_failed",
+        problems = problems.len(),
+        job_id = %job.job_id,
+        "Found block fails {} pre-submission check(s), the pool will likely reject it:\n{}\n\
+        Header: {}\n\
+        Hash: {}\n\
+        Merkle root: {}\n\
+        Extranonce1: {}\n\
+        Extranonce2: {}\n\
+        Nonce: {}\n\
+        Ntime: {}\n\
+        Job: version {} prevhash {} nbits {} ntime {} ({} merkle branch entries)",
+        problems.len(),
+        list.join("\n"),
+        hex::encode(&found.header),
+        hash_hex(&found.hash),
+        found.merkle_root,
+        extranonce1,
+        found.extranonce2,
+        found.nonce,
+        found.ntime,
+        job.version,
+        job.prevhash,
+        job.nbits,
+        job.ntime,
+        job.merkle_branch.len()
+    );
+}
+
+/// Log how the pool is expected to answer a share, returning the prediction for the
+/// block archive
+fn report_pool_prediction(rejections: &[PoolRejection]) -> String {
+    if rejections.is_empty() {
+        debug!(event = "pool_check", predicted_accept = true, "Pool is expected to accept the share");
+        return "accept".to_string();
+    }
+    let reasons: Vec<String> = rejections.iter().map(ToString::to_string).collect();
+    warn!(
+        event = "pool_check",
+        predicted_accept = false,
+        "Pool is expected to reject the share: {}",
+        reasons.join("; ")
+    );
+    format!("reject: {}", reasons.join("; "))
+}
+
+/// Compare the pool's verdict with the prediction, to tell our bugs from pool policy
+fn diagnose_pool_response(rejections: &[PoolRejection], result: &SubmitResult) {
+    match (rejections.is_empty(), result.accepted) {
+        (true, false) => error!(
+            event = "pool_reject_unexplained",
+            reason = result.reason.as_deref().unwrap_or("none given"),
+            "Pool rejected a share that passed every local check: pool policy or something these checks don't model"
+        ),
+        (false, false) => error!(
+            event = "pool_reject_predicted",
+            reason = result.reason.as_deref().unwrap_or("none given"),
+            "Pool rejected the share as predicted, the fault is on the miner's side: {}",
+            rejections[0]
+        ),
+        (false, true) => warn!(
+            event = "pool_accept_unexpected",
+            "Pool accepted a share predicted to be rejected ({}), the local checks are stricter than the pool",
+            rejections[0]
+        ),
+        (true, true) => {}
+    }
+}
+
+/// Mine on one connection to the pool until a restart is requested or an error
+///
+/// Jobs follow each other on the same connection: a new job or block ends the one being
+/// hashed and the next is taken up, already prepared if the pool sent it meanwhile.
+pub async fn bitcoin_miner(config: Arc<Mutex<MiningConfig>>, session: &mut SessionMachine) -> Result<()> {
+    let (address, pool) = {
+        let config_guard = config.lock().unwrap();
+        (config_guard.address.clone(), config_guard.pool.clone())
+    };
+
+    let mut backend = StratumV1::connect(&pool, &address, session).await?;
+    info!(event = "pool_connected", protocol = backend.protocol(), "Connected to mining pool");
+
+    // A job that can't be worked doesn't mean the connection is bad: wait for the next one
+    let mut next_job = None;
+    loop {
+        match mine_job(Arc::clone(&config), &mut backend, session, &mut next_job).await {
+            Ok("restart_requested") => return Ok(()),
+            Ok(_) => {}
+            Err(e) if Recovery::of(&e) == Recovery::Retry => {
+                warn!(event = "job_skipped", "Skipping job: {:#}", e);
+                session.transition(SessionState::Degraded)?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Hash the next job from `backend` until a block is found, the network moves on or an error,
+/// returning why it ended
+///
+/// A job the pool sends meanwhile is prepared on a blocking thread while the workers hash
+/// and left in `next_job`, ending this one if it replaces it.
+async fn mine_job(
+    config: Arc<Mutex<MiningConfig>>,
+    backend: &mut impl PoolBackend,
+    session: &mut SessionMachine,
+    next_job: &mut Option<PreparedJob>,
+) -> Result<&'static str> {
+    let (address, pool, threads, dry_run, rng, proxy, coordinator, payout_check, low_bandwidth) = {
+        let config_guard = config.lock().unwrap();
+        (
+            config_guard.address.clone(),
+            config_guard.pool.clone(),
+            config_guard.threads,
+            config_guard.dry_run,
+            config_guard.rng.clone(),
+            config_guard.proxy.clone(),
+            config_guard.coordinator.clone(),
+            config_guard.payout_check,
+            config_guard.low_bandwidth,
+        )
+    };
+
+    let prepared = match next_job.take() {
+        Some(prepared) => prepared,
+        None => {
+            info!(event = "waiting_for_job", "Waiting for mining job...");
+            let work = session.within(backend.next_work()).await?;
+            PreparedJob::prepare(work, &address)
+        }
+    };
+    let PreparedJob {
+        work: WorkTemplate { job: mining_job, extranonce1, extranonce2_size },
+        height,
+        reward,
+        witness,
+        target,
+        difficulty,
+        template,
+    } = prepared;
+    let extranonce1 = extranonce1.as_str();
+    check_payout(&reward, &address, payout_check)?;
+    let reward = reward.ok();
+    if reward.is_some() {
+        config.lock().unwrap().last_reward = reward;
+    }
+    check_witness_commitment(witness)?;
+
+    let target = target.context("Failed to calculate target from nbits")?;
+    let difficulty = difficulty?;
+    
+    let work = Arc::new(JobWork {
+        job: mining_job.clone(),
+        template: template?,
+        target: target.clone(),
+        rng,
+    });
+    
+    // The block the job builds on, one below the height in its coinbase; the explorer is
+    // only asked when the coinbase doesn't say
+    let work_on = match height.ok().and_then(|height| height.checked_sub(1)) {
+        Some(height) => height,
+        None => get_current_block_height().await?,
+    };
+
+    let job_span = info_span!("job", job_id = %mining_job.job_id, height = work_on);
+    events::emit(MinerEvent::JobReceived {
+        job_id: mining_job.job_id.clone(),
+        height: work_on,
+        nbits: mining_job.nbits.clone(),
+        ntime: mining_job.ntime.clone(),
+        clean_jobs: mining_job.clean_jobs,
+        network_difficulty: difficulty,
+        reward_sats: reward.map(|r| r.total),
+        payout_sats: reward.map(|r| r.to_address),
+    });
+    error_report::set_job(Some(&mining_job.job_id));
+    error_report::record_success("mining_session");
+
+    let job_started = Instant::now();
+    let mut job_best_difficulty = 0f64;
+    let (found_tx, mut found_rx) = tokio::sync::mpsc::unbounded_channel();
+    // LAN miners and cluster workers work the same job in their own extranonce2 ranges. The
+    // local workers still draw extranonce2 from the whole space: landing in someone else's
+    // range only means the same coinbase is searched twice, and with random draws that is
+    // vanishingly rare.
+    if proxy.is_some() || coordinator.is_some() {
+        let shared = Arc::new(SharedWork {
+            job: mining_job.clone(),
+            extranonce1: extranonce1.to_string(),
+            extranonce2_size,
+            target: target.clone(),
+            found: found_tx.clone(),
+        });
+        if let Some(proxy) = &proxy {
+            proxy.publish(Arc::clone(&shared));
+        }
+        if let Some(coordinator) = &coordinator {
+            coordinator.publish(shared);
+        }
+    }
+    let mut workers = job_span.in_scope(|| WorkerPool::start(threads, work, found_tx))?;
+    // A new job doesn't end a pause
+    if config.lock().unwrap().paused {
+        workers.pause();
+    }
+    session.transition(SessionState::Working)?;
+    // The workers are hashing, so the explorer doesn't hold them up
+    let explorer = explorer_difficulty(&config, work_on).await;
+    report_difficulty_mismatches(difficulty, explorer, backend.share_difficulty());
+    if let Some(pool_difficulty) = backend.share_difficulty() {
+        report_share_difficulty_change(&config, pool_difficulty);
+    }
+    observe_pool_tip(&config, &mining_job);
+
+    let outcome = async {
+        match reward {
+            Some(reward) => info!(
+                event = "job_started",
+                nbits = %mining_job.nbits,
+                network_difficulty = difficulty,
+                reward_sats = reward.total,
+                payout_sats = reward.to_address,
+                "Working on network block height: {}, network difficulty {} (block reward {}, {} to {})",
+                work_on, format_difficulty(difficulty), format_btc(reward.total), format_btc(reward.to_address), address
+            ),
+            None => info!(
+                event = "job_started",
+                nbits = %mining_job.nbits,
+                network_difficulty = difficulty,
+                "Working on network block height: {}, network difficulty {}",
+                work_on, format_difficulty(difficulty)
+            ),
+        }
+        // Subscribed, authorized and holding work: the service is up
+        sd_notify::ready(&format!("Mining block {} on {} with {} thread(s)", work_on, pool, threads));
+        info!(event = "hashing_started", threads, "Starting hash generation on {} thread(s)...", threads);
+    
+        let mut hash_count = 0u64;
+        let mut counted_hashes = 0u64;
+        let mut last_log_time = Instant::now();
+        let mut last_ping = Instant::now();
+        let mut poll = tokio::time::interval(Duration::from_millis(WORKER_POLL_INTERVAL_MS));
+    
+        loop {
+            let found = tokio::select! {
+                found = found_rx.recv() => Some(found.context("Mining workers exited unexpectedly")??),
+                work = backend.next_work() => {
+                    let work = work?;
+                    let job_id = work.job.job_id.clone();
+                    let address = address.clone();
+                    let prepared = tokio::task::spawn_blocking(move || PreparedJob::prepare(work, &address)).await
+                        .context("Failed to prepare the next job")?;
+                    let (new_block, replaces) = (prepared.new_block(&mining_job), prepared.replaces(&mining_job));
+                    debug!(event = "job_prefetched", job_id = %job_id, new_block, "Prepared job {} while hashing", job_id);
+                    *next_job = Some(prepared);
+                    if replaces {
+                        info!(event = "new_job", job_id = %job_id, new_block, "The pool sent job {}, switching to it", job_id);
+                        return Ok(if new_block { "new_block" } else { "new_job" });
+                    }
+                    None
+                }
+                _ = poll.tick() => None,
+            };
+
+            if let Some(found) = found {
+                let (timestamp, time) = {
+                    let config_guard = config.lock().unwrap();
+                    let timestamp = config_guard.clock.unix_secs();
+                    (timestamp, config_guard.timestamps.format_unix(timestamp))
+                };
+                report_candidate_problems(&mining_job, extranonce1, &found, timestamp);
+
+                let FoundBlock {
+                    nonce: nonce_hex,
+                    extranonce2,
+                    ntime,
+                    merkle_root: merkle_root_hex,
+                    header: header_bytes,
+                    hash: hash_bytes,
+                } = found;
+                let hash_hex = hash_hex(&hash_bytes);
+                let target_hex = hex::encode(&target);
+                let share_difficulty = hash_difficulty(&hash_bytes);
+            
+                let block_info = format!(
+                    "[!] VALID BLOCK HASH DISCOVERED!\n\
+                    [*] Hash: {}\n\
+                    [*] Target: {}\n\
+                    [*] Difficulty: network {}, this hash {}\n\
+                    [*] Nonce: {}\n\
+                    [*] Address: {}\n\
+                    [*] Reward: {}\n\
+                    [*] Time: {}\n",
+                    hash_hex, target_hex, format_difficulty(difficulty), format_difficulty(share_difficulty),
+                    nonce_hex, address, reward_text(reward), time
+                );
+            
+                warn!(
+                    target: logging::SUMMARY_TARGET,
+                    event = "block_found",
+                    hash = %hash_hex,
+                    target = %target_hex,
+                    nonce = %nonce_hex,
+                    address = %address,
+                    timestamp,
+                    "{}", block_info
+                );
+                events::emit(MinerEvent::BlockFound {
+                    job_id: mining_job.job_id.clone(),
+                    hash: hash_hex.clone(),
+                    target: target_hex.clone(),
+                    nonce: nonce_hex.clone(),
+                    address: address.clone(),
+                    network_difficulty: difficulty,
+                    hash_difficulty: share_difficulty,
+                    reward_sats: reward.map(|r| r.total),
+                    payout_sats: reward.map(|r| r.to_address),
+                });
+            
+                let solution = Solution {
+                    job_id: mining_job.job_id.clone(),
+                    extranonce2: extranonce2.clone(),
+                    ntime: ntime.clone(),
+                    nonce: nonce_hex.clone(),
+                };
+                let rejections = {
+                    let config_guard = config.lock().unwrap();
+                    let pool_state = PoolState {
+                        newer_jobs: &backend.pending_jobs(),
+                        extranonce2_size,
+                        difficulty: backend.share_difficulty(),
+                        submitted: &config_guard.submitted_shares,
+                    };
+                    pool_check::predict(&mining_job, &solution, share_difficulty, pool_state)
+                };
+                let prediction = report_pool_prediction(&rejections);
+
+                let assembled = block_archive::assemble_block(&mining_job, extranonce1, &extranonce2, &header_bytes).ok();
+                let block_record = |pool_response: String| BlockRecord {
+                    timestamp,
+                    time: time.clone(),
+                    height: work_on,
+                    address: address.clone(),
+                    hash: hash_hex.clone(),
+                    target: target_hex.clone(),
+                    difficulty: share_difficulty,
+                    network_difficulty: difficulty,
+                    nonce: nonce_hex.clone(),
+                    extranonce1: extranonce1.to_string(),
+                    extranonce2: extranonce2.clone(),
+                    merkle_root: merkle_root_hex.clone(),
+                    header: hex::encode(&header_bytes),
+                    coinbase: assembled.as_ref().map(|assembled| hex::encode(&assembled.coinbase)).unwrap_or_default(),
+                    block: assembled.as_ref().and_then(|assembled| assembled.block.as_ref()).map(hex::encode),
+                    job: mining_job.clone(),
+                    predicted_response: prediction.clone(),
+                    pool_response,
+                };
+
+                if dry_run {
+                    warn!(event = "submit_skipped", nonce = %nonce_hex, "Dry run: solution not submitted to pool");
+                    log_block_record(&config, block_record("dry run: not submitted".to_string()));
+                    return Ok("block_found");
+                }
+
+                // Submit solution to pool; resends are the same share, so it's recorded once
+                let submit_started = Instant::now();
+                let submit_policy = {
+                    let mut config_guard = config.lock().unwrap();
+                    config_guard.submitted_shares.record(&solution);
+                    config_guard.submit_policy
+                };
+                let result = match submit::submit(backend, &solution, submit_policy).await {
+                    Ok(submitted) => submitted.result,
+                    Err(e) => {
+                        // Keep the block on disk even though the pool never confirmed it
+                        log_block_record(&config, block_record(format!("submit failed: {:#}", e)));
+                        return Err(e);
+                    }
+                };
+                let round_trip = submit_started.elapsed();
+                record_pool_latency(&config, round_trip);
+                session.record_submit(result.accepted);
+                warn!(
+                    event = "share_submitted",
+                    nonce = %nonce_hex,
+                    difficulty = share_difficulty,
+                    "Solution submitted to pool (difficulty {})",
+                    format_difficulty(share_difficulty)
+                );
+                events::emit(MinerEvent::ShareSubmitted {
+                    job_id: mining_job.job_id.clone(),
+                    extranonce2: extranonce2.clone(),
+                    ntime: ntime.clone(),
+                    nonce: nonce_hex.clone(),
+                    difficulty: share_difficulty,
+                    predicted_rejection: rejections.first().map(ToString::to_string),
+                });
+                warn!(event = "pool_response", "Pool response: {}", result);
+                diagnose_pool_response(&rejections, &result);
+
+                log_block_record(&config, block_record(result.to_string()));
+
+                events::emit(MinerEvent::ShareAccepted {
+                    job_id: mining_job.job_id.clone(),
+                    accepted: result.accepted,
+                    error: result.reason,
+                    round_trip_secs: round_trip.as_secs_f64(),
+                });
+
+                return Ok("block_found");
+            }
+
+            // Check if new block was found
+            let current_height = {
+                let config_guard = config.lock().unwrap();
+                config_guard.current_height
+            };
+        
+            if current_height > work_on {
+                info!(event = "new_block", current_height, "New block detected, restarting mining operation");
+                // Anything the pool sent before the block is stale now
+                *next_job = None;
+                return Ok("new_block");
+            }
+
+            // Apply a pause, resume or thread count change without dropping the job
+            let (configured_threads, paused, restart) = {
+                let mut config_guard = config.lock().unwrap();
+                (config_guard.threads, config_guard.paused, std::mem::take(&mut config_guard.restart_requested))
+            };
+            if restart {
+                info!(event = "session_restart", "Restarting the pool session on request");
+                return Ok("restart_requested");
+            }
+            if paused && !workers.is_paused() {
+                info!(event = "hashing_paused", "Hashing paused, keeping the pool connection");
+                workers.pause();
+            } else if !paused && workers.is_paused() {
+                info!(event = "hashing_resumed", threads = configured_threads, "Hashing resumed on {} thread(s)", configured_threads);
+                workers.resume(configured_threads)?;
+            } else if !paused && configured_threads != workers.threads() {
+                info!(event = "threads_changed", threads = configured_threads, "Hashing threads: {} -> {}", workers.threads(), configured_threads);
+                workers.resize(configured_threads)?;
+            }
+
+            // Measures the round trip, and keeps a paused session alive: nothing else is
+            // sent while paused, and pools drop sessions that go quiet
+            let ping_interval = if low_bandwidth { LOW_BANDWIDTH_PING_INTERVAL_SECS } else { PING_INTERVAL_SECS };
+            if last_ping.elapsed() >= Duration::from_secs(ping_interval) {
+                let ping_started = Instant::now();
+                tokio::time::timeout(Duration::from_secs(KEEPALIVE_RESPONSE_TIMEOUT_SECS), backend.keepalive())
+                    .await
+                    .unwrap_or_else(|_| Err(anyhow!("no reply within {}s", KEEPALIVE_RESPONSE_TIMEOUT_SECS)))
+                    .context("Pool keepalive failed")?;
+                let round_trip = ping_started.elapsed();
+                debug!(event = "pool_keepalive", round_trip_ms = round_trip.as_secs_f64() * 1000.0, "Pool answered the keepalive in {} ms", round_trip.as_millis());
+                record_pool_latency(&config, round_trip);
+                last_ping = Instant::now();
+            }
+
+            // Fold the workers' progress into the cumulative stats
+            let total_hashes = workers.progress().hashes();
+            let new_hashes = total_hashes - counted_hashes;
+            counted_hashes = total_hashes;
+            hash_count += new_hashes;
+            {
+                let mut config_guard = config.lock().unwrap();
+                config_guard.stats.record_hashes(new_hashes);
+                config_guard.stats.record_expected_blocks(new_hashes, difficulty);
+                if let Some(best_hash) = workers.progress().take_best_hash() {
+                    job_best_difficulty = job_best_difficulty.max(record_best_hash(&mut config_guard.stats, &best_hash, difficulty));
+                }
+            }
+
+            // Log hash rate periodically
+            let elapsed = last_log_time.elapsed();
+            if elapsed.as_secs() >= HASH_RATE_LOG_INTERVAL_SECS {
+                let elapsed_secs = elapsed.as_secs_f64();
+                let hash_rate = (hash_count as f64 / elapsed_secs) as u64;
+                events::emit(MinerEvent::HashrateSample {
+                    hashrate: hash_rate,
+                    hashes: hash_count,
+                    interval_secs: elapsed_secs,
+                });
+                let readings = sensors::read();
+                telemetry::record_sensors(&readings);
+                let trend = {
+                    let mut config_guard = config.lock().unwrap();
+                    config_guard.stats.record_sensors(readings);
+                    config_guard.hashrate_history.push(hash_rate);
+                    if config_guard.console_sparkline {
+                        format!(" | {}", config_guard.hashrate_history.sparkline())
+                    } else {
+                        String::new()
+                    }
+                };
+                info!(
+                    event = "hashrate",
+                    hashrate = hash_rate,
+                    hashes = hash_count,
+                    cpu_temp_c = readings.cpu_temp_c,
+                    fan_rpm = readings.fan_rpm,
+                    cpu_mhz = readings.cpu_mhz,
+                    "Hash rate: {:>8} h/s | Total hashes: {:>12}{}", hash_rate, hash_count, trend
+                );
+                hash_count = 0;
+                last_log_time = Instant::now();
+            }
+        }
+    }.instrument(job_span.clone()).await;
+    let progress = workers.join();
+    // Whatever the workers improved on since the last poll
+    if let Some(best_hash) = progress.take_best_hash() {
+        job_best_difficulty = job_best_difficulty.max(record_best_hash(&mut config.lock().unwrap().stats, &best_hash, difficulty));
+    }
+
+    let job_stats = JobStats {
+        job_id: mining_job.job_id.clone(),
+        height: work_on,
+        worked_secs: job_started.elapsed().as_secs_f64(),
+        hashes: progress.hashes(),
+        extranonce2_rolls: progress.extranonce2_rolls(),
+        network_difficulty: difficulty,
+        best_share_difficulty: job_best_difficulty,
+        end_reason: match &outcome {
+            Ok(reason) => reason.to_string(),
+            Err(e) => format!("error: {}", e),
+        },
+    };
+    job_span.in_scope(|| info!(
+        event = "job_finished",
+        worked_secs = job_stats.worked_secs,
+        hashes = job_stats.hashes,
+        extranonce2_rolls = job_stats.extranonce2_rolls,
+        best_share_difficulty = job_stats.best_share_difficulty,
+        end_reason = %job_stats.end_reason,
+        "Job {} finished after {:.1}s: {} hashes, {} extranonce2 rolls, best share difficulty {} of {} ({})",
+        job_stats.job_id,
+        job_stats.worked_secs,
+        job_stats.hashes,
+        job_stats.extranonce2_rolls,
+        format_difficulty(job_stats.best_share_difficulty),
+        format_difficulty(job_stats.network_difficulty),
+        job_stats.end_reason
+    ));
+    events::emit(MinerEvent::JobFinished(job_stats));
+
+    outcome
+}
+
+/// Count a round trip to the pool towards the stats and metrics, warning when the link
+/// degrades or recovers
+fn record_pool_latency(config: &Arc<Mutex<MiningConfig>>, round_trip: Duration) {
+    telemetry::record_pool_latency(round_trip);
+    let change = config.lock().unwrap().stats.record_pool_latency(round_trip);
+    match change {
+        Some(LatencyChange::Degraded { recent_ms, baseline_ms }) => warn!(
+            event = "pool_latency_degraded",
+            recent_ms,
+            baseline_ms,
+            "Pool round trips are slow: {:.0} ms recently, {:.0} ms at best; a block found now is more likely to reach the pool stale",
+            recent_ms,
+            baseline_ms
+        ),
+        Some(LatencyChange::Recovered { recent_ms, baseline_ms }) => info!(
+            event = "pool_latency_recovered",
+            recent_ms,
+            baseline_ms,
+            "Pool round trips are back to {:.0} ms ({:.0} ms at best)",
+            recent_ms,
+            baseline_ms
+        ),
+        None => {}
+    }
+}
+
+/// Count the best hash a job's workers reported towards the stats, returning its difficulty
+fn record_best_hash(stats: &mut MinerStats, hash: &[u8], network_difficulty: f64) -> f64 {
+    let difficulty = hash_difficulty(hash);
+    stats.record_share_difficulty(difficulty, network_difficulty);
+    debug!(
+        event = "best_share",
+        difficulty,
+        "Best share on this job so far: difficulty {} ({} of a block)",
+        format_difficulty(difficulty),
+        format_closest_call(difficulty / network_difficulty)
+    );
+    difficulty
+}
+
+/// A block's reward for messages, e.g. `3.12500000 BTC (3.12500000 BTC to you)`
+fn reward_text(reward: Option<Reward>) -> String {
+    match reward {
+        Some(reward) => format!("{} ({} to you)", format_btc(reward.total), format_btc(reward.to_address)),
+        None => "unknown".to_string(),
+    }
+}
+
+/// The summaries' line giving the last job's reward in fiat, if a price source is set
+async fn reward_value_line(config: &Arc<Mutex<MiningConfig>>) -> Option<String> {
+    let (price, reward) = {
+        let config_guard = config.lock().unwrap();
+        (config_guard.btc_price.clone()?, config_guard.last_reward?)
+    };
+    let value = btc_price::value_of(&price, reward.to_address).await?;
+    Some(format!("\nBlock reward: <code>{} to you ({})</code>", format_btc(reward.to_address), value))
+}
+
+/// Send a Telegram message for every block found and every hashing thread restarted
+async fn telegram_notifier(config: Arc<Mutex<MiningConfig>>, mut notify_events: broadcast::Receiver<MinerEvent>, cancel: CancellationToken) {
+    // Pools repeat their announcement on every connection; it's only forwarded once
+    let mut last_pool_message = None;
+    while let Some(event) = events::next_until(&mut notify_events, &cancel).await {
+        if !matches!(
+            event,
+            MinerEvent::BlockFound { .. }
+                | MinerEvent::WorkerRestarted { .. }
+                | MinerEvent::PoolBlacklisted { .. }
+                | MinerEvent::PoolMessage { .. }
+                | MinerEvent::SubmitUnacknowledged { .. }
+                | MinerEvent::NetworkBlock { .. }
+                | MinerEvent::ShareDifficultyChanged { .. }
+                | MinerEvent::ChainTipDiverged { .. }
+        ) {
+            continue;
+        }
+        let (telegram, dry_run, time, price) = {
+            let config_guard = config.lock().unwrap();
+            let timestamp = config_guard.clock.unix_secs();
+            (
+                config_guard.telegram.clone(),
+                config_guard.dry_run,
+                config_guard.timestamps.format_unix(timestamp),
+                config_guard.btc_price.clone(),
+            )
+        };
+        let Some(telegram) = telegram else {
+            continue;
+        };
+        let message = match event {
+            MinerEvent::BlockFound { hash, target, nonce, address, network_difficulty, hash_difficulty, reward_sats, payout_sats, .. } => {
+                let value = match (&price, payout_sats) {
+                    (Some(price), Some(sats)) => btc_price::value_of(price, sats).await,
+                    _ => None,
+                };
+                format!(
+                    "{}🎉 <b>BLOCK FOUND!</b>\n\n\
+                    Hash: <code>{}</code>\n\
+                    Target: <code>{}</code>\n\
+                    Difficulty: <code>network {}, this hash {}</code>\n\
+                    Nonce: <code>{}</code>\n\
+                    Address: <code>{}</code>\n\
+                    Reward: <code>{}</code>\n\
+                    {}\
+                    Time: <code>{}</code>",
+                    if dry_run { "🧪 <b>DRY RUN</b> (not submitted)\n" } else { "" },
+                    hash, target, format_difficulty(network_difficulty), format_difficulty(hash_difficulty), nonce, address,
+                    reward_text(reward_sats.zip(payout_sats).map(|(total, to_address)| Reward { total, to_address })),
+                    value.map_or(String::new(), |value| format!("Value: <code>{} to you</code>\n", value)),
+                    time
+                )
+            }
+            MinerEvent::WorkerRestarted { worker, job_id, panic, restarts } => format!(
+                "⚠️ <b>Worker restarted</b>\n\n\
+                Hashing thread {} panicked and was restarted (restart {} on this job)\n\
+                Job: <code>{}</code>\n\
+                Panic: <code>{}</code>\n\
+                Time: <code>{}</code>",
+                worker, restarts, html_escape(&job_id), html_escape(&panic), time
+            ),
+            MinerEvent::PoolMessage { message } => {
+                if !telegram.notify_pool_messages || last_pool_message.as_ref() == Some(&message) {
+                    continue;
+                }
+                let text = format!("📢 <b>Message from the pool</b>\n\n{}\n\nTime: <code>{}</code>", html_escape(&message), time);
+                last_pool_message = Some(message);
+                text
+            }
+            MinerEvent::PoolBlacklisted { pool, failures, cooldown_secs } => format!(
+                "🚫 <b>Pool blacklisted</b>\n\n\
+                Pool: <code>{}</code>\n\
+                Failed {} sessions in a row and is skipped for {} minutes\n\
+                Time: <code>{}</code>",
+                html_escape(&pool), failures, cooldown_secs / 60, time
+            ),
+            MinerEvent::NetworkBlock { height, hash, interval_secs } => {
+                if !telegram.notify_network_blocks {
+                    continue;
+                }
+                format!(
+                    "⛓ <b>Network block {}</b>\n\n\
+                    {}\
+                    Hash: <code>{}</code>\n\
+                    Time: <code>{}</code>",
+                    height,
+                    interval_secs.map_or(String::new(), |secs| format!("Found {} after the previous block\n", format_interval(Duration::from_secs(secs)))),
+                    hash, time
+                )
+            }
+            MinerEvent::ShareDifficultyChanged { from, to } => format!(
+                "📊 <b>Share difficulty changed</b>\n\n\
+                The pool changed the share difficulty {}. \
+                The hash rate changed a lot, or the connection has trouble.\n\
+                Time: <code>{}</code>",
+                DifficultyChange { from, to }, time
+            ),
+            MinerEvent::ChainTipDiverged { pool_height, network_height, blocks, forked } => format!(
+                "⚠️ <b>Pool on a stale or forked chain?</b>\n\n\
+                The pool's jobs build on {} block {}, while the block explorer's tip is {} ({} block(s) apart). \
+                A block found there would likely be orphaned.\n\
+                Time: <code>{}</code>",
+                if forked { "a different" } else { "the old" }, pool_height, network_height, blocks, time
+            ),
+            MinerEvent::SubmitUnacknowledged { job_id, nonce, attempts, timeout_secs } => format!(
+                "🚨 <b>Block submission unacknowledged</b>\n\n\
+                The pool never answered the solution, sent {} time(s) with {}s to reply each time. \
+                It is kept in the block archive to resubmit by hand.\n\
+                Job: <code>{}</code>\n\
+                Nonce: <code>{}</code>\n\
+                Time: <code>{}</code>",
+                attempts, timeout_secs, html_escape(&job_id), nonce, time
+            ),
+            _ => continue,
+        };
+        if let Err(e) = send_telegram_message(&telegram, &message).await {
+            warn!("Failed to send Telegram notification: {}", e);
+        }
+    }
+}
+
+/// Run the configured hook for each event, without waiting for one to finish before the next
+///
+/// Hooks still running when the miner stops are waited for.
+async fn hook_runner(config: Arc<Mutex<MiningConfig>>, mut hook_events: broadcast::Receiver<MinerEvent>, cancel: CancellationToken) {
+    let mut running = JoinSet::new();
+    while let Some(event) = events::next_until(&mut hook_events, &cancel).await {
+        let hooks = config.lock().unwrap().hooks.clone();
+        let Some((name, command)) = hooks.for_event(&event) else {
+            continue;
+        };
+        let command = command.to_string();
+        running.spawn(async move {
+            info!(event = "hook_started", hook = name, "Running {} hook", name);
+            if let Err(e) = hooks::run(&command, &event).await {
+                warn!(event = "hook_failed", hook = name, "{} hook failed: {:#}", name, e);
+            }
+        }.in_current_span());
+        // Forget the hooks that have finished
+        while running.try_join_next().is_some() {}
+    }
+    running.join_all().await;
+}
+
+/// Monitor for new blocks on the network
+async fn new_block_listener(config: Arc<Mutex<MiningConfig>>) -> Result<()> {
+    let mut tips = TipTracker::default();
+    loop {
+        let current_height = {
+            let config_guard = config.lock().unwrap();
+            config_guard.current_height
+        };
+        
+        match get_chain_tip().await {
+            Ok(tip) => {
+                error_report::record_success("block_height");
+                let network_height = tip.height;
+                config.lock().unwrap().tip_watch.network_tip(&tip);
+                report_tip_change(&config);
+                let new_block = tips.observe(tip);
+                if let Some(block) = &new_block {
+                    report_network_block(block);
+                }
+                if network_height > current_height {
+                    let mut local_config = config.lock().unwrap();
+                    // A new block has had its own line
+                    if new_block.is_none() {
+                        info!(event = "network_height", "Network block height updated to {}", network_height);
+                    }
+                    local_config.current_height = network_height;
+                }
+            }
+            Err(e) => {
+                error!("Failed to fetch network block height: {}", e);
+                error_report::record_failure("block_height", &e);
+            }
+        }
+        
+        // The pool's jobs announce new blocks too, so a metered link can do with fewer checks
+        let interval = if config.lock().unwrap().low_bandwidth { LOW_BANDWIDTH_HEIGHT_CHECK_SECS } else { BLOCK_HEIGHT_CHECK_INTERVAL_SECS };
+        tokio::time::sleep(Duration::from_secs(interval)).await;
+    }
+}
+
+/// Log a block the network found and emit it as an event
+fn report_network_block(block: &NewBlock) {
+    let NewBlock { tip, interval, skipped } = block;
+    let after = match interval {
+        Some(interval) => format!(", {} after the previous one", format_interval(*interval)),
+        None if *skipped > 0 => format!(", {} more since the last one seen", skipped),
+        None => String::new(),
+    };
+    info!(
+        event = "network_block",
+        height = tip.height,
+        hash = %tip.hash,
+        interval_secs = interval.map(|interval| interval.as_secs()),
+        "The network found block {}{}", tip.height, after
+    );
+    events::emit(MinerEvent::NetworkBlock {
+        height: tip.height,
+        hash: tip.hash.clone(),
+        interval_secs: interval.map(|interval| interval.as_secs()),
+    });
+}
+
+/// Periodically persist cumulative stats so they survive restarts
+async fn stats_persister(config: Arc<Mutex<MiningConfig>>) -> Result<()> {
+    loop {
+        tokio::time::sleep(Duration::from_secs(STATS_SAVE_INTERVAL_SECS)).await;
+        save_state(&config);
+    }
+}
+
+/// Send the weekly report each time a week's figures are complete
+async fn weekly_reporter(config: Arc<Mutex<MiningConfig>>) {
+    let mut interval = tokio::time::interval(Duration::from_secs(WEEKLY_CHECK_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        let finished = {
+            let mut config_guard = config.lock().unwrap();
+            let now = config_guard.clock.unix_secs();
+            config_guard.stats.roll_week(now)
+        };
+        if let Some(week) = finished {
+            // The new week is on disk before the report goes out, so it's sent only once
+            save_state(&config);
+            report_week(&config, week).await;
+        }
+    }
+}
+
+/// Log, emit and send to Telegram the figures for a finished week
+async fn report_week(config: &Arc<Mutex<MiningConfig>>, week: WeeklyStats) {
+    let (telegram, verbosity, started) = {
+        let config_guard = config.lock().unwrap();
+        (config_guard.telegram.clone(), config_guard.verbosity, config_guard.timestamps.format_unix(week.started_at))
+    };
+    info!(
+        target: logging::SUMMARY_TARGET,
+        event = "weekly_report",
+        started_at = week.started_at,
+        hashes = week.hashes,
+        average_hashrate = week.average_hashrate(),
+        availability_percent = week.availability_percent(),
+        best_share_difficulty = week.best_share_difficulty,
+        blocks_submitted = week.blocks_submitted,
+        expected_blocks = week.expected_blocks,
+        energy_kwh = week.energy_kwh,
+        energy_cost = week.energy_cost,
+        "Weekly report since {}: {}", started, week.summary_line()
+    );
+    events::emit(MinerEvent::WeeklyReport(week.clone()));
+
+    let Some(telegram) = telegram.filter(|t| t.notify_weekly && verbosity >= Verbosity::Summary) else {
+        return;
+    };
+    let mut message = format!(
+        "📅 <b>Weekly Report</b>\n\n\
+        Since: <code>{}</code>\n\
+        Hashes: <code>{}</code>\n\
+        Average hashrate: <code>{:.0} h/s</code>\n\
+        Uptime: <code>{:.1}h</code>\n\
+        Availability: <code>{:.2}%</code>\n\
+        Best share difficulty: <code>{}</code>\n\
+        Blocks: <code>{}</code>",
+        started,
+        week.hashes,
+        week.average_hashrate(),
+        week.uptime_secs as f64 / 3600.0,
+        week.availability_percent(),
+        format_difficulty(week.best_share_difficulty),
+        week.odds_line()
+    );
+    if let Some(energy) = week.energy_line() {
+        message.push_str(&format!("\nEnergy: <code>{}</code>", energy));
+    }
+    if let Some(value) = reward_value_line(config).await {
+        message.push_str(&value);
+    }
+    if let Err(e) = send_telegram_message(&telegram, &message).await {
+        warn!("Failed to send weekly report Telegram notification: {}", e);
+    }
+}
+
+/// Write the lifetime totals to the state file
+fn save_state(config: &Arc<Mutex<MiningConfig>>) {
+    let (totals, state_path) = {
+        let config_guard = config.lock().unwrap();
+        (config_guard.stats.totals(), config_guard.state_file.clone())
+    };
+    if let Err(e) = totals.save(&state_path) {
+        warn!("Failed to save miner state: {:#}", e);
+    }
+}
+
+/// Ping the systemd watchdog for as long as hashing makes progress
+///
+/// A miner that stops hashing (a wedged pool connection, stuck workers) stops pinging,
+/// and systemd restarts it once `WatchdogSec` passes without a ping.
+async fn hashrate_watchdog(config: Arc<Mutex<MiningConfig>>, timeout: Duration) {
+    let mut last_hashes = config.lock().unwrap().stats.totals().total_hashes;
+    let mut interval = tokio::time::interval(timeout / 2);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let (hashes, paused) = {
+            let config_guard = config.lock().unwrap();
+            (config_guard.stats.totals().total_hashes, config_guard.paused)
+        };
+        // A paused miner is idle on purpose, not stuck
+        if hashes > last_hashes || paused {
+            sd_notify::watchdog();
+            last_hashes = hashes;
+        } else {
+            warn!(
+                event = "watchdog_stalled",
+                "No hashes in the last {}s, withholding the systemd watchdog ping",
+                (timeout / 2).as_secs()
+            );
+            sd_notify::status("Hashing stalled, waiting for the watchdog");
+        }
+    }
+}
+
+/// Wait for Ctrl-C or (on Unix) SIGTERM, returning the signal's name
+pub async fn shutdown_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => tokio::select! {
+                result = tokio::signal::ctrl_c() => {
+                    if let Err(e) = result {
+                        error!("Failed to listen for Ctrl-C: {}", e);
+                    }
+                    return "SIGINT";
+                }
+                _ = terminate.recv() => return "SIGTERM",
+            },
+            Err(e) => error!("Failed to listen for SIGTERM: {}", e),
+        }
+    }
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        error!("Failed to listen for Ctrl-C: {}", e);
+        // Without a signal handler there's nothing to wait for; keep mining
+        std::future::pending::<()>().await;
+    }
+    "SIGINT"
+}
+
+/// Print (and optionally send via Telegram) the wrap-up for this run
+async fn report_session_summary(config: &Arc<Mutex<MiningConfig>>) {
+    let (summary, cluster, telegram, verbosity) = {
+        let mut config_guard = config.lock().unwrap();
+        config_guard.stats.mark_disconnected();
+        let summary = config_guard.stats.session_summary();
+        let cluster = config_guard.coordinator.as_ref()
+            .map(|coordinator| coordinator.stats(summary.average_hashrate, summary.hashes, summary.best_share_difficulty));
+        (summary, cluster, config_guard.telegram.clone(), config_guard.verbosity)
+    };
+
+    info!(
+        target: logging::SUMMARY_TARGET,
+        event = "session_summary",
+        duration_secs = summary.duration_secs,
+        hashes = summary.hashes,
+        average_hashrate = summary.average_hashrate,
+        shares = summary.shares,
+        best_share_difficulty = summary.best_share_difficulty,
+        reconnects = summary.reconnects,
+        price_paused_secs = summary.price_paused_secs,
+        electricity_savings = summary.electricity_savings,
+        peak_cpu_temp_c = summary.peak_cpu_temp_c,
+        energy_kwh = summary.energy_kwh,
+        energy_cost = summary.energy_cost,
+        "Session summary: {}",
+        summary.summary_line()
+    );
+    events::emit(MinerEvent::SessionSummary(summary.clone()));
+    if let Some(cluster) = &cluster {
+        info!(
+            target: logging::SUMMARY_TARGET,
+            event = "cluster_summary",
+            workers = cluster.workers.len(),
+            hashrate = cluster.hashrate,
+            hashes = cluster.hashes,
+            best_share_difficulty = cluster.best_share_difficulty,
+            "Cluster summary: {}",
+            cluster.summary_line()
+        );
+    }
+
+    if let Some(telegram) = telegram.filter(|t| t.notify_summary && verbosity >= Verbosity::Summary) {
+        let mut message = format!(
+            "🛑 <b>Bitcoin Solo Miner Stopped</b>\n\n\
+            Duration: <code>{}s</code>\n\
+            Hashes: <code>{}</code>\n\
+            Average hashrate: <code>{:.0} h/s</code>\n\
+            Shares: <code>{}</code>\n\
+            Best share difficulty: <code>{}</code>\n\
+            Closest call: <code>{}</code>\n\
+            Reconnects: <code>{}</code>",
+            summary.duration_secs,
+            summary.hashes,
+            summary.average_hashrate,
+            summary.shares,
+            format_difficulty(summary.best_share_difficulty),
+            format_closest_call(summary.closest_call),
+            summary.reconnects
+        );
+        if summary.price_paused_secs > 0 {
+            message.push_str(&format!(
+                "\nPaused for electricity price: <code>{}s</code> (saved {:.2})",
+                summary.price_paused_secs, summary.electricity_savings
+            ));
+        }
+        if let Some(temp) = summary.peak_cpu_temp_c {
+            message.push_str(&format!("\nPeak CPU temperature: <code>{:.1}°C</code>", temp));
+        }
+        if let Some(energy) = summary.energy_line() {
+            message.push_str(&format!("\nEnergy: <code>{}</code>", energy));
+        }
+        message.push_str(&format!("\nPool traffic: <code>{}</code>", summary.traffic_line()));
+        if let Some(value) = reward_value_line(config).await {
+            message.push_str(&value);
+        }
+        if let Some(cluster) = &cluster {
+            message.push_str(&control::cluster_lines(cluster));
+        }
+        if let Err(e) = send_telegram_message(&telegram, &message).await {
+            warn!("Failed to send session summary Telegram notification: {}", e);
+        }
+    }
+}
+
+/// Mine until `shutdown` resolves with the name of what stopped the miner
+pub async fn mine(options: &RunOptions, config: LoadedConfig, shutdown: impl Future<Output = &'static str>) -> Result<()> {
+    // Settings in effect, compared against on config reload
+    let mut active_config = config.clone();
+    let LoadedConfig {
+        address: config_address,
+        payout_addresses,
+        address_rotation,
+        payout_check,
+        low_bandwidth,
+        submit_policy,
+        difficulty_change_factor,
+        stale_tip_blocks,
+        btc_price,
+        pool,
+        threads,
+        verbosity,
+        telegram: telegram_config,
+        log_format,
+        otlp_endpoint,
+        sentry_dsn,
+        main_log,
+        log_dir,
+        log_rotation,
+        system_log,
+        timestamps,
+        state_file,
+        blocks_dir,
+        profile,
+        config_path,
+        prompt_timeout,
+        hooks,
+        script,
+        script_tick,
+        grpc_listen,
+        control_socket,
+        api_token,
+        proxy_listen,
+        proxy_difficulty,
+        cluster_listen,
+        cluster_token,
+        cluster_redis,
+        cluster_mdns,
+        electricity,
+        solar,
+        activity,
+        quiet,
+        power,
+        failover,
+        ..
+    } = config;
+
+    if log_format == LogFormat::Text && options.output == OutputMode::Text && verbosity >= Verbosity::Normal {
+        println!("{}", CREDITS);
+    }
+
+    // Prompts would corrupt the ndjson stream, so that mode is always non-interactive
+    let interactive = atty::is(atty::Stream::Stdin) && options.output == OutputMode::Text;
+    
+    // Get Bitcoin address - command line, env var or config, prompting only as a last resort
+    let address = if !config_address.is_empty() {
+        config_address
+    } else {
+        // A prompt timeout lets semi-interactive launch scripts fail instead of hanging
+        let entered = if interactive {
+            print!("Enter your Bitcoin wallet address for mining rewards: ");
+            std::io::stdout().flush()?;
+            console::read_line(prompt_timeout)?
+        } else {
+            None
+        };
+        match entered {
+            Some(input) => input.trim().to_string(),
+            None => {
+                let mut message = if interactive {
+                    println!();
+                    format!("No Bitcoin address entered within {}s.", prompt_timeout.unwrap_or_default().as_secs())
+                } else {
+                    "Bitcoin address is required.".to_string()
+                };
+                message.push_str(" Pass --address, set the BTC_ADDRESS environment variable or configure wallet_address in config.ini");
+                // On a first run without any config, leave a template to fill in
+                if config_path.is_none() {
+                    match config::write_config_template() {
+                        Ok(path) => message.push_str(&format!(
+                            "\nA commented config template was written to {}: set wallet_address there and start the miner again",
+                            path.display()
+                        )),
+                        Err(e) => message.push_str(&format!("\n(No config template written: {:#})", e)),
+                    }
+                }
+                return Err(anyhow!(message)).map_err(fatal(ExitCode::InvalidAddress));
+            }
+        }
+    };
+
+    // Rewards paid to a mistyped address are lost for good, so refuse to mine
+    let payout_addresses = if payout_addresses.is_empty() {
+        vec![PayoutAddress { address: address.clone(), weight: 1 }]
+    } else {
+        payout_addresses
+    };
+    // One generator for the run, shared by address rotation and the workers
+    let rng = RandomSource::from_entropy();
+    let mut payouts = PayoutRotation::new(payout_addresses, address_rotation)
+        .map_err(fatal(ExitCode::InvalidAddress))?
+        .with_rng(rng.clone());
+    active_config.address = address.clone();
+
+    // Optional OTLP export of traces and metrics
+    let (telemetry, telemetry_error) = match otlp_endpoint.as_deref().map(Telemetry::init) {
+        Some(Ok(telemetry)) => (Some(telemetry), None),
+        Some(Err(e)) => (None, Some(e)),
+        None => (None, None),
+    };
+
+    // Optional main log file alongside console output
+    let main_log_file = if main_log {
+        paths::ensure_dir(&log_dir, "log_dir")
+            .and_then(|_| {
+                let path = log_dir.join(MAIN_LOG_FILE_NAME);
+                RotatingFile::open(&path, log_rotation)
+                    .with_context(|| format!("Failed to open {}", path.display()))
+            })
+            .map_err(|e| eprintln!("Main log file disabled, logging to console only: {:#}", e))
+            .ok()
+    } else {
+        None
+    };
+
+    // Optional copy of the log to journald/syslog for systemd-managed hosts
+    let system_log_writer = system_log.and_then(|kind| {
+        SystemLogWriter::connect(kind)
+            .map_err(|e| eprintln!("System log disabled: {:#}", e))
+            .ok()
+    });
+
+    // Logging is initialized once the verbosity is known so every output path shares one filter
+    let color = console::use_color(options.no_color, options.output);
+    let log_level = logging::init(log_format, verbosity, options.output, color, &timestamps, main_log_file, system_log_writer, telemetry.as_ref());
+
+    if let Some(e) = telemetry_error {
+        warn!("OpenTelemetry export disabled: {}", e);
+    } else if let Some(endpoint) = &otlp_endpoint {
+        info!(event = "telemetry", endpoint = %endpoint, "Exporting traces and metrics to {}", endpoint);
+    }
+
+    // Hashing that can't reproduce known blocks could never find one either
+    let checks = self_test::run().await
+        .context("Hashing self-test failed, refusing to mine")
+        .map_err(fatal(ExitCode::SelfTestFailed))?;
+    info!(event = "self_test", checks, sha256 = SHA256_IMPL, "Hashing self-test passed ({} checks against known blocks, {} SHA-256)", checks, SHA256_IMPL);
+    if low_bandwidth {
+        info!(event = "low_bandwidth", "Low-bandwidth mode: pinging the pool every {}s, checking the block height every {}s, no difficulty cross-check", LOW_BANDWIDTH_PING_INTERVAL_SECS, LOW_BANDWIDTH_HEIGHT_CHECK_SECS);
+    }
+
+    // A replay stands in for the pool (and its backups), and a recording must see the
+    // first connection
+    let (pool, failover, replay_finished) = match &options.replay {
+        Some(path) => {
+            let (addr, finished) = recording::replay(path).await.map_err(fatal(ExitCode::InvalidConfig))?;
+            (addr.to_string(), None, Some(finished))
+        }
+        None => {
+            let failover = failover.map(|settings| FailoverSettings {
+                pools: settings.pools.iter().map(|pool| presets::address(pool)).collect(),
+                ..settings
+            });
+            (presets::resolve(&pool).await, failover, None)
+        }
+    };
+    if let Some(path) = &options.record {
+        recording::start(path).map_err(fatal(ExitCode::InvalidConfig))?;
+    }
+
+    // Optional panic and repeated-error reporting
+    if let Some(dsn) = &sentry_dsn {
+        match error_report::init(dsn, &pool, hashing::backend()) {
+            Ok(()) => info!(event = "error_reporting", "Error reporting enabled"),
+            Err(e) => warn!("Error reporting disabled: {}", e),
+        }
+    }
+    exit_code::exit_on_panic();
+
+    let telegram_enabled = telegram_config.is_some();
+    for payout in payouts.addresses() {
+        let address_type = address::parse(&payout.address)?;
+        if payouts.addresses().len() == 1 {
+            info!(event = "startup", address = %payout.address, address_type = %address_type, "Bitcoin address: {} ({})", payout.address, address_type);
+        } else {
+            info!(
+                event = "startup",
+                address = %payout.address,
+                address_type = %address_type,
+                weight = payout.weight,
+                "Payout address: {} ({}, weight {})",
+                payout.address,
+                address_type,
+                payout.weight
+            );
+        }
+    }
+    if payouts.addresses().len() > 1 {
+        info!(event = "startup", rotation = ?payouts.rotation(), "Address rotation: {:?}", payouts.rotation());
+    }
+    info!(event = "startup", verbosity = verbosity.name(), "Verbosity: {}", verbosity.name());
+    if let Some(profile) = &profile {
+        info!(event = "startup", profile = %profile, "Config profile: {}", profile);
+    }
+    info!(event = "startup", pool = %pool, threads, "Pool: {} | Threads: {}", pool, threads);
+    info!(
+        event = "startup",
+        telegram = telegram_enabled,
+        "Telegram notifications: {}",
+        if telegram_enabled { "enabled" } else { "disabled" }
+    );
+    if options.dry_run {
+        warn!(event = "startup", dry_run = true, "Dry run: blocks will be detected, logged and notified but never submitted");
+    }
+    info!(event = "startup", "Starting miner...");
+
+    // Restore cumulative stats from previous runs
+    info!(event = "startup", log_dir = %log_dir.display(), "Log directory: {}", log_dir.display());
+    if state_file.parent() != Some(log_dir.as_path()) || blocks_dir != log_dir {
+        info!(
+            event = "startup",
+            state_file = %state_file.display(),
+            blocks_dir = %blocks_dir.display(),
+            "State file: {} | Block archive: {}",
+            state_file.display(),
+            blocks_dir.display()
+        );
+    }
+    let lifetime_stats = LifetimeStats::load(&state_file).unwrap_or_else(|e| {
+        warn!("Failed to load miner state, starting fresh: {}", e);
+        LifetimeStats::default()
+    });
+    payouts.resume_after(lifetime_stats.runs);
+    if lifetime_stats.uptime_secs > 0 {
+        info!(
+            target: logging::SUMMARY_TARGET,
+            event = "lifetime_stats",
+            total_hashes = lifetime_stats.total_hashes,
+            shares_submitted = lifetime_stats.shares_submitted,
+            best_share_difficulty = lifetime_stats.best_share_difficulty,
+            uptime_secs = lifetime_stats.uptime_secs,
+            "Lifetime stats: {} hashes | {} shares | best share difficulty {} | uptime {}s",
+            lifetime_stats.total_hashes,
+            lifetime_stats.shares_submitted,
+            format_difficulty(lifetime_stats.best_share_difficulty),
+            lifetime_stats.uptime_secs
+        );
+        info!(
+            target: logging::SUMMARY_TARGET,
+            event = "lifetime_stats",
+            availability_percent = lifetime_stats.availability_percent(),
+            reconnects = lifetime_stats.reconnects,
+            "Lifetime connection: {}",
+            lifetime_stats.connection_summary()
+        );
+    }
+
+    let mut mining_config = MiningConfig::new(
+        address.clone(),
+        pool.clone(),
+        threads,
+        telegram_config.clone(),
+        MinerStats::new(lifetime_stats),
+        blocks_dir,
+        state_file,
+        log_rotation,
+        // The sparkline is for human eyes only, not JSON consumers
+        log_format == LogFormat::Text,
+        options.dry_run,
+        verbosity,
+        timestamps,
+        hooks,
+    );
+    mining_config.rng = rng;
+    mining_config.payout_check = payout_check;
+    mining_config.low_bandwidth = low_bandwidth;
+    mining_config.submit_policy = submit_policy;
+    mining_config.difficulty_change_factor = difficulty_change_factor;
+    mining_config.stale_tip_blocks = stale_tip_blocks;
+    mining_config.btc_price = btc_price;
+    mining_config.quiet = quiet;
+    let config = Arc::new(Mutex::new(mining_config));
+
+    // Send startup Telegram notification
+    if let Some(telegram) = telegram_config.as_ref().filter(|_| verbosity >= Verbosity::Normal) {
+        let startup_message = format!(
+            "🚀 <b>Bitcoin Solo Miner Started</b>\n\n\
+            Address: <code>{}</code>\n\
+            Verbosity: {}\n\
+            Pool: <code>{}</code>{}",
+            payouts.addresses().iter().map(|p| p.address.as_str()).collect::<Vec<_>>().join(", "),
+            verbosity.name(),
+            pool,
+            if options.dry_run { "\n🧪 Dry run: solutions are not submitted" } else { "" }
+        );
+        if let Err(e) = send_telegram_message(telegram, &startup_message).await {
+            warn!("Failed to send startup Telegram notification: {}", e);
+        }
+    }
+
+    // Stats, metrics and block notifications follow the event bus
+    let mut tasks = Tasks::new();
+    let mut stats_events = events::subscribe();
+    let config_clone = Arc::clone(&config);
+    tasks.spawn_with(Stage::Notifiers, "stats_recorder", |cancel| async move {
+        while let Some(event) = events::next_until(&mut stats_events, &cancel).await {
+            config_clone.lock().unwrap().stats.record_event(&event);
+        }
+    }.instrument(info_span!("stats_recorder")));
+    let mut metric_events = events::subscribe();
+    tasks.spawn_with(Stage::Notifiers, "metrics_recorder", |cancel| async move {
+        while let Some(event) = events::next_until(&mut metric_events, &cancel).await {
+            telemetry::record_event(&event);
+        }
+    }.instrument(info_span!("metrics_recorder")));
+    let notify_events = events::subscribe();
+    let config_clone = Arc::clone(&config);
+    tasks.spawn_with(Stage::Notifiers, "telegram_notifier", |cancel| {
+        telegram_notifier(config_clone, notify_events, cancel).instrument(info_span!("telegram_notifier"))
+    });
+    let hook_events = events::subscribe();
+    let config_clone = Arc::clone(&config);
+    tasks.spawn_with(Stage::Notifiers, "hooks", |cancel| {
+        hook_runner(config_clone, hook_events, cancel).instrument(info_span!("hooks"))
+    });
+    if let Some(path) = &script {
+        let config_clone = Arc::clone(&config);
+        info_span!("script").in_scope(|| scripting::start(path, script_tick, config_clone, &mut tasks))
+            .map_err(fatal(ExitCode::InvalidConfig))?;
+    }
+    events::emit(MinerEvent::Started {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        pool: pool.clone(),
+        addresses: payouts.addresses().iter().map(|p| p.address.clone()).collect(),
+        dry_run: options.dry_run,
+    });
+
+    // Spawn block height monitor
+    let config_clone = Arc::clone(&config);
+    tasks.spawn(Stage::Mining, "block_listener", async move {
+        if let Err(e) = new_block_listener(config_clone).await {
+            error!("Block monitoring error: {}", e);
+        }
+    }.instrument(info_span!("block_listener")));
+
+    // Spawn stats persistence task
+    let config_clone = Arc::clone(&config);
+    tasks.spawn(Stage::Mining, "stats_persister", async move {
+        if let Err(e) = stats_persister(config_clone).await {
+            error!("Stats persistence error: {}", e);
+        }
+    }.instrument(info_span!("stats_persister")));
+
+    let config_clone = Arc::clone(&config);
+    tasks.spawn(Stage::Mining, "weekly_reporter", weekly_reporter(config_clone).instrument(info_span!("weekly_reporter")));
+
+    // Under systemd with WatchdogSec=, ping the watchdog while hashing progresses
+    if let Some(timeout) = sd_notify::watchdog_timeout() {
+        info!(event = "watchdog", timeout_secs = timeout.as_secs(), "systemd watchdog enabled ({}s)", timeout.as_secs());
+        let config_clone = Arc::clone(&config);
+        tasks.spawn(Stage::Mining, "watchdog", hashrate_watchdog(config_clone, timeout).instrument(info_span!("watchdog")));
+    }
+
+    if let Some(listen) = proxy_listen {
+        let proxy = proxy::start(listen, proxy_difficulty, &mut tasks).instrument(info_span!("proxy")).await?;
+        config.lock().unwrap().proxy = Some(proxy);
+    }
+    if let Some(listen) = cluster_listen {
+        let coordinator = cluster::start(listen, cluster_token, cluster_redis, &mut tasks).instrument(info_span!("cluster")).await?;
+        if cluster_mdns {
+            let advert = mdns::Advert { name: cluster::host_name(), port: coordinator.addr().port() };
+            info_span!("cluster").in_scope(|| mdns::advertise(advert, &mut tasks))?;
+        }
+        config.lock().unwrap().coordinator = Some(coordinator);
+    }
+
+    // Reload live-applicable settings on SIGHUP or when the config file changes
+    let watched_file = active_config.config_path.clone().filter(|_| active_config.watch_config);
+    let reloader = Arc::new(ConfigReloader::new(options.overrides.clone(), Arc::clone(&config), log_level, active_config));
+    let control = Control::new(Arc::clone(&config), Arc::clone(&reloader));
+    if let Some(listen) = grpc_listen {
+        grpc::start(listen, api_token.clone(), control.clone(), &mut tasks).instrument(info_span!("grpc")).await?;
+    }
+    if let Some(socket) = control_socket {
+        rpc::start(socket, api_token.clone(), control.clone(), &mut tasks).instrument(info_span!("control_socket")).await?;
+    }
+    if let Some(settings) = electricity {
+        electricity::start(settings, Arc::clone(&config), control.clone(), &mut tasks);
+    }
+    if let Some(settings) = solar {
+        solar::start(settings, Arc::clone(&config), control.clone(), &mut tasks);
+    }
+    if let Some(settings) = activity {
+        activity::start(settings, Arc::clone(&config), control.clone(), &mut tasks);
+    }
+    quiet::start(Arc::clone(&config), control.clone(), &mut tasks);
+    if let Some(settings) = power {
+        power::start(settings, Arc::clone(&config), &mut tasks);
+    }
+    let control_clone = control.clone();
+    tasks.spawn(Stage::Api, "telegram_commands", async move {
+        control_clone.telegram_commands().await;
+    }.instrument(info_span!("telegram_commands")));
+    #[cfg(unix)]
+    {
+        let control = control.clone();
+        tasks.spawn(Stage::Api, "pause_signals", async move {
+            if let Err(e) = control.pause_on_signals().await {
+                error!("Pause signal handler error: {}", e);
+            }
+        }.instrument(info_span!("pause_signals")));
+    }
+    #[cfg(unix)]
+    {
+        let reloader = Arc::clone(&reloader);
+        tasks.spawn(Stage::Api, "config_reload", async move {
+            if let Err(e) = reloader.reload_on_sighup().await {
+                error!("Config reload handler error: {}", e);
+            }
+        }.instrument(info_span!("config_reload")));
+    }
+    if let Some(path) = watched_file {
+        info!(event = "config_watch", path = %path.display(), "Watching {} for changes", path.display());
+        tasks.spawn(Stage::Api, "config_watch", async move {
+            reloader.watch_file(path).await;
+        }.instrument(info_span!("config_reload")));
+    }
+
+    // Main mining loop, until Ctrl-C or a fatal error
+    let mut mining_loop = Box::pin(async {
+        let mut session_id: u64 = 0;
+        let started = Instant::now();
+        let mut connected_once = false;
+        let mut pools = PoolList::new(pool.clone(), failover.as_ref());
+        loop {
+            session_id += 1;
+            let payout_address = payouts.next_address().to_string();
+            if let Some(pool) = config.lock().unwrap().requested_pool.take() {
+                pools.set_primary(pool);
+            }
+            for released in pools.release_expired(Instant::now().into_std()) {
+                info!(event = "pool_blacklist_ended", pool = %released, "{} is off the blacklist and will be tried again", released);
+            }
+            let all_blacklisted = pools.all_blacklisted();
+            let session_pool = pools.select().to_string();
+            if all_blacklisted {
+                warn!(event = "pools_all_blacklisted", pool = %session_pool, "Every pool is blacklisted, trying {} as its cooldown ends first", session_pool);
+            }
+            {
+                let mut config_guard = config.lock().unwrap();
+                if config_guard.address != payout_address {
+                    info!(event = "payout_address", address = %payout_address, "Mining for {}", payout_address);
+                    config_guard.address = payout_address;
+                }
+                if config_guard.pool != session_pool {
+                    info!(event = "pool_switched", pool = %session_pool, "Switching to pool {}", session_pool);
+                    config_guard.pool = session_pool.clone();
+                }
+            }
+            let config_clone = Arc::clone(&config);
+            let mut session = SessionMachine::new(Arc::clone(&config), session_pool.clone());
+            let session_span = info_span!("session", id = session_id, pool = %session_pool);
+            let result = bitcoin_miner(config_clone, &mut session)
+                .instrument(session_span.clone())
+                .await;
+            session_span.in_scope(|| session.disconnect(result.as_ref().err()));
+            if let Some(blacklisting) = pools.record(session.outcome(), Instant::now().into_std()) {
+                let cooldown_minutes = blacklisting.cooldown.as_secs() / 60;
+                warn!(
+                    event = "pool_blacklisted",
+                    pool = %blacklisting.pool,
+                    failures = blacklisting.failures,
+                    cooldown_minutes,
+                    "Pool {} failed {} sessions in a row, blacklisted for {} minutes",
+                    blacklisting.pool, blacklisting.failures, cooldown_minutes
+                );
+                events::emit(MinerEvent::PoolBlacklisted {
+                    pool: blacklisting.pool,
+                    failures: blacklisting.failures,
+                    cooldown_secs: blacklisting.cooldown.as_secs(),
+                });
+            }
+            let totals = config.lock().unwrap().stats.totals();
+            if let Err(e) = result {
+                let unreachable = ExitCode::of(&e) == ExitCode::PoolUnreachable;
+                match Recovery::of(&e) {
+                    Recovery::Abort => return e,
+                    // Only give up on pools that were never reachable, after every backup got
+                    // blacklisted too; later outages are retried
+                    _ if unreachable && !connected_once && (!pools.can_fail_over() || pools.all_blacklisted())
+                        && started.elapsed() >= Duration::from_secs(STARTUP_CONNECT_TIMEOUT_SECS) => return e,
+                    _ if unreachable => {}
+                    Recovery::Retry | Recovery::Reconnect => connected_once = true,
+                }
+                error!("Mining operation error: {:#}", e);
+                error_report::record_failure("mining_session", &e);
+                info!(
+                    event = "connection_stats",
+                    availability_percent = totals.availability_percent(),
+                    reconnects = totals.reconnects,
+                    "Connection stats: {}",
+                    totals.connection_summary()
+                );
+                tokio::time::sleep(Duration::from_millis(MINING_RESTART_DELAY_MS)).await;
+            }
+        }
+    });
+
+    let replay_finished = async {
+        match replay_finished {
+            Some(finished) => {
+                let _ = finished.await;
+            }
+            None => std::future::pending().await,
+        }
+    };
+
+    let fatal_error = tokio::select! {
+        e = &mut mining_loop => {
+            error!(event = "fatal_error", exit_code = ExitCode::of(&e) as u8, "Giving up: {:#}", e);
+            sd_notify::stopping();
+            Some(e)
+        }
+        signal = shutdown => {
+            warn!(event = "shutdown", signal, "Received {}, shutting down", signal);
+            sd_notify::stopping();
+            None
+        }
+        _ = replay_finished => {
+            info!(event = "replay_finished", "Replay finished, shutting down");
+            sd_notify::stopping();
+            None
+        }
+    };
+
+    // Stop in stage order: no remote command may resume or restart mining while it winds
+    // down, and the notifiers outlive the mining to deliver its last events.
+    // Dropping the mining loop stops and joins the workers and closes the pool connection.
+    tasks.stop(Stage::Api).await;
+    drop(mining_loop);
+    tasks.stop(Stage::Mining).await;
+    save_state(&config);
+    // A slow Telegram API must not stall a shutdown the service manager is waiting on
+    if tokio::time::timeout(Duration::from_secs(SHUTDOWN_TIMEOUT_SECS), report_session_summary(&config))
+        .await
+        .is_err()
+    {
+        warn!("Session summary timed out during shutdown");
+    }
+    tasks.stop(Stage::Notifiers).await;
+    info!(event = "stopped", "Miner stopped");
+    fatal_error.map_or(Ok(()), Err)
+}
@@ -0,0 +1,153 @@
+use std::time::Duration;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+use crate::error::Recovery;
+
+pub const TELEGRAM_API: &str = "https://api.telegram.org/bot";
+
+/// A Telegram message that didn't get through
+#[derive(Debug, thiserror::Error)]
+pub enum NotifyError {
+    #[error("Failed to send Telegram message: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Telegram API error: {status} - {body}")]
+    Api {
+        status: StatusCode,
+        body: String,
+    },
+}
+
+impl NotifyError {
+    /// Network trouble, rate limits and server errors pass; anything else the API refused
+    /// (a wrong token or chat id) will be refused again
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            NotifyError::Http(_) => Recovery::Retry,
+            NotifyError::Api { status, .. } if *status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() => Recovery::Retry,
+            NotifyError::Api { .. } => Recovery::Abort,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TelegramConfig {
+    pub bot_token: String,
+    pub user_id: String,
+    pub notify_summary: bool,
+    /// Send the weekly report
+    pub notify_weekly: bool,
+    /// Take `/pause`, `/resume`, `/restart`, `/status` and the `/set...` commands from `user_id`
+    pub commands: bool,
+    /// Forward `client.show_message` announcements from the pool
+    pub notify_pool_messages: bool,
+    /// Tell of every block the network finds
+    pub notify_network_blocks: bool,
+}
+
+/// A message someone sent the bot
+#[derive(Debug, Clone)]
+pub struct TelegramUpdate {
+    pub update_id: i64,
+    pub chat_id: String,
+    /// The user who sent it, which in a group isn't the chat
+    pub sender_id: String,
+    /// Unix time the message was sent
+    pub date: u64,
+    /// Empty for anything but a text message
+    pub text: String,
+}
+
+impl TelegramConfig {
+    pub fn is_configured(&self) -> bool {
+        !self.bot_token.is_empty() && !self.user_id.is_empty()
+    }
+
+    /// Whether `update` was sent by the configured user in their private chat with the bot,
+    /// the only messages commands are taken from
+    pub fn accepts_command(&self, update: &TelegramUpdate) -> bool {
+        update.chat_id == self.user_id && update.sender_id == self.user_id
+    }
+}
+
+/// Send Telegram message
+pub async fn send_telegram_message(telegram: &TelegramConfig, message: &str) -> Result<(), NotifyError> {
+    if !telegram.is_configured() {
+        return Ok(());
+    }
+    
+    let url = format!("{}{}/sendMessage", TELEGRAM_API, telegram.bot_token);
+    let payload = json!({
+        "chat_id": telegram.user_id,
+        "text": message,
+        "parse_mode": "HTML"
+    });
+    
+    let client = reqwest::Client::new();
+    let response = client
+        .post(&url)
+        .json(&payload)
+        .send()
+        .await?;
+    
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(NotifyError::Api { status, body });
+    }
+    
+    Ok(())
+}
+
+/// Wait up to `timeout` for messages to the bot with an update id of at least `offset`
+///
+/// Passing the last id seen plus one confirms everything before it, so Telegram stops
+/// returning those.
+pub async fn get_telegram_updates(telegram: &TelegramConfig, offset: i64, timeout: Duration) -> Result<Vec<TelegramUpdate>, NotifyError> {
+    let url = format!("{}{}/getUpdates", TELEGRAM_API, telegram.bot_token);
+    let payload = json!({
+        "offset": offset,
+        "timeout": timeout.as_secs(),
+        "allowed_updates": ["message"]
+    });
+
+    // Long polling holds the request open for `timeout`, so allow a little more than that
+    let client = reqwest::Client::builder()
+        .timeout(timeout + Duration::from_secs(10))
+        .build()?;
+    let response = client
+        .post(&url)
+        .json(&payload)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        return Err(NotifyError::Api { status, body });
+    }
+
+    let body: Value = response.json().await?;
+    Ok(parse_telegram_updates(&body))
+}
+
+/// The messages in a `getUpdates` reply
+pub fn parse_telegram_updates(body: &Value) -> Vec<TelegramUpdate> {
+    body["result"].as_array().into_iter().flatten()
+        .filter_map(|update| {
+            let message = &update["message"];
+            Some(TelegramUpdate {
+                update_id: update["update_id"].as_i64()?,
+                chat_id: message["chat"]["id"].to_string(),
+                sender_id: message["from"]["id"].to_string(),
+                date: message["date"].as_u64().unwrap_or(0),
+                text: message["text"].as_str().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// `text` made safe to put in an HTML-formatted Telegram message
+pub fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,66 @@
+use std::path::{Path, PathBuf};
+use anyhow::{Result, Context};
+
+const APP_DIR_NAME: &str = "bitcoin-solo-miner";
+const DOCKER_LOGS_DIR: &str = "/app/logs";
+
+/// Platform-appropriate default log directory
+///
+/// Docker: /app/logs (the mounted volume)
+/// Linux: $XDG_DATA_HOME/bitcoin-solo-miner/logs, falling back to ~/.local/share
+/// macOS: ~/Library/Application Support/bitcoin-solo-miner/logs
+/// Windows: %APPDATA%\bitcoin-solo-miner\logs
+/// Otherwise ./logs
+pub fn default_log_dir() -> PathBuf {
+    if std::env::var("DOCKER_CONTAINER").is_ok_and(|v| v == "1") {
+        return PathBuf::from(DOCKER_LOGS_DIR);
+    }
+    data_dir()
+        .map(|dir| dir.join(APP_DIR_NAME).join("logs"))
+        .unwrap_or_else(|| PathBuf::from("logs"))
+}
+
+/// Platform config directory, searched for a config file when there's none in the
+/// working directory
+///
+/// Linux: $XDG_CONFIG_HOME/bitcoin-solo-miner, falling back to ~/.config
+/// macOS: ~/Library/Application Support/bitcoin-solo-miner
+/// Windows: %APPDATA%\bitcoin-solo-miner
+pub fn config_dir() -> Option<PathBuf> {
+    let dir = if cfg!(windows) || cfg!(target_os = "macos") {
+        data_dir()
+    } else {
+        env_path("XDG_CONFIG_HOME").or_else(|| env_path("HOME").map(|home| home.join(".config")))
+    };
+    dir.map(|dir| dir.join(APP_DIR_NAME))
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+fn data_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        env_path("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_path("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_path("XDG_DATA_HOME")
+            .or_else(|| env_path("HOME").map(|home| home.join(".local").join("share")))
+    }
+}
+
+/// Create a directory (and parents) on first use, with an actionable error naming the
+/// `setting` that chose it
+pub fn ensure_dir(dir: &Path, setting: &str) -> Result<()> {
+    if dir.exists() {
+        return Ok(());
+    }
+    std::fs::create_dir_all(dir).with_context(|| format!(
+        "Failed to create directory {} (set {} in the config file, or its environment variable, to a writable location)",
+        dir.display(),
+        setting
+    ))
+}
@@ -0,0 +1,172 @@
+use anyhow::{Result, Context, bail};
+use crate::clock::RandomSource;
+use crate::stratum::MiningJob;
+use crate::{address, coinbase};
+use crate::coinbase::Reward;
+
+/// How the miner picks among several payout addresses
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddressRotation {
+    /// One address per miner run, advancing to the next address on every start
+    #[default]
+    Session,
+    /// The next address in turn for every job
+    Job,
+    /// A random address for every job, chosen in proportion to its weight
+    Weighted,
+}
+
+impl AddressRotation {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "session" => Ok(AddressRotation::Session),
+            "job" => Ok(AddressRotation::Job),
+            "weighted" => Ok(AddressRotation::Weighted),
+            other => bail!("Unknown address_rotation '{}' (expected 'session', 'job' or 'weighted')", other),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AddressRotation::Session => "session",
+            AddressRotation::Job => "job",
+            AddressRotation::Weighted => "weighted",
+        }
+    }
+}
+
+/// What to do when the pool's coinbase has no output paying the payout address
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PayoutCheck {
+    /// Stop the miner rather than mine a block that would pay someone else
+    #[default]
+    Refuse,
+    /// Log a warning and mine anyway
+    Warn,
+    /// Don't look at the coinbase, for pools that pay out some other way
+    Off,
+}
+
+impl PayoutCheck {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "refuse" => Ok(PayoutCheck::Refuse),
+            "warn" => Ok(PayoutCheck::Warn),
+            "off" => Ok(PayoutCheck::Off),
+            other => bail!("Unknown payout_check '{}' (expected 'refuse', 'warn' or 'off')", other),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PayoutCheck::Refuse => "refuse",
+            PayoutCheck::Warn => "warn",
+            PayoutCheck::Off => "off",
+        }
+    }
+}
+
+/// What a block found on the job would pay in all, and how much of that goes to `address`
+///
+/// A `to_address` of zero means the coinbase doesn't pay the address at all.
+pub fn coinbase_reward(job: &MiningJob, extranonce1: &str, extranonce2_size: usize, address: &str) -> Result<Reward> {
+    let script = address::script_pubkey(address)?;
+    let outputs = coinbase::outputs(job, extranonce1, extranonce2_size)?;
+    Ok(Reward::of(&outputs, &script))
+}
+
+/// A payout address and its share of the work under weighted rotation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutAddress {
+    pub address: String,
+    pub weight: u32,
+}
+
+/// Parse a `payout_addresses` list: comma-separated addresses, each optionally
+/// followed by `:weight` (default 1), e.g. `bc1q...:3, 1A1z...`
+pub fn parse_addresses(list: &str) -> Result<Vec<PayoutAddress>> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (address, weight) = match entry.split_once(':') {
+                Some((address, weight)) => {
+                    let weight = weight.trim().parse::<u32>()
+                        .ok()
+                        .filter(|w| *w > 0)
+                        .with_context(|| format!("Invalid weight in payout address '{}' (expected a whole number of at least 1)", entry))?;
+                    (address.trim(), weight)
+                }
+                None => (entry, 1),
+            };
+            Ok(PayoutAddress { address: address.to_string(), weight })
+        })
+        .collect()
+}
+
+/// Chooses the payout address for each job
+#[derive(Debug)]
+pub struct PayoutRotation {
+    addresses: Vec<PayoutAddress>,
+    rotation: AddressRotation,
+    next: usize,
+    rng: RandomSource,
+}
+
+impl PayoutRotation {
+    /// Every address must be valid
+    pub fn new(addresses: Vec<PayoutAddress>, rotation: AddressRotation) -> Result<Self> {
+        if addresses.is_empty() {
+            bail!("No payout address configured");
+        }
+        for payout in &addresses {
+            address::parse(&payout.address)
+                .with_context(|| format!("Invalid Bitcoin address '{}'", payout.address))?;
+        }
+        Ok(Self { addresses, rotation, next: 0, rng: RandomSource::from_entropy() })
+    }
+
+    /// Draw weighted picks from `rng` instead of a generator of its own
+    pub fn with_rng(mut self, rng: RandomSource) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    /// Continue the rotation after `runs` earlier miner runs, so session rotation
+    /// picks up where the last run left off
+    pub fn resume_after(&mut self, runs: u64) {
+        self.next = (runs % self.addresses.len() as u64) as usize;
+    }
+
+    pub fn addresses(&self) -> &[PayoutAddress] {
+        &self.addresses
+    }
+
+    pub fn rotation(&self) -> AddressRotation {
+        self.rotation
+    }
+
+    /// Address to mine the next job for
+    pub fn next_address(&mut self) -> &str {
+        let index = match self.rotation {
+            AddressRotation::Session => self.next,
+            AddressRotation::Job => {
+                let index = self.next;
+                self.next = (self.next + 1) % self.addresses.len();
+                index
+            }
+            AddressRotation::Weighted => {
+                let total: u64 = self.addresses.iter().map(|a| a.weight as u64).sum();
+                let mut pick = self.rng.below(total);
+                self.addresses.iter()
+                    .position(|a| {
+                        let hit = pick < a.weight as u64;
+                        pick = pick.saturating_sub(a.weight as u64);
+                        hit
+                    })
+                    .unwrap_or(0)
+            }
+        };
+        &self.addresses[index].address
+    }
+}
@@ -0,0 +1,122 @@
+//! Predicting the pool's verdict on a share before it is submitted
+//!
+//! The checks follow the ones ckpool runs on a `mining.submit`: the job must be one it
+//! still holds, the extranonce2, ntime and nonce must be well formed, ntime must fall in
+//! the window the job allows, the share must not repeat one already sent and it must meet
+//! the difficulty the pool set. A reject the prediction saw coming points at our own
+//! bug; one it didn't points at pool policy or something these checks don't model.
+
+use std::collections::VecDeque;
+
+use crate::backend::Solution;
+use crate::stratum::MiningJob;
+
+/// How far past the job's ntime ckpool lets a share's ntime roll
+pub const NTIME_ROLL_LIMIT_SECS: u32 = 7000;
+
+/// How many submitted shares are remembered for duplicate detection
+pub const SUBMITTED_SHARES_KEPT: usize = 1024;
+
+/// Why the pool is expected to reject a share
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum PoolRejection {
+    #[error("job {0} isn't one the pool sent")]
+    UnknownJob(String),
+    #[error("job {0} is stale, the pool has since sent a clean job")]
+    StaleJob(String),
+    #[error("extranonce2 {extranonce2} isn't {expected} bytes of hex")]
+    Extranonce2Length { extranonce2: String, expected: usize },
+    #[error("ntime {0} isn't 4 bytes of hex")]
+    InvalidNtime(String),
+    #[error("nonce {0} isn't 4 bytes of hex")]
+    InvalidNonce(String),
+    #[error("ntime {ntime} is outside {job_ntime}..={job_ntime}+{NTIME_ROLL_LIMIT_SECS}")]
+    NtimeOutOfRange { ntime: u32, job_ntime: u32 },
+    #[error("share was already submitted")]
+    Duplicate,
+    #[error("share difficulty {difficulty} is below the pool's {required}")]
+    LowDifficulty { difficulty: f64, required: f64 },
+}
+
+/// What the pool has told us that decides a share's fate, besides the job it was found on
+#[derive(Debug, Clone, Copy)]
+pub struct PoolState<'a> {
+    /// Jobs the pool sent after the share's job that haven't been worked yet
+    pub newer_jobs: &'a [MiningJob],
+    /// Size of the extranonce2 the pool expects, in bytes
+    pub extranonce2_size: usize,
+    /// The last `mining.set_difficulty`, if the pool sent one
+    pub difficulty: Option<f64>,
+    pub submitted: &'a SubmittedShares,
+}
+
+/// The most recent shares handed to the pool, oldest first
+#[derive(Debug, Clone, Default)]
+pub struct SubmittedShares {
+    shares: VecDeque<Solution>,
+}
+
+impl SubmittedShares {
+    pub fn contains(&self, solution: &Solution) -> bool {
+        self.shares.iter().any(|share| same_share(share, solution))
+    }
+
+    /// Remember a share, forgetting the oldest past `SUBMITTED_SHARES_KEPT`
+    pub fn record(&mut self, solution: &Solution) {
+        if self.shares.len() == SUBMITTED_SHARES_KEPT {
+            self.shares.pop_front();
+        }
+        self.shares.push_back(solution.clone());
+    }
+}
+
+/// The pool compares hex fields as numbers, so case doesn't make a share new
+fn same_share(a: &Solution, b: &Solution) -> bool {
+    a.job_id == b.job_id
+        && a.extranonce2.eq_ignore_ascii_case(&b.extranonce2)
+        && a.ntime.eq_ignore_ascii_case(&b.ntime)
+        && a.nonce.eq_ignore_ascii_case(&b.nonce)
+}
+
+fn hex_u32(value: &str) -> Option<u32> {
+    if value.len() != 8 {
+        return None;
+    }
+    u32::from_str_radix(value, 16).ok()
+}
+
+/// Every reason the pool would reject `solution`, found on `job` at `difficulty`; empty
+/// if it should be accepted
+pub fn predict(job: &MiningJob, solution: &Solution, difficulty: f64, pool: PoolState) -> Vec<PoolRejection> {
+    let mut rejections = Vec::new();
+    if solution.job_id != job.job_id && !pool.newer_jobs.iter().any(|newer| newer.job_id == solution.job_id) {
+        rejections.push(PoolRejection::UnknownJob(solution.job_id.clone()));
+    } else if pool.newer_jobs.iter().any(|newer| newer.clean_jobs || newer.prevhash != job.prevhash) {
+        rejections.push(PoolRejection::StaleJob(solution.job_id.clone()));
+    }
+
+    if solution.extranonce2.len() != pool.extranonce2_size * 2 || hex::decode(&solution.extranonce2).is_err() {
+        rejections.push(PoolRejection::Extranonce2Length {
+            extranonce2: solution.extranonce2.clone(),
+            expected: pool.extranonce2_size,
+        });
+    }
+    match (hex_u32(&solution.ntime), hex_u32(&job.ntime)) {
+        (None, _) => rejections.push(PoolRejection::InvalidNtime(solution.ntime.clone())),
+        (Some(ntime), Some(job_ntime)) if ntime < job_ntime || ntime - job_ntime > NTIME_ROLL_LIMIT_SECS => {
+            rejections.push(PoolRejection::NtimeOutOfRange { ntime, job_ntime });
+        }
+        _ => {}
+    }
+    if hex_u32(&solution.nonce).is_none() {
+        rejections.push(PoolRejection::InvalidNonce(solution.nonce.clone()));
+    }
+
+    if pool.submitted.contains(solution) {
+        rejections.push(PoolRejection::Duplicate);
+    }
+    if let Some(required) = pool.difficulty.filter(|&required| difficulty < required) {
+        rejections.push(PoolRejection::LowDifficulty { difficulty, required });
+    }
+    rejections
+}
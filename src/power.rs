@@ -0,0 +1,163 @@
+//! Power use and its cost
+//!
+//! Energy is either estimated from a configured draw per hashing thread or measured with
+//! the CPU's RAPL energy counters, and priced at a fixed rate per kWh. The session summary
+//! reports the total and what it comes to per day.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::{Result, Context, bail};
+use tracing::{info, warn, Instrument, info_span};
+
+use crate::miner::MiningConfig;
+use crate::tasks::{Stage, Tasks};
+
+/// How often energy use is added up
+pub const POWER_SAMPLE_SECS: u64 = 10;
+/// Where the kernel exposes RAPL
+const POWERCAP_DIR: &str = "/sys/class/powercap";
+
+/// How energy use is found
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerSource {
+    /// What one hashing thread draws, in watts; nothing is counted while paused
+    Estimate { watts_per_thread: f64 },
+    /// The CPU packages' RAPL counters, which include whatever else the CPU is doing
+    Rapl,
+}
+
+/// Power accounting, as configured in `[power]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerSettings {
+    pub source: PowerSource,
+    /// Per kWh; `None` reports energy without a cost
+    pub price: Option<f64>,
+}
+
+/// Joules used by `threads` threads drawing `watts_per_thread` each for `elapsed`
+pub fn estimated_joules(watts_per_thread: f64, threads: usize, elapsed: Duration) -> f64 {
+    watts_per_thread * threads as f64 * elapsed.as_secs_f64()
+}
+
+/// Microjoules counted between two readings of a counter that wraps at `range`
+pub fn counter_delta(previous: u64, current: u64, range: u64) -> u64 {
+    if current >= previous {
+        current - previous
+    } else {
+        range.saturating_sub(previous) + current
+    }
+}
+
+#[derive(Debug)]
+struct RaplPackage {
+    energy: PathBuf,
+    range: u64,
+    last: u64,
+}
+
+/// The RAPL package counters, with the reading each was last seen at
+#[derive(Debug)]
+pub struct Rapl {
+    packages: Vec<RaplPackage>,
+}
+
+impl Rapl {
+    /// Open every CPU package under a powercap directory (`/sys/class/powercap`)
+    ///
+    /// Subzones such as `intel-rapl:0:0` (cores) are already counted in their package.
+    pub fn open(powercap: &Path) -> Result<Self> {
+        let mut packages = Vec::new();
+        let mut zones: Vec<PathBuf> = std::fs::read_dir(powercap)
+            .with_context(|| format!("No RAPL counters in {}", powercap.display()))?
+            .flatten()
+            .filter(|entry| {
+                entry.file_name().to_str()
+                    .and_then(|name| name.strip_prefix("intel-rapl:"))
+                    .is_some_and(|zone| !zone.contains(':'))
+            })
+            .map(|entry| entry.path())
+            .collect();
+        zones.sort();
+        for zone in zones {
+            let energy = zone.join("energy_uj");
+            let last = read_counter(&energy)
+                .with_context(|| format!("Failed to read {} (the counters are only readable by root on most kernels)", energy.display()))?;
+            let range = read_counter(&zone.join("max_energy_range_uj")).unwrap_or(u64::MAX);
+            packages.push(RaplPackage { energy, range, last });
+        }
+        if packages.is_empty() {
+            bail!("No RAPL package counters in {}", powercap.display());
+        }
+        Ok(Self { packages })
+    }
+
+    /// Joules used by all packages since the last call, or since opening
+    pub fn joules(&mut self) -> Result<f64> {
+        let mut microjoules = 0;
+        for package in &mut self.packages {
+            let current = read_counter(&package.energy)?;
+            microjoules += counter_delta(package.last, current, package.range);
+            package.last = current;
+        }
+        Ok(microjoules as f64 / 1e6)
+    }
+}
+
+fn read_counter(path: &Path) -> Result<u64> {
+    let text = std::fs::read_to_string(path)?;
+    text.trim().parse().with_context(|| format!("'{}' in {} isn't a counter", text.trim(), path.display()))
+}
+
+/// Count energy use into the session stats
+pub fn start(settings: PowerSettings, config: Arc<Mutex<MiningConfig>>, tasks: &mut Tasks) {
+    let mut rapl = None;
+    match settings.source {
+        PowerSource::Estimate { watts_per_thread } => info!(
+            event = "power_accounting",
+            watts_per_thread,
+            price = settings.price,
+            "Estimating power use at {} W per hashing thread",
+            watts_per_thread
+        ),
+        PowerSource::Rapl => match Rapl::open(Path::new(POWERCAP_DIR)) {
+            Ok(opened) => {
+                info!(event = "power_accounting", source = "rapl", price = settings.price, "Measuring power use with RAPL");
+                rapl = Some(opened);
+            }
+            Err(e) => {
+                warn!(event = "power_accounting_failed", "Power accounting disabled: {:#}", e);
+                return;
+            }
+        },
+    }
+    tasks.spawn(Stage::Api, "power", watch(settings, rapl, config).instrument(info_span!("power")));
+}
+
+async fn watch(settings: PowerSettings, mut rapl: Option<Rapl>, config: Arc<Mutex<MiningConfig>>) {
+    let mut last_sample = Instant::now();
+    let mut interval = tokio::time::interval(Duration::from_secs(POWER_SAMPLE_SECS));
+    // The first tick is immediate, with nothing to count yet
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let elapsed = last_sample.elapsed();
+        last_sample = Instant::now();
+        let joules = match (&settings.source, &mut rapl) {
+            (_, Some(rapl)) => match rapl.joules() {
+                Ok(joules) => joules,
+                Err(e) => {
+                    warn!(event = "power_reading_failed", "Failed to read the RAPL counters: {:#}", e);
+                    continue;
+                }
+            },
+            (PowerSource::Estimate { watts_per_thread }, None) => {
+                let config_guard = config.lock().unwrap();
+                let threads = if config_guard.paused { 0 } else { config_guard.threads };
+                estimated_joules(*watts_per_thread, threads, elapsed)
+            }
+            (PowerSource::Rapl, None) => return,
+        };
+        config.lock().unwrap().stats.record_energy(joules, settings.price);
+    }
+}
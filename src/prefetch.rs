@@ -0,0 +1,57 @@
+//! Preparing the next job while the current one is hashed
+//!
+//! The pool's notifications are read while the workers hash, and as soon as a job arrives
+//! everything derived from it (its height, coinbase outputs and reward, the witness
+//! commitment check, the target and difficulty, and the decoded coinbase, merkle branch
+//! and header the workers build on) is worked out on a blocking thread. When the job is
+//! switched to, all that's left is starting the workers.
+
+use anyhow::Result;
+
+use crate::backend::WorkTemplate;
+use crate::coinbase::{self, Reward, WitnessCheck};
+use crate::payout;
+use crate::stratum::MiningJob;
+use crate::work::{calculate_target, network_difficulty, HashingError, HeaderTemplate};
+
+/// A job with everything the miner checks and reports about it worked out
+#[derive(Debug)]
+pub struct PreparedJob {
+    pub work: WorkTemplate,
+    /// The height of the block the job builds, from its coinbase
+    pub height: Result<u64>,
+    /// What the coinbase pays, in all and to the payout address
+    pub reward: Result<Reward>,
+    pub witness: Result<WitnessCheck>,
+    pub target: Result<Vec<u8>, HashingError>,
+    pub difficulty: Result<f64, HashingError>,
+    /// The headers the workers hash, less the extranonce2 each picks
+    pub template: Result<HeaderTemplate, HashingError>,
+}
+
+impl PreparedJob {
+    /// Work out a job's derived data for mining it to `address`
+    pub fn prepare(work: WorkTemplate, address: &str) -> Self {
+        let WorkTemplate { job, extranonce1, extranonce2_size } = &work;
+        Self {
+            height: coinbase::height(job),
+            reward: payout::coinbase_reward(job, extranonce1, *extranonce2_size, address),
+            witness: coinbase::check_witness_commitment(job, extranonce1, *extranonce2_size),
+            target: calculate_target(&job.nbits),
+            difficulty: network_difficulty(&job.nbits),
+            template: HeaderTemplate::new(job, extranonce1, *extranonce2_size),
+            work,
+        }
+    }
+
+    /// Whether this job builds on a different block than `current`
+    pub fn new_block(&self, current: &MiningJob) -> bool {
+        self.work.job.prevhash != current.prevhash
+    }
+
+    /// Whether `current` should be dropped for this job: it's on a new block, or the pool
+    /// said to drop earlier jobs
+    pub fn replaces(&self, current: &MiningJob) -> bool {
+        self.work.job.clean_jobs || self.new_block(current)
+    }
+}
@@ -0,0 +1,117 @@
+//! Built-in pool presets
+//!
+//! `pool = ckpool-eu` (or another preset's name) stands for that ckpool solo server, and
+//! `pool = ckpool` for whichever of them is closest: each is connected to a few times at
+//! startup and the lowest connect time wins.
+
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+/// The pool setting that picks the fastest ckpool server
+pub const CKPOOL_AUTO: &str = "ckpool";
+/// Connections made to each server, the fastest of which counts
+const PROBE_ATTEMPTS: usize = 3;
+const PROBE_TIMEOUT_SECS: u64 = 5;
+
+/// A pool known by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PoolPreset {
+    pub name: &'static str,
+    pub address: &'static str,
+    pub region: &'static str,
+}
+
+/// The solo.ckpool.org servers, the main one first
+pub const CKPOOL_PRESETS: &[PoolPreset] = &[
+    PoolPreset { name: "ckpool-main", address: "solo.ckpool.org:3333", region: "main server" },
+    PoolPreset { name: "ckpool-eu", address: "eusolo.ckpool.org:3333", region: "Europe" },
+    PoolPreset { name: "ckpool-au", address: "ausolo.ckpool.org:3333", region: "Australia" },
+];
+
+/// The preset called `name`, ignoring case
+pub fn find(name: &str) -> Option<&'static PoolPreset> {
+    CKPOOL_PRESETS.iter().find(|preset| preset.name.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Whether a pool setting asks for the fastest ckpool server
+pub fn is_auto(pool: &str) -> bool {
+    pool.trim().eq_ignore_ascii_case(CKPOOL_AUTO)
+}
+
+/// The fastest of the presets that answered, given each one's connect time
+pub fn fastest(probes: &[(&'static PoolPreset, Option<Duration>)]) -> Option<(&'static PoolPreset, Duration)> {
+    probes
+        .iter()
+        .filter_map(|(preset, connect_time)| Some((*preset, (*connect_time)?)))
+        .min_by_key(|(_, connect_time)| *connect_time)
+}
+
+/// The best TCP connect time to `address` over a few attempts, `None` if it never answered
+pub async fn probe(address: &str) -> Option<Duration> {
+    let mut best: Option<Duration> = None;
+    for _ in 0..PROBE_ATTEMPTS {
+        let started = Instant::now();
+        if let Ok(Ok(_)) = tokio::time::timeout(Duration::from_secs(PROBE_TIMEOUT_SECS), TcpStream::connect(address)).await {
+            let connect_time = started.elapsed();
+            best = Some(best.map_or(connect_time, |best| best.min(connect_time)));
+        }
+    }
+    best
+}
+
+/// The pool a `pool` or failover setting stands for: a preset's address, or the setting
+/// itself; `ckpool` stands for the main server until `resolve` picks one
+pub fn address(pool: &str) -> String {
+    if is_auto(pool) {
+        return CKPOOL_PRESETS[0].address.to_string();
+    }
+    find(pool).map_or_else(|| pool.to_string(), |preset| preset.address.to_string())
+}
+
+/// Like `address`, but for `ckpool` every ckpool server is probed and the fastest used
+pub async fn resolve(pool: &str) -> String {
+    if !is_auto(pool) {
+        if let Some(preset) = find(pool) {
+            info!(event = "pool_preset", preset = preset.name, pool = preset.address, "Pool preset {}: {} ({})", preset.name, preset.address, preset.region);
+        }
+        return address(pool);
+    }
+
+    let mut probes = JoinSet::new();
+    for preset in CKPOOL_PRESETS {
+        probes.spawn(async move { (preset, probe(preset.address).await) });
+    }
+    let mut results = Vec::new();
+    while let Some(Ok(result)) = probes.join_next().await {
+        results.push(result);
+    }
+    results.sort_by_key(|(preset, _)| CKPOOL_PRESETS.iter().position(|known| known == *preset));
+    let timings: Vec<String> = results
+        .iter()
+        .map(|(preset, connect_time)| match connect_time {
+            Some(connect_time) => format!("{} {} ms", preset.name, connect_time.as_millis()),
+            None => format!("{} unreachable", preset.name),
+        })
+        .collect();
+    match fastest(&results) {
+        Some((preset, connect_time)) => {
+            info!(
+                event = "pool_auto_selected",
+                preset = preset.name,
+                pool = preset.address,
+                connect_ms = connect_time.as_millis() as u64,
+                "Fastest ckpool server: {} ({}, {} ms) [{}]",
+                preset.address, preset.region, connect_time.as_millis(), timings.join(", ")
+            );
+            preset.address.to_string()
+        }
+        None => {
+            let preset = &CKPOOL_PRESETS[0];
+            warn!(event = "pool_auto_select_failed", pool = preset.address, "No ckpool server answered, using {}", preset.address);
+            preset.address.to_string()
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Local Stratum server for other miners on the LAN, behind the miner's one pool connection
+//!
+//! Each downstream miner gets its own slice of the pool's extranonce2 space: its extranonce1
+//! is the pool's followed by a `PREFIX_BYTES` prefix unique to the connection, and its
+//! extranonce2 is whatever is left. Shares are checked here against the proxy's own
+//! difficulty, and one that also meets the network target goes to the pool the same way
+//! a block found by the local workers does.
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use anyhow::{Result, Context};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::watch;
+use tracing::{debug, error, info, warn, Instrument};
+
+use crate::hashing::{double_sha256, hash_meets_target};
+use crate::stats::hash_difficulty;
+use crate::stratum::MiningJob;
+use crate::tasks::{Stage, Tasks};
+use crate::work::{compute_merkle_root_hex, create_block_header};
+use crate::worker::{FoundBlock, SharedWork};
+
+/// Bytes of the pool's extranonce2 taken by the per-connection prefix
+pub const PREFIX_BYTES: usize = 2;
+
+// Stratum error codes, as used by ckpool and most other pools
+const OTHER_ERROR: i64 = 20;
+const JOB_NOT_FOUND: i64 = 21;
+const DUPLICATE_SHARE: i64 = 22;
+const LOW_DIFFICULTY: i64 = 23;
+const UNAUTHORIZED: i64 = 24;
+
+/// A `mining.notify` for `job`
+fn notify(job: &MiningJob) -> Value {
+    // Shares for an earlier job can't be submitted anymore, so every job replaces the last
+    json!({
+        "id": null,
+        "method": "mining.notify",
+        "params": [job.job_id, job.prevhash, job.coinb1, job.coinb2, job.merkle_branch, job.version, job.nbits, job.ntime, true],
+    })
+}
+
+/// Hands each new job to the proxy's connections
+#[derive(Debug, Clone)]
+pub struct Proxy {
+    addr: SocketAddr,
+    work: Arc<watch::Sender<Option<Arc<SharedWork>>>>,
+}
+
+impl Proxy {
+    /// Where the proxy is listening, with the port filled in if it was 0
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Replace the downstream miners' job, e.g. whenever the local workers start a new one
+    pub fn publish(&self, work: Arc<SharedWork>) {
+        self.work.send_replace(Some(work));
+    }
+}
+
+/// Accept downstream miners on `listen` until the mining stage stops
+///
+/// Miners connect with any user name and password. They get work once the miner has its
+/// first job from the pool, at a share difficulty of `difficulty`. Fails if the port can't
+/// be opened.
+pub async fn start(listen: SocketAddr, difficulty: u64, tasks: &mut Tasks) -> Result<Proxy> {
+    let listener = tokio::net::TcpListener::bind(listen).await
+        .with_context(|| format!("Failed to listen for Stratum miners on {}", listen))?;
+    let addr = listener.local_addr()?;
+    info!(event = "proxy_listening", listen = %addr, difficulty, "Stratum proxy listening on {}", addr);
+
+    let (work_tx, work_rx) = watch::channel(None);
+    tasks.spawn_with(Stage::Mining, "proxy", |cancel| async move {
+        // Prefix 0 is never handed out. After 65535 connections prefixes are reused, by
+        // which time the miners that had them are long gone.
+        let mut prefix: u16 = 0;
+        loop {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = cancel.cancelled() => return,
+            };
+            match accepted {
+                Ok((stream, peer)) => {
+                    prefix = prefix.checked_add(1).unwrap_or(1);
+                    let connection = Downstream::new(peer, prefix, difficulty).serve(stream, work_rx.clone());
+                    tokio::spawn(cancel.child_token().run_until_cancelled_owned(connection).in_current_span());
+                }
+                Err(e) => {
+                    error!("Stratum proxy stopped: {}", e);
+                    return;
+                }
+            }
+        }
+    });
+    Ok(Proxy { addr, work: Arc::new(work_tx) })
+}
+
+fn error_reply(id: &Value, code: i64, message: &str) -> Value {
+    json!({"id": id, "result": null, "error": [code, message, null]})
+}
+
+/// One downstream miner's connection
+struct Downstream {
+    peer: SocketAddr,
+    /// This connection's slice of extranonce2, as hex
+    prefix: String,
+    difficulty: u64,
+    worker: Option<String>,
+    /// The pool's extranonce1 the miner's was built from, once subscribed
+    pool_extranonce1: Option<String>,
+    /// Whether the miner takes `mining.set_extranonce` instead of reconnecting
+    extranonce_subscribe: bool,
+    /// The job the miner is working on, once it has been sent one
+    work: Option<Arc<SharedWork>>,
+    /// Shares already taken for the current job, as `(extranonce2, ntime, nonce)`
+    submitted: HashSet<(String, String, String)>,
+    accepted: u64,
+    /// Set when the connection should close after the pending replies
+    disconnect: bool,
+}
+
+impl Downstream {
+    fn new(peer: SocketAddr, prefix: u16, difficulty: u64) -> Self {
+        Self {
+            peer,
+            prefix: format!("{:0width$x}", prefix, width = PREFIX_BYTES * 2),
+            difficulty,
+            worker: None,
+            pool_extranonce1: None,
+            extranonce_subscribe: false,
+            work: None,
+            submitted: HashSet::new(),
+            accepted: 0,
+            disconnect: false,
+        }
+    }
+
+    async fn serve(mut self, stream: TcpStream, mut work: watch::Receiver<Option<Arc<SharedWork>>>) {
+        debug!(event = "proxy_connected", peer = %self.peer, prefix = %self.prefix, "Stratum miner connected from {}", self.peer);
+        let (reader, mut writer) = stream.into_split();
+        let mut lines = BufReader::new(reader).lines();
+        while !self.disconnect {
+            let messages = tokio::select! {
+                line = lines.next_line() => match line {
+                    Ok(Some(line)) if line.trim().is_empty() => continue,
+                    Ok(Some(line)) => self.handle(&line, &mut work).await,
+                    _ => break,
+                },
+                changed = work.changed(), if self.worker.is_some() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let latest = work.borrow_and_update().clone();
+                    latest.map(|latest| self.new_work(latest)).unwrap_or_default()
+                }
+            };
+            for message in messages {
+                if writer.write_all(format!("{}\n", message).as_bytes()).await.is_err() {
+                    return;
+                }
+            }
+        }
+        info!(
+            event = "proxy_disconnected",
+            peer = %self.peer,
+            worker = self.worker.as_deref().unwrap_or(""),
+            accepted = self.accepted,
+            "Stratum miner {} disconnected after {} share(s)", self.peer, self.accepted
+        );
+    }
+
+    /// Answer one request, along with any notifications that follow it
+    async fn handle(&mut self, line: &str, work: &mut watch::Receiver<Option<Arc<SharedWork>>>) -> Vec<Value> {
+        let request: Value = match serde_json::from_str(line) {
+            Ok(request) => request,
+            Err(_) => {
+                warn!(event = "proxy_invalid_message", peer = %self.peer, message = %line, "Invalid message from Stratum miner {}: {}", self.peer, line);
+                self.disconnect = true;
+                return Vec::new();
+            }
+        };
+        let id = request["id"].clone();
+        let params = &request["params"];
+        match request["method"].as_str().unwrap_or_default() {
+            "mining.subscribe" => {
+                // The extranonce1 to hand out depends on the pool's, so wait for the first job
+                let Ok(latest) = work.wait_for(Option::is_some).await.map(|latest| latest.clone()) else {
+                    self.disconnect = true;
+                    return Vec::new();
+                };
+                let latest = latest.expect("waited for work");
+                if latest.extranonce2_size <= PREFIX_BYTES {
+                    self.disconnect = true;
+                    return vec![error_reply(&id, OTHER_ERROR, "Pool extranonce2 too small to share")];
+                }
+                self.pool_extranonce1 = Some(latest.extranonce1.clone());
+                let subscriptions = json!([["mining.set_difficulty", self.prefix], ["mining.notify", self.prefix]]);
+                vec![json!({"id": id, "result": [subscriptions, self.extranonce1(&latest), latest.extranonce2_size - PREFIX_BYTES], "error": null})]
+            }
+            "mining.extranonce.subscribe" => {
+                self.extranonce_subscribe = true;
+                vec![json!({"id": id, "result": true, "error": null})]
+            }
+            "mining.configure" => {
+                // Version rolling would need the header built from the miner's version bits
+                vec![json!({"id": id, "result": {"version-rolling": false}, "error": null})]
+            }
+            "mining.authorize" => {
+                let worker = params[0].as_str().unwrap_or_default().to_string();
+                info!(event = "proxy_authorized", peer = %self.peer, worker = %worker, "Stratum miner {} authorized as {}", self.peer, worker);
+                let first_authorize = self.worker.replace(worker).is_none();
+                let mut messages = vec![json!({"id": id, "result": true, "error": null})];
+                if first_authorize {
+                    messages.push(json!({"id": null, "method": "mining.set_difficulty", "params": [self.difficulty]}));
+                    if let Some(latest) = work.borrow_and_update().clone() {
+                        messages.extend(self.new_work(latest));
+                    }
+                }
+                messages
+            }
+            "mining.submit" => vec![self.submit(&id, params)],
+            "mining.suggest_difficulty" => vec![json!({"id": id, "result": true, "error": null})],
+            other => {
+                debug!(peer = %self.peer, method = %other, "Unsupported method from Stratum miner");
+                vec![error_reply(&id, OTHER_ERROR, "Unsupported method")]
+            }
+        }
+    }
+
+    fn extranonce1(&self, work: &SharedWork) -> String {
+        format!("{}{}", work.extranonce1, self.prefix)
+    }
+
+    /// Messages that move the miner on to `latest`
+    fn new_work(&mut self, latest: Arc<SharedWork>) -> Vec<Value> {
+        let mut messages = Vec::new();
+        if self.pool_extranonce1.as_ref() != Some(&latest.extranonce1) {
+            if !self.extranonce_subscribe || latest.extranonce2_size <= PREFIX_BYTES {
+                // Miners pick their extranonce1 up on subscribe, so they need a new connection
+                debug!(event = "proxy_extranonce_changed", peer = %self.peer, "Pool extranonce changed, disconnecting {} to resubscribe", self.peer);
+                self.disconnect = true;
+                return messages;
+            }
+            messages.push(json!({
+                "id": null,
+                "method": "mining.set_extranonce",
+                "params": [self.extranonce1(&latest), latest.extranonce2_size - PREFIX_BYTES],
+            }));
+            self.pool_extranonce1 = Some(latest.extranonce1.clone());
+        }
+        messages.push(notify(&latest.job));
+        self.submitted.clear();
+        self.work = Some(latest);
+        messages
+    }
+
+    /// Check a share and pass it on to the pool if it is a block
+    fn submit(&mut self, id: &Value, params: &Value) -> Value {
+        if self.worker.is_none() {
+            return error_reply(id, UNAUTHORIZED, "Unauthorized worker");
+        }
+        let field = |index: usize| params[index].as_str().unwrap_or_default().to_string();
+        let (job_id, extranonce2, ntime, nonce) = (field(1), field(2), field(3), field(4));
+        let Some(work) = self.work.clone().filter(|work| work.job.job_id == job_id) else {
+            return error_reply(id, JOB_NOT_FOUND, "Job not found");
+        };
+        let is_hex = |value: &str, bytes: usize| value.len() == bytes * 2 && value.bytes().all(|b| b.is_ascii_hexdigit());
+        if !is_hex(&extranonce2, work.extranonce2_size - PREFIX_BYTES) {
+            return error_reply(id, OTHER_ERROR, "Invalid extranonce2 size");
+        }
+        if !is_hex(&ntime, 4) || !is_hex(&nonce, 4) {
+            return error_reply(id, OTHER_ERROR, "Invalid ntime or nonce");
+        }
+        if !self.submitted.insert((extranonce2.clone(), ntime.clone(), nonce.clone())) {
+            return error_reply(id, DUPLICATE_SHARE, "Duplicate share");
+        }
+
+        let job = &work.job;
+        let extranonce2 = format!("{}{}", self.prefix, extranonce2);
+        let built = compute_merkle_root_hex(job, &work.extranonce1, &extranonce2).and_then(|merkle_root| {
+            let header = create_block_header(&job.version, &job.prevhash, &merkle_root, &job.nbits, &ntime, &nonce)?;
+            Ok((merkle_root, header))
+        });
+        let (merkle_root, header) = match built {
+            Ok(built) => built,
+            Err(e) => return error_reply(id, OTHER_ERROR, &e.to_string()),
+        };
+        let hash = double_sha256(&header);
+        let difficulty = hash_difficulty(&hash);
+        if difficulty < self.difficulty as f64 {
+            return error_reply(id, LOW_DIFFICULTY, "Low difficulty share");
+        }
+        self.accepted += 1;
+        debug!(event = "proxy_share", peer = %self.peer, difficulty, "Share from {} at difficulty {:.0}", self.peer, difficulty);
+
+        if hash_meets_target(&hash, &work.target) {
+            warn!(event = "proxy_block_found", peer = %self.peer, worker = self.worker.as_deref().unwrap_or(""), "Stratum miner {} found a block", self.peer);
+            let found = FoundBlock { nonce, extranonce2, ntime, merkle_root, header, hash };
+            // The job ended between the notify and this share
+            if work.found.send(Ok(found)).is_err() {
+                return error_reply(id, JOB_NOT_FOUND, "Stale share");
+            }
+        }
+        json!({"id": id, "result": true, "error": null})
+    }
+}
@@ -0,0 +1,138 @@
+//! Daily quiet windows
+//!
+//! During a window, such as the night hours when a fan would be heard, hashing pauses
+//! but the pool session stays up, with the keepalives any pause sends, so the worker
+//! stays registered with the pool. Hashing resumes by itself when the window ends.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{Result, Context, bail};
+use chrono::{DateTime, NaiveTime, Timelike};
+use tracing::{info, Instrument, info_span};
+
+use crate::control::Control;
+use crate::miner::MiningConfig;
+use crate::tasks::{Stage, Tasks};
+use crate::timestamp::TimeZone;
+
+/// How often the time of day is compared against the windows
+pub const QUIET_CHECK_SECS: u64 = 30;
+
+/// A daily stretch of time, e.g. `22:00-07:00`; it wraps past midnight when it ends at or
+/// before its start
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietWindow {
+    /// Minutes after midnight
+    pub start: u32,
+    pub end: u32,
+}
+
+impl QuietWindow {
+    fn parse_time(value: &str) -> Result<u32> {
+        let time = NaiveTime::parse_from_str(value.trim(), "%H:%M")
+            .with_context(|| format!("Invalid time '{}' (expected HH:MM)", value.trim()))?;
+        Ok(time.hour() * 60 + time.minute())
+    }
+
+    pub fn parse(value: &str) -> Result<Self> {
+        let Some((start, end)) = value.split_once('-') else {
+            bail!("Invalid quiet window '{}' (expected HH:MM-HH:MM)", value.trim());
+        };
+        let window = Self { start: Self::parse_time(start)?, end: Self::parse_time(end)? };
+        if window.start == window.end {
+            bail!("Quiet window '{}' is empty", value.trim());
+        }
+        Ok(window)
+    }
+
+    /// Whether the window covers `minute` minutes after midnight
+    pub fn contains(&self, minute: u32) -> bool {
+        if self.start < self.end {
+            (self.start..self.end).contains(&minute)
+        } else {
+            minute >= self.start || minute < self.end
+        }
+    }
+}
+
+impl fmt::Display for QuietWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}:{:02}-{:02}:{:02}", self.start / 60, self.start % 60, self.end / 60, self.end % 60)
+    }
+}
+
+/// A comma-separated list of windows, e.g. `22:00-07:00, 12:00-13:00`
+pub fn parse_windows(value: &str) -> Result<Vec<QuietWindow>> {
+    value.split(',').filter(|window| !window.trim().is_empty()).map(QuietWindow::parse).collect()
+}
+
+/// Quiet windows in a time zone, as configured in `[quiet]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuietSchedule {
+    pub windows: Vec<QuietWindow>,
+    pub zone: TimeZone,
+}
+
+impl QuietSchedule {
+    /// The window covering the unix time `secs`, if any
+    pub fn window_at(&self, secs: u64) -> Option<QuietWindow> {
+        let time = self.zone.time_of_day(DateTime::from_timestamp(secs as i64, 0)?);
+        let minute = time.hour() * 60 + time.minute();
+        self.windows.iter().copied().find(|window| window.contains(minute))
+    }
+
+    /// The windows as they are configured, e.g. `22:00-07:00, 12:00-13:00`
+    pub fn windows_list(&self) -> String {
+        self.windows.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    }
+}
+
+impl fmt::Display for QuietSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.windows_list(), self.zone.name())
+    }
+}
+
+/// Pause hashing through every quiet window in `config`, following changes to the schedule
+pub fn start(config: Arc<Mutex<MiningConfig>>, control: Control, tasks: &mut Tasks) {
+    if let Some(schedule) = config.lock().unwrap().quiet.as_ref() {
+        info!(
+            event = "quiet_windows",
+            windows = %schedule.windows_list(),
+            timezone = %schedule.zone.name(),
+            "Hashing pauses daily during {}",
+            schedule
+        );
+    }
+    tasks.spawn(Stage::Api, "quiet_windows", watch(config, control).instrument(info_span!("quiet_windows")));
+}
+
+async fn watch(config: Arc<Mutex<MiningConfig>>, control: Control) {
+    // The window we paused for, so a pause from someone else is never lifted here
+    let mut quiet_paused: Option<QuietWindow> = None;
+    let mut interval = tokio::time::interval(Duration::from_secs(QUIET_CHECK_SECS));
+    loop {
+        interval.tick().await;
+        // Read every time, as the schedule can be changed or turned off at runtime
+        let window = {
+            let config_guard = config.lock().unwrap();
+            let now = config_guard.clock.unix_secs();
+            config_guard.quiet.as_ref().and_then(|schedule| schedule.window_at(now))
+        };
+        match (window, quiet_paused) {
+            // A window entered while already paused is left to whoever paused
+            (Some(window), None) if control.pause("quiet window") => {
+                quiet_paused = Some(window);
+                info!(event = "quiet_started", window = %window, "Quiet window {} started, hashing paused with the pool session kept up", window);
+            }
+            (None, Some(window)) => {
+                quiet_paused = None;
+                if control.resume("quiet window") {
+                    info!(event = "quiet_ended", window = %window, "Quiet window {} ended, hashing resumed", window);
+                }
+            }
+            _ => {}
+        }
+    }
+}
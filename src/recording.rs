@@ -0,0 +1,188 @@
+//! Recording pool traffic to a file, and replaying a recording as a local pool
+//!
+//! A recording is NDJSON, one entry per line: `{"t": 1.25, "type": "received", "line": "..."}`
+//! where `t` is seconds since recording started.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use anyhow::{Result, Context, bail};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tracing::{debug, info, warn, Instrument};
+
+/// What happened, in the order it happened
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EntryKind {
+    /// A new pool connection
+    Connected { pool: String },
+    /// A line the miner sent the pool
+    Sent { line: String },
+    /// A line the pool sent the miner
+    Received { line: String },
+    /// The pool closed the connection
+    Closed,
+    /// The miner closed the connection
+    Dropped,
+    /// The network block height the miner looked up
+    Height { height: u64 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub t: f64,
+    #[serde(flatten)]
+    pub kind: EntryKind,
+}
+
+struct Recorder {
+    file: File,
+    started: Instant,
+}
+
+static RECORDER: OnceLock<Mutex<Recorder>> = OnceLock::new();
+static REPLAYED_HEIGHT: OnceLock<u64> = OnceLock::new();
+
+/// Record everything passed to `record` to `path` from now on, replacing the file
+pub fn start(path: &Path) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Failed to create recording {}", path.display()))?;
+    if RECORDER.set(Mutex::new(Recorder { file, started: Instant::now() })).is_err() {
+        bail!("Already recording");
+    }
+    info!(event = "recording", path = %path.display(), "Recording pool traffic to {}", path.display());
+    Ok(())
+}
+
+pub fn is_recording() -> bool {
+    RECORDER.get().is_some()
+}
+
+/// Append an entry if recording; a write error stops nothing but is logged
+pub fn record(kind: EntryKind) {
+    let Some(recorder) = RECORDER.get() else {
+        return;
+    };
+    let mut recorder = recorder.lock().unwrap();
+    let entry = Entry { t: recorder.started.elapsed().as_secs_f64(), kind };
+    // Written line by line so a crash leaves everything up to it on disk
+    let written = serde_json::to_string(&entry)
+        .map_err(anyhow::Error::from)
+        .and_then(|line| Ok(writeln!(recorder.file, "{}", line)?));
+    if let Err(e) = written {
+        warn!("Failed to write to the recording: {:#}", e);
+    }
+}
+
+/// The block height a replay reports instead of asking the network
+pub fn replayed_height() -> Option<u64> {
+    REPLAYED_HEIGHT.get().copied()
+}
+
+/// One recorded connection, from the pool's side
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    /// Send the miner this line
+    Send(String),
+    /// Wait for the miner to send a line
+    Expect,
+    /// Close the connection
+    Close,
+}
+
+/// Split a recording into its connections
+fn load(path: &Path) -> Result<(Vec<Vec<Step>>, Option<u64>)> {
+    let file = File::open(path).with_context(|| format!("Failed to open recording {}", path.display()))?;
+    let mut connections: Vec<Vec<Step>> = Vec::new();
+    let mut height = None;
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry: Entry = serde_json::from_str(&line)
+            .with_context(|| format!("{}:{}: not a recording entry", path.display(), number + 1))?;
+        let step = match entry.kind {
+            EntryKind::Connected { .. } => {
+                connections.push(Vec::new());
+                continue;
+            }
+            EntryKind::Height { height: recorded } => {
+                height.get_or_insert(recorded);
+                continue;
+            }
+            EntryKind::Sent { .. } => Step::Expect,
+            EntryKind::Received { line } => Step::Send(line),
+            EntryKind::Closed => Step::Close,
+            // The miner will hang up by itself at the same point
+            EntryKind::Dropped => continue,
+        };
+        let Some(connection) = connections.last_mut() else {
+            bail!("{}:{}: traffic before the first connection", path.display(), number + 1);
+        };
+        connection.push(step);
+    }
+    if connections.is_empty() {
+        bail!("{} has no recorded connections", path.display());
+    }
+    Ok((connections, height))
+}
+
+/// Serve a recording as a pool on a local port, returning its address and a channel that
+/// fires once every recorded connection has been replayed
+///
+/// The n-th connection to it gets the n-th recorded connection: whatever the pool sent is
+/// sent again, in order, each time after the miner has sent as many lines as it had by
+/// then. The block height lookups answer the first recorded height.
+pub async fn replay(path: &Path) -> Result<(SocketAddr, oneshot::Receiver<()>)> {
+    let (connections, height) = load(path)?;
+    let _ = REPLAYED_HEIGHT.set(height.unwrap_or(0));
+    let listener = TcpListener::bind("127.0.0.1:0").await.context("Failed to start the replay pool")?;
+    let addr = listener.local_addr()?;
+    info!(event = "replaying", path = %path.display(), connections = connections.len(), "Replaying {} connection(s) from {}", connections.len(), path.display());
+
+    let (done_tx, done_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        for (number, steps) in connections.into_iter().enumerate() {
+            let Ok((stream, _)) = listener.accept().await else {
+                return;
+            };
+            debug!(event = "replay_connection", connection = number + 1, "Replaying connection {}", number + 1);
+            play(stream, steps).await;
+        }
+        // The next connection attempt is the end of the recording
+        let _ = listener.accept().await;
+        let _ = done_tx.send(());
+    }.in_current_span());
+    Ok((addr, done_rx))
+}
+
+async fn play(stream: tokio::net::TcpStream, steps: Vec<Step>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+    for step in steps {
+        match step {
+            Step::Send(line) => {
+                if writer.write_all(format!("{}\n", line).as_bytes()).await.is_err() {
+                    return;
+                }
+            }
+            Step::Expect => {
+                if !matches!(lines.next_line().await, Ok(Some(_))) {
+                    return;
+                }
+            }
+            Step::Close => return,
+        }
+    }
+    // Recorded up to the miner hanging up: wait for it to do so again, or hang up on it if
+    // it goes on past the recording
+    if let Ok(Some(line)) = lines.next_line().await {
+        warn!(event = "replay_diverged", line = %line, "The miner sent more than the recording has, closing the connection: {}", line);
+    }
+}
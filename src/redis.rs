@@ -0,0 +1,218 @@
+//! A minimal Redis client: RESP2 over TCP, with just the commands the cluster
+//! coordinator needs to keep its bookkeeping in Redis
+//!
+//! The client needs the `redis` feature; without it only URLs are parsed, so a configured
+//! `[cluster] redis` is still checked and then refused when the coordinator starts.
+use std::fmt;
+use anyhow::{bail, Context, Result};
+
+#[cfg(feature = "redis")]
+use std::future::Future;
+#[cfg(feature = "redis")]
+use std::pin::Pin;
+#[cfg(feature = "redis")]
+use anyhow::anyhow;
+#[cfg(feature = "redis")]
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+#[cfg(feature = "redis")]
+use tokio::net::TcpStream;
+
+const DEFAULT_PORT: u16 = 6379;
+/// Longest reply line read, a status, error or length header; the server's own limit on
+/// inline requests
+#[cfg(feature = "redis")]
+const MAX_LINE_BYTES: u64 = 64 * 1024;
+/// Largest bulk string accepted, Redis's default `proto-max-bulk-len`
+#[cfg(feature = "redis")]
+const MAX_BULK_BYTES: usize = 512 * 1024 * 1024;
+/// Array elements room is made for up front; a longer array grows as its elements
+/// arrive, so a bogus length can't allocate more than the server actually sends
+#[cfg(feature = "redis")]
+const PREALLOCATED_ITEMS: usize = 1024;
+
+/// Where a Redis server is: `redis://[[user]:password@]host[:port][/db]`, or just `host[:port]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedisUrl {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub db: u32,
+}
+
+impl RedisUrl {
+    pub fn parse(url: &str) -> Result<Self> {
+        let rest = url.strip_prefix("redis://").unwrap_or(url);
+        let (credentials, rest) = match rest.rsplit_once('@') {
+            Some((credentials, rest)) => (Some(credentials), rest),
+            None => (None, rest),
+        };
+        let (address, db) = match rest.split_once('/') {
+            Some((address, "")) => (address, 0),
+            Some((address, db)) => (address, db.parse().with_context(|| format!("Invalid Redis database '{}'", db))?),
+            None => (rest, 0),
+        };
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().with_context(|| format!("Invalid Redis port '{}'", port))?),
+            None => (address, DEFAULT_PORT),
+        };
+        if host.is_empty() {
+            bail!("Redis URL '{}' has no host", url);
+        }
+        let (username, password) = match credentials {
+            Some(credentials) => match credentials.split_once(':') {
+                Some((user, password)) => (Some(user.to_string()).filter(|user| !user.is_empty()), Some(password.to_string())),
+                None => (None, Some(credentials.to_string())),
+            },
+            None => (None, None),
+        };
+        Ok(Self { host: host.to_string(), port, username, password, db })
+    }
+}
+
+/// The URL without its credentials, for logs
+impl fmt::Display for RedisUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "redis://{}:{}/{}", self.host, self.port, self.db)
+    }
+}
+
+/// A reply other than an error, which `Redis::command` returns as `Err`
+#[cfg(feature = "redis")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reply {
+    Status(String),
+    Integer(i64),
+    /// `None` for a missing value
+    Bulk(Option<Vec<u8>>),
+    Array(Option<Vec<Reply>>),
+}
+
+#[cfg(feature = "redis")]
+impl Reply {
+    fn into_string(self) -> Result<Option<String>> {
+        match self {
+            Reply::Bulk(None) => Ok(None),
+            Reply::Bulk(Some(bytes)) => Ok(Some(String::from_utf8(bytes).context("Redis value isn't UTF-8")?)),
+            Reply::Status(status) => Ok(Some(status)),
+            other => bail!("Expected a string from Redis, got {:?}", other),
+        }
+    }
+}
+
+/// One connection to a Redis server
+#[cfg(feature = "redis")]
+pub struct Redis {
+    stream: BufReader<TcpStream>,
+}
+
+#[cfg(feature = "redis")]
+impl Redis {
+    /// Connect, log in if the URL has a password and select its database
+    pub async fn connect(url: &RedisUrl) -> Result<Self> {
+        let stream = TcpStream::connect((url.host.as_str(), url.port)).await
+            .with_context(|| format!("Failed to connect to Redis at {}", url))?;
+        let mut redis = Self { stream: BufReader::new(stream) };
+        if let Some(password) = &url.password {
+            match &url.username {
+                Some(username) => redis.command(&["AUTH", username, password]).await,
+                None => redis.command(&["AUTH", password]).await,
+            }
+            .context("Redis refused the password")?;
+        }
+        if url.db != 0 {
+            redis.command(&["SELECT", &url.db.to_string()]).await?;
+        }
+        Ok(redis)
+    }
+
+    /// Send a command and read its reply
+    pub async fn command(&mut self, args: &[&str]) -> Result<Reply> {
+        let mut request = format!("*{}\r\n", args.len());
+        for arg in args {
+            request.push_str(&format!("${}\r\n{}\r\n", arg.len(), arg));
+        }
+        self.stream.get_mut().write_all(request.as_bytes()).await?;
+        self.read_reply().await
+    }
+
+    pub async fn get(&mut self, key: &str) -> Result<Option<String>> {
+        self.command(&["GET", key]).await?.into_string()
+    }
+
+    pub async fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        self.command(&["SET", key, value]).await.map(drop)
+    }
+
+    pub async fn del(&mut self, key: &str) -> Result<()> {
+        self.command(&["DEL", key]).await.map(drop)
+    }
+
+    pub async fn hset(&mut self, key: &str, field: &str, value: &str) -> Result<()> {
+        self.command(&["HSET", key, field, value]).await.map(drop)
+    }
+
+    pub async fn hdel(&mut self, key: &str, field: &str) -> Result<()> {
+        self.command(&["HDEL", key, field]).await.map(drop)
+    }
+
+    /// Every field of a hash with its value
+    pub async fn hgetall(&mut self, key: &str) -> Result<Vec<(String, String)>> {
+        let Reply::Array(items) = self.command(&["HGETALL", key]).await? else {
+            bail!("Expected an array from HGETALL");
+        };
+        let mut items = items.unwrap_or_default().into_iter();
+        let mut fields = Vec::new();
+        while let (Some(field), Some(value)) = (items.next(), items.next()) {
+            fields.push((field.into_string()?.unwrap_or_default(), value.into_string()?.unwrap_or_default()));
+        }
+        Ok(fields)
+    }
+
+    fn read_reply(&mut self) -> Pin<Box<dyn Future<Output = Result<Reply>> + Send + '_>> {
+        Box::pin(async move {
+            let mut line = String::new();
+            let read = (&mut self.stream).take(MAX_LINE_BYTES + 1).read_line(&mut line).await?;
+            if read == 0 {
+                bail!("Redis closed the connection");
+            }
+            if read as u64 > MAX_LINE_BYTES {
+                bail!("Reply line from Redis longer than {} bytes", MAX_LINE_BYTES);
+            }
+            let line = line.trim_end_matches("\r\n");
+            let (kind, rest) = line.split_at_checked(1).ok_or_else(|| anyhow!("Empty reply from Redis"))?;
+            match kind {
+                "+" => Ok(Reply::Status(rest.to_string())),
+                "-" => Err(anyhow!("Redis error: {}", rest)),
+                ":" => Ok(Reply::Integer(rest.parse().context("Invalid integer from Redis")?)),
+                "$" => {
+                    let Ok(len) = usize::try_from(rest.parse::<i64>().context("Invalid length from Redis")?) else {
+                        return Ok(Reply::Bulk(None));
+                    };
+                    if len > MAX_BULK_BYTES {
+                        bail!("Bulk string of {} bytes from Redis is over the {} byte limit", len, MAX_BULK_BYTES);
+                    }
+                    // Read into a growing buffer rather than one of the announced size
+                    let mut value = Vec::new();
+                    (&mut self.stream).take(len as u64 + 2).read_to_end(&mut value).await?;
+                    if value.len() != len + 2 {
+                        bail!("Redis closed the connection");
+                    }
+                    value.truncate(len);
+                    Ok(Reply::Bulk(Some(value)))
+                }
+                "*" => {
+                    let Ok(len) = usize::try_from(rest.parse::<i64>().context("Invalid length from Redis")?) else {
+                        return Ok(Reply::Array(None));
+                    };
+                    let mut items = Vec::with_capacity(len.min(PREALLOCATED_ITEMS));
+                    for _ in 0..len {
+                        items.push(self.read_reply().await?);
+                    }
+                    Ok(Reply::Array(Some(items)))
+                }
+                _ => bail!("Unexpected reply from Redis: {}", line),
+            }
+        })
+    }
+}
@@ -0,0 +1,294 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use anyhow::Result;
+use tracing::{info, warn};
+
+use crate::btc_price::PriceSettings;
+use crate::logging::{LogLevel, Verbosity};
+use crate::config::{ConfigOverrides, LoadedConfig, load_config};
+use crate::miner::MiningConfig;
+use crate::presets;
+use crate::quiet::QuietSchedule;
+
+const CONFIG_WATCH_INTERVAL_SECS: u64 = 2;
+
+/// Re-reads the configuration on request and applies whatever can change without reconnecting
+pub struct ConfigReloader {
+    overrides: ConfigOverrides,
+    config: Arc<Mutex<MiningConfig>>,
+    log_level: LogLevel,
+    /// Settings currently in use
+    active: Mutex<LoadedConfig>,
+}
+
+impl ConfigReloader {
+    pub fn new(overrides: ConfigOverrides, config: Arc<Mutex<MiningConfig>>, log_level: LogLevel, active: LoadedConfig) -> Self {
+        Self { overrides, config, log_level, active: Mutex::new(active) }
+    }
+
+    /// Change the thread count at runtime, as if the config file had changed
+    pub fn set_threads(&self, threads: usize) {
+        let threads = threads.max(1);
+        self.config.lock().unwrap().threads = threads;
+        self.active.lock().unwrap().threads = threads;
+    }
+
+    /// Change the verbosity of the logs and notifications at runtime
+    pub fn set_verbosity(&self, verbosity: Verbosity) -> Result<()> {
+        self.log_level.set_verbosity(verbosity)?;
+        self.config.lock().unwrap().verbosity = verbosity;
+        self.active.lock().unwrap().verbosity = verbosity;
+        Ok(())
+    }
+
+    /// Move to another primary pool at runtime, as if the config file had changed
+    pub fn set_pool(&self, pool: &str) {
+        switch_pool(&self.config, pool);
+        self.active.lock().unwrap().pool = pool.to_string();
+    }
+
+    /// Replace or turn off the quiet windows at runtime, as if the config file had changed
+    pub fn set_quiet(&self, quiet: Option<QuietSchedule>) {
+        self.config.lock().unwrap().quiet = quiet.clone();
+        self.active.lock().unwrap().quiet = quiet;
+    }
+
+    /// The quiet windows currently in use
+    pub fn quiet(&self) -> Option<QuietSchedule> {
+        self.active.lock().unwrap().quiet.clone()
+    }
+
+    pub fn reload(&self) {
+        let mut active = self.active.lock().unwrap();
+        reload_config(&self.overrides, &self.config, &self.log_level, &mut active);
+    }
+
+    /// Reload the configuration every time the process receives SIGHUP
+    #[cfg(unix)]
+    pub async fn reload_on_sighup(&self) -> anyhow::Result<()> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut hangups = signal(SignalKind::hangup())?;
+        while hangups.recv().await.is_some() {
+            info!(event = "sighup", "SIGHUP received, reloading configuration");
+            self.reload();
+        }
+        Ok(())
+    }
+
+    /// Reload the configuration whenever the config file is modified
+    ///
+    /// Polls the modification time, which works the same on every platform and on
+    /// bind-mounted files in Docker where inotify events don't arrive.
+    pub async fn watch_file(&self, path: PathBuf) {
+        let modified = |path: &PathBuf| -> Option<SystemTime> {
+            std::fs::metadata(path).and_then(|m| m.modified()).ok()
+        };
+        let mut last_modified = modified(&path);
+        let mut interval = tokio::time::interval(Duration::from_secs(CONFIG_WATCH_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            let current = modified(&path);
+            // A file that is missing mid-save is picked up once it reappears
+            if current.is_some() && current != last_modified {
+                last_modified = current;
+                info!(event = "config_changed", path = %path.display(), "{} changed, reloading configuration", path.display());
+                self.reload();
+            }
+        }
+    }
+}
+
+/// Make `pool` the primary pool and restart the session on it
+///
+/// Presets are taken by their address; `ckpool` isn't probed again and gets the first
+/// ckpool server.
+fn switch_pool(config: &Mutex<MiningConfig>, pool: &str) {
+    let mut config_guard = config.lock().unwrap();
+    config_guard.requested_pool = Some(presets::address(pool));
+    config_guard.restart_requested = true;
+}
+
+/// Re-read the configuration and apply whatever can change without a restart
+///
+/// Notification settings, thread count, verbosity and quiet windows take effect
+/// immediately; a new pool restarts the session on it. Settings that need a restart are
+/// reported and keep their current value. `active` tracks the settings currently in use.
+fn reload_config(
+    overrides: &ConfigOverrides,
+    config: &Arc<Mutex<MiningConfig>>,
+    log_level: &LogLevel,
+    active: &mut LoadedConfig,
+) {
+    let new = match load_config(overrides).map_err(anyhow::Error::from) {
+        Ok(new) => new,
+        Err(e) => {
+            warn!(event = "config_reload_failed", "Config reload failed, keeping current settings: {:#}", e);
+            return;
+        }
+    };
+
+    let mut applied = Vec::new();
+    if new.telegram != active.telegram {
+        config.lock().unwrap().telegram = new.telegram.clone();
+        active.telegram = new.telegram.clone();
+        applied.push(format!(
+            "telegram ({})",
+            if new.telegram.is_some() { "enabled" } else { "disabled" }
+        ));
+    }
+    if new.hooks != active.hooks {
+        config.lock().unwrap().hooks = new.hooks.clone();
+        active.hooks = new.hooks.clone();
+        applied.push("hooks".to_string());
+    }
+    if new.payout_check != active.payout_check {
+        config.lock().unwrap().payout_check = new.payout_check;
+        applied.push(format!("payout_check {} -> {}", active.payout_check.name(), new.payout_check.name()));
+        active.payout_check = new.payout_check;
+    }
+    if new.threads != active.threads {
+        config.lock().unwrap().threads = new.threads;
+        applied.push(format!("threads {} -> {}", active.threads, new.threads));
+        active.threads = new.threads;
+    }
+    if new.low_bandwidth != active.low_bandwidth {
+        config.lock().unwrap().low_bandwidth = new.low_bandwidth;
+        applied.push(format!("low_bandwidth {} -> {}", active.low_bandwidth, new.low_bandwidth));
+        active.low_bandwidth = new.low_bandwidth;
+    }
+    if new.submit_policy != active.submit_policy {
+        config.lock().unwrap().submit_policy = new.submit_policy;
+        applied.push(format!(
+            "submit {}s x{} -> {}s x{}",
+            active.submit_policy.timeout.as_secs(), active.submit_policy.retries + 1,
+            new.submit_policy.timeout.as_secs(), new.submit_policy.retries + 1
+        ));
+        active.submit_policy = new.submit_policy;
+    }
+    if new.difficulty_change_factor != active.difficulty_change_factor {
+        config.lock().unwrap().difficulty_change_factor = new.difficulty_change_factor;
+        applied.push(format!("difficulty_change_factor {} -> {}", active.difficulty_change_factor, new.difficulty_change_factor));
+        active.difficulty_change_factor = new.difficulty_change_factor;
+    }
+    if new.stale_tip_blocks != active.stale_tip_blocks {
+        config.lock().unwrap().stale_tip_blocks = new.stale_tip_blocks;
+        applied.push(format!("stale_tip_blocks {} -> {}", active.stale_tip_blocks, new.stale_tip_blocks));
+        active.stale_tip_blocks = new.stale_tip_blocks;
+    }
+    if new.btc_price != active.btc_price {
+        config.lock().unwrap().btc_price = new.btc_price.clone();
+        let describe = |price: &Option<PriceSettings>| {
+            price.as_ref().map_or("off".to_string(), |price| format!("{} {}", price.source.name(), price.currency))
+        };
+        applied.push(format!("btc_price {} -> {}", describe(&active.btc_price), describe(&new.btc_price)));
+        active.btc_price = new.btc_price.clone();
+    }
+    if new.quiet != active.quiet {
+        config.lock().unwrap().quiet = new.quiet.clone();
+        let describe = |quiet: &Option<QuietSchedule>| quiet.as_ref().map_or("off".to_string(), ToString::to_string);
+        applied.push(format!("quiet {} -> {}", describe(&active.quiet), describe(&new.quiet)));
+        active.quiet = new.quiet.clone();
+    }
+    if new.pool != active.pool {
+        switch_pool(config, &new.pool);
+        applied.push(format!("pool {} -> {}", active.pool, new.pool));
+        active.pool = new.pool.clone();
+    }
+    let verbosity_changed = new.verbosity != active.verbosity;
+    if verbosity_changed {
+        config.lock().unwrap().verbosity = new.verbosity;
+        applied.push(format!("verbosity {} -> {}", active.verbosity.name(), new.verbosity.name()));
+    }
+
+    let mut restart_required = Vec::new();
+    // An address given at the interactive prompt isn't in the config, so an empty one isn't a change
+    if !new.address.is_empty() && new.address != active.address {
+        restart_required.push("address");
+    }
+    if new.payout_addresses != active.payout_addresses || new.address_rotation != active.address_rotation {
+        restart_required.push("payout_addresses");
+    }
+    if new.log_format != active.log_format {
+        restart_required.push("log_format");
+    }
+    if new.log_dir != active.log_dir {
+        restart_required.push("log_dir");
+    }
+    if new.state_file != active.state_file {
+        restart_required.push("state_file");
+    }
+    if new.blocks_dir != active.blocks_dir {
+        restart_required.push("blocks_dir");
+    }
+    if new.main_log != active.main_log {
+        restart_required.push("main_log");
+    }
+    if new.log_rotation != active.log_rotation {
+        restart_required.push("log rotation");
+    }
+    if new.system_log != active.system_log {
+        restart_required.push("system_log");
+    }
+    if new.timestamps != active.timestamps {
+        restart_required.push("timestamps");
+    }
+    if new.otlp_endpoint != active.otlp_endpoint {
+        restart_required.push("otlp_endpoint");
+    }
+    if new.sentry_dsn != active.sentry_dsn {
+        restart_required.push("sentry_dsn");
+    }
+    if new.script != active.script || new.script_tick != active.script_tick {
+        restart_required.push("script");
+    }
+    if new.grpc_listen != active.grpc_listen || new.control_socket != active.control_socket || new.api_token != active.api_token {
+        restart_required.push("api");
+    }
+    if new.proxy_listen != active.proxy_listen || new.proxy_difficulty != active.proxy_difficulty {
+        restart_required.push("proxy");
+    }
+    if new.cluster_listen != active.cluster_listen || new.cluster_token != active.cluster_token || new.cluster_redis != active.cluster_redis || new.cluster_mdns != active.cluster_mdns {
+        restart_required.push("cluster");
+    }
+    if new.electricity != active.electricity {
+        restart_required.push("electricity");
+    }
+    if new.solar != active.solar {
+        restart_required.push("solar");
+    }
+    if new.activity != active.activity {
+        restart_required.push("activity");
+    }
+    if new.power != active.power {
+        restart_required.push("power");
+    }
+    if new.failover != active.failover {
+        restart_required.push("failover");
+    }
+    if new.watch_config != active.watch_config {
+        restart_required.push("watch_config");
+    }
+
+    info!(
+        event = "config_reloaded",
+        applied = ?applied,
+        restart_required = ?restart_required,
+        "Configuration reloaded: applied [{}]{}",
+        applied.join(", "),
+        if restart_required.is_empty() {
+            String::new()
+        } else {
+            format!(", restart required for [{}]", restart_required.join(", "))
+        }
+    );
+
+    // Changed last, so the summary above is still visible when lowering the verbosity
+    if verbosity_changed {
+        match log_level.set_verbosity(new.verbosity) {
+            Ok(()) => active.verbosity = new.verbosity,
+            Err(e) => warn!("Failed to change log level: {}", e),
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use anyhow::{Result, Context};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::{debug, error, info, warn, Instrument};
+
+use crate::control::Control;
+use crate::logging::Verbosity;
+use crate::tasks::{Stage, Tasks};
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+/// Server-defined: the token is missing or wrong
+const UNAUTHORIZED: i64 = -32001;
+
+/// Where the control socket listens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ControlSocket {
+    /// A Unix domain socket, only reachable by users allowed to open the file
+    Unix(PathBuf),
+    /// A TCP port, which needs the API token if one is set
+    Tcp(SocketAddr),
+}
+
+impl ControlSocket {
+    /// `host:port` is TCP, anything else a socket path
+    pub fn parse(value: &str) -> Self {
+        match value.parse::<SocketAddr>() {
+            Ok(addr) => ControlSocket::Tcp(addr),
+            Err(_) => ControlSocket::Unix(PathBuf::from(value)),
+        }
+    }
+}
+
+impl fmt::Display for ControlSocket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlSocket::Unix(path) => write!(f, "{}", path.display()),
+            ControlSocket::Tcp(addr) => write!(f, "{}", addr),
+        }
+    }
+}
+
+/// A failed call, answered as a JSON-RPC error object
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into() }
+    }
+}
+
+/// Serve newline-delimited JSON-RPC 2.0 on `socket` until the API stage stops
+///
+/// Methods: `status`, `pause`, `resume`, `restart`, `set_log_level` (`{"level": "verbose"}`),
+/// `set_threads` (`{"threads": 4}`) and `reload`. Over TCP with a `token`, every request
+/// must carry it as `params.token`; a Unix socket is created owner-only instead.
+/// Fails if the socket can't be created.
+pub async fn start(socket: ControlSocket, token: Option<String>, control: Control, tasks: &mut Tasks) -> Result<()> {
+    match socket {
+        ControlSocket::Tcp(addr) => {
+            let listener = tokio::net::TcpListener::bind(addr).await
+                .with_context(|| format!("Failed to listen for control connections on {}", addr))?;
+            if token.is_none() && !addr.ip().is_loopback() {
+                warn!(event = "api_unauthenticated", "Control socket on {} accepts calls from the network without a token", addr);
+            }
+            info!(event = "control_listening", socket = %addr, "Control socket listening on {}", addr);
+            tasks.spawn_with(Stage::Api, "control_socket", |cancel| async move {
+                loop {
+                    let accepted = tokio::select! {
+                        accepted = listener.accept() => accepted,
+                        _ = cancel.cancelled() => return,
+                    };
+                    match accepted {
+                        Ok((stream, peer)) => {
+                            debug!(peer = %peer, "Control connection");
+                            let connection = serve(stream, control.clone(), token.clone());
+                            tokio::spawn(cancel.child_token().run_until_cancelled_owned(connection).in_current_span());
+                        }
+                        Err(e) => {
+                            error!("Control socket stopped: {}", e);
+                            return;
+                        }
+                    }
+                }
+            });
+        }
+        ControlSocket::Unix(path) => start_unix(path, control, tasks).await?,
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+async fn start_unix(path: PathBuf, control: Control, tasks: &mut Tasks) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if path.exists() {
+        // A socket nobody answers on is left over from a miner that didn't exit cleanly
+        if tokio::net::UnixStream::connect(&path).await.is_ok() {
+            anyhow::bail!("Control socket {} is in use by another miner", path.display());
+        }
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Failed to remove stale control socket {}", path.display()))?;
+    }
+    let listener = tokio::net::UnixListener::bind(&path)
+        .with_context(|| format!("Failed to create control socket {}", path.display()))?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to restrict control socket {}", path.display()))?;
+    info!(event = "control_listening", socket = %path.display(), "Control socket listening on {}", path.display());
+    tasks.spawn_with(Stage::Api, "control_socket", |cancel| async move {
+        loop {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = cancel.cancelled() => break,
+            };
+            match accepted {
+                Ok((stream, _)) => {
+                    let connection = serve(stream, control.clone(), None);
+                    tokio::spawn(cancel.child_token().run_until_cancelled_owned(connection).in_current_span());
+                }
+                Err(e) => {
+                    error!("Control socket stopped: {}", e);
+                    break;
+                }
+            }
+        }
+        // Leave no stale socket behind for the next miner to clean up
+        let _ = std::fs::remove_file(&path);
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn start_unix(path: PathBuf, _control: Control, _tasks: &mut Tasks) -> Result<()> {
+    anyhow::bail!("Unix control sockets aren't supported on this platform; use host:port instead of {}", path.display())
+}
+
+/// Answer requests on one connection, one JSON object per line each way
+async fn serve<S: AsyncRead + AsyncWrite + Unpin>(stream: S, control: Control, token: Option<String>) {
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = handle(&line, &control, token.as_deref());
+        if writer.write_all(format!("{}\n", response).as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+fn handle(line: &str, control: &Control, token: Option<&str>) -> Value {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => return error_response(Value::Null, RpcError::new(PARSE_ERROR, e.to_string())),
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        return error_response(id, RpcError::new(INVALID_REQUEST, "missing method"));
+    };
+    let params = request.get("params").cloned().unwrap_or_else(|| json!({}));
+    if let Some(expected) = token {
+        if params.get("token").and_then(Value::as_str) != Some(expected) {
+            return error_response(id, RpcError::new(UNAUTHORIZED, "missing or wrong API token"));
+        }
+    }
+    match call(method, &params, control) {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err(e) => error_response(id, e),
+    }
+}
+
+fn call(method: &str, params: &Value, control: &Control) -> Result<Value, RpcError> {
+    match method {
+        "status" => {}
+        "pause" | "resume" => {
+            let changed = if method == "pause" { control.pause("control_socket") } else { control.resume("control_socket") };
+            return Ok(json!({"changed": changed, "status": control.status()}));
+        }
+        "set_log_level" => {
+            let level = params.get("level").and_then(Value::as_str)
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "expected {\"level\": \"...\"}"))?;
+            let verbosity = Verbosity::parse(level).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))?;
+            control.set_verbosity(verbosity).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+            info!(event = "config_updated", source = "control_socket", verbosity = verbosity.name(), "Log level set to {} over the control socket", verbosity.name());
+        }
+        "set_threads" => {
+            let threads = params.get("threads").and_then(Value::as_u64).filter(|&threads| threads > 0)
+                .ok_or_else(|| RpcError::new(INVALID_PARAMS, "expected {\"threads\": n} with n at least 1"))?;
+            control.set_threads(threads as usize);
+            info!(event = "config_updated", source = "control_socket", threads, "Threads set to {} over the control socket", threads);
+        }
+        "restart" => control.restart("control_socket"),
+        "reload" => control.reload(),
+        other => return Err(RpcError::new(METHOD_NOT_FOUND, format!("unknown method '{}'", other))),
+    }
+    Ok(json!(control.status()))
+}
+
+fn error_response(id: Value, error: RpcError) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": error.code, "message": error.message}})
+}
@@ -0,0 +1,198 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::Result;
+
+use crate::miner::MiningConfig;
+use crate::tasks::Tasks;
+
+#[cfg(feature = "scripting")]
+use std::sync::mpsc;
+#[cfg(feature = "scripting")]
+use anyhow::{Context, anyhow};
+#[cfg(feature = "scripting")]
+use rhai::{CallFnOptions, Dynamic, Engine, EvalAltResult, Map, Scope, AST};
+#[cfg(feature = "scripting")]
+use serde_json::{json, Value};
+#[cfg(feature = "scripting")]
+use tracing::{debug, info, warn};
+
+#[cfg(feature = "scripting")]
+use crate::events;
+#[cfg(feature = "scripting")]
+use crate::notify::send_telegram_message;
+#[cfg(feature = "scripting")]
+use crate::tasks::Stage;
+
+/// Upper bound on the work one callback may do, so a runaway loop can't hang the script thread
+#[cfg(feature = "scripting")]
+const MAX_OPERATIONS: u64 = 1_000_000;
+#[cfg(feature = "scripting")]
+const MAX_STRING_SIZE: usize = 1024 * 1024;
+#[cfg(feature = "scripting")]
+const MAX_COLLECTION_SIZE: usize = 10_000;
+#[cfg(feature = "scripting")]
+const HTTP_TIMEOUT_SECS: u64 = 10;
+
+/// What the script thread is asked to do
+#[cfg(feature = "scripting")]
+enum Call {
+    Event(Value),
+    Tick,
+}
+
+/// Load a Rhai script and run its callbacks until the miner exits
+///
+/// The script's top level runs once at startup. After that `on_<event>(event)` is called
+/// for every event on the bus that the script defines a function for (`on_block_found`,
+/// `on_disconnected`, ... with the event as a map) and `on_tick()` every `tick`. Callbacks
+/// share `this`, an object map that keeps its contents between calls.
+/// Scripts can call `stats()`, `notify(message)`, `set_threads(n)`, `log(message)`,
+/// `http_get(url)` and `parse_json(text)`; they have no file or process access.
+///
+/// Fails if the script can't be read or doesn't compile. Errors raised by a callback
+/// are logged and the script keeps running.
+#[cfg(feature = "scripting")]
+pub fn start(path: &Path, tick: Duration, config: Arc<Mutex<MiningConfig>>, tasks: &mut Tasks) -> Result<()> {
+    let source = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read script {}", path.display()))?;
+    let (notify_tx, mut notify_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let (call_tx, call_rx) = mpsc::channel();
+    let (ready_tx, ready_rx) = mpsc::sync_channel(1);
+
+    let script_config = Arc::clone(&config);
+    let span = tracing::Span::current();
+    std::thread::Builder::new()
+        .name("script".to_string())
+        .spawn(move || span.in_scope(|| {
+            let engine = engine(script_config, notify_tx);
+            let mut scope = Scope::new();
+            // Functions can't see the script's variables; `this` carries state between callbacks
+            let mut state = Dynamic::from_map(Map::new());
+            let ast = match engine.compile(&source) {
+                Ok(ast) => ast,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(anyhow!("{}", e)));
+                    return;
+                }
+            };
+            let _ = ready_tx.send(Ok(()));
+            if let Err(e) = engine.run_ast_with_scope(&mut scope, &ast) {
+                warn!(event = "script_error", "Script failed at startup: {}", e);
+            }
+            loop {
+                let call = match call_rx.recv_timeout(tick) {
+                    Ok(call) => call,
+                    Err(mpsc::RecvTimeoutError::Timeout) => Call::Tick,
+                    Err(mpsc::RecvTimeoutError::Disconnected) => return,
+                };
+                let (name, args) = match call {
+                    Call::Event(event) => {
+                        let name = format!("on_{}", event["event"].as_str().unwrap_or_default());
+                        let Ok(event) = rhai::serde::to_dynamic(&event) else {
+                            continue;
+                        };
+                        (name, vec![event])
+                    }
+                    Call::Tick => ("on_tick".to_string(), Vec::new()),
+                };
+                if !defines(&ast, &name, args.len()) {
+                    continue;
+                }
+                let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut state);
+                if let Err(e) = engine.call_fn_with_options::<Dynamic>(options, &mut scope, &ast, &name, args) {
+                    warn!(event = "script_error", callback = %name, "Script error in {}: {}", name, e);
+                }
+            }
+        }))
+        .context("Failed to start the script thread")?;
+    ready_rx.recv()
+        .context("Script thread exited during startup")?
+        .with_context(|| format!("Invalid script {}", path.display()))?;
+    info!(event = "script_loaded", path = %path.display(), "Running script {}", path.display());
+
+    // Feed the script every event on the bus; the script thread exits once this stops
+    let mut script_events = events::subscribe();
+    tasks.spawn_with(Stage::Notifiers, "script_events", |cancel| async move {
+        while let Some(event) = events::next_until(&mut script_events, &cancel).await {
+            if call_tx.send(Call::Event(event.to_json())).is_err() {
+                return;
+            }
+        }
+    });
+
+    // Notifications are sent from here; the script thread has no runtime
+    tasks.spawn(Stage::Notifiers, "script_notifications", async move {
+        while let Some(message) = notify_rx.recv().await {
+            let telegram = config.lock().unwrap().telegram.clone();
+            let Some(telegram) = telegram else {
+                debug!("Script notification dropped, Telegram isn't configured: {}", message);
+                continue;
+            };
+            if let Err(e) = send_telegram_message(&telegram, &message).await {
+                warn!("Failed to send script Telegram notification: {}", e);
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(feature = "scripting"))]
+pub fn start(_path: &Path, _tick: Duration, _config: Arc<Mutex<MiningConfig>>, _tasks: &mut Tasks) -> Result<()> {
+    anyhow::bail!("Scripts require building with `--features scripting`")
+}
+
+/// Whether the script defines `name` taking `params` arguments
+#[cfg(feature = "scripting")]
+fn defines(ast: &AST, name: &str, params: usize) -> bool {
+    ast.iter_functions().any(|f| f.name == name && f.params.len() == params)
+}
+
+/// A sandboxed engine with the miner API registered
+#[cfg(feature = "scripting")]
+fn engine(config: Arc<Mutex<MiningConfig>>, notify: tokio::sync::mpsc::UnboundedSender<String>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.set_max_string_size(MAX_STRING_SIZE);
+    engine.set_max_array_size(MAX_COLLECTION_SIZE);
+    engine.set_max_map_size(MAX_COLLECTION_SIZE);
+    engine.on_print(|text| info!(event = "script_log", "{}", text));
+    engine.on_debug(|text, _, position| debug!(event = "script_debug", %position, "{}", text));
+
+    let stats_config = Arc::clone(&config);
+    engine.register_fn("stats", move || -> Result<Dynamic, Box<EvalAltResult>> {
+        let config_guard = stats_config.lock().unwrap();
+        let mut stats = json!(config_guard.stats.session_summary());
+        stats["hashrate"] = json!(config_guard.hashrate_history.latest().unwrap_or(0));
+        stats["threads"] = json!(config_guard.threads);
+        stats["height"] = json!(config_guard.current_height);
+        stats["lifetime_hashes"] = json!(config_guard.stats.totals().total_hashes);
+        rhai::serde::to_dynamic(&stats)
+    });
+    engine.register_fn("notify", move |message: &str| {
+        let _ = notify.send(message.to_string());
+    });
+    engine.register_fn("log", |message: &str| info!(event = "script_log", "{}", message));
+    engine.register_fn("set_threads", move |threads: i64| {
+        let threads = threads.max(1) as usize;
+        let mut config_guard = config.lock().unwrap();
+        if config_guard.threads != threads {
+            info!(event = "script_set_threads", threads, "Script set hashing threads to {}", threads);
+            config_guard.threads = threads;
+        }
+    });
+    engine.register_fn("http_get", |url: &str| -> Result<String, Box<EvalAltResult>> {
+        let response = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(HTTP_TIMEOUT_SECS))
+            .build()
+            .and_then(|client| client.get(url).send())
+            .and_then(|response| response.error_for_status())
+            .and_then(|response| response.text());
+        response.map_err(|e| format!("http_get {}: {}", url, e).into())
+    });
+    engine.register_fn("parse_json", |text: &str| -> Result<Dynamic, Box<EvalAltResult>> {
+        let value: Value = serde_json::from_str(text).map_err(|e| format!("parse_json: {}", e))?;
+        rhai::serde::to_dynamic(&value)
+    });
+    engine
+}
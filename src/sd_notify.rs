@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+#[cfg(target_os = "linux")]
+use std::os::linux::net::SocketAddrExt;
+#[cfg(unix)]
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+
+/// Tell systemd the miner is up and working (for `Type=notify` units)
+pub fn ready(status: &str) {
+    notify(&format!("READY=1\nSTATUS={}", status));
+}
+
+pub fn status(status: &str) {
+    notify(&format!("STATUS={}", status));
+}
+
+pub fn stopping() {
+    notify("STOPPING=1");
+}
+
+/// Reset the unit's watchdog timer
+pub fn watchdog() {
+    notify("WATCHDOG=1");
+}
+
+/// Watchdog timeout configured with `WatchdogSec=`, if systemd expects pings from this process
+pub fn watchdog_timeout() -> Option<Duration> {
+    let usec = std::env::var("WATCHDOG_USEC").ok()?.parse::<u64>().ok()?;
+    // WATCHDOG_PID, when set, names the process that has to ping
+    if let Ok(pid) = std::env::var("WATCHDOG_PID") {
+        if pid.parse::<u32>().ok()? != std::process::id() {
+            return None;
+        }
+    }
+    Some(Duration::from_micros(usec)).filter(|timeout| !timeout.is_zero())
+}
+
+/// Send a state update to `$NOTIFY_SOCKET`; a no-op when not run by systemd
+#[cfg(unix)]
+fn notify(state: &str) {
+    let Ok(path) = std::env::var("NOTIFY_SOCKET") else {
+        return;
+    };
+    let address = match path.strip_prefix('@') {
+        #[cfg(target_os = "linux")]
+        Some(name) => SocketAddr::from_abstract_name(name.as_bytes()),
+        #[cfg(not(target_os = "linux"))]
+        Some(_) => return,
+        None => SocketAddr::from_pathname(&path),
+    };
+    // Notifications are best effort; systemd enforces its own timeouts if they're lost
+    if let (Ok(socket), Ok(address)) = (UnixDatagram::unbound(), address) {
+        let _ = socket.send_to_addr(state.as_bytes(), &address);
+    }
+}
+
+#[cfg(not(unix))]
+fn notify(_state: &str) {}
@@ -0,0 +1,90 @@
+use std::path::Path;
+use std::process::Command;
+use anyhow::{Result, Context, bail};
+
+/// Keyring service name the secrets are stored under
+const KEYRING_SERVICE: &str = "bitcoin-solo-miner";
+
+/// Compare a secret with what a peer sent, in time that depends only on the lengths
+///
+/// A plain `==` stops at the first differing byte, which lets a peer timing its refusals
+/// guess a token byte by byte.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let difference = a.iter().zip(b).fold(0u8, |difference, (x, y)| difference | (x ^ y));
+    std::hint::black_box(difference) == 0
+}
+
+/// Resolve a secret setting that may point somewhere else instead of holding the value
+///
+/// - `file:/run/secrets/telegram_token` reads the file (Docker/Kubernetes secrets)
+/// - `cmd:pass show miner/telegram` runs the command and uses its output
+/// - `keyring:telegram_bot_token` looks the name up in the OS keyring
+///
+/// Anything else is returned as is. Surrounding whitespace and newlines are trimmed.
+pub fn resolve(value: &str) -> Result<String> {
+    let secret = if let Some(path) = value.strip_prefix("file:") {
+        read_file(Path::new(path.trim()))?
+    } else if let Some(command) = value.strip_prefix("cmd:") {
+        run_command(command.trim())?
+    } else if let Some(name) = value.strip_prefix("keyring:") {
+        keyring_lookup(name.trim())?
+    } else {
+        return Ok(value.to_string());
+    };
+    if secret.is_empty() {
+        bail!("'{}' resolved to an empty value", value);
+    }
+    Ok(secret)
+}
+
+/// Read a secret file, e.g. one named by a `*_FILE` environment variable
+pub fn read_file(path: &Path) -> Result<String> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read secret file {}", path.display()))?;
+    Ok(contents.trim().to_string())
+}
+
+fn run_command(command: &str) -> Result<String> {
+    #[cfg(windows)]
+    let output = Command::new("cmd").args(["/C", command]).output();
+    #[cfg(not(windows))]
+    let output = Command::new("sh").args(["-c", command]).output();
+    let output = output.with_context(|| format!("Failed to run secret command '{}'", command))?;
+    if !output.status.success() {
+        bail!(
+            "Secret command '{}' failed ({}): {}",
+            command,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Look a secret up with the platform's keyring tool
+///
+/// Linux uses the Secret Service (`secret-tool`, stored with
+/// `secret-tool store --label=... service bitcoin-solo-miner key NAME`) and macOS the
+/// login keychain (`security add-generic-password -s bitcoin-solo-miner -a NAME -w`).
+fn keyring_lookup(name: &str) -> Result<String> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("security");
+        command.args(["find-generic-password", "-s", KEYRING_SERVICE, "-a", name, "-w"]);
+        command
+    } else if cfg!(unix) {
+        let mut command = Command::new("secret-tool");
+        command.args(["lookup", "service", KEYRING_SERVICE, "key", name]);
+        command
+    } else {
+        bail!("keyring: secrets aren't supported on this platform; use file: or cmd: instead");
+    };
+    let output = command.output()
+        .with_context(|| format!("Failed to run {:?} to query the keyring for '{}'", command.get_program(), name))?;
+    if !output.status.success() || output.stdout.is_empty() {
+        bail!("No keyring entry '{}' for service {}", name, KEYRING_SERVICE);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
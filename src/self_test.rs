@@ -0,0 +1,170 @@
+//! Checking the hashing pipeline against real blocks before mining
+//!
+//! A miner with broken header assembly, double SHA-256 or target comparison, whether from
+//! a bad build, an assembly SHA-256 on the wrong CPU or an endianness slip, would hash for
+//! weeks without ever being able to find a block. Before any work, known blocks are rebuilt
+//! the way a pool's job describes them, and mining refuses to start if one comes out wrong.
+
+use std::str::FromStr;
+use std::sync::Arc;
+use bitcoin::consensus::serialize;
+use bitcoin::hashes::Hash;
+use bitcoin::{Network, Txid};
+
+use crate::hashing::{double_sha256, hash_hex, hash_meets_target};
+use crate::stratum::MiningJob;
+use crate::work::{calculate_target, create_block_header, fold_merkle_branch, stratum_prevhash_hex, HashingError, HeaderTemplate};
+use crate::worker::{RangeSearch, RangeWork};
+
+const GENESIS_HASH: &str = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+const BLOCK_1_HASH: &str = "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048";
+/// Block 170 holds the first transaction between two people, so it has a merkle branch
+const BLOCK_170_HASH: &str = "00000000d1145790a8694403d4063f323d499e655c83426834d4ce2f8dd4a2ee";
+const BLOCK_170_PREVHASH: &str = "000000002a22cfee1f2c846adbd12b3e183d4f97683f85dad08a79780a84bd55";
+const BLOCK_170_COINBASE_TXID: &str = "b1fea52486ce0c62bb442b530a3f0132b826c74e473d1f2c220bfa78111c5082";
+const BLOCK_170_TXID: &str = "f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16";
+const BLOCK_170_MERKLE_ROOT: &str = "7dac2c5666815c17a3b36427de37bb9d2e2c5ccec3f8633eb91a4205cb4c10ff";
+/// Nonces either side of the genesis nonce the workers search, none of which meet its target
+const GENESIS_SEARCH_RADIUS: u32 = 64;
+
+/// A step of the pipeline that didn't reproduce a known block
+#[derive(Debug, thiserror::Error)]
+pub enum SelfTestFailure {
+    #[error("{block}: {source}")]
+    Job {
+        block: &'static str,
+        #[source]
+        source: HashingError,
+    },
+    #[error("{block}: {what} is {found}, expected {expected}")]
+    Mismatch { block: &'static str, what: &'static str, expected: String, found: String },
+    #[error("{block}: the block's hash doesn't meet its own target")]
+    TargetMissed { block: &'static str },
+    #[error("{block}: nonce {nonce:08x} meets the target, but only the block's own nonce should")]
+    TargetMetByWrongNonce { block: &'static str, nonce: u32 },
+    #[error("{block}: the hashing threads {outcome}")]
+    Workers { block: &'static str, outcome: String },
+}
+
+fn job_error(block: &'static str) -> impl Fn(HashingError) -> SelfTestFailure {
+    move |source| SelfTestFailure::Job { block, source }
+}
+
+fn expect(block: &'static str, what: &'static str, expected: &str, found: String) -> Result<(), SelfTestFailure> {
+    if found == expected {
+        Ok(())
+    } else {
+        Err(SelfTestFailure::Mismatch { block, what, expected: expected.to_string(), found })
+    }
+}
+
+/// The genesis block as a pool would hand it out: its coinbase split around an
+/// extranonce1 and extranonce2, with the nonce it was mined with
+fn genesis_job() -> (MiningJob, String, String, u32) {
+    let genesis = bitcoin::constants::genesis_block(Network::Bitcoin);
+    let coinbase = hex::encode(serialize(&genesis.txdata[0]));
+    // The split falls inside the coinbase script, where pools put the extranonces
+    let (coinb1, rest) = coinbase.split_at(84);
+    let (extranonce1, rest) = rest.split_at(8);
+    let (extranonce2, coinb2) = rest.split_at(8);
+    let job = MiningJob {
+        job_id: "genesis".to_string(),
+        prevhash: stratum_prevhash_hex(genesis.header.prev_blockhash),
+        coinb1: coinb1.to_string(),
+        coinb2: coinb2.to_string(),
+        merkle_branch: Vec::new(),
+        version: format!("{:08x}", genesis.header.version.to_consensus()),
+        nbits: format!("{:08x}", genesis.header.bits.to_consensus()),
+        ntime: format!("{:08x}", genesis.header.time),
+        clean_jobs: true,
+    };
+    (job, extranonce1.to_string(), extranonce2.to_string(), genesis.header.nonce)
+}
+
+/// Coinbase, merkle root, header and hash of the genesis block, and the target comparison
+/// either side of its nonce
+fn check_genesis() -> Result<(), SelfTestFailure> {
+    const BLOCK: &str = "genesis block";
+    let (job, extranonce1, extranonce2, nonce) = genesis_job();
+    let mut header = HeaderTemplate::new(&job, &extranonce1, extranonce2.len() / 2)
+        .and_then(|template| template.with_extranonce2(&extranonce2))
+        .map_err(job_error(BLOCK))?;
+    header.nonce = nonce;
+    let hash = double_sha256(&serialize(&header));
+    expect(BLOCK, "hash", GENESIS_HASH, hash_hex(&hash))?;
+    let target = calculate_target(&job.nbits).map_err(job_error(BLOCK))?;
+    if !hash_meets_target(&hash, &target) {
+        return Err(SelfTestFailure::TargetMissed { block: BLOCK });
+    }
+    for wrong in [nonce - 1, nonce + 1] {
+        header.nonce = wrong;
+        if hash_meets_target(&double_sha256(&serialize(&header)), &target) {
+            return Err(SelfTestFailure::TargetMetByWrongNonce { block: BLOCK, nonce: wrong });
+        }
+    }
+    Ok(())
+}
+
+/// A header built from Stratum fields, which word-swap the previous block hash
+fn check_block_1() -> Result<(), SelfTestFailure> {
+    const BLOCK: &str = "block 1";
+    let prevhash = bitcoin::BlockHash::from_str(GENESIS_HASH).expect("valid hash");
+    let header = create_block_header(
+        "00000001",
+        &stratum_prevhash_hex(prevhash),
+        "0e3e2357e806b6cdb1f70b54c3a3a17b6714ee1f0e68bebb44a74b1efd512098",
+        "1d00ffff",
+        "4966bc61",
+        "9962e301",
+    )
+    .map_err(job_error(BLOCK))?;
+    expect(BLOCK, "hash", BLOCK_1_HASH, hash_hex(&double_sha256(&header)))
+}
+
+/// A merkle branch folded into the coinbase txid
+fn check_block_170() -> Result<(), SelfTestFailure> {
+    const BLOCK: &str = "block 170";
+    let coinbase = Txid::from_str(BLOCK_170_COINBASE_TXID).expect("valid txid");
+    let branch = vec![Txid::from_str(BLOCK_170_TXID).expect("valid txid").to_byte_array().to_vec()];
+    let merkle_root = fold_merkle_branch(coinbase.to_byte_array(), &branch).to_string();
+    expect(BLOCK, "merkle root", BLOCK_170_MERKLE_ROOT, merkle_root.clone())?;
+    let prevhash = bitcoin::BlockHash::from_str(BLOCK_170_PREVHASH).expect("valid hash");
+    let header = create_block_header("00000001", &stratum_prevhash_hex(prevhash), &merkle_root, "1d00ffff", "496ab951", "709e3e28")
+        .map_err(job_error(BLOCK))?;
+    expect(BLOCK, "hash", BLOCK_170_HASH, hash_hex(&double_sha256(&header)))
+}
+
+/// The hashing threads themselves, searching nonces around the genesis nonce
+async fn check_workers() -> Result<(), SelfTestFailure> {
+    const BLOCK: &str = "genesis block";
+    let (job, extranonce1, extranonce2, nonce) = genesis_job();
+    let target = calculate_target(&job.nbits).map_err(job_error(BLOCK))?;
+    let work = Arc::new(RangeWork {
+        job,
+        extranonce1,
+        extranonce2,
+        nonce_start: nonce - GENESIS_SEARCH_RADIUS,
+        nonce_end: nonce + GENESIS_SEARCH_RADIUS,
+        target,
+    });
+    let workers_failed = |outcome: String| SelfTestFailure::Workers { block: BLOCK, outcome };
+    let (results_tx, mut results_rx) = tokio::sync::mpsc::unbounded_channel();
+    let search = RangeSearch::start(1, work, results_tx).map_err(|e| workers_failed(format!("didn't start: {:#}", e)))?;
+    let result = results_rx.recv().await;
+    search.join();
+    match result {
+        Some(Ok(Some(found))) => expect(BLOCK, "nonce the workers found", &format!("{:08x}", nonce), found.nonce),
+        Some(Ok(None)) => Err(workers_failed("searched past the block's nonce without finding it".to_string())),
+        Some(Err(e)) => Err(workers_failed(format!("failed: {:#}", e))),
+        None => Err(workers_failed("stopped without an answer".to_string())),
+    }
+}
+
+/// Rebuild the known blocks, returning how many checks passed or the first that failed
+pub async fn run() -> Result<usize, SelfTestFailure> {
+    check_genesis()?;
+    check_block_1()?;
+    check_block_170()?;
+    check_workers().await?;
+    Ok(4)
+}
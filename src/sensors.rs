@@ -0,0 +1,117 @@
+//! Hardware sensor readings
+//!
+//! CPU temperature, fan speed and clock frequency, read from the Linux hwmon, thermal and
+//! cpufreq interfaces in sysfs, so a miner throttling or overheating somewhere remote shows
+//! it in its stats. Other platforms have no readings.
+
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+/// hwmon drivers whose temperatures are the CPU's
+const CPU_HWMON_DRIVERS: [&str; 6] = ["coretemp", "k10temp", "zenpower", "cpu_thermal", "cpu-thermal", "soc_thermal"];
+/// Thermal zone types that are the CPU's, for boards without a CPU hwmon driver
+const CPU_THERMAL_ZONES: [&str; 4] = ["x86_pkg_temp", "cpu", "soc", "acpitz"];
+
+/// What the sensors showed at one moment; a reading the machine doesn't offer is `None`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SensorReadings {
+    /// Hottest CPU temperature sensor, in °C
+    pub cpu_temp_c: Option<f64>,
+    /// Fastest fan, in RPM
+    pub fan_rpm: Option<u64>,
+    /// Average current clock across cores, in MHz
+    pub cpu_mhz: Option<f64>,
+}
+
+impl SensorReadings {
+    /// e.g. `CPU 71.0°C | fan 1450 RPM | 3400 MHz`, or `None` with nothing to show
+    pub fn summary(&self) -> Option<String> {
+        let parts: Vec<String> = [
+            self.cpu_temp_c.map(|temp| format!("CPU {:.1}°C", temp)),
+            self.fan_rpm.map(|rpm| format!("fan {} RPM", rpm)),
+            self.cpu_mhz.map(|mhz| format!("{:.0} MHz", mhz)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        (!parts.is_empty()).then(|| parts.join(" | "))
+    }
+}
+
+fn read_number(path: &Path) -> Option<f64> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+fn read_name(path: &Path) -> String {
+    std::fs::read_to_string(path).map(|name| name.trim().to_string()).unwrap_or_default()
+}
+
+/// Entries of `dir` whose names start with `prefix`
+fn entries(dir: &Path, prefix: &str) -> Vec<std::path::PathBuf> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter(|entry| entry.file_name().to_str().is_some_and(|name| name.starts_with(prefix)))
+        .map(|entry| entry.path())
+        .collect();
+    paths.sort();
+    paths
+}
+
+/// Take the readings from a sysfs tree mounted at `sys`
+pub fn read_from(sys: &Path) -> SensorReadings {
+    let mut cpu_temps = Vec::new();
+    let mut fans = Vec::new();
+    for hwmon in entries(&sys.join("class/hwmon"), "hwmon") {
+        let is_cpu = CPU_HWMON_DRIVERS.contains(&read_name(&hwmon.join("name")).as_str());
+        for input in entries(&hwmon, "") {
+            let Some(file) = input.file_name().and_then(|name| name.to_str()) else { continue };
+            if !file.ends_with("_input") {
+                continue;
+            }
+            if is_cpu && file.starts_with("temp") {
+                // Millidegrees
+                cpu_temps.extend(read_number(&input).map(|temp| temp / 1000.0));
+            } else if file.starts_with("fan") {
+                fans.extend(read_number(&input).map(|rpm| rpm as u64));
+            }
+        }
+    }
+    if cpu_temps.is_empty() {
+        for zone in entries(&sys.join("class/thermal"), "thermal_zone") {
+            let zone_type = read_name(&zone.join("type")).to_lowercase();
+            if CPU_THERMAL_ZONES.iter().any(|cpu| zone_type.contains(cpu)) {
+                cpu_temps.extend(read_number(&zone.join("temp")).map(|temp| temp / 1000.0));
+            }
+        }
+    }
+
+    let frequencies: Vec<f64> = entries(&sys.join("devices/system/cpu"), "cpu")
+        .into_iter()
+        .filter(|cpu| {
+            cpu.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix("cpu"))
+                .is_some_and(|number| !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()))
+        })
+        // kHz
+        .filter_map(|cpu| read_number(&cpu.join("cpufreq/scaling_cur_freq")).map(|khz| khz / 1000.0))
+        .collect();
+
+    SensorReadings {
+        cpu_temp_c: cpu_temps.into_iter().reduce(f64::max),
+        fan_rpm: fans.into_iter().max(),
+        cpu_mhz: (!frequencies.is_empty()).then(|| frequencies.iter().sum::<f64>() / frequencies.len() as f64),
+    }
+}
+
+/// Take the readings from this machine's sensors
+pub fn read() -> SensorReadings {
+    if cfg!(target_os = "linux") {
+        read_from(Path::new("/sys"))
+    } else {
+        SensorReadings::default()
+    }
+}
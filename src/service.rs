@@ -0,0 +1,150 @@
+use std::ffi::OsString;
+use std::time::Duration;
+use anyhow::{Result, Context};
+use clap::Parser;
+use windows_service::service::{
+    ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+    ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+};
+use windows_service::service_control_handler::{self, ServiceControlHandlerResult};
+use windows_service::service_manager::{ServiceManager, ServiceManagerAccess};
+use windows_service::{define_windows_service, service_dispatcher};
+
+use bitcoin_solo_miner::config::load_config;
+use bitcoin_solo_miner::exit_code::{ExitCode, fatal};
+use bitcoin_solo_miner::miner::mine;
+
+use crate::CliArgs;
+
+const SERVICE_NAME: &str = "BitcoinSoloMiner";
+const SERVICE_DISPLAY_NAME: &str = "Bitcoin Solo Miner";
+const SERVICE_DESCRIPTION: &str = "Bitcoin solo miner for solo.ckpool.org";
+const SERVICE_STOP_WAIT_SECS: u64 = 15;
+
+/// Register the miner as an auto-start service running this executable
+///
+/// The service starts in the executable's directory, so a config.ini next to the exe
+/// is picked up; an explicit `--config` is stored as an absolute path.
+pub fn install(cli: &CliArgs) -> Result<()> {
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    ).context("Failed to open the service manager (run from an elevated prompt)")?;
+
+    let mut launch_arguments = vec![OsString::from("--service")];
+    if let Some(config) = &cli.config {
+        let config = std::fs::canonicalize(config)
+            .with_context(|| format!("Config file not found: {}", config.display()))?;
+        launch_arguments.push("--config".into());
+        launch_arguments.push(config.into_os_string());
+    }
+    let info = ServiceInfo {
+        name: SERVICE_NAME.into(),
+        display_name: SERVICE_DISPLAY_NAME.into(),
+        service_type: ServiceType::OWN_PROCESS,
+        start_type: ServiceStartType::AutoStart,
+        error_control: ServiceErrorControl::Normal,
+        executable_path: std::env::current_exe()?,
+        launch_arguments,
+        dependencies: vec![],
+        account_name: None,
+        account_password: None,
+    };
+    let service = manager.create_service(&info, ServiceAccess::CHANGE_CONFIG)
+        .context("Failed to create the service")?;
+    service.set_description(SERVICE_DESCRIPTION)?;
+    println!("Installed the '{}' service; it starts at boot.", SERVICE_DISPLAY_NAME);
+    println!("Start it now with: sc start {}", SERVICE_NAME);
+    Ok(())
+}
+
+/// Stop the service if it's running and remove it
+pub fn uninstall() -> Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
+        .context("Failed to open the service manager (run from an elevated prompt)")?;
+    let service = manager.open_service(
+        SERVICE_NAME,
+        ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+    ).context("The service is not installed")?;
+
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        service.stop()?;
+        let deadline = std::time::Instant::now() + Duration::from_secs(SERVICE_STOP_WAIT_SECS);
+        while service.query_status()?.current_state != ServiceState::Stopped
+            && std::time::Instant::now() < deadline
+        {
+            std::thread::sleep(Duration::from_millis(500));
+        }
+    }
+    service.delete().context("Failed to delete the service")?;
+    println!("Removed the '{}' service.", SERVICE_DISPLAY_NAME);
+    Ok(())
+}
+
+/// Hand this process to the service control manager; returns when the service stops
+pub fn run() -> Result<()> {
+    service_dispatcher::start(SERVICE_NAME, ffi_service_main)
+        .context("Failed to start as a service (--service is only for the service manager)")
+}
+
+define_windows_service!(ffi_service_main, service_main);
+
+fn service_main(_arguments: Vec<OsString>) {
+    if let Err(e) = run_service() {
+        // There's no console; the main log (if enabled) already has the details
+        eprintln!("Service failed: {:#}", e);
+    }
+}
+
+fn run_service() -> Result<()> {
+    let (stop_tx, stop_rx) = tokio::sync::oneshot::channel();
+    let mut stop_tx = Some(stop_tx);
+    let status_handle = service_control_handler::register(SERVICE_NAME, move |control| match control {
+        ServiceControl::Stop | ServiceControl::Shutdown => {
+            if let Some(stop_tx) = stop_tx.take() {
+                let _ = stop_tx.send(());
+            }
+            ServiceControlHandlerResult::NoError
+        }
+        ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+        _ => ServiceControlHandlerResult::NotImplemented,
+    })?;
+    let set_state = |state: ServiceState, exit_code: u32| {
+        status_handle.set_service_status(ServiceStatus {
+            service_type: ServiceType::OWN_PROCESS,
+            current_state: state,
+            controls_accepted: if state == ServiceState::Running {
+                ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN
+            } else {
+                ServiceControlAccept::empty()
+            },
+            exit_code: match exit_code {
+                0 => ServiceExitCode::Win32(0),
+                code => ServiceExitCode::ServiceSpecific(code),
+            },
+            checkpoint: 0,
+            wait_hint: Duration::from_secs(SERVICE_STOP_WAIT_SECS),
+            process_id: None,
+        })
+    };
+
+    // Services start in System32; look for config.ini and write logs beside the exe instead
+    if let Some(dir) = std::env::current_exe()?.parent() {
+        std::env::set_current_dir(dir)?;
+    }
+    let cli = CliArgs::parse();
+    set_state(ServiceState::Running, 0)?;
+    let result = load_config(&cli.overrides()).map_err(fatal(ExitCode::InvalidConfig)).and_then(|config| {
+        tokio::runtime::Runtime::new()?.block_on(mine(&cli.run_options(), config, async {
+            let _ = stop_rx.await;
+            "service stop"
+        }))
+    });
+    // The service-specific exit code shows up in `sc query` and the event log
+    let exit_code = match &result {
+        Ok(()) => ExitCode::Success,
+        Err(e) => ExitCode::of(e),
+    };
+    set_state(ServiceState::Stopped, exit_code as u32)?;
+    result
+}
@@ -0,0 +1,228 @@
+use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::Result;
+use serde::Serialize;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+use crate::error::Recovery;
+use crate::events::{self, MinerEvent};
+use crate::failover::SessionOutcome;
+use crate::miner::MiningConfig;
+use crate::sd_notify;
+
+/// How long the TCP connection and the `mining.subscribe` reply may take
+const CONNECTING_TIMEOUT_SECS: u64 = 30;
+/// How long the `mining.authorize` reply may take
+const SUBSCRIBED_TIMEOUT_SECS: u64 = 30;
+/// How long an authorized session may wait for work
+const AUTHORIZED_TIMEOUT_SECS: u64 = 120;
+/// How long a session may go without a usable job before it's dropped
+const DEGRADED_TIMEOUT_SECS: u64 = 300;
+
+/// Where a pool session is
+///
+/// ```text
+/// Disconnected -> Connecting -> Subscribed -> Authorized -> Working
+///                                                  |          ^  |
+///                                                  +-----> Degraded
+/// ```
+///
+/// Every state can fall back to `Disconnected`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionState {
+    #[default]
+    Disconnected,
+    /// Opening the connection and subscribing
+    Connecting,
+    /// Subscribed, waiting for the authorization
+    Subscribed,
+    /// Authorized, waiting for a job
+    Authorized,
+    /// Hashing a job
+    Working,
+    /// Connected, but the pool's last job couldn't be worked
+    Degraded,
+}
+
+impl SessionState {
+    pub fn name(self) -> &'static str {
+        match self {
+            SessionState::Disconnected => "disconnected",
+            SessionState::Connecting => "connecting",
+            SessionState::Subscribed => "subscribed",
+            SessionState::Authorized => "authorized",
+            SessionState::Working => "working",
+            SessionState::Degraded => "degraded",
+        }
+    }
+
+    /// Longest a session may stay in this state; `None` for as long as it takes
+    pub fn timeout(self) -> Option<Duration> {
+        match self {
+            SessionState::Connecting => Some(Duration::from_secs(CONNECTING_TIMEOUT_SECS)),
+            SessionState::Subscribed => Some(Duration::from_secs(SUBSCRIBED_TIMEOUT_SECS)),
+            SessionState::Authorized => Some(Duration::from_secs(AUTHORIZED_TIMEOUT_SECS)),
+            SessionState::Degraded => Some(Duration::from_secs(DEGRADED_TIMEOUT_SECS)),
+            SessionState::Disconnected | SessionState::Working => None,
+        }
+    }
+
+    /// Whether a session may go from this state to `next`
+    pub fn can_become(self, next: SessionState) -> bool {
+        use SessionState::*;
+        matches!(
+            (self, next),
+            (_, Disconnected)
+                | (Disconnected, Connecting)
+                | (Connecting, Subscribed)
+                | (Subscribed, Authorized)
+                | (Authorized, Working)
+                | (Authorized, Degraded)
+                | (Working, Degraded)
+                | (Degraded, Working)
+        )
+    }
+}
+
+impl fmt::Display for SessionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A session that broke the state machine's rules
+#[derive(Debug, thiserror::Error)]
+pub enum SessionError {
+    #[error("Pool session still {state} after {}s", .timeout.as_secs())]
+    Timeout {
+        state: SessionState,
+        timeout: Duration,
+    },
+    #[error("Pool session can't go from {from} to {to}")]
+    InvalidTransition {
+        from: SessionState,
+        to: SessionState,
+    },
+}
+
+impl SessionError {
+    /// A stuck or confused session is replaced with a fresh one
+    pub fn recovery(&self) -> Recovery {
+        Recovery::Reconnect
+    }
+}
+
+/// One pool session's progress through `SessionState`
+///
+/// Entering a state runs its entry actions (stats, events, logs) and starts its timeout,
+/// which `within` enforces on whatever the session waits for in that state.
+pub struct SessionMachine {
+    config: Arc<Mutex<MiningConfig>>,
+    pool: String,
+    state: SessionState,
+    entered: Instant,
+    /// Whether the session got as far as hashing a job
+    worked: bool,
+    submits: u32,
+    accepted: u32,
+}
+
+impl SessionMachine {
+    pub fn new(config: Arc<Mutex<MiningConfig>>, pool: String) -> Self {
+        Self {
+            config,
+            pool,
+            state: SessionState::Disconnected,
+            entered: Instant::now(),
+            worked: false,
+            submits: 0,
+            accepted: 0,
+        }
+    }
+
+    pub fn state(&self) -> SessionState {
+        self.state
+    }
+
+    /// Count the pool's answer to a submitted share
+    pub fn record_submit(&mut self, accepted: bool) {
+        self.submits += 1;
+        if accepted {
+            self.accepted += 1;
+        }
+    }
+
+    /// How the session went, for deciding whether its pool is failing
+    pub fn outcome(&self) -> SessionOutcome {
+        if !self.worked {
+            SessionOutcome::NeverWorked
+        } else if self.submits > 0 && self.accepted == 0 {
+            SessionOutcome::RejectedEverything
+        } else {
+            SessionOutcome::Worked
+        }
+    }
+
+    /// Move to `next` and run its entry actions; staying in the same state is a no-op
+    /// and keeps its timeout running
+    pub fn transition(&mut self, next: SessionState) -> Result<(), SessionError> {
+        if next == self.state {
+            return Ok(());
+        }
+        if !self.state.can_become(next) {
+            return Err(SessionError::InvalidTransition { from: self.state, to: next });
+        }
+        let previous = self.state;
+        self.state = next;
+        self.entered = Instant::now();
+        self.config.lock().unwrap().session_state = next;
+        debug!(event = "session_state", from = previous.name(), to = next.name(), "Pool session {} -> {}", previous, next);
+
+        match next {
+            SessionState::Connecting => {
+                info!(event = "pool_connecting", "Connecting to {}...", self.pool);
+            }
+            SessionState::Authorized => {
+                self.config.lock().unwrap().stats.mark_connected();
+                events::emit(MinerEvent::Connected { pool: self.pool.clone() });
+            }
+            SessionState::Degraded => {
+                warn!(event = "session_degraded", "Pool session degraded, waiting up to {}s for a usable job", DEGRADED_TIMEOUT_SECS);
+                sd_notify::status("Waiting for a usable job from the pool");
+            }
+            SessionState::Working => self.worked = true,
+            SessionState::Disconnected | SessionState::Subscribed => {}
+        }
+        Ok(())
+    }
+
+    /// End the session, with the error that ended it if there was one
+    pub fn disconnect(&mut self, error: Option<&anyhow::Error>) {
+        let was_up = self.state != SessionState::Disconnected;
+        // Every state may become Disconnected
+        let _ = self.transition(SessionState::Disconnected);
+        if was_up {
+            self.config.lock().unwrap().stats.mark_disconnected();
+            events::emit(MinerEvent::Disconnected {
+                pool: self.pool.clone(),
+                error: error.map(|e| e.to_string()),
+            });
+        }
+    }
+
+    /// Wait for `operation`, failing with `SessionError::Timeout` if the current state's
+    /// timeout runs out first
+    pub async fn within<T, E: Into<anyhow::Error>>(&self, operation: impl Future<Output = Result<T, E>>) -> Result<T> {
+        let Some(timeout) = self.state.timeout() else {
+            return operation.await.map_err(Into::into);
+        };
+        tokio::time::timeout_at(self.entered + timeout, operation)
+            .await
+            .map_err(|_| SessionError::Timeout { state: self.state, timeout })?
+            .map_err(Into::into)
+    }
+}
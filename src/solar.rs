@@ -0,0 +1,138 @@
+//! Hashing only on surplus solar power
+//!
+//! An HTTP endpoint, such as an inverter's or a home energy manager's API, reports the
+//! watts currently going to the grid. The thread count is scaled so that mining uses that
+//! surplus and no more, down to a pause when there is not enough for one thread.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{Result, Context};
+use serde_json::Value;
+use tracing::{info, warn, Instrument, info_span};
+
+use crate::control::Control;
+use crate::miner::MiningConfig;
+use crate::tasks::{Stage, Tasks};
+
+pub const DEFAULT_SOLAR_POLL_SECS: u64 = 30;
+/// How long a surplus request may take before the reading is skipped
+const SOLAR_REQUEST_TIMEOUT_SECS: u64 = 10;
+
+/// Surplus-driven mining, as configured in `[solar]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SolarSettings {
+    /// Answers with the surplus in watts
+    pub url: String,
+    /// JSON pointer to the surplus in the answer, e.g. `/grid/export_w`; `None` when the
+    /// answer is the number itself
+    pub field: Option<String>,
+    /// What one hashing thread draws
+    pub watts_per_thread: u64,
+    /// Threads to run with surplus to spare
+    pub max_threads: usize,
+    pub poll: Duration,
+}
+
+/// The surplus in an endpoint's answer: the number `field` points to, or the whole
+/// answer when there is no field
+pub fn parse_surplus(body: &str, field: Option<&str>) -> Result<f64> {
+    let Some(field) = field else {
+        return body.trim().parse().with_context(|| format!("Surplus '{}' isn't a number", body.trim()));
+    };
+    let json: Value = serde_json::from_str(body).context("Surplus answer isn't JSON")?;
+    let value = json.pointer(field).with_context(|| format!("Surplus answer has no {}", field))?;
+    // Some energy managers send numbers as strings
+    value
+        .as_f64()
+        .or_else(|| value.as_str().and_then(|text| text.trim().parse().ok()))
+        .with_context(|| format!("Surplus {} is {}, not a number", field, value))
+}
+
+/// Threads that fit in the surplus
+///
+/// Meters report the surplus left over with the miner already running, so the watts of
+/// the `running` threads are available to them as well.
+pub fn threads_for(surplus_watts: f64, running: usize, watts_per_thread: u64, max_threads: usize) -> usize {
+    if watts_per_thread == 0 {
+        return max_threads;
+    }
+    let available = surplus_watts + (running as u64 * watts_per_thread) as f64;
+    ((available.max(0.0) / watts_per_thread as f64) as usize).min(max_threads)
+}
+
+async fn fetch_surplus(client: &reqwest::Client, settings: &SolarSettings) -> Result<f64> {
+    let body = client.get(&settings.url).send().await?.error_for_status()?.text().await?;
+    parse_surplus(&body, settings.field.as_deref())
+}
+
+/// Poll the surplus and scale the hashing threads to it
+pub fn start(settings: SolarSettings, config: Arc<Mutex<MiningConfig>>, control: Control, tasks: &mut Tasks) {
+    info!(
+        event = "solar",
+        url = %settings.url,
+        watts_per_thread = settings.watts_per_thread,
+        max_threads = settings.max_threads,
+        "Scaling hashing to surplus solar power from {} ({} W per thread, up to {} threads)",
+        settings.url,
+        settings.watts_per_thread,
+        settings.max_threads
+    );
+    tasks.spawn(Stage::Api, "solar", watch(settings, config, control).instrument(info_span!("solar")));
+}
+
+async fn watch(settings: SolarSettings, config: Arc<Mutex<MiningConfig>>, control: Control) {
+    let client = match reqwest::Client::builder().timeout(Duration::from_secs(SOLAR_REQUEST_TIMEOUT_SECS)).build() {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Solar scaling disabled: {}", e);
+            return;
+        }
+    };
+    // Whether the current pause is ours, so a pause from the user is never lifted here
+    let mut solar_paused = false;
+    let mut interval = tokio::time::interval(settings.poll);
+    loop {
+        interval.tick().await;
+        let surplus = match fetch_surplus(&client, &settings).await {
+            Ok(surplus) => surplus,
+            Err(e) => {
+                warn!(event = "solar_reading_failed", "Failed to read the solar surplus: {:#}", e);
+                continue;
+            }
+        };
+        let (threads, paused) = {
+            let config_guard = config.lock().unwrap();
+            (config_guard.threads, config_guard.paused)
+        };
+        if solar_paused && !paused {
+            // Resumed by hand: leave hashing on until the surplus says otherwise
+            solar_paused = false;
+        }
+        let running = if paused { 0 } else { threads };
+        let wanted = threads_for(surplus, running, settings.watts_per_thread, settings.max_threads);
+        if wanted == 0 {
+            if !paused && control.pause("solar surplus") {
+                solar_paused = true;
+                info!(event = "solar_threads", surplus_watts = surplus, threads = 0, "Solar surplus {:.0} W is below one thread, pausing hashing", surplus);
+            }
+            continue;
+        }
+        if wanted != threads {
+            info!(
+                event = "solar_threads",
+                surplus_watts = surplus,
+                threads = wanted,
+                "Solar surplus {:.0} W: hashing on {} thread(s)",
+                surplus,
+                wanted
+            );
+            control.set_threads(wanted);
+        }
+        if solar_paused {
+            solar_paused = false;
+            if control.resume("solar surplus") {
+                info!(event = "solar_threads", surplus_watts = surplus, threads = wanted, "Solar surplus {:.0} W, resuming hashing on {} thread(s)", surplus, wanted);
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use std::collections::VecDeque;
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Fixed-size history of hashrate samples rendered as a unicode sparkline
+#[derive(Debug)]
+pub struct HashrateHistory {
+    samples: VecDeque<u64>,
+    capacity: usize,
+}
+
+impl HashrateHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            samples: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, hash_rate: u64) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(hash_rate);
+    }
+
+    /// The most recent sample
+    pub fn latest(&self) -> Option<u64> {
+        self.samples.back().copied()
+    }
+
+    /// One bar per sample, scaled between the minimum and maximum in the window
+    pub fn sparkline(&self) -> String {
+        let min = self.samples.iter().copied().min().unwrap_or(0);
+        let max = self.samples.iter().copied().max().unwrap_or(0);
+        let range = max - min;
+        self.samples
+            .iter()
+            .map(|&sample| {
+                // A flat window renders as mid-height bars
+                let level = ((sample - min) * (BARS.len() as u64 - 1))
+                    .checked_div(range)
+                    .unwrap_or(BARS.len() as u64 / 2);
+                BARS[level as usize]
+            })
+            .collect()
+    }
+}
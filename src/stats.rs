@@ -0,0 +1,491 @@
+use std::path::Path;
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use anyhow::{Result, Context};
+
+use crate::bandwidth::{self, Traffic};
+use crate::events::MinerEvent;
+use crate::latency::{LatencyChange, LatencyStats, LatencyTracker};
+use crate::sensors::SensorReadings;
+use crate::weekly::{self, WeeklyStats};
+
+/// Cumulative counters persisted to disk between runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LifetimeStats {
+    pub total_hashes: u64,
+    pub shares_submitted: u64,
+    pub uptime_secs: u64,
+    pub best_share_difficulty: f64,
+    /// Best share difficulty as a fraction of the network difficulty of its job
+    pub closest_call: f64,
+    pub connected_secs: u64,
+    pub disconnected_secs: u64,
+    pub reconnects: u64,
+    pub longest_session_secs: u64,
+    /// Number of times the miner has been started
+    pub runs: u64,
+    /// The week so far, for the weekly report
+    pub week: WeeklyStats,
+}
+
+impl LifetimeStats {
+    /// Load stats from a JSON state file, returning defaults if it doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read state file {}", path.display()))?;
+        serde_json::from_str(&data)
+            .with_context(|| format!("Failed to parse state file {}", path.display()))
+    }
+
+    /// Write stats to disk (via a temporary file so a crash mid-write can't corrupt it)
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            if !dir.as_os_str().is_empty() && !dir.exists() {
+                std::fs::create_dir_all(dir)?;
+            }
+        }
+        let tmp_path = path.with_extension("json.tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write state file {}", tmp_path.display()))?;
+        std::fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to replace state file {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Percentage of tracked time spent connected to the pool
+    pub fn availability_percent(&self) -> f64 {
+        let tracked = self.connected_secs + self.disconnected_secs;
+        if tracked == 0 {
+            return 100.0;
+        }
+        self.connected_secs as f64 * 100.0 / tracked as f64
+    }
+
+    /// One-line summary of connection health
+    pub fn connection_summary(&self) -> String {
+        format!(
+            "availability {:.2}% | connected {}s | disconnected {}s | reconnects {} | longest session {}s",
+            self.availability_percent(),
+            self.connected_secs,
+            self.disconnected_secs,
+            self.reconnects,
+            self.longest_session_secs
+        )
+    }
+}
+
+/// Work done on a single job, recorded when the miner stops working it
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStats {
+    pub job_id: String,
+    pub height: u64,
+    pub worked_secs: f64,
+    pub hashes: u64,
+    pub extranonce2_rolls: u64,
+    pub network_difficulty: f64,
+    pub best_share_difficulty: f64,
+    pub end_reason: String,
+}
+
+/// Wrap-up figures for the current process run
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionSummary {
+    pub duration_secs: u64,
+    pub hashes: u64,
+    pub average_hashrate: f64,
+    pub shares: u64,
+    pub best_share_difficulty: f64,
+    /// Best share difficulty as a fraction of the network difficulty of its job
+    pub closest_call: f64,
+    pub reconnects: u64,
+    pub jobs: u64,
+    pub average_job_secs: f64,
+    /// Time hashing was paused because electricity cost more than `max_price`
+    pub price_paused_secs: u64,
+    /// What hashing through those pauses would have cost, in the tariff's currency
+    pub electricity_savings: f64,
+    /// Hottest the CPU got, where there is a temperature sensor
+    pub peak_cpu_temp_c: Option<f64>,
+    /// Energy used, if power accounting is on
+    pub energy_kwh: f64,
+    /// What that energy cost, if an electricity price is set
+    pub energy_cost: Option<f64>,
+    /// Bytes sent to and received from the pool
+    pub pool_traffic: Traffic,
+}
+
+impl SessionSummary {
+    /// `amount` over this session scaled to a day
+    pub fn per_day(&self, amount: f64) -> f64 {
+        amount * 86_400.0 / self.duration_secs.max(1) as f64
+    }
+
+    /// e.g. `0.412 kWh (2.47 kWh/day), cost 0.12 (0.74/day)`, or `None` without power accounting
+    pub fn energy_line(&self) -> Option<String> {
+        if self.energy_kwh <= 0.0 {
+            return None;
+        }
+        let mut line = format!("{:.3} kWh ({:.2} kWh/day)", self.energy_kwh, self.per_day(self.energy_kwh));
+        if let Some(cost) = self.energy_cost {
+            line.push_str(&format!(", cost {:.2} ({:.2}/day)", cost, self.per_day(cost)));
+        }
+        Some(line)
+    }
+
+    /// e.g. `12.3 KB sent, 45.6 KB received (1.2 MB/day)`
+    pub fn traffic_line(&self) -> String {
+        self.pool_traffic.summary(Duration::from_secs(self.duration_secs))
+    }
+
+    pub fn summary_line(&self) -> String {
+        let mut line = format!(
+            "duration {}s | {} hashes | avg {:.0} h/s | {} shares | best share difficulty {} | closest call {} | reconnects {} | {} jobs (avg {:.1}s each)",
+            self.duration_secs,
+            self.hashes,
+            self.average_hashrate,
+            self.shares,
+            format_difficulty(self.best_share_difficulty),
+            format_closest_call(self.closest_call),
+            self.reconnects,
+            self.jobs,
+            self.average_job_secs
+        );
+        line.push_str(&format!(" | pool traffic {}", self.traffic_line()));
+        if self.price_paused_secs > 0 {
+            line.push_str(&format!(
+                " | paused {}s for electricity price, saving {:.2}",
+                self.price_paused_secs, self.electricity_savings
+            ));
+        }
+        if let Some(temp) = self.peak_cpu_temp_c {
+            line.push_str(&format!(" | peak CPU temperature {:.1}°C", temp));
+        }
+        if let Some(energy) = self.energy_line() {
+            line.push_str(&format!(" | {}", energy));
+        }
+        line
+    }
+}
+
+/// Session counters layered on top of the lifetime totals loaded at startup
+#[derive(Debug)]
+pub struct MinerStats {
+    baseline: LifetimeStats,
+    session_hashes: u64,
+    session_shares: u64,
+    session_best_difficulty: f64,
+    session_closest_call: f64,
+    session_start: Instant,
+    session_connected_secs: u64,
+    session_disconnected_secs: u64,
+    session_reconnects: u64,
+    session_longest_secs: u64,
+    connected_since: Option<Instant>,
+    disconnected_since: Option<Instant>,
+    has_connected: bool,
+    session_jobs: u64,
+    session_job_secs: f64,
+    session_price_paused_secs: u64,
+    session_electricity_savings: f64,
+    session_peak_cpu_temp: Option<f64>,
+    sensors: SensorReadings,
+    session_energy_kwh: f64,
+    session_energy_cost: Option<f64>,
+    pool_latency: LatencyTracker,
+    /// Pool traffic before this run's stats started
+    traffic_baseline: Traffic,
+    /// The week so far, with time counted up to `week_counted_from`
+    week: WeeklyStats,
+    /// When this run started counting time towards `week`
+    week_counted_from: Instant,
+}
+
+impl MinerStats {
+    pub fn new(baseline: LifetimeStats) -> Self {
+        Self {
+            session_hashes: 0,
+            session_shares: 0,
+            session_best_difficulty: 0.0,
+            session_closest_call: 0.0,
+            session_start: Instant::now(),
+            session_connected_secs: 0,
+            session_disconnected_secs: 0,
+            session_reconnects: 0,
+            session_longest_secs: 0,
+            connected_since: None,
+            disconnected_since: Some(Instant::now()),
+            has_connected: false,
+            session_jobs: 0,
+            session_job_secs: 0.0,
+            session_price_paused_secs: 0,
+            session_electricity_savings: 0.0,
+            session_peak_cpu_temp: None,
+            sensors: SensorReadings::default(),
+            session_energy_kwh: 0.0,
+            session_energy_cost: None,
+            pool_latency: LatencyTracker::default(),
+            traffic_baseline: bandwidth::totals(),
+            week: baseline.week.clone(),
+            week_counted_from: Instant::now(),
+            baseline,
+        }
+    }
+
+    /// Update the figures an event affects; the miner feeds every event on the bus through this
+    pub fn record_event(&mut self, event: &MinerEvent) {
+        match event {
+            MinerEvent::BlockFound { hash_difficulty, network_difficulty, .. } => {
+                self.record_share_difficulty(*hash_difficulty, *network_difficulty);
+            }
+            MinerEvent::ShareSubmitted { .. } => self.record_share(),
+            MinerEvent::JobFinished(job) => self.record_job(job),
+            _ => {}
+        }
+    }
+
+    /// Count a finished job towards the session's job churn figures
+    pub fn record_job(&mut self, job: &JobStats) {
+        self.session_jobs += 1;
+        self.session_job_secs += job.worked_secs;
+    }
+
+    /// Record that a pool connection was established
+    /// Whether a pool session is up right now
+    pub fn is_connected(&self) -> bool {
+        self.connected_since.is_some()
+    }
+
+    pub fn mark_connected(&mut self) {
+        if self.connected_since.is_some() {
+            return;
+        }
+        if let Some(since) = self.disconnected_since.take() {
+            self.session_disconnected_secs += since.elapsed().as_secs();
+            self.week.disconnected_secs += since.max(self.week_counted_from).elapsed().as_secs();
+        }
+        if self.has_connected {
+            self.session_reconnects += 1;
+        }
+        self.has_connected = true;
+        self.connected_since = Some(Instant::now());
+    }
+
+    /// Record that the pool connection was lost
+    pub fn mark_disconnected(&mut self) {
+        if let Some(since) = self.connected_since.take() {
+            let session_secs = since.elapsed().as_secs();
+            self.session_connected_secs += session_secs;
+            self.session_longest_secs = self.session_longest_secs.max(session_secs);
+            self.week.connected_secs += since.max(self.week_counted_from).elapsed().as_secs();
+            self.disconnected_since = Some(Instant::now());
+        }
+    }
+
+    pub fn record_hashes(&mut self, count: u64) {
+        self.session_hashes += count;
+        self.week.hashes += count;
+    }
+
+    /// Count the blocks `hashes` done at `network_difficulty` would find on average
+    pub fn record_expected_blocks(&mut self, hashes: u64, network_difficulty: f64) {
+        self.week.expected_blocks += weekly::expected_blocks(hashes, network_difficulty);
+    }
+
+    pub fn record_share(&mut self) {
+        self.session_shares += 1;
+        self.week.blocks_submitted += 1;
+    }
+
+    /// Count a share's difficulty towards the best and the closest call, given the network
+    /// difficulty of the job it was found on
+    pub fn record_share_difficulty(&mut self, difficulty: f64, network_difficulty: f64) {
+        if difficulty > self.session_best_difficulty {
+            self.session_best_difficulty = difficulty;
+        }
+        self.week.best_share_difficulty = self.week.best_share_difficulty.max(difficulty);
+        if network_difficulty > 0.0 {
+            self.session_closest_call = self.session_closest_call.max(difficulty / network_difficulty);
+        }
+    }
+
+    /// Count time paused for the electricity price, and what hashing through it would have cost
+    pub fn record_price_pause(&mut self, secs: u64, savings: f64) {
+        self.session_price_paused_secs += secs;
+        self.session_electricity_savings += savings;
+    }
+
+    /// Keep the latest sensor readings, and the peak temperature
+    pub fn record_sensors(&mut self, readings: SensorReadings) {
+        if let Some(temp) = readings.cpu_temp_c {
+            self.session_peak_cpu_temp = Some(self.session_peak_cpu_temp.map_or(temp, |peak| peak.max(temp)));
+        }
+        self.sensors = readings;
+    }
+
+    /// Count energy used, at `price` per kWh if known
+    pub fn record_energy(&mut self, joules: f64, price: Option<f64>) {
+        let kwh = joules / 3.6e6;
+        self.session_energy_kwh += kwh;
+        self.week.energy_kwh += kwh;
+        if let Some(price) = price {
+            self.session_energy_cost = Some(self.session_energy_cost.unwrap_or(0.0) + kwh * price);
+            self.week.energy_cost = Some(self.week.energy_cost.unwrap_or(0.0) + kwh * price);
+        }
+    }
+
+    /// The most recent sensor readings
+    pub fn sensors(&self) -> SensorReadings {
+        self.sensors
+    }
+
+    /// Count a round trip to the pool, returning whether the link just became degraded or
+    /// recovered
+    pub fn record_pool_latency(&mut self, round_trip: Duration) -> Option<LatencyChange> {
+        self.pool_latency.record(round_trip)
+    }
+
+    pub fn pool_latency(&self) -> LatencyStats {
+        self.pool_latency.stats()
+    }
+
+    /// Figures for this run only (excluding previous runs)
+    pub fn session_summary(&self) -> SessionSummary {
+        let duration = self.session_start.elapsed();
+        let average_hashrate = if duration.as_secs_f64() > 0.0 {
+            self.session_hashes as f64 / duration.as_secs_f64()
+        } else {
+            0.0
+        };
+        SessionSummary {
+            duration_secs: duration.as_secs(),
+            hashes: self.session_hashes,
+            average_hashrate,
+            shares: self.session_shares,
+            best_share_difficulty: self.session_best_difficulty,
+            closest_call: self.session_closest_call,
+            reconnects: self.session_reconnects,
+            jobs: self.session_jobs,
+            average_job_secs: if self.session_jobs > 0 {
+                self.session_job_secs / self.session_jobs as f64
+            } else {
+                0.0
+            },
+            price_paused_secs: self.session_price_paused_secs,
+            electricity_savings: self.session_electricity_savings,
+            peak_cpu_temp_c: self.session_peak_cpu_temp,
+            energy_kwh: self.session_energy_kwh,
+            energy_cost: self.session_energy_cost,
+            pool_traffic: bandwidth::totals().since(self.traffic_baseline),
+        }
+    }
+
+    /// The week so far, including the time since this run last counted it
+    pub fn week(&self) -> WeeklyStats {
+        let mut week = self.week.clone();
+        week.uptime_secs += self.week_counted_from.elapsed().as_secs();
+        if let Some(since) = self.connected_since {
+            week.connected_secs += since.max(self.week_counted_from).elapsed().as_secs();
+        }
+        if let Some(since) = self.disconnected_since {
+            week.disconnected_secs += since.max(self.week_counted_from).elapsed().as_secs();
+        }
+        week
+    }
+
+    /// Start the next week if the current one is over by unix time `now`, returning the
+    /// finished week
+    ///
+    /// The very first week starts at the first call.
+    pub fn roll_week(&mut self, now: u64) -> Option<WeeklyStats> {
+        if self.week.started_at == 0 {
+            self.week.started_at = now;
+            return None;
+        }
+        if !self.week.is_over(now) {
+            return None;
+        }
+        let finished = self.week();
+        self.week = WeeklyStats::starting(now);
+        self.week_counted_from = Instant::now();
+        Some(finished)
+    }
+
+    /// Lifetime totals including the current session
+    pub fn totals(&self) -> LifetimeStats {
+        let current_session = self.connected_since
+            .map(|since| since.elapsed().as_secs())
+            .unwrap_or(0);
+        let current_outage = self.disconnected_since
+            .map(|since| since.elapsed().as_secs())
+            .unwrap_or(0);
+        LifetimeStats {
+            total_hashes: self.baseline.total_hashes + self.session_hashes,
+            shares_submitted: self.baseline.shares_submitted + self.session_shares,
+            uptime_secs: self.baseline.uptime_secs + self.session_start.elapsed().as_secs(),
+            best_share_difficulty: self.baseline.best_share_difficulty
+                .max(self.session_best_difficulty),
+            closest_call: self.baseline.closest_call.max(self.session_closest_call),
+            connected_secs: self.baseline.connected_secs
+                + self.session_connected_secs + current_session,
+            disconnected_secs: self.baseline.disconnected_secs
+                + self.session_disconnected_secs + current_outage,
+            reconnects: self.baseline.reconnects + self.session_reconnects,
+            longest_session_secs: self.baseline.longest_session_secs
+                .max(self.session_longest_secs)
+                .max(current_session),
+            // This run counts once it has started
+            runs: self.baseline.runs + 1,
+            week: self.week(),
+        }
+    }
+}
+
+/// A difficulty with an SI suffix from a thousand up, e.g. `83.7T` or `10k`, and with four
+/// decimals below that, where CPU shares usually are
+pub fn format_difficulty(difficulty: f64) -> String {
+    const SUFFIXES: [&str; 6] = ["k", "M", "G", "T", "P", "E"];
+    if !difficulty.is_finite() || difficulty < 1000.0 {
+        return format!("{:.4}", difficulty);
+    }
+    let mut scaled = difficulty;
+    let mut suffix = "";
+    for next in SUFFIXES {
+        // Past 999.95 one decimal would round up to 1000.0
+        if scaled < 999.95 {
+            break;
+        }
+        scaled /= 1000.0;
+        suffix = next;
+    }
+    let number = format!("{:.1}", scaled);
+    format!("{}{}", number.strip_suffix(".0").unwrap_or(&number), suffix)
+}
+
+/// A closest call as how much of a block it was, e.g. `1/83.7M`
+pub fn format_closest_call(closest_call: f64) -> String {
+    if closest_call <= 0.0 {
+        "none".to_string()
+    } else if closest_call >= 1.0 {
+        "a block".to_string()
+    } else if closest_call > 0.001 {
+        format!("1/{:.0}", 1.0 / closest_call)
+    } else {
+        format!("1/{}", format_difficulty(1.0 / closest_call))
+    }
+}
+
+/// Difficulty achieved by a hash, relative to the difficulty-1 target (0x00000000ffff0000...)
+///
+/// The hash is read as the little-endian number `double_sha256` gives.
+pub fn hash_difficulty(hash: &[u8]) -> f64 {
+    let hash_value = hash.iter().rev().fold(0f64, |acc, &b| acc * 256.0 + b as f64);
+    if hash_value == 0.0 {
+        return f64::INFINITY;
+    }
+    let diff1_target = 65535f64 * 2f64.powi(208);
+    diff1_target / hash_value
+}
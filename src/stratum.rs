@@ -0,0 +1,353 @@
+//! Async Stratum v1 client: subscribe, authorize, receive jobs and submit shares
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use bitcoin_solo_miner::stratum::StratumClient;
+//!
+//! let mut client = StratumClient::connect("solo.ckpool.org:3333").await?;
+//! let subscription = client.subscribe().await?;
+//! let reply = client.authorize("bc1q...", "x").await?;
+//! if !reply.accepted() {
+//!     anyhow::bail!("authorization rejected: {}", reply.reason());
+//! }
+//! let job = client.next_job().await?;
+//! println!("job {} on prevhash {} (extranonce1 {})", job.job_id, job.prevhash, subscription.extranonce1);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tracing::{debug, info, trace};
+
+use crate::bandwidth;
+use crate::error::Recovery;
+use crate::events::{self, MinerEvent};
+use crate::recording::{self, EntryKind};
+
+/// Longest pool message shown; the rest is cut off
+const MAX_POOL_MESSAGE_CHARS: usize = 500;
+
+/// What can go wrong talking to a Stratum pool
+#[derive(Debug, thiserror::Error)]
+pub enum StratumError {
+    /// The pool couldn't be reached
+    #[error(transparent)]
+    Connect(io::Error),
+    /// Reading from or writing to an established connection failed
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("Connection closed by pool")]
+    Closed,
+    #[error("Invalid message from pool: {line}")]
+    InvalidMessage {
+        line: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// A reply or notification is missing a field or has the wrong shape
+    #[error("Invalid {method} message: {problem}")]
+    Malformed {
+        method: &'static str,
+        problem: &'static str,
+    },
+    #[error("Pool rejected subscription: {0}")]
+    SubscriptionRejected(String),
+    #[error("Pool rejected authorization: {0}")]
+    AuthorizationRejected(String),
+}
+
+impl StratumError {
+    /// A refused login won't succeed on another try; anything else calls for a fresh connection
+    pub fn recovery(&self) -> Recovery {
+        match self {
+            StratumError::AuthorizationRejected(_) => Recovery::Abort,
+            _ => Recovery::Reconnect,
+        }
+    }
+}
+
+fn malformed(method: &'static str, problem: &'static str) -> StratumError {
+    StratumError::Malformed { method, problem }
+}
+
+/// A `mining.notify` job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MiningJob {
+    pub job_id: String,
+    /// Previous block hash with each 4-byte word byte-swapped, see `work::stratum_prevhash`
+    pub prevhash: String,
+    pub coinb1: String,
+    pub coinb2: String,
+    pub merkle_branch: Vec<String>,
+    pub version: String,
+    pub nbits: String,
+    pub ntime: String,
+    pub clean_jobs: bool,
+}
+
+impl MiningJob {
+    /// Parse the params of a `mining.notify` message
+    pub fn from_params(params: &Value) -> Result<Self, StratumError> {
+        if params.as_array().map(|a| a.len()).unwrap_or(0) < 9 {
+            return Err(malformed("mining.notify", "insufficient parameters"));
+        }
+        let field = |index: usize, problem| params[index].as_str()
+            .map(str::to_string)
+            .ok_or_else(|| malformed("mining.notify", problem));
+        Ok(Self {
+            job_id: field(0, "missing job_id")?,
+            prevhash: field(1, "missing prevhash")?,
+            coinb1: field(2, "missing coinb1")?,
+            coinb2: field(3, "missing coinb2")?,
+            merkle_branch: params[4].as_array()
+                .unwrap_or(&vec![])
+                .iter()
+                .map(|v| v.as_str().unwrap_or("").to_string())
+                .collect(),
+            version: field(5, "missing version")?,
+            nbits: field(6, "missing nbits")?,
+            ntime: field(7, "missing ntime")?,
+            clean_jobs: params[8].as_bool().unwrap_or(false),
+        })
+    }
+}
+
+/// The pool's reply to `mining.subscribe`
+#[derive(Debug, Clone)]
+pub struct Subscription {
+    /// Prefix the pool assigned to this connection, placed before extranonce2 in the coinbase
+    pub extranonce1: String,
+    /// Size of extranonce2 in bytes, as announced by the pool (0 if missing)
+    pub extranonce2_size: u64,
+}
+
+/// A message the pool sent on its own, such as `mining.notify` or `mining.set_difficulty`
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub method: String,
+    pub params: Value,
+}
+
+/// The pool's reply to a request
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub id: u64,
+    pub result: Value,
+    pub error: Value,
+}
+
+impl Response {
+    /// Whether the pool answered `true` without an error
+    pub fn accepted(&self) -> bool {
+        self.result == Value::Bool(true) && self.error.is_null()
+    }
+
+    /// The pool's error, for a rejected request
+    pub fn reason(&self) -> String {
+        match &self.error {
+            Value::Null => "no reason given".to_string(),
+            error => error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Response {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", json!({ "id": self.id, "result": self.result, "error": self.error }))
+    }
+}
+
+/// A connection to a Stratum v1 pool
+///
+/// Requests wait for the reply with their id; notifications that arrive in the meantime
+/// are queued and returned by `next_notification` and `next_job` in order.
+pub struct StratumClient {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+    next_id: u64,
+    notifications: VecDeque<Notification>,
+    /// The share difficulty from the last `mining.set_difficulty`
+    difficulty: Option<f64>,
+    /// Whether the pool has closed the connection
+    closed: bool,
+}
+
+impl StratumClient {
+    pub async fn connect(pool: impl ToSocketAddrs) -> Result<Self, StratumError> {
+        let stream = TcpStream::connect(pool).await.map_err(StratumError::Connect)?;
+        if recording::is_recording() {
+            let pool = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_default();
+            recording::record(EntryKind::Connected { pool });
+        }
+        let (reader, writer) = stream.into_split();
+        Ok(Self {
+            lines: BufReader::new(reader).lines(),
+            writer,
+            next_id: 1,
+            notifications: VecDeque::new(),
+            difficulty: None,
+            closed: false,
+        })
+    }
+
+    /// `mining.subscribe`, returning the extranonce1 assigned to this connection
+    pub async fn subscribe(&mut self) -> Result<Subscription, StratumError> {
+        let response = self.request("mining.subscribe", json!([])).await?;
+        if !response.error.is_null() {
+            return Err(StratumError::SubscriptionRejected(response.reason()));
+        }
+        Ok(Subscription {
+            extranonce1: response.result[1].as_str()
+                .ok_or_else(|| malformed("mining.subscribe", "missing extranonce1"))?
+                .to_string(),
+            extranonce2_size: response.result[2].as_u64().unwrap_or(0),
+        })
+    }
+
+    /// `mining.authorize`; a rejection is an `Ok` reply that isn't `accepted()`
+    pub async fn authorize(&mut self, user: &str, password: &str) -> Result<Response, StratumError> {
+        self.request("mining.authorize", json!([user, password])).await
+    }
+
+    /// `mining.submit` a share; the reply says whether the pool accepted it
+    pub async fn submit(&mut self, user: &str, job_id: &str, extranonce2: &str, ntime: &str, nonce: &str) -> Result<Response, StratumError> {
+        self.request("mining.submit", json!([user, job_id, extranonce2, ntime, nonce])).await
+    }
+
+    /// Send any request and wait for its reply
+    pub async fn request(&mut self, method: &str, params: Value) -> Result<Response, StratumError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let message = json!({ "id": id, "method": method, "params": params });
+        trace!(event = "pool_request", message = %message, "Sending {}", method);
+        let line = format!("{}\n", message);
+        self.writer.write_all(line.as_bytes()).await?;
+        bandwidth::record_sent(line.len());
+        recording::record(EntryKind::Sent { line: message.to_string() });
+
+        loop {
+            let message = self.read_message().await?;
+            if message["method"].is_string() {
+                self.queue_notification(message);
+            } else if message["id"].as_u64() == Some(id) {
+                return Ok(Response {
+                    id,
+                    result: message["result"].clone(),
+                    error: message["error"].clone(),
+                });
+            }
+        }
+    }
+
+    /// The next notification from the pool, waiting for one if none are queued
+    pub async fn next_notification(&mut self) -> Result<Notification, StratumError> {
+        loop {
+            if let Some(notification) = self.notifications.pop_front() {
+                return Ok(notification);
+            }
+            let message = self.read_message().await?;
+            // Replies to requests nobody is waiting for anymore are dropped
+            if message["method"].is_string() {
+                self.queue_notification(message);
+            }
+        }
+    }
+
+    /// The next `mining.notify` job, skipping other notifications
+    pub async fn next_job(&mut self) -> Result<MiningJob, StratumError> {
+        loop {
+            let notification = self.next_notification().await?;
+            if notification.method == "mining.notify" {
+                return MiningJob::from_params(&notification.params);
+            }
+        }
+    }
+
+    /// The share difficulty the pool last set, if it has set one
+    pub fn difficulty(&self) -> Option<f64> {
+        self.difficulty
+    }
+
+    /// Jobs that have arrived but haven't been returned by `next_job` yet, oldest first
+    pub fn queued_jobs(&self) -> Vec<MiningJob> {
+        self.notifications
+            .iter()
+            .filter(|notification| notification.method == "mining.notify")
+            .filter_map(|notification| MiningJob::from_params(&notification.params).ok())
+            .collect()
+    }
+
+    fn queue_notification(&mut self, message: Value) {
+        // Announcements from the pool operator are shown rather than queued
+        if message["method"] == "client.show_message" {
+            if let Some(text) = pool_message_text(&message["params"]) {
+                info!(event = "pool_announcement", message = %text, "Message from the pool: {}", text);
+                events::emit(MinerEvent::PoolMessage { message: text });
+            }
+            return;
+        }
+        if message["method"] == "mining.set_difficulty" {
+            if let Some(difficulty) = message["params"][0].as_f64() {
+                self.difficulty = Some(difficulty);
+            }
+        }
+        self.notifications.push_back(Notification {
+            method: message["method"].as_str().unwrap_or_default().to_string(),
+            params: message["params"].clone(),
+        });
+    }
+
+    /// Read the next JSON message, skipping blank lines
+    async fn read_message(&mut self) -> Result<Value, StratumError> {
+        loop {
+            let Some(line) = self.lines.next_line().await? else {
+                if !self.closed {
+                    self.closed = true;
+                    recording::record(EntryKind::Closed);
+                }
+                return Err(StratumError::Closed);
+            };
+            // The newline the line was split at came over the wire too
+            bandwidth::record_received(line.len() + 1);
+            if line.trim().is_empty() {
+                continue;
+            }
+            recording::record(EntryKind::Received { line: line.clone() });
+            debug!(event = "pool_message", message = %line, "Pool message");
+            return serde_json::from_str(&line).map_err(|source| StratumError::InvalidMessage { line, source });
+        }
+    }
+}
+
+/// The text of a `client.show_message`, on one line and cut to a sensible length, since
+/// it's whatever the pool chose to send; `None` if there's nothing to show
+fn pool_message_text(params: &Value) -> Option<String> {
+    let text: String = params[0]
+        .as_str()?
+        .chars()
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return None;
+    }
+    Some(match text.char_indices().nth(MAX_POOL_MESSAGE_CHARS) {
+        Some((cut, _)) => format!("{}…", &text[..cut]),
+        None => text,
+    })
+}
+
+impl Drop for StratumClient {
+    fn drop(&mut self) {
+        if !self.closed {
+            recording::record(EntryKind::Dropped);
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Getting a solution to the pool when a reply goes missing
+//!
+//! A `mining.submit` the pool doesn't answer within `submit_timeout` is sent again on the
+//! same connection, up to `submit_retries` times. A resend is the same share, so a pool
+//! that did get an earlier attempt rejects it as a duplicate, which counts as that
+//! attempt's acknowledgement. A submit still unanswered after every retry is logged as
+//! `submit_unacknowledged` and alerted on, rather than the block going missing silently.
+
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use tracing::{error, info, warn};
+
+use crate::backend::{PoolBackend, Solution, SubmitResult};
+use crate::events::{self, MinerEvent};
+
+pub const DEFAULT_SUBMIT_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_SUBMIT_RETRIES: u32 = 2;
+
+/// How long to wait for the pool to answer a submit, and how often to send it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmitPolicy {
+    /// Wait for a reply to each attempt
+    pub timeout: Duration,
+    /// Resends after the first attempt goes unanswered
+    pub retries: u32,
+}
+
+impl Default for SubmitPolicy {
+    fn default() -> Self {
+        Self { timeout: Duration::from_secs(DEFAULT_SUBMIT_TIMEOUT_SECS), retries: DEFAULT_SUBMIT_RETRIES }
+    }
+}
+
+/// The pool's verdict, and the attempt it came for
+#[derive(Debug, Clone)]
+pub struct Submitted {
+    pub result: SubmitResult,
+    /// 1 when the first attempt was answered
+    pub attempts: u32,
+}
+
+/// Whether the pool rejected a share because it already had it
+pub fn is_duplicate(result: &SubmitResult) -> bool {
+    !result.accepted && result.reason.as_deref().is_some_and(|reason| reason.to_ascii_lowercase().contains("duplicate"))
+}
+
+/// Submit `solution`, sending it again each time the pool leaves it unanswered for
+/// `policy.timeout`
+///
+/// A duplicate reject of a resend is returned as accepted, since the pool got an earlier
+/// attempt. Fails straight away if the connection does, as a resend can't get through
+/// then, and once every attempt has gone unanswered.
+pub async fn submit(backend: &mut impl PoolBackend, solution: &Solution, policy: SubmitPolicy) -> Result<Submitted> {
+    let attempts = policy.retries.saturating_add(1);
+    for attempt in 1..=attempts {
+        match tokio::time::timeout(policy.timeout, backend.submit(solution)).await {
+            Ok(Ok(result)) if attempt > 1 && is_duplicate(&result) => {
+                info!(
+                    event = "submit_acknowledged",
+                    nonce = %solution.nonce,
+                    attempt,
+                    "The pool already had the solution: an earlier attempt reached it ({})", result
+                );
+                return Ok(Submitted { result: SubmitResult { accepted: true, reason: None, reply: result.reply }, attempts: attempt });
+            }
+            Ok(Ok(result)) => return Ok(Submitted { result, attempts: attempt }),
+            Ok(Err(e)) => return Err(e),
+            Err(_) if attempt < attempts => warn!(
+                event = "submit_retry",
+                nonce = %solution.nonce,
+                attempt = attempt + 1,
+                "No reply to mining.submit within {}s, sending it again (attempt {} of {})",
+                policy.timeout.as_secs(), attempt + 1, attempts
+            ),
+            Err(_) => {}
+        }
+    }
+
+    error!(
+        event = "submit_unacknowledged",
+        job_id = %solution.job_id,
+        nonce = %solution.nonce,
+        attempts,
+        "The pool never acknowledged the solution for job {} after {} attempt(s) of {}s; it is kept in the block archive",
+        solution.job_id, attempts, policy.timeout.as_secs()
+    );
+    events::emit(MinerEvent::SubmitUnacknowledged {
+        job_id: solution.job_id.clone(),
+        nonce: solution.nonce.clone(),
+        attempts,
+        timeout_secs: policy.timeout.as_secs(),
+    });
+    Err(anyhow!("no reply after {} attempt(s) of {}s", attempts, policy.timeout.as_secs()))
+}
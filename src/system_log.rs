@@ -0,0 +1,171 @@
+use std::io::{self, Write};
+use anyhow::{Result, bail};
+use tracing::{Level, Metadata};
+use tracing_subscriber::fmt::MakeWriter;
+
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+#[cfg(unix)]
+use std::sync::Arc;
+#[cfg(unix)]
+use anyhow::Context;
+
+#[cfg(unix)]
+const IDENTIFIER: &str = "bitcoin-solo-miner";
+#[cfg(unix)]
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+#[cfg(all(unix, target_os = "macos"))]
+const SYSLOG_SOCKET: &str = "/var/run/syslog";
+#[cfg(all(unix, not(target_os = "macos")))]
+const SYSLOG_SOCKET: &str = "/dev/log";
+// syslog facility "user" (1), shifted into the PRI field
+#[cfg(unix)]
+const SYSLOG_FACILITY_USER: u8 = 1 << 3;
+
+/// Host log service that receives a copy of every log event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemLog {
+    /// systemd-journald native protocol
+    Journald,
+    /// Local syslog daemon (RFC 3164 over the /dev/log socket)
+    Syslog,
+}
+
+impl SystemLog {
+    /// Parse the `system_log` setting; empty or `none` disables it
+    pub fn parse(value: &str) -> Result<Option<Self>> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "none" => Ok(None),
+            "journald" => Ok(Some(SystemLog::Journald)),
+            "syslog" => Ok(Some(SystemLog::Syslog)),
+            other => bail!("Unknown system_log '{}' (expected 'journald', 'syslog' or 'none')", other),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SystemLog::Journald => "journald",
+            SystemLog::Syslog => "syslog",
+        }
+    }
+}
+
+/// Connected socket to journald or syslog, used as a tracing writer
+#[derive(Clone)]
+pub struct SystemLogWriter {
+    #[cfg(unix)]
+    socket: Arc<UnixDatagram>,
+    kind: SystemLog,
+}
+
+impl SystemLogWriter {
+    #[cfg(unix)]
+    pub fn connect(kind: SystemLog) -> Result<Self> {
+        let path = match kind {
+            SystemLog::Journald => JOURNALD_SOCKET,
+            SystemLog::Syslog => SYSLOG_SOCKET,
+        };
+        let socket = UnixDatagram::unbound().context("Failed to create log socket")?;
+        socket.connect(path)
+            .with_context(|| format!("Failed to connect to {}", path))?;
+        Ok(Self { socket: Arc::new(socket), kind })
+    }
+
+    #[cfg(not(unix))]
+    pub fn connect(_kind: SystemLog) -> Result<Self> {
+        bail!("system_log (journald/syslog) is only supported on Unix")
+    }
+}
+
+impl<'a> MakeWriter<'a> for SystemLogWriter {
+    type Writer = SystemLogEntry;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        SystemLogEntry { writer: self.clone(), level: Level::INFO, buffer: Vec::new() }
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        SystemLogEntry { writer: self.clone(), level: *meta.level(), buffer: Vec::new() }
+    }
+}
+
+/// One formatted event, sent as a single datagram when dropped
+pub struct SystemLogEntry {
+    writer: SystemLogWriter,
+    level: Level,
+    buffer: Vec<u8>,
+}
+
+impl SystemLogEntry {
+    #[cfg(unix)]
+    fn send(&self) -> io::Result<()> {
+        let message = String::from_utf8_lossy(&self.buffer);
+        let message = message.trim_end();
+        if message.is_empty() {
+            return Ok(());
+        }
+        let datagram = match self.writer.kind {
+            SystemLog::Journald => journald_datagram(self.level, message),
+            SystemLog::Syslog => format!(
+                "<{}>{}[{}]: {}",
+                SYSLOG_FACILITY_USER + severity(self.level),
+                IDENTIFIER,
+                std::process::id(),
+                message
+            ).into_bytes(),
+        };
+        self.writer.socket.send(&datagram).map(|_| ())
+    }
+
+    #[cfg(not(unix))]
+    fn send(&self) -> io::Result<()> {
+        let _ = (&self.writer.kind, self.level);
+        Ok(())
+    }
+}
+
+impl Write for SystemLogEntry {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SystemLogEntry {
+    fn drop(&mut self) {
+        // Logging must never take the miner down, so a missing daemon is ignored
+        let _ = self.send();
+    }
+}
+
+/// syslog severity for a tracing level
+#[cfg(unix)]
+fn severity(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+
+/// Encode a journald native-protocol entry
+///
+/// MESSAGE uses the length-prefixed form because block reports span several lines.
+#[cfg(unix)]
+fn journald_datagram(level: Level, message: &str) -> Vec<u8> {
+    let mut datagram = format!(
+        "PRIORITY={}\nSYSLOG_IDENTIFIER={}\nSYSLOG_PID={}\nMESSAGE\n",
+        severity(level),
+        IDENTIFIER,
+        std::process::id()
+    ).into_bytes();
+    datagram.extend_from_slice(&(message.len() as u64).to_le_bytes());
+    datagram.extend_from_slice(message.as_bytes());
+    datagram.push(b'\n');
+    datagram
+}
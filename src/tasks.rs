@@ -0,0 +1,117 @@
+//! The miner's background tasks and the order they stop in
+//!
+//! Every task belongs to a `Stage`. Each stage has a cancellation token, a child of one
+//! root token, and stopping a stage cancels its token and waits for its tasks. A task can
+//! hand child tokens of its stage's token to work it spawns, so that work stops with it.
+//! Dropping `Tasks` cancels the root, so nothing outlives the miner even on an early return.
+
+use std::future::Future;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{debug, warn, Instrument};
+
+/// How long a stage's tasks get to stop before they are aborted
+const STAGE_STOP_TIMEOUT_SECS: u64 = 5;
+
+/// When a task stops during shutdown, in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Remote control and signals, stopped first so nothing resumes or restarts mining
+    /// while it winds down
+    Api,
+    /// The block height listener, watchdog and stats persistence
+    Mining,
+    /// Event consumers (stats, metrics, notifications, hooks, scripts), stopped last so
+    /// the final events still reach them
+    Notifiers,
+}
+
+impl Stage {
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Api => "api",
+            Stage::Mining => "mining",
+            Stage::Notifiers => "notifiers",
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Stage::Api => 0,
+            Stage::Mining => 1,
+            Stage::Notifiers => 2,
+        }
+    }
+}
+
+struct StageTasks {
+    token: CancellationToken,
+    handles: Vec<(&'static str, JoinHandle<()>)>,
+}
+
+/// Background tasks grouped by stage
+pub struct Tasks {
+    root: CancellationToken,
+    stages: [StageTasks; 3],
+}
+
+impl Tasks {
+    pub fn new() -> Self {
+        let root = CancellationToken::new();
+        let stage = || StageTasks { token: root.child_token(), handles: Vec::new() };
+        let stages = [stage(), stage(), stage()];
+        Self { root, stages }
+    }
+
+    /// A token cancelled when `stage` stops
+    pub fn token(&self, stage: Stage) -> CancellationToken {
+        self.stages[stage.index()].token.child_token()
+    }
+
+    /// Run `task` in the current span until it finishes or `stage` stops, when it is dropped
+    pub fn spawn(&mut self, stage: Stage, name: &'static str, task: impl Future<Output = ()> + Send + 'static) {
+        self.spawn_with(stage, name, |token| async move {
+            token.run_until_cancelled(task).await;
+        });
+    }
+
+    /// Run a task that watches its stage's token itself, for one that should finish what
+    /// it is doing (e.g. deliver queued events) before it stops
+    pub fn spawn_with<F>(&mut self, stage: Stage, name: &'static str, task: impl FnOnce(CancellationToken) -> F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let future = task(self.token(stage));
+        let handle = tokio::spawn(future.in_current_span());
+        self.stages[stage.index()].handles.push((name, handle));
+    }
+
+    /// Cancel `stage` and wait for its tasks, aborting any that take too long
+    ///
+    /// Stages are meant to stop in the order of `Stage`; stopping one twice is a no-op.
+    pub async fn stop(&mut self, stage: Stage) {
+        let stage_tasks = &mut self.stages[stage.index()];
+        stage_tasks.token.cancel();
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(STAGE_STOP_TIMEOUT_SECS);
+        for (name, mut handle) in std::mem::take(&mut stage_tasks.handles) {
+            if tokio::time::timeout_at(deadline, &mut handle).await.is_err() {
+                warn!(event = "task_stuck", stage = stage.name(), task = name, "Task {} didn't stop within {}s, aborting it", name, STAGE_STOP_TIMEOUT_SECS);
+                handle.abort();
+            }
+        }
+        debug!(event = "stage_stopped", stage = stage.name(), "Stopped the {} tasks", stage.name());
+    }
+}
+
+impl Default for Tasks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Tasks {
+    fn drop(&mut self) {
+        self.root.cancel();
+    }
+}
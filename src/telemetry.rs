@@ -0,0 +1,238 @@
+use std::time::Duration;
+use anyhow::Result;
+
+use crate::events::MinerEvent;
+#[cfg(feature = "otel")]
+use crate::hashing;
+use crate::sensors::SensorReadings;
+
+#[cfg(feature = "otel")]
+use std::sync::OnceLock;
+#[cfg(feature = "otel")]
+use anyhow::Context;
+#[cfg(feature = "otel")]
+use opentelemetry::KeyValue;
+#[cfg(feature = "otel")]
+use opentelemetry::metrics::{Counter, Gauge, Histogram, MeterProvider};
+#[cfg(feature = "otel")]
+use opentelemetry::trace::TracerProvider;
+#[cfg(feature = "otel")]
+use opentelemetry_otlp::WithExportConfig;
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::Resource;
+
+#[cfg(feature = "otel")]
+const SERVICE_NAME: &str = "bitcoin-solo-miner";
+/// Bucket bounds in seconds for one batch of hashes, normally well under a millisecond;
+/// the upper buckets catch threads that stall
+#[cfg(feature = "otel")]
+const BATCH_TIME_BUCKETS: [f64; 14] = [0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0];
+/// Bucket bounds in seconds for round trips to the pool; the SDK's default buckets are
+/// meant for milliseconds and would put nearly every round trip in the first one
+#[cfg(feature = "otel")]
+const ROUND_TRIP_BUCKETS: [f64; 13] = [0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+/// Metric instruments, registered once the OTLP exporter is up
+#[cfg(feature = "otel")]
+struct Instruments {
+    hashrate: Gauge<f64>,
+    hashes: Counter<u64>,
+    batch_time: Histogram<f64>,
+    submit_round_trip: Histogram<f64>,
+    pool_latency: Histogram<f64>,
+    cpu_temperature: Gauge<f64>,
+    fan_speed: Gauge<u64>,
+    cpu_frequency: Gauge<f64>,
+    /// `backend`, on the share and pool metrics, which belong to no one thread
+    backend_label: [KeyValue; 1],
+}
+
+#[cfg(feature = "otel")]
+static INSTRUMENTS: OnceLock<Instruments> = OnceLock::new();
+
+/// OTLP trace and metric pipelines; dropping this flushes and shuts them down
+pub struct Telemetry {
+    #[cfg(feature = "otel")]
+    tracer_provider: SdkTracerProvider,
+    #[cfg(feature = "otel")]
+    _meter_provider: SdkMeterProvider,
+}
+
+impl Telemetry {
+    /// Start exporting traces and metrics over OTLP/HTTP to `endpoint` (e.g. http://localhost:4318)
+    #[cfg(feature = "otel")]
+    pub fn init(endpoint: &str) -> Result<Self> {
+        let endpoint = endpoint.trim_end_matches('/');
+        let resource = Resource::builder().with_service_name(SERVICE_NAME).build();
+
+        let span_exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/traces", endpoint))
+            .build()
+            .context("Failed to build OTLP span exporter")?;
+        let tracer_provider = SdkTracerProvider::builder()
+            .with_batch_exporter(span_exporter)
+            .with_resource(resource.clone())
+            .build();
+
+        let metric_exporter = opentelemetry_otlp::MetricExporter::builder()
+            .with_http()
+            .with_endpoint(format!("{}/v1/metrics", endpoint))
+            .build()
+            .context("Failed to build OTLP metric exporter")?;
+        let meter_provider = SdkMeterProvider::builder()
+            .with_periodic_exporter(metric_exporter)
+            .with_resource(resource)
+            .build();
+
+        let meter = meter_provider.meter(SERVICE_NAME);
+        let _ = INSTRUMENTS.set(Instruments {
+            hashrate: meter
+                .f64_gauge("miner.hashrate")
+                .with_unit("H/s")
+                .with_description("Hash rate of one hashing thread over its last batch")
+                .build(),
+            hashes: meter
+                .u64_counter("miner.hashes")
+                .with_description("Total hashes computed")
+                .build(),
+            batch_time: meter
+                .f64_histogram("miner.hashing.batch_time")
+                .with_unit("s")
+                .with_description("Time one thread takes to hash a batch of nonces")
+                .with_boundaries(BATCH_TIME_BUCKETS.to_vec())
+                .build(),
+            submit_round_trip: meter
+                .f64_histogram("miner.submit.round_trip")
+                .with_unit("s")
+                .with_description("Time between sending mining.submit and the pool's response")
+                .with_boundaries(ROUND_TRIP_BUCKETS.to_vec())
+                .build(),
+            pool_latency: meter
+                .f64_histogram("miner.pool.latency")
+                .with_unit("s")
+                .with_description("Round trip of any request to the pool, submits and pings alike")
+                .with_boundaries(ROUND_TRIP_BUCKETS.to_vec())
+                .build(),
+            cpu_temperature: meter
+                .f64_gauge("miner.cpu.temperature")
+                .with_unit("Cel")
+                .with_description("Hottest CPU temperature sensor")
+                .build(),
+            fan_speed: meter
+                .u64_gauge("miner.fan.speed")
+                .with_unit("{rpm}")
+                .with_description("Fastest fan")
+                .build(),
+            cpu_frequency: meter
+                .f64_gauge("miner.cpu.frequency")
+                .with_unit("MHz")
+                .with_description("Average current clock across cores")
+                .build(),
+            backend_label: [KeyValue::new("backend", hashing::backend())],
+        });
+
+        Ok(Self {
+            tracer_provider,
+            _meter_provider: meter_provider,
+        })
+    }
+
+    #[cfg(not(feature = "otel"))]
+    pub fn init(_endpoint: &str) -> Result<Self> {
+        anyhow::bail!("OTLP export requires building with `--features otel`")
+    }
+
+    /// Tracer used to bridge tracing spans into OTLP
+    #[cfg(feature = "otel")]
+    pub fn tracer(&self) -> SdkTracer {
+        self.tracer_provider.tracer(SERVICE_NAME)
+    }
+}
+
+/// The `backend` and `device` labels on one hashing thread's metrics
+#[derive(Debug, Clone)]
+pub struct HashingLabels {
+    #[cfg(feature = "otel")]
+    labels: [KeyValue; 2],
+}
+
+impl HashingLabels {
+    /// Labels for the thread hashing as `device` of `backend`; CPU backends number their
+    /// devices by worker thread
+    pub fn new(backend: &str, device: usize) -> Self {
+        #[cfg(feature = "otel")]
+        {
+            Self { labels: [KeyValue::new("backend", backend.to_string()), KeyValue::new("device", device.to_string())] }
+        }
+        #[cfg(not(feature = "otel"))]
+        {
+            let _ = (backend, device);
+            Self {}
+        }
+    }
+}
+
+/// Update the metrics an event affects
+pub fn record_event(event: &MinerEvent) {
+    if let MinerEvent::ShareAccepted { round_trip_secs, .. } = event {
+        record_submit_round_trip(Duration::from_secs_f64(*round_trip_secs));
+    }
+}
+
+/// Record one thread's batch of `count` nonces hashed in `elapsed`: the hashes, the batch
+/// time and the thread's hash rate over the batch
+pub fn record_batch(count: u64, elapsed: Duration, labels: &HashingLabels) {
+    #[cfg(feature = "otel")]
+    if let Some(instruments) = INSTRUMENTS.get() {
+        instruments.hashes.add(count, &labels.labels);
+        instruments.batch_time.record(elapsed.as_secs_f64(), &labels.labels);
+        if !elapsed.is_zero() {
+            instruments.hashrate.record(count as f64 / elapsed.as_secs_f64(), &labels.labels);
+        }
+    }
+    #[cfg(not(feature = "otel"))]
+    let _ = (count, elapsed, labels);
+}
+
+/// Record the round-trip time of a mining.submit
+pub fn record_submit_round_trip(elapsed: Duration) {
+    #[cfg(feature = "otel")]
+    if let Some(instruments) = INSTRUMENTS.get() {
+        instruments.submit_round_trip.record(elapsed.as_secs_f64(), &instruments.backend_label);
+    }
+    #[cfg(not(feature = "otel"))]
+    let _ = elapsed;
+}
+
+/// Record the round trip of a request to the pool
+pub fn record_pool_latency(elapsed: Duration) {
+    #[cfg(feature = "otel")]
+    if let Some(instruments) = INSTRUMENTS.get() {
+        instruments.pool_latency.record(elapsed.as_secs_f64(), &instruments.backend_label);
+    }
+    #[cfg(not(feature = "otel"))]
+    let _ = elapsed;
+}
+
+/// Record the readings a machine's sensors offer
+pub fn record_sensors(readings: &SensorReadings) {
+    #[cfg(feature = "otel")]
+    if let Some(instruments) = INSTRUMENTS.get() {
+        if let Some(temp) = readings.cpu_temp_c {
+            instruments.cpu_temperature.record(temp, &[]);
+        }
+        if let Some(rpm) = readings.fan_rpm {
+            instruments.fan_speed.record(rpm, &[]);
+        }
+        if let Some(mhz) = readings.cpu_mhz {
+            instruments.cpu_frequency.record(mhz, &[]);
+        }
+    }
+    #[cfg(not(feature = "otel"))]
+    let _ = readings;
+}
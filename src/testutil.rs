@@ -0,0 +1,313 @@
+//! A scriptable Stratum v1 pool for tests, built with the `testutil` feature
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use bitcoin_solo_miner::stratum::StratumClient;
+//! use bitcoin_solo_miner::testutil::{notify_params, MockPool, MockPoolConfig};
+//!
+//! let pool = MockPool::start(MockPoolConfig {
+//!     jobs: vec![notify_params("job1", "207fffff")],
+//!     ..Default::default()
+//! }).await?;
+//! let mut client = StratumClient::connect(pool.addr()).await?;
+//! client.subscribe().await?;
+//! client.authorize("1BoatSLRHtKNngkdXEeobR76b53LETtpyT", "x").await?;
+//! assert_eq!(client.next_job().await?.job_id, "job1");
+//! # Ok(())
+//! # }
+//! ```
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{Result, Context};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// Misbehaviour the mock pool can be told to show
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// Write every message in chunks of this many bytes, pausing between them
+    PartialFrames { chunk: usize, delay: Duration },
+    /// Close the connection once this many messages have been sent on it
+    DisconnectAfter(usize),
+    /// Never answer requests for this method
+    Ignore(String),
+    /// Leave the first this many requests for this method unanswered, as if the reply
+    /// was lost
+    IgnoreFirst(String, usize),
+    /// Wait this long before every reply
+    Delay(Duration),
+    /// Send this line before the first reply, e.g. something that isn't JSON
+    Garbage(String),
+}
+
+/// How the mock pool behaves
+#[derive(Debug, Clone)]
+pub struct MockPoolConfig {
+    pub extranonce1: String,
+    pub extranonce2_size: u64,
+    /// `mining.notify` params sent after each successful authorization
+    pub jobs: Vec<Value>,
+    /// Refuse `mining.authorize` with this message
+    pub reject_authorization: Option<String>,
+    /// Refuse every `mining.submit` with this message
+    pub reject_submissions: Option<String>,
+    pub faults: Vec<Fault>,
+}
+
+impl Default for MockPoolConfig {
+    fn default() -> Self {
+        Self {
+            extranonce1: "f000000f".to_string(),
+            extranonce2_size: 4,
+            jobs: Vec::new(),
+            reject_authorization: None,
+            reject_submissions: None,
+            faults: Vec::new(),
+        }
+    }
+}
+
+/// What a test tells a connection to do
+#[derive(Debug)]
+enum Command {
+    Send(Value),
+    Disconnect,
+}
+
+#[derive(Default)]
+struct Shared {
+    requests: Mutex<Vec<Value>>,
+    clients: Mutex<Vec<mpsc::UnboundedSender<Command>>>,
+}
+
+/// A Stratum v1 pool on a local port, stopped when dropped
+pub struct MockPool {
+    addr: SocketAddr,
+    shared: Arc<Shared>,
+    task: JoinHandle<()>,
+}
+
+impl MockPool {
+    /// Listen on a free port on 127.0.0.1
+    pub async fn start(config: MockPoolConfig) -> Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.context("Failed to start the mock pool")?;
+        let addr = listener.local_addr()?;
+        let shared = Arc::new(Shared::default());
+        let config = Arc::new(config);
+        let accept_shared = Arc::clone(&shared);
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let (commands_tx, commands_rx) = mpsc::unbounded_channel();
+                accept_shared.clients.lock().unwrap().push(commands_tx);
+                tokio::spawn(serve(stream, Arc::clone(&config), Arc::clone(&accept_shared), commands_rx));
+            }
+        });
+        Ok(Self { addr, shared, task })
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// `host:port`, as the miner's pool setting takes it
+    pub fn url(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// Every request received so far, on any connection, in order
+    pub fn requests(&self) -> Vec<Value> {
+        self.shared.requests.lock().unwrap().clone()
+    }
+
+    /// The received requests for one method
+    pub fn requests_for(&self, method: &str) -> Vec<Value> {
+        self.requests().into_iter().filter(|request| request["method"] == method).collect()
+    }
+
+    /// Connections still open
+    pub fn connections(&self) -> usize {
+        let mut clients = self.shared.clients.lock().unwrap();
+        clients.retain(|client| !client.is_closed());
+        clients.len()
+    }
+
+    /// Send a `mining.notify` with these params to every open connection
+    pub fn notify(&self, params: Value) {
+        self.broadcast(|| Command::Send(json!({"id": null, "method": "mining.notify", "params": params.clone()})));
+    }
+
+    /// Send a `client.show_message` announcement to every open connection
+    pub fn show_message(&self, message: &str) {
+        self.broadcast(|| Command::Send(json!({"id": null, "method": "client.show_message", "params": [message]})));
+    }
+
+    /// Close every open connection, as a pool restart would
+    pub fn disconnect_all(&self) {
+        self.broadcast(|| Command::Disconnect);
+    }
+
+    fn broadcast(&self, command: impl Fn() -> Command) {
+        let mut clients = self.shared.clients.lock().unwrap();
+        clients.retain(|client| client.send(command()).is_ok());
+    }
+}
+
+impl Drop for MockPool {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.disconnect_all();
+    }
+}
+
+/// `mining.notify` params for a job the miner can work, with an empty merkle branch
+///
+/// `207fffff` is the regtest target, which nearly every hash meets.
+pub fn notify_params(job_id: &str, nbits: &str) -> Value {
+    json!([
+        job_id,
+        "00".repeat(32),
+        "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff20",
+        "ffffffff0100f2052a010000001976a914000000000000000000000000000000000000000088ac00000000",
+        [],
+        "20000000",
+        nbits,
+        "5f5e1000",
+        true
+    ])
+}
+
+/// One connection's writer, applying the configured faults
+struct Writer {
+    writer: OwnedWriteHalf,
+    config: Arc<MockPoolConfig>,
+    sent: usize,
+    garbage_sent: bool,
+}
+
+impl Writer {
+    /// Send a message; false once the connection should be closed
+    async fn send(&mut self, message: &Value) -> bool {
+        let disconnect_after = self.config.faults.iter().find_map(|fault| match fault {
+            Fault::DisconnectAfter(count) => Some(*count),
+            _ => None,
+        });
+        if disconnect_after.is_some_and(|count| self.sent >= count) {
+            return false;
+        }
+        let line = format!("{}\n", message);
+        let partial = self.config.faults.iter().find_map(|fault| match fault {
+            Fault::PartialFrames { chunk, delay } => Some((*chunk, *delay)),
+            _ => None,
+        });
+        let written = match partial {
+            Some((chunk, delay)) => {
+                let mut written = Ok(());
+                for piece in line.as_bytes().chunks(chunk.max(1)) {
+                    written = self.writer.write_all(piece).await;
+                    if written.is_err() {
+                        break;
+                    }
+                    let _ = self.writer.flush().await;
+                    tokio::time::sleep(delay).await;
+                }
+                written
+            }
+            None => self.writer.write_all(line.as_bytes()).await,
+        };
+        self.sent += 1;
+        written.is_ok() && disconnect_after.is_none_or(|count| self.sent < count)
+    }
+
+    async fn reply(&mut self, message: &Value) -> bool {
+        for fault in &self.config.faults {
+            match fault {
+                Fault::Delay(delay) => tokio::time::sleep(*delay).await,
+                Fault::Garbage(line) if !self.garbage_sent => {
+                    self.garbage_sent = true;
+                    if self.writer.write_all(format!("{}\n", line).as_bytes()).await.is_err() {
+                        return false;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.send(message).await
+    }
+}
+
+async fn serve(stream: TcpStream, config: Arc<MockPoolConfig>, shared: Arc<Shared>, mut commands: mpsc::UnboundedReceiver<Command>) {
+    let (reader, writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut writer = Writer { writer, config: Arc::clone(&config), sent: 0, garbage_sent: false };
+    loop {
+        let line = tokio::select! {
+            line = lines.next_line() => match line {
+                Ok(Some(line)) => line,
+                _ => return,
+            },
+            command = commands.recv() => match command {
+                Some(Command::Send(message)) => {
+                    if !writer.send(&message).await {
+                        return;
+                    }
+                    continue;
+                }
+                Some(Command::Disconnect) | None => return,
+            },
+        };
+        let Ok(request) = serde_json::from_str::<Value>(&line) else {
+            continue;
+        };
+        let method = request["method"].as_str().unwrap_or_default();
+        let (earlier, duplicate) = {
+            let mut requests = shared.requests.lock().unwrap();
+            let earlier: Vec<&Value> = requests.iter().filter(|earlier| earlier["method"] == method).collect();
+            let duplicate = method == "mining.submit" && earlier.iter().any(|earlier| earlier["params"] == request["params"]);
+            let earlier = earlier.len();
+            requests.push(request.clone());
+            (earlier, duplicate)
+        };
+        let ignored = config.faults.iter().any(|fault| match fault {
+            Fault::Ignore(ignored) => ignored == method,
+            Fault::IgnoreFirst(ignored, count) => ignored == method && earlier < *count,
+            _ => false,
+        });
+        if ignored {
+            continue;
+        }
+        let id = request["id"].clone();
+        let (result, error) = match method {
+            "mining.subscribe" => (
+                json!([[["mining.notify", "1"]], config.extranonce1, config.extranonce2_size]),
+                Value::Null,
+            ),
+            "mining.authorize" => match &config.reject_authorization {
+                Some(reason) => (json!(false), json!([24, reason, null])),
+                None => (json!(true), Value::Null),
+            },
+            "mining.submit" => match &config.reject_submissions {
+                Some(reason) => (json!(false), json!([23, reason, null])),
+                // Like a real pool, a share it already has is a duplicate
+                None if duplicate => (json!(false), json!([22, "Duplicate share", null])),
+                None => (json!(true), Value::Null),
+            },
+            _ => (Value::Null, json!([20, "Unsupported method", null])),
+        };
+        let authorized = method == "mining.authorize" && error.is_null();
+        if !writer.reply(&json!({"id": id, "result": result, "error": error})).await {
+            return;
+        }
+        if authorized {
+            for job in &config.jobs {
+                if !writer.send(&json!({"id": null, "method": "mining.notify", "params": job})).await {
+                    return;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::fmt;
+use anyhow::{Result, bail};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Local, NaiveTime, Utc};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+
+/// ISO-8601 with milliseconds and the UTC offset, e.g. `2024-05-01T12:34:56.789+00:00`
+pub const DEFAULT_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3f%:z";
+
+/// Time zone timestamps are shown in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimeZone {
+    #[default]
+    Utc,
+    /// The host's local time zone
+    Local,
+    /// A fixed offset from UTC, such as `+02:00`
+    Fixed(FixedOffset),
+}
+
+impl TimeZone {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "" | "utc" | "z" => Ok(TimeZone::Utc),
+            "local" => Ok(TimeZone::Local),
+            offset => match offset.parse::<FixedOffset>() {
+                Ok(offset) => Ok(TimeZone::Fixed(offset)),
+                Err(_) => bail!("Unknown timezone '{}' (expected 'utc', 'local' or an offset like '+02:00')", value.trim()),
+            },
+        }
+    }
+
+    /// The wall-clock time of day of `time` in this zone
+    pub fn time_of_day(self, time: DateTime<Utc>) -> NaiveTime {
+        match self {
+            TimeZone::Utc => time.time(),
+            TimeZone::Local => time.with_timezone(&Local).time(),
+            TimeZone::Fixed(offset) => time.with_timezone(&offset).time(),
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            TimeZone::Utc => "utc".to_string(),
+            TimeZone::Local => "local".to_string(),
+            TimeZone::Fixed(offset) => offset.to_string(),
+        }
+    }
+}
+
+/// How log lines and block records are timestamped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timestamps {
+    format: String,
+    zone: TimeZone,
+}
+
+impl Default for Timestamps {
+    fn default() -> Self {
+        Self { format: DEFAULT_FORMAT.to_string(), zone: TimeZone::Utc }
+    }
+}
+
+impl Timestamps {
+    /// `format` uses strftime syntax (see chrono's `format::strftime`); empty means `DEFAULT_FORMAT`
+    pub fn new(format: &str, zone: TimeZone) -> Result<Self> {
+        let format = if format.is_empty() { DEFAULT_FORMAT } else { format };
+        if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+            bail!("Invalid timestamp_format '{}'", format);
+        }
+        Ok(Self { format: format.to_string(), zone })
+    }
+
+    pub fn format(&self, time: DateTime<Utc>) -> String {
+        match self.zone {
+            TimeZone::Utc => time.format(&self.format).to_string(),
+            TimeZone::Local => time.with_timezone(&Local).format(&self.format).to_string(),
+            TimeZone::Fixed(offset) => time.with_timezone(&offset).format(&self.format).to_string(),
+        }
+    }
+
+    /// The strftime pattern in use
+    pub fn pattern(&self) -> &str {
+        &self.format
+    }
+
+    pub fn zone(&self) -> TimeZone {
+        self.zone
+    }
+
+    pub fn now(&self) -> String {
+        self.format(Utc::now())
+    }
+
+    /// Format a unix timestamp in seconds
+    pub fn format_unix(&self, secs: u64) -> String {
+        match DateTime::from_timestamp(secs as i64, 0) {
+            Some(time) => self.format(time),
+            None => secs.to_string(),
+        }
+    }
+}
+
+impl FormatTime for Timestamps {
+    fn format_time(&self, w: &mut Writer<'_>) -> fmt::Result {
+        write!(w, "{}", self.now())
+    }
+}
@@ -0,0 +1,58 @@
+//! Watching the share difficulty the pool assigns
+//!
+//! Pools with vardiff retune each miner's share difficulty to its hash rate, in small
+//! steps. A jump by more than `difficulty_change_factor` either way usually means the hash
+//! rate changed a lot, or that something went wrong on the connection, such as a pool that
+//! lost track of the miner and started it over. Each such jump is logged as
+//! `share_difficulty_changed`, emitted as an event and sent to Telegram.
+
+use std::fmt;
+
+use crate::stats::format_difficulty;
+
+/// How many times higher or lower the share difficulty has to get to be reported
+pub const DEFAULT_DIFFICULTY_CHANGE_FACTOR: f64 = 4.0;
+
+/// A share difficulty change worth reporting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DifficultyChange {
+    pub from: f64,
+    pub to: f64,
+}
+
+impl DifficultyChange {
+    /// How many times higher the new difficulty is; below 1 for a drop
+    pub fn ratio(&self) -> f64 {
+        self.to / self.from
+    }
+}
+
+/// e.g. `from 10k to 50k (5.0x higher)`
+impl fmt::Display for DifficultyChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (times, direction) = if self.to >= self.from { (self.ratio(), "higher") } else { (1.0 / self.ratio(), "lower") };
+        write!(f, "from {} to {} ({:.1}x {})", format_difficulty(self.from), format_difficulty(self.to), times, direction)
+    }
+}
+
+/// The share difficulty seen last, across pool sessions
+#[derive(Debug, Default)]
+pub struct DifficultyWatch {
+    last: Option<f64>,
+}
+
+impl DifficultyWatch {
+    /// Take in the pool's share difficulty, returning the change if it moved by more than
+    /// `factor` either way since the last one seen
+    ///
+    /// The first difficulty seen is only remembered, a difficulty that isn't a positive
+    /// number is ignored, and a `factor` of 1 or less turns reporting off.
+    pub fn observe(&mut self, difficulty: f64, factor: f64) -> Option<DifficultyChange> {
+        if !(difficulty.is_finite() && difficulty > 0.0) {
+            return None;
+        }
+        let from = self.last.replace(difficulty)?;
+        let ratio = difficulty / from;
+        (factor > 1.0 && (ratio > factor || ratio < 1.0 / factor)).then_some(DifficultyChange { from, to: difficulty })
+    }
+}
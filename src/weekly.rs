@@ -0,0 +1,111 @@
+//! The weekly statistics report
+//!
+//! A week's figures are kept in the state file next to the lifetime totals, so restarts
+//! during the week don't lose them. Once a week has passed since it started, the roll-up
+//! is logged as `weekly_report`, emitted as a `weekly_report` event and sent to Telegram,
+//! and the next week starts.
+
+use serde::{Deserialize, Serialize};
+
+use crate::stats::format_difficulty;
+
+pub const WEEK_SECS: u64 = 7 * 86_400;
+/// How often the reporter checks whether the week is over
+pub const WEEKLY_CHECK_INTERVAL_SECS: u64 = 600;
+
+/// Hashes it takes on average to find a block at difficulty 1
+const HASHES_PER_DIFFICULTY: f64 = 4_294_967_296.0;
+
+/// One week's figures
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeeklyStats {
+    /// Unix time the week started; 0 before the first run
+    pub started_at: u64,
+    pub hashes: u64,
+    /// Time the miner was running
+    pub uptime_secs: u64,
+    pub connected_secs: u64,
+    pub disconnected_secs: u64,
+    /// Solutions submitted to the pool, i.e. blocks found
+    pub blocks_submitted: u64,
+    pub best_share_difficulty: f64,
+    /// Blocks the hashes would find on average, at the network difficulty they were done at
+    pub expected_blocks: f64,
+    /// Energy used, if power accounting is on
+    pub energy_kwh: f64,
+    /// What that energy cost, if an electricity price is set
+    pub energy_cost: Option<f64>,
+}
+
+impl WeeklyStats {
+    /// An empty week starting at `now`
+    pub fn starting(now: u64) -> Self {
+        Self { started_at: now, ..Default::default() }
+    }
+
+    /// Whether a full week has passed since the week started
+    pub fn is_over(&self, now: u64) -> bool {
+        now.saturating_sub(self.started_at) >= WEEK_SECS
+    }
+
+    /// Average hash rate over the time the miner was running
+    pub fn average_hashrate(&self) -> f64 {
+        self.hashes as f64 / self.uptime_secs.max(1) as f64
+    }
+
+    /// Percentage of tracked time spent connected to the pool
+    pub fn availability_percent(&self) -> f64 {
+        let tracked = self.connected_secs + self.disconnected_secs;
+        if tracked == 0 {
+            return 100.0;
+        }
+        self.connected_secs as f64 * 100.0 / tracked as f64
+    }
+
+    /// e.g. `0 found, 1.68e-14 expected (1 in 59.4T)`
+    pub fn odds_line(&self) -> String {
+        let odds = if self.expected_blocks > 0.0 {
+            format!(" (1 in {})", format_difficulty(1.0 / self.expected_blocks))
+        } else {
+            String::new()
+        };
+        format!("{} found, {:.2e} expected{}", self.blocks_submitted, self.expected_blocks, odds)
+    }
+
+    /// e.g. `2.47 kWh, cost 0.74`, or `None` without power accounting
+    pub fn energy_line(&self) -> Option<String> {
+        if self.energy_kwh <= 0.0 {
+            return None;
+        }
+        let mut line = format!("{:.2} kWh", self.energy_kwh);
+        if let Some(cost) = self.energy_cost {
+            line.push_str(&format!(", cost {:.2}", cost));
+        }
+        Some(line)
+    }
+
+    pub fn summary_line(&self) -> String {
+        let mut line = format!(
+            "{} hashes | avg {:.0} h/s | uptime {:.1}h | availability {:.2}% | best share difficulty {} | blocks {}",
+            self.hashes,
+            self.average_hashrate(),
+            self.uptime_secs as f64 / 3600.0,
+            self.availability_percent(),
+            format_difficulty(self.best_share_difficulty),
+            self.odds_line()
+        );
+        if let Some(energy) = self.energy_line() {
+            line.push_str(&format!(" | {}", energy));
+        }
+        line
+    }
+}
+
+/// Blocks `hashes` would find on average at `network_difficulty`
+pub fn expected_blocks(hashes: u64, network_difficulty: f64) -> f64 {
+    if network_difficulty <= 0.0 {
+        return 0.0;
+    }
+    hashes as f64 / (network_difficulty * HASHES_PER_DIFFICULTY)
+}
@@ -0,0 +1,226 @@
+use std::str::FromStr;
+use bitcoin::block::{Header, Version};
+use bitcoin::consensus::serialize;
+use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::{BlockHash, CompactTarget, Target, TxMerkleNode};
+
+use crate::clock::RandomSource;
+use crate::error::Recovery;
+use crate::stratum::MiningJob;
+
+/// A job whose fields can't be turned into a block header
+#[derive(Debug, thiserror::Error)]
+pub enum HashingError {
+    #[error("Invalid {field} hex")]
+    InvalidHex {
+        field: &'static str,
+        #[source]
+        source: hex::FromHexError,
+    },
+    #[error("Invalid {field} '{value}'")]
+    InvalidField {
+        field: &'static str,
+        value: String,
+    },
+    #[error("Coinbase witness commitment {found} isn't the {expected} the block needs")]
+    WitnessCommitment {
+        expected: String,
+        found: String,
+    },
+    #[error("Invalid nbits: {0}")]
+    InvalidNbits(&'static str),
+}
+
+impl HashingError {
+    /// The job is bad, not the connection: wait for the pool's next one
+    pub fn recovery(&self) -> Recovery {
+        Recovery::Retry
+    }
+}
+
+fn decode(field: &'static str, hex_str: &str) -> Result<Vec<u8>, HashingError> {
+    hex::decode(hex_str).map_err(|source| HashingError::InvalidHex { field, source })
+}
+
+/// Size of the extranonce2 used with pools that don't announce one
+pub const EXTRANONCE2_SIZE_BYTES: usize = 4; // 4 bytes = 8 hex characters
+
+/// A 4-byte header field, sent by Stratum as the big-endian hex of its value
+fn decode_u32(field: &'static str, hex_str: &str) -> Result<u32, HashingError> {
+    if hex_str.len() != 8 {
+        return Err(HashingError::InvalidField { field, value: hex_str.to_string() });
+    }
+    u32::from_str_radix(hex_str, 16).map_err(|_| HashingError::InvalidField { field, value: hex_str.to_string() })
+}
+
+/// The previous block hash from a `mining.notify` prevhash
+///
+/// Stratum sends the hash in internal byte order but with the bytes of every 4-byte word
+/// reversed, so each word has to be swapped back before it goes into the header.
+pub fn stratum_prevhash(prevhash: &str) -> Result<BlockHash, HashingError> {
+    let mut bytes: [u8; 32] = decode("prevhash", prevhash)?
+        .try_into()
+        .map_err(|_| HashingError::InvalidHex { field: "prevhash", source: hex::FromHexError::InvalidStringLength })?;
+    for word in bytes.chunks_exact_mut(4) {
+        word.reverse();
+    }
+    Ok(BlockHash::from_byte_array(bytes))
+}
+
+/// A previous block hash in the word-swapped form `mining.notify` sends it in, the
+/// reverse of `stratum_prevhash`
+pub fn stratum_prevhash_hex(hash: BlockHash) -> String {
+    let mut bytes = hash.to_byte_array();
+    for word in bytes.chunks_exact_mut(4) {
+        word.reverse();
+    }
+    hex::encode(bytes)
+}
+
+/// Build a block header from Stratum's hex fields
+///
+/// `merkle_root` is in display order, as `compute_merkle_root_hex` returns it; `prevhash`
+/// is as the job sends it (see `stratum_prevhash`).
+pub fn block_header(
+    version: &str,
+    prevhash: &str,
+    merkle_root: &str,
+    nbits: &str,
+    ntime: &str,
+    nonce: &str,
+) -> Result<Header, HashingError> {
+    let prev_blockhash = stratum_prevhash(prevhash)?;
+    let merkle_root = TxMerkleNode::from_str(merkle_root)
+        .map_err(|_| HashingError::InvalidField { field: "merkle root", value: merkle_root.to_string() })?;
+    Ok(Header {
+        version: Version::from_consensus(decode_u32("version", version)? as i32),
+        prev_blockhash,
+        merkle_root,
+        time: decode_u32("ntime", ntime)?,
+        bits: CompactTarget::from_consensus(decode_u32("nbits", nbits)?),
+        nonce: decode_u32("nonce", nonce)?,
+    })
+}
+
+/// Create the serialized 80-byte block header, as `block_header` builds it
+pub fn create_block_header(
+    version: &str,
+    prevhash: &str,
+    merkle_root: &str,
+    nbits: &str,
+    ntime: &str,
+    nonce: &str,
+) -> Result<Vec<u8>, HashingError> {
+    Ok(serialize(&block_header(version, prevhash, merkle_root, nbits, ntime, nonce)?))
+}
+
+/// How many times harder than difficulty 1 the nbits target is to meet
+pub fn network_difficulty(nbits: &str) -> Result<f64, HashingError> {
+    let bits = decode_u32("nbits", nbits)?;
+    Ok(Target::from_compact(CompactTarget::from_consensus(bits)).difficulty_float())
+}
+
+/// The target nbits encodes, as a 32-byte big-endian number
+pub fn calculate_target(nbits: &str) -> Result<Vec<u8>, HashingError> {
+    let bits = decode_u32("nbits", nbits).map_err(|_| HashingError::InvalidNbits("must be 8 hex characters (4 bytes)"))?;
+    // Nothing above 2^256 fits a hash
+    if bits >> 24 > 32 {
+        return Err(HashingError::InvalidNbits("exponent too large"));
+    }
+    Ok(Target::from_compact(CompactTarget::from_consensus(bits)).to_be_bytes().to_vec())
+}
+
+/// Random extranonce2 of `size` bytes, as hex
+pub fn random_extranonce2(rng: &RandomSource, size: usize) -> String {
+    let mut extranonce2_bytes = vec![0u8; size];
+    rng.fill(&mut extranonce2_bytes);
+    hex::encode(extranonce2_bytes)
+}
+
+/// The coinbase transaction for an extranonce2, without its witness, as the pool splits it
+pub fn coinbase_bytes(job: &MiningJob, extranonce1: &str, extranonce2: &str) -> Result<Vec<u8>, HashingError> {
+    decode("coinbase", &format!("{}{}{}{}", job.coinb1, extranonce1, extranonce2, job.coinb2))
+}
+
+/// Build the coinbase for an extranonce2 and fold in the merkle branch, giving the root in
+/// display order
+pub fn compute_merkle_root_hex(job: &MiningJob, extranonce1: &str, extranonce2: &str) -> Result<String, HashingError> {
+    let branch = decode_branch(job)?;
+    Ok(merkle_root(&coinbase_bytes(job, extranonce1, extranonce2)?, &branch).to_string())
+}
+
+fn decode_branch(job: &MiningJob) -> Result<Vec<Vec<u8>>, HashingError> {
+    job.merkle_branch.iter().map(|branch| decode("merkle branch", branch)).collect()
+}
+
+/// Hash the coinbase and fold in the merkle branch, whose hashes come in internal byte order
+fn merkle_root(coinbase: &[u8], branch: &[Vec<u8>]) -> TxMerkleNode {
+    fold_merkle_branch(sha256d::Hash::hash(coinbase).to_byte_array(), branch)
+}
+
+/// Fold a merkle branch into the coinbase's txid, both in internal byte order
+pub fn fold_merkle_branch(coinbase_txid: [u8; 32], branch: &[Vec<u8>]) -> TxMerkleNode {
+    let mut merkle_root = TxMerkleNode::from_byte_array(coinbase_txid);
+    for branch in branch {
+        let mut combined = merkle_root.to_byte_array().to_vec();
+        combined.extend_from_slice(branch);
+        merkle_root = TxMerkleNode::from_byte_array(sha256d::Hash::hash(&combined).to_byte_array());
+    }
+    merkle_root
+}
+
+/// A job's header fields and coinbase pieces, decoded once, for building the header of
+/// every extranonce2 a worker tries
+///
+/// Each extranonce2 gives a different coinbase and so a different merkle root, which has
+/// to be rebuilt whenever a worker moves to a new extranonce2.
+#[derive(Debug, Clone)]
+pub struct HeaderTemplate {
+    /// coinb1 and extranonce1
+    coinbase_start: Vec<u8>,
+    /// Bytes of extranonce2 the pool expects between the two halves of the coinbase
+    extranonce2_size: usize,
+    coinb2: Vec<u8>,
+    merkle_branch: Vec<Vec<u8>>,
+    /// The job's header with an all-zero merkle root and nonce
+    header: Header,
+}
+
+impl HeaderTemplate {
+    pub fn new(job: &MiningJob, extranonce1: &str, extranonce2_size: usize) -> Result<Self, HashingError> {
+        let mut coinbase_start = decode("coinbase", &job.coinb1)?;
+        coinbase_start.extend(decode("extranonce1", extranonce1)?);
+        Ok(Self {
+            coinbase_start,
+            extranonce2_size,
+            coinb2: decode("coinbase", &job.coinb2)?,
+            merkle_branch: decode_branch(job)?,
+            header: block_header(&job.version, &job.prevhash, &"00".repeat(32), &job.nbits, &job.ntime, "00000000")?,
+        })
+    }
+
+    pub fn extranonce2_size(&self) -> usize {
+        self.extranonce2_size
+    }
+
+    /// The merkle root of the coinbase with `extranonce2`, which has to be the pool's size
+    /// or the coinbase wouldn't match the scriptSig length it declares
+    pub fn merkle_root(&self, extranonce2: &str) -> Result<TxMerkleNode, HashingError> {
+        let extranonce2_bytes = decode("extranonce2", extranonce2)?;
+        if extranonce2_bytes.len() != self.extranonce2_size {
+            return Err(HashingError::InvalidField { field: "extranonce2", value: extranonce2.to_string() });
+        }
+        let mut coinbase = self.coinbase_start.clone();
+        coinbase.extend(extranonce2_bytes);
+        coinbase.extend_from_slice(&self.coinb2);
+        Ok(merkle_root(&coinbase, &self.merkle_branch))
+    }
+
+    /// The header for `extranonce2`, with nonce 0 for the caller to fill in
+    ///
+    /// Rebuilds the coinbase and folds the merkle branch into it again, so it's called
+    /// whenever a worker moves to a new extranonce2.
+    pub fn with_extranonce2(&self, extranonce2: &str) -> Result<Header, HashingError> {
+        Ok(Header { merkle_root: self.merkle_root(extranonce2)?, ..self.header })
+    }
+}
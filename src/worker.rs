@@ -0,0 +1,499 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Instant;
+use anyhow::{Result, Context, anyhow, bail};
+use bitcoin::consensus::serialize;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::{error, info, info_span, Span};
+
+use crate::clock::RandomSource;
+use crate::events::{self, MinerEvent};
+use crate::exit_code::catch_panic;
+use crate::hashing::{self, double_sha256, hash_below, hash_meets_target};
+use crate::stratum::MiningJob;
+use crate::telemetry::{self, HashingLabels};
+use crate::work::{random_extranonce2, HeaderTemplate};
+
+const HASHES_PER_BATCH: u32 = 1000;
+/// Times a worker is restarted after panicking on one job before the panic is an error
+const MAX_WORKER_RESTARTS: u32 = 3;
+
+/// Everything a worker needs to build headers for the current job
+#[derive(Debug)]
+pub struct JobWork {
+    pub job: MiningJob,
+    /// The job's headers for the pool's extranonce1
+    pub template: HeaderTemplate,
+    pub target: Vec<u8>,
+    /// Where the workers' extranonce2 values come from
+    pub rng: RandomSource,
+}
+
+/// A header whose hash meets the target
+#[derive(Debug)]
+pub struct FoundBlock {
+    pub nonce: String,
+    pub extranonce2: String,
+    pub ntime: String,
+    pub merkle_root: String,
+    pub header: Vec<u8>,
+    pub hash: Vec<u8>,
+}
+
+/// The current job as handed to miners outside the local workers (the proxy's LAN miners
+/// and cluster workers), with the way back in for the blocks they find
+#[derive(Debug)]
+pub struct SharedWork {
+    pub job: MiningJob,
+    pub extranonce1: String,
+    /// Size of the pool's extranonce2 in bytes
+    pub extranonce2_size: usize,
+    pub target: Vec<u8>,
+    /// Where blocks found elsewhere go, next to the local workers' finds
+    pub found: UnboundedSender<Result<FoundBlock>>,
+}
+
+/// Counters shared between the workers and the task supervising the job
+#[derive(Debug, Default)]
+pub struct WorkerProgress {
+    stop: AtomicBool,
+    hashes: AtomicU64,
+    extranonce2_rolls: AtomicU64,
+    best_hash: Mutex<Option<Vec<u8>>>,
+}
+
+impl WorkerProgress {
+    /// Ask every worker to finish its current batch and exit
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    /// Hashes computed for this job so far, across all workers
+    pub fn hashes(&self) -> u64 {
+        self.hashes.load(Ordering::Relaxed)
+    }
+
+    pub fn extranonce2_rolls(&self) -> u64 {
+        self.extranonce2_rolls.load(Ordering::Relaxed)
+    }
+
+    /// Lowest hash seen since the last call, if it improved
+    pub fn take_best_hash(&self) -> Option<Vec<u8>> {
+        self.best_hash.lock().unwrap().take()
+    }
+
+    fn offer_best_hash(&self, hash: &[u8]) {
+        let mut best = self.best_hash.lock().unwrap();
+        if best.as_deref().is_none_or(|current| hash_below(hash, current)) {
+            *best = Some(hash.to_vec());
+        }
+    }
+}
+
+/// Hashing threads working one job
+///
+/// Each worker starts from its own random extranonce2, so the threads search
+/// disjoint coinbases without coordinating nonce ranges. Workers draw from generators
+/// forked off `JobWork::rng` in thread order, so a seeded source gives every thread the
+/// same extranonce2 sequence on every run. Each thread reports its hashes and batch times
+/// to the metrics as its own device of the pool's backend.
+pub struct WorkerPool {
+    handles: Vec<JoinHandle<()>>,
+    progress: Arc<WorkerProgress>,
+    work: Arc<JobWork>,
+    found: UnboundedSender<Result<FoundBlock>>,
+    backend: &'static str,
+    span: Span,
+}
+
+impl WorkerPool {
+    pub fn start(threads: usize, work: Arc<JobWork>, found: UnboundedSender<Result<FoundBlock>>) -> Result<Self> {
+        let mut pool = Self {
+            handles: Vec::new(),
+            progress: Arc::new(WorkerProgress::default()),
+            work,
+            found,
+            backend: hashing::backend(),
+            span: Span::current(),
+        };
+        pool.spawn(threads)?;
+        Ok(pool)
+    }
+
+    /// Threads currently hashing; 0 while paused
+    pub fn threads(&self) -> usize {
+        self.handles.len()
+    }
+
+    /// Stop hashing but keep the job, so `resume` can pick it up again
+    pub fn pause(&mut self) {
+        self.stop_and_join();
+        self.progress.stop.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Start hashing again after `pause`
+    pub fn resume(&mut self, threads: usize) -> Result<()> {
+        if !self.is_paused() {
+            return Ok(());
+        }
+        self.spawn(threads)
+    }
+
+    /// Change the number of threads working the job; progress counters carry over
+    pub fn resize(&mut self, threads: usize) -> Result<()> {
+        if threads.max(1) == self.threads() {
+            return Ok(());
+        }
+        self.pause();
+        self.spawn(threads)
+    }
+
+    fn spawn(&mut self, threads: usize) -> Result<()> {
+        self.handles = (0..threads.max(1))
+            .map(|id| {
+                let work = Arc::clone(&self.work);
+                let rng = work.rng.fork();
+                let progress = Arc::clone(&self.progress);
+                let found = self.found.clone();
+                let labels = HashingLabels::new(self.backend, id);
+                let span = info_span!(parent: &self.span, "worker", id);
+                std::thread::Builder::new()
+                    .name(format!("miner-worker-{}", id))
+                    .spawn(move || {
+                        let _entered = span.enter();
+                        let result = supervise(id, &work.job.job_id, "", &progress, || hash_job(&work, &rng, &progress, &labels));
+                        // Only a found block or an error is reported; a stop request ends quietly
+                        if let Some(result) = result.transpose() {
+                            progress.stop();
+                            let _ = found.send(result);
+                        }
+                    })
+                    .context("Failed to spawn mining worker thread")
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(())
+    }
+
+    fn stop_and_join(&mut self) {
+        self.progress.stop();
+        for handle in std::mem::take(&mut self.handles) {
+            let _ = handle.join();
+        }
+    }
+
+    pub fn progress(&self) -> &WorkerProgress {
+        &self.progress
+    }
+
+    /// Stop all workers and wait for them to exit
+    pub fn join(mut self) -> Arc<WorkerProgress> {
+        self.stop_and_join();
+        Arc::clone(&self.progress)
+    }
+}
+
+impl Drop for WorkerPool {
+    // A cancelled job (e.g. on shutdown) must not leave threads hashing in the background;
+    // joining takes at most one batch per worker
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+/// One extranonce2 and nonce range to search, as handed out by a cluster coordinator
+#[derive(Debug)]
+pub struct RangeWork {
+    pub job: MiningJob,
+    pub extranonce1: String,
+    pub extranonce2: String,
+    pub nonce_start: u32,
+    /// Last nonce to try, inclusive
+    pub nonce_end: u32,
+    pub target: Vec<u8>,
+}
+
+/// Hashing threads searching one `RangeWork`, which is split evenly between them
+///
+/// Each thread sends `Ok(Some(block))` when it finds a block, which stops the others, and
+/// `Ok(None)` once it has searched its part without one.
+pub struct RangeSearch {
+    handles: Vec<JoinHandle<()>>,
+    progress: Arc<WorkerProgress>,
+}
+
+impl RangeSearch {
+    pub fn start(threads: usize, work: Arc<RangeWork>, results: UnboundedSender<Result<Option<FoundBlock>>>) -> Result<Self> {
+        if work.nonce_end < work.nonce_start {
+            bail!("Nonce range {:08x}..={:08x} ends before it starts", work.nonce_start, work.nonce_end);
+        }
+        let progress = Arc::new(WorkerProgress::default());
+        let backend = hashing::backend();
+        let span = Span::current();
+        let nonces = (work.nonce_end - work.nonce_start) as u64 + 1;
+        let threads = (threads.max(1) as u64).min(nonces);
+        let handles = (0..threads)
+            .map(|id| {
+                let first = work.nonce_start + (nonces * id / threads) as u32;
+                let last = work.nonce_start + (nonces * (id + 1) / threads - 1) as u32;
+                let work = Arc::clone(&work);
+                let progress = Arc::clone(&progress);
+                let results = results.clone();
+                let labels = HashingLabels::new(backend, id as usize);
+                let span = info_span!(parent: &span, "worker", id);
+                std::thread::Builder::new()
+                    .name(format!("miner-worker-{}", id))
+                    .spawn(move || {
+                        let _entered = span.enter();
+                        let range = format!(" (extranonce2 {}, nonces {:08x}..={:08x})", work.extranonce2, first, last);
+                        let result = supervise(id as usize, &work.job.job_id, &range, &progress, || hash_range(&work, first, last, &progress, &labels));
+                        // A stop request ends quietly
+                        if matches!(result, Ok(Some(_)) | Err(_)) {
+                            progress.stop();
+                        } else if progress.stopped() {
+                            return;
+                        }
+                        let _ = results.send(result);
+                    })
+                    .context("Failed to spawn mining worker thread")
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { handles, progress })
+    }
+
+    /// Threads searching the range, each of which reports once
+    pub fn threads(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn progress(&self) -> &WorkerProgress {
+        &self.progress
+    }
+
+    /// Stop all threads and wait for them to exit
+    pub fn join(mut self) -> Arc<WorkerProgress> {
+        self.stop_and_join();
+        Arc::clone(&self.progress)
+    }
+
+    fn stop_and_join(&mut self) {
+        self.progress.stop();
+        for handle in std::mem::take(&mut self.handles) {
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for RangeSearch {
+    fn drop(&mut self) {
+        self.stop_and_join();
+    }
+}
+
+/// Run worker `id`'s `hash`, starting it again on the same work whenever it panics
+///
+/// Each panic is logged with the job and `context` and published as
+/// `MinerEvent::WorkerRestarted`, so one bad job or arithmetic edge case costs a restart
+/// rather than silently losing the thread's share of the hashrate. Past
+/// `MAX_WORKER_RESTARTS` the panic is returned as an error.
+fn supervise(
+    id: usize,
+    job_id: &str,
+    context: &str,
+    progress: &WorkerProgress,
+    mut hash: impl FnMut() -> Result<Option<FoundBlock>>,
+) -> Result<Option<FoundBlock>> {
+    let mut restarts = 0;
+    loop {
+        let panic = match catch_panic(&mut hash) {
+            Ok(result) => return result,
+            Err(panic) => panic,
+        };
+        if restarts == MAX_WORKER_RESTARTS {
+            error!(event = "worker_failed", worker = id, job_id, panic = %panic, "Worker {} panicked on job {}{} again after {} restarts: {}", id, job_id, context, restarts, panic);
+            return Err(anyhow!("Worker {} kept panicking on job {}{}: {}", id, job_id, context, panic));
+        }
+        if progress.stopped() {
+            return Ok(None);
+        }
+        restarts += 1;
+        error!(event = "worker_restarted", worker = id, job_id, restarts, panic = %panic, "Worker {} panicked on job {}{}, restarting it: {}", id, job_id, context, panic);
+        events::emit(MinerEvent::WorkerRestarted { worker: id, job_id: job_id.to_string(), panic, restarts });
+    }
+}
+
+/// Hash nonces `first..=last` until a block is found, the range ends or the workers are stopped
+fn hash_range(work: &RangeWork, first: u32, last: u32, progress: &WorkerProgress, labels: &HashingLabels) -> Result<Option<FoundBlock>> {
+    let job = &work.job;
+    let mut header = HeaderTemplate::new(job, &work.extranonce1, work.extranonce2.len() / 2)?.with_extranonce2(&work.extranonce2)?;
+    let mut best_hash = vec![0xffu8; 32];
+    let mut nonce = first as u64;
+    while !progress.stopped() && nonce <= last as u64 {
+        let batch_end = (nonce + HASHES_PER_BATCH as u64).min(last as u64 + 1);
+        let batch_started = Instant::now();
+        let mut best_hash_improved = false;
+        for candidate in nonce..batch_end {
+            header.nonce = candidate as u32;
+            let header_bytes = serialize(&header);
+            let hash_bytes = double_sha256(&header_bytes);
+            if hash_below(&hash_bytes, &best_hash) {
+                best_hash = hash_bytes.clone();
+                best_hash_improved = true;
+            }
+            if hash_meets_target(&hash_bytes, &work.target) {
+                return Ok(Some(FoundBlock {
+                    nonce: format!("{:08x}", header.nonce),
+                    extranonce2: work.extranonce2.clone(),
+                    ntime: job.ntime.clone(),
+                    merkle_root: header.merkle_root.to_string(),
+                    header: header_bytes,
+                    hash: hash_bytes,
+                }));
+            }
+        }
+        telemetry::record_batch(batch_end - nonce, batch_started.elapsed(), labels);
+        progress.hashes.fetch_add(batch_end - nonce, Ordering::Relaxed);
+        if best_hash_improved {
+            progress.offer_best_hash(&best_hash);
+        }
+        nonce = batch_end;
+    }
+    Ok(None)
+}
+
+/// Hash until a block is found or the workers are stopped
+fn hash_job(work: &JobWork, rng: &RandomSource, progress: &WorkerProgress, labels: &HashingLabels) -> Result<Option<FoundBlock>> {
+    let job = &work.job;
+    let template = &work.template;
+    let mut extranonce2 = random_extranonce2(rng, template.extranonce2_size());
+    let mut header = template.with_extranonce2(&extranonce2)?;
+    let mut nonce_counter: u32 = 0;
+    let mut best_hash = vec![0xffu8; 32];
+    let mut best_hash_improved = false;
+
+    while !progress.stopped() {
+        let batch_started = Instant::now();
+        for _ in 0..HASHES_PER_BATCH {
+            // Use sequential nonce for better performance
+            nonce_counter = nonce_counter.wrapping_add(1);
+            if nonce_counter == 0 {
+                // Nonce space exhausted: roll extranonce2, which changes the coinbase and
+                // with it the merkle root
+                extranonce2 = random_extranonce2(rng, template.extranonce2_size());
+                header = template.with_extranonce2(&extranonce2)?;
+                progress.extranonce2_rolls.fetch_add(1, Ordering::Relaxed);
+                info!(event = "extranonce2_rolled", extranonce2 = %extranonce2, "Nonce range exhausted, rolled extranonce2");
+            }
+            header.nonce = nonce_counter;
+            let header_bytes = serialize(&header);
+
+            let hash_bytes = double_sha256(&header_bytes);
+
+            if hash_below(&hash_bytes, &best_hash) {
+                best_hash = hash_bytes.clone();
+                best_hash_improved = true;
+            }
+
+            // Check if hash meets target
+            if hash_meets_target(&hash_bytes, &work.target) {
+                return Ok(Some(FoundBlock {
+                    nonce: format!("{:08x}", nonce_counter),
+                    extranonce2,
+                    ntime: job.ntime.clone(),
+                    merkle_root: header.merkle_root.to_string(),
+                    header: header_bytes,
+                    hash: hash_bytes,
+                }));
+            }
+        }
+
+        telemetry::record_batch(HASHES_PER_BATCH as u64, batch_started.elapsed(), labels);
+        progress.hashes.fetch_add(HASHES_PER_BATCH as u64, Ordering::Relaxed);
+        if best_hash_improved {
+            progress.offer_best_hash(&best_hash);
+            best_hash_improved = false;
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block() -> FoundBlock {
+        FoundBlock {
+            nonce: "0000002a".to_string(),
+            extranonce2: "00000000".to_string(),
+            ntime: "5f5e1000".to_string(),
+            merkle_root: "00".repeat(32),
+            header: vec![0; 80],
+            hash: vec![0; 32],
+        }
+    }
+
+    /// The restarts published for `job`, as (worker, panic, restarts)
+    fn restarts(events: &mut tokio::sync::broadcast::Receiver<MinerEvent>, job: &str) -> Vec<(usize, String, u32)> {
+        std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|event| match event {
+                MinerEvent::WorkerRestarted { worker, job_id, panic, restarts } if job_id == job => Some((worker, panic, restarts)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_panicking_worker_is_restarted_until_it_recovers() {
+        let mut events = events::subscribe();
+        let progress = WorkerProgress::default();
+        let mut runs = 0;
+        let result = supervise(1, "job-recovers", "", &progress, || {
+            runs += 1;
+            if runs <= 2 {
+                panic!("panic {}", runs);
+            }
+            Ok(Some(block()))
+        });
+        assert_eq!(result.unwrap().unwrap().nonce, "0000002a");
+        assert_eq!(runs, 3);
+        assert_eq!(restarts(&mut events, "job-recovers"), [(1, "panic 1".to_string(), 1), (1, "panic 2".to_string(), 2)]);
+    }
+
+    #[test]
+    fn a_worker_that_keeps_panicking_is_an_error() {
+        let mut events = events::subscribe();
+        let progress = WorkerProgress::default();
+        let mut runs = 0;
+        let error = supervise(2, "job-fails", " (extranonce2 00000000)", &progress, || -> Result<Option<FoundBlock>> {
+            runs += 1;
+            panic!("always")
+        })
+        .unwrap_err();
+        assert_eq!(runs, MAX_WORKER_RESTARTS + 1);
+        assert_eq!(error.to_string(), "Worker 2 kept panicking on job job-fails (extranonce2 00000000): always");
+        assert_eq!(restarts(&mut events, "job-fails").len(), MAX_WORKER_RESTARTS as usize);
+    }
+
+    #[test]
+    fn a_stopped_worker_is_not_restarted() {
+        let mut events = events::subscribe();
+        let progress = WorkerProgress::default();
+        progress.stop();
+        let mut runs = 0;
+        let result = supervise(3, "job-stopped", "", &progress, || -> Result<Option<FoundBlock>> {
+            runs += 1;
+            panic!("while stopping")
+        });
+        assert!(result.unwrap().is_none());
+        assert_eq!(runs, 1);
+        assert!(restarts(&mut events, "job-stopped").is_empty());
+    }
+}
@@ -0,0 +1,36 @@
+use std::time::Duration;
+use bitcoin_solo_miner::activity::{ActivitySample, ActivitySettings, ActivityTracker};
+use tokio::time::Instant;
+
+fn settings() -> ActivitySettings {
+    ActivitySettings { idle: Duration::from_secs(300), max_load: Some(1.0), busy_threads: 0 }
+}
+
+#[test]
+fn recent_input_counts_as_use_until_the_idle_period_passes() {
+    let settings = settings();
+    let mut tracker = ActivityTracker::default();
+    let start = Instant::now();
+    let typing = ActivitySample { input_idle: Some(Duration::from_secs(5)), load: Some(0.2) };
+    assert!(tracker.observe(&settings, start, typing, 0));
+
+    // The terminal stays untouched from then on
+    let later = start + Duration::from_secs(200);
+    assert!(tracker.observe(&settings, later, ActivitySample { input_idle: Some(Duration::from_secs(205)), ..typing }, 0));
+    let idle = start + Duration::from_secs(400);
+    assert!(!tracker.observe(&settings, idle, ActivitySample { input_idle: Some(Duration::from_secs(405)), ..typing }, 0));
+}
+
+#[test]
+fn only_load_beyond_the_hashing_threads_counts() {
+    let settings = settings();
+    let mut tracker = ActivityTracker::default();
+    let now = Instant::now();
+    // Four hashing threads explain a load of 4.5
+    assert!(!tracker.observe(&settings, now, ActivitySample { input_idle: None, load: Some(4.5) }, 4));
+    assert!(tracker.observe(&settings, now, ActivitySample { input_idle: None, load: Some(6.0) }, 4));
+    // Input-only watching ignores the load
+    let mut tracker = ActivityTracker::default();
+    let input_only = ActivitySettings { max_load: None, ..settings };
+    assert!(!tracker.observe(&input_only, now, ActivitySample { input_idle: None, load: Some(60.0) }, 0));
+}
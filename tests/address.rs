@@ -0,0 +1,54 @@
+use bitcoin_solo_miner::address::{self, AddressType};
+
+#[test]
+fn mainnet_addresses_of_every_type_are_recognised() {
+    // BIP 173 and BIP 350 valid mainnet vectors
+    assert_eq!(address::parse("1BoatSLRHtKNngkdXEeobR76b53LETtpyT").unwrap(), AddressType::P2pkh);
+    assert_eq!(address::parse("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy").unwrap(), AddressType::P2sh);
+    assert_eq!(address::parse("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4").unwrap(), AddressType::P2wpkh);
+    assert_eq!(
+        address::parse("bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3").unwrap(),
+        AddressType::P2wsh
+    );
+    assert_eq!(
+        address::parse("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0").unwrap(),
+        AddressType::P2tr
+    );
+    assert_eq!(address::parse("bc1zw508d6qejxtdg4y5r3zarvaryvaxxpcs").unwrap(), AddressType::Witness(2));
+    assert_eq!(address::parse(" 1BoatSLRHtKNngkdXEeobR76b53LETtpyT\n").unwrap(), AddressType::P2pkh);
+}
+
+#[test]
+fn bad_checksums_are_rejected() {
+    assert!(address::parse("1BoatSLRHtKNngkdXEeobR76b53LETtpyU").is_err());
+    assert!(address::parse("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5").is_err());
+    // BIP 350: a v1+ program with a bech32 checksum, and v0 with a bech32m one
+    assert!(address::parse("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd").is_err());
+    assert!(address::parse("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh").is_err());
+}
+
+#[test]
+fn malformed_bech32_is_rejected() {
+    // Mixed case
+    assert!(address::parse("bc1qW508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4").is_err());
+    // Invalid character, bad witness version, empty data
+    assert!(address::parse("bc1p38j9r5y49hruaue7wxjce0updqjuyyx0kh56v8s25huc6995vvpql3jow4").is_err());
+    assert!(address::parse("BC130XLXVLHEMJA6C4DQV22UAPCTQUPFHLXM9H8Z3K2E72Q4K9HCZ7VQ7ZWS8R").is_err());
+    assert!(address::parse("bc1gmk9yu").is_err());
+    // Program lengths: 1 byte, 41 bytes, and 16 bytes for v0
+    assert!(address::parse("bc1pw5dgrnzv").is_err());
+    assert!(address::parse("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7v8n0nx0muaewav253zgeav").is_err());
+    assert!(address::parse("BC1QR508D6QEJXTDG4Y5R3ZARVARYV98GJ9P").is_err());
+    // More than 4 bits of padding
+    assert!(address::parse("bc1zw508d6qejxtdg4y5r3zarvaryvqyzf3du").is_err());
+    // Over 90 characters
+    assert!(address::parse(&format!("bc1q{}", "q".repeat(90))).is_err());
+}
+
+#[test]
+fn testnet_and_empty_addresses_are_rejected() {
+    let error = address::parse("tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx").unwrap_err();
+    assert!(error.to_string().contains("testnet"), "{}", error);
+    assert!(address::parse("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn").unwrap_err().to_string().contains("testnet"));
+    assert!(address::parse("").is_err());
+}
@@ -0,0 +1,25 @@
+use std::time::Duration;
+use bitcoin_solo_miner::bandwidth::{format_bytes, Traffic};
+
+#[test]
+fn bytes_are_shown_in_decimal_units() {
+    assert_eq!(format_bytes(0), "0 B");
+    assert_eq!(format_bytes(999), "999 B");
+    assert_eq!(format_bytes(1_000), "1.00 KB");
+    assert_eq!(format_bytes(12_345), "12.3 KB");
+    assert_eq!(format_bytes(456_789), "457 KB");
+    assert_eq!(format_bytes(4_560_000), "4.56 MB");
+    assert_eq!(format_bytes(7_000_000_000_000_000), "7000 TB");
+}
+
+#[test]
+fn traffic_is_counted_from_a_baseline_and_scaled_to_a_day() {
+    let earlier = Traffic { sent_bytes: 1_000, received_bytes: 5_000 };
+    let now = Traffic { sent_bytes: 3_000, received_bytes: 15_000 };
+    let run = now.since(earlier);
+    assert_eq!(run, Traffic { sent_bytes: 2_000, received_bytes: 10_000 });
+    assert_eq!(run.total_bytes(), 12_000);
+    assert_eq!(run.summary(Duration::from_secs(3_600)), "2.00 KB sent, 10.0 KB received (288 KB/day)");
+    // A baseline from before a reset can't make the figures negative
+    assert_eq!(earlier.since(now), Traffic::default());
+}
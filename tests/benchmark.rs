@@ -0,0 +1,38 @@
+use bitcoin_solo_miner::benchmark::{BenchmarkReport, HostInfo, RateSpread, Sha256Rates};
+
+#[test]
+fn spread_summarises_per_second_rates() {
+    let spread = RateSpread::of(&[900.0, 1000.0, 1100.0, 1000.0]).unwrap();
+    assert_eq!(spread.mean, 1000.0);
+    assert_eq!((spread.min, spread.max), (900.0, 1100.0));
+    assert!((spread.stddev - 50f64.sqrt() * 10.0).abs() < 1e-9);
+    assert!((spread.coefficient_of_variation - spread.stddev / 1000.0).abs() < 1e-12);
+    assert_eq!(RateSpread::of(&[]), None);
+}
+
+#[test]
+fn report_round_trips_through_its_file() {
+    let path = std::env::temp_dir().join(format!("miner-benchmark-{}", std::process::id())).join("report.json");
+    let samples = vec![1000.0, 1010.0];
+    let report = BenchmarkReport {
+        version: "0.0.0".to_string(),
+        timestamp: 1_700_000_000,
+        host: HostInfo::detect(),
+        sha256: "portable".to_string(),
+        threads: 2,
+        duration_secs: 2.0,
+        hashes: 2010,
+        hashrate: 1005.0,
+        hashrate_per_thread: 502.5,
+        spread: RateSpread::of(&samples),
+        samples,
+        sha256_rates: Sha256Rates { portable: 600.0, asm: None },
+    };
+    report.save(&path).unwrap();
+    let saved: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    assert_eq!(saved["hashrate"], 1005.0);
+    assert_eq!(saved["host"]["os"], std::env::consts::OS);
+    assert!(saved["host"]["logical_cpus"].as_u64().unwrap() >= 1);
+    assert_eq!(serde_json::from_value::<BenchmarkReport>(saved).unwrap(), report);
+}
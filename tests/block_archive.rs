@@ -0,0 +1,76 @@
+use bitcoin_solo_miner::coinbase::{check_witness_commitment, WitnessCheck};
+use bitcoin_solo_miner::block_archive::assemble_block;
+use bitcoin_solo_miner::stratum::MiningJob;
+use bitcoin_solo_miner::testutil::notify_params;
+use bitcoin_solo_miner::work::coinbase_bytes;
+
+const HEADER: [u8; 80] = [0x11; 80];
+
+#[test]
+fn a_coinbase_only_job_gives_the_whole_block() {
+    let job = MiningJob::from_params(&notify_params("job1", "1d00ffff")).unwrap();
+    let assembled = assemble_block(&job, "f000000f", "00000001", &HEADER).unwrap();
+    let coinbase = coinbase_bytes(&job, "f000000f", "00000001").unwrap();
+    assert_eq!(assembled.coinbase, coinbase);
+
+    let mut block = HEADER.to_vec();
+    block.push(1);
+    block.extend_from_slice(&coinbase);
+    assert_eq!(assembled.block, Some(block));
+}
+
+#[test]
+fn a_witness_commitment_gets_the_reserved_value_and_other_transactions_are_missing() {
+    let mut params = notify_params("job1", "1d00ffff");
+    // Two outputs: the payout and a witness commitment
+    params[3] = format!(
+        "ffffffff0200f2052a010000001976a914000000000000000000000000000000000000000088ac0000000000000000266a24aa21a9ed{}00000000",
+        "ab".repeat(32)
+    ).into();
+    params[4] = serde_json::json!(["cd".repeat(32)]);
+    let job = MiningJob::from_params(&params).unwrap();
+    let assembled = assemble_block(&job, "f000000f", "00000001", &HEADER).unwrap();
+    let stripped = coinbase_bytes(&job, "f000000f", "00000001").unwrap();
+
+    assert_eq!(&assembled.coinbase[..6], &[0x01, 0x00, 0x00, 0x00, 0x00, 0x01]);
+    assert_eq!(&assembled.coinbase[6..stripped.len() - 2], &stripped[4..stripped.len() - 4]);
+    let witness = &assembled.coinbase[stripped.len() - 2..];
+    assert_eq!(witness.len(), 2 + 32 + 4);
+    assert_eq!(&witness[..2], &[0x01, 0x20]);
+    assert_eq!(assembled.block, None);
+}
+
+/// A coinbase-only job whose coinbase commits to `commitment`, with extranonces of 4 bytes each
+fn job_committing_to(commitment: &str, merkle_branch: serde_json::Value) -> MiningJob {
+    let mut params = notify_params("job1", "1d00ffff");
+    params[2] = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff08".into();
+    params[3] = format!(
+        "ffffffff0200f2052a010000001976a914000000000000000000000000000000000000000088ac0000000000000000266a24aa21a9ed{}00000000",
+        commitment
+    ).into();
+    params[4] = merkle_branch;
+    MiningJob::from_params(&params).unwrap()
+}
+
+#[test]
+fn the_witness_commitment_of_a_coinbase_only_block_is_checked() {
+    // sha256d of the all-zero witness root and the all-zero reserved value
+    let valid = "e2f61c3f71d1defd3fa999dfa36953755c690689799962b48bebd836974e8cf9";
+    let job = job_committing_to(valid, serde_json::json!([]));
+    assert_eq!(check_witness_commitment(&job, "f000000f", 4).unwrap(), WitnessCheck::Matches);
+
+    let job = job_committing_to(&"ab".repeat(32), serde_json::json!([]));
+    assert_eq!(
+        check_witness_commitment(&job, "f000000f", 4).unwrap(),
+        WitnessCheck::Mismatch { expected: valid.to_string(), found: "ab".repeat(32) }
+    );
+
+    // The other transactions' witnesses aren't in the job
+    let job = job_committing_to(&"ab".repeat(32), serde_json::json!(["cd".repeat(32)]));
+    assert_eq!(check_witness_commitment(&job, "f000000f", 4).unwrap(), WitnessCheck::Unverifiable);
+
+    let mut params = notify_params("job1", "1d00ffff");
+    params[2] = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff08".into();
+    let job = MiningJob::from_params(&params).unwrap();
+    assert_eq!(check_witness_commitment(&job, "f000000f", 4).unwrap(), WitnessCheck::Absent);
+}
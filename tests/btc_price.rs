@@ -0,0 +1,51 @@
+use bitcoin_solo_miner::btc_price::{format_value, PriceSettings, PriceSource};
+use serde_json::json;
+
+#[test]
+fn no_source_means_no_price() {
+    assert_eq!(PriceSettings::parse("", "eur").unwrap(), None);
+    assert_eq!(PriceSettings::parse("none", "").unwrap(), None);
+    assert!(PriceSettings::parse("kraken", "usd").is_err());
+}
+
+#[test]
+fn currency_defaults_to_usd_and_is_lowercased() {
+    let settings = PriceSettings::parse("CoinGecko", "").unwrap().unwrap();
+    assert_eq!(settings, PriceSettings { source: PriceSource::CoinGecko, currency: "usd".to_string() });
+    let settings = PriceSettings::parse("coinbase", " EUR ").unwrap().unwrap();
+    assert_eq!(settings.currency, "eur");
+    assert!(PriceSettings::parse("coinbase", "us$").is_err());
+}
+
+#[test]
+fn mempool_only_takes_the_currencies_it_quotes() {
+    assert!(PriceSettings::parse("mempool", "jpy").unwrap().is_some());
+    assert!(PriceSettings::parse("mempool", "brl").is_err());
+    assert!(PriceSettings::parse("coingecko", "brl").unwrap().is_some());
+}
+
+#[test]
+fn each_source_response_is_read() {
+    let coingecko = json!({ "bitcoin": { "eur": 91234.5 } });
+    assert_eq!(PriceSource::CoinGecko.parse_price(&coingecko, "eur").unwrap(), 91234.5);
+    let coinbase = json!({ "data": { "amount": "64380.12", "base": "BTC", "currency": "USD" } });
+    assert_eq!(PriceSource::Coinbase.parse_price(&coinbase, "usd").unwrap(), 64380.12);
+    let mempool = json!({ "time": 1760000000, "USD": 64380, "EUR": 59210 });
+    assert_eq!(PriceSource::Mempool.parse_price(&mempool, "eur").unwrap(), 59210.0);
+    assert!(PriceSource::Mempool.parse_price(&mempool, "gbp").is_err());
+    assert!(PriceSource::CoinGecko.parse_price(&json!({ "bitcoin": { "usd": 0 } }), "usd").is_err());
+}
+
+#[test]
+fn source_urls_carry_the_currency() {
+    assert!(PriceSource::CoinGecko.url("eur").ends_with("vs_currencies=eur"));
+    assert!(PriceSource::Coinbase.url("eur").ends_with("/BTC-EUR/spot"));
+}
+
+#[test]
+fn values_are_rounded_and_grouped() {
+    assert_eq!(format_value(312_500_000, 64_395.0, "usd"), "≈ 201,234 USD");
+    assert_eq!(format_value(100_000_000, 999.4, "eur"), "≈ 999 EUR");
+    assert_eq!(format_value(100_000_000, 1_000_000.0, "jpy"), "≈ 1,000,000 JPY");
+    assert_eq!(format_value(0, 64_395.0, "usd"), "≈ 0 USD");
+}
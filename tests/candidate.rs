@@ -0,0 +1,60 @@
+use bitcoin_solo_miner::candidate::{check, CandidateProblem};
+use bitcoin_solo_miner::hashing::{double_sha256, hash_meets_target};
+use bitcoin_solo_miner::stratum::MiningJob;
+use bitcoin_solo_miner::testutil::notify_params;
+use bitcoin_solo_miner::work::{calculate_target, compute_merkle_root_hex, create_block_header};
+use bitcoin_solo_miner::worker::FoundBlock;
+
+const EXTRANONCE1: &str = "f000000f";
+
+/// A block for `job` at the easy regtest target, found the way the workers find one
+fn find_block(job: &MiningJob) -> FoundBlock {
+    let target = calculate_target(&job.nbits).unwrap();
+    let extranonce2 = "00000001".to_string();
+    let merkle_root = compute_merkle_root_hex(job, EXTRANONCE1, &extranonce2).unwrap();
+    (0u32..)
+        .map(|nonce| {
+            let nonce = format!("{:08x}", nonce);
+            let header = create_block_header(&job.version, &job.prevhash, &merkle_root, &job.nbits, &job.ntime, &nonce).unwrap();
+            let hash = double_sha256(&header);
+            FoundBlock { nonce, extranonce2: extranonce2.clone(), ntime: job.ntime.clone(), merkle_root: merkle_root.clone(), header, hash }
+        })
+        .find(|found| hash_meets_target(&found.hash, &target))
+        .unwrap()
+}
+
+#[test]
+fn a_block_found_by_the_workers_passes() {
+    let job = MiningJob::from_params(&notify_params("job1", "207fffff")).unwrap();
+    let ntime = u32::from_str_radix(&job.ntime, 16).unwrap() as u64;
+    assert_eq!(check(&job, EXTRANONCE1, &find_block(&job), ntime), vec![]);
+}
+
+#[test]
+fn every_broken_part_of_a_candidate_is_reported() {
+    let job = MiningJob::from_params(&notify_params("job1", "207fffff")).unwrap();
+    let ntime = u32::from_str_radix(&job.ntime, 16).unwrap();
+    let found = find_block(&job);
+
+    // Built on another extranonce2 than the one reported
+    let mut other_extranonce2 = find_block(&job);
+    other_extranonce2.extranonce2 = "00000002".into();
+    let problems = check(&job, EXTRANONCE1, &other_extranonce2, ntime as u64);
+    assert_eq!(problems.len(), 2);
+    assert!(problems.iter().all(|problem| matches!(problem, CandidateProblem::MerkleRoot { .. })));
+
+    // A hash that isn't the header's
+    let mut wrong_hash = find_block(&job);
+    wrong_hash.hash = vec![0; 32];
+    assert!(matches!(check(&job, EXTRANONCE1, &wrong_hash, ntime as u64)[..], [CandidateProblem::HashMismatch { .. }]));
+
+    // Truncated, so every field after the prevhash is missing too
+    let mut short = find_block(&job);
+    short.header.truncate(40);
+    let problems = check(&job, EXTRANONCE1, &short, ntime as u64);
+    assert_eq!(problems[0], CandidateProblem::HeaderLength(40));
+
+    // A local clock more than two hours behind the block's time
+    let problems = check(&job, EXTRANONCE1, &found, ntime as u64 - 7201);
+    assert_eq!(problems, vec![CandidateProblem::NtimeInFuture { ntime, now: ntime as u64 - 7201 }]);
+}
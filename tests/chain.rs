@@ -0,0 +1,105 @@
+use std::time::Duration;
+use bitcoin_solo_miner::chain::{format_interval, ChainTip, NewBlock, TipChange, TipDivergence, TipTracker, TipWatch};
+use bitcoin_solo_miner::coinbase;
+use bitcoin_solo_miner::stratum::MiningJob;
+use bitcoin_solo_miner::testutil::notify_params;
+
+fn tip(height: u64, time: u64) -> ChainTip {
+    ChainTip { height, hash: format!("hash{}", height), time: Some(time) }
+}
+
+#[test]
+fn the_first_tip_is_not_a_new_block() {
+    let mut tips = TipTracker::default();
+    assert_eq!(tips.observe(tip(870_000, 1_000)), None);
+    assert_eq!(tips.observe(tip(870_000, 1_000)), None);
+}
+
+#[test]
+fn a_new_block_carries_the_time_since_the_previous_one() {
+    let mut tips = TipTracker::default();
+    tips.observe(tip(870_000, 1_000));
+    assert_eq!(
+        tips.observe(tip(870_001, 1_552)),
+        Some(NewBlock { tip: tip(870_001, 1_552), interval: Some(Duration::from_secs(552)), skipped: 0 })
+    );
+    // A header time before the previous block's is no interval at all
+    assert_eq!(tips.observe(tip(870_002, 1_500)).unwrap().interval, Some(Duration::ZERO));
+}
+
+#[test]
+fn blocks_missed_in_between_leave_the_interval_unknown() {
+    let mut tips = TipTracker::default();
+    tips.observe(tip(870_000, 1_000));
+    let block = tips.observe(tip(870_003, 2_800)).unwrap();
+    assert_eq!(block.skipped, 2);
+    assert_eq!(block.interval, None);
+    // An explorer lagging behind doesn't take the tip back
+    assert_eq!(tips.observe(tip(870_002, 2_000)), None);
+    assert_eq!(tips.observe(tip(870_004, 3_400)).unwrap().interval, Some(Duration::from_secs(600)));
+}
+
+#[test]
+fn intervals_are_formatted_for_reading() {
+    assert_eq!(format_interval(Duration::from_secs(45)), "45s");
+    assert_eq!(format_interval(Duration::from_secs(552)), "9m 12s");
+    assert_eq!(format_interval(Duration::from_secs(3_840)), "1h 04m");
+}
+
+#[test]
+fn a_job_builds_on_the_block_before_its_coinbase_height() {
+    let mut params = notify_params("job1", "1d00ffff");
+    // A 3-byte push of 870001 starts the coinbase script
+    params[2] = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff240371460d".into();
+    assert_eq!(coinbase::height(&MiningJob::from_params(&params).unwrap()).unwrap(), 870_001);
+    // Without a height push there is nothing to read
+    assert!(coinbase::height(&MiningJob::from_params(&notify_params("job2", "1d00ffff")).unwrap()).is_err());
+}
+
+#[test]
+fn a_pool_a_couple_of_blocks_behind_is_not_reported() {
+    let mut watch = TipWatch::default();
+    watch.pool_tip(870_000, "hash870000".to_string());
+    watch.network_tip(&tip(870_002, 2_000));
+    assert_eq!(watch.check(2), None);
+
+    watch.network_tip(&tip(870_003, 2_600));
+    assert_eq!(
+        watch.check(2),
+        Some(TipChange::Diverged(TipDivergence::Stale { pool_height: 870_000, network_height: 870_003 }))
+    );
+    // Reported once, then again only after catching up
+    assert_eq!(watch.check(2), None);
+    watch.pool_tip(870_003, "hash870003".to_string());
+    assert_eq!(watch.check(2), Some(TipChange::Converged));
+    assert_eq!(watch.check(2), None);
+}
+
+#[test]
+fn a_pool_building_on_another_block_is_forked() {
+    let mut watch = TipWatch::default();
+    watch.network_tip(&tip(870_000, 1_000));
+    watch.pool_tip(870_000, "other".to_string());
+    // A race for the same height settles by itself
+    assert_eq!(watch.check(2), None);
+    watch.network_tip(&tip(870_001, 1_600));
+    watch.pool_tip(870_001, "other2".to_string());
+    watch.network_tip(&tip(870_002, 2_200));
+    assert_eq!(watch.check(2), None);
+    watch.network_tip(&tip(870_003, 2_800));
+    let Some(TipChange::Diverged(divergence)) = watch.check(2) else {
+        panic!("expected a divergence");
+    };
+    assert_eq!(divergence.blocks(), 3);
+    assert!(matches!(divergence, TipDivergence::Forked { height: 870_001, .. }));
+    // 0 turns reporting off
+    assert_eq!(watch.check(0), Some(TipChange::Converged));
+}
+
+#[test]
+fn tips_without_hashes_are_not_compared() {
+    let mut watch = TipWatch::default();
+    watch.pool_tip(870_000, "hash870000".to_string());
+    watch.network_tip(&ChainTip { height: 870_010, hash: String::new(), time: None });
+    assert_eq!(watch.divergence(), None);
+}
@@ -0,0 +1,405 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use bitcoin_solo_miner::cluster::{self, Assignment, CoordinatorMessage, WorkerMessage, WorkerOptions, WorkerTotals};
+use bitcoin_solo_miner::hashing::{double_sha256, hash_meets_target};
+use bitcoin_solo_miner::mdns;
+use bitcoin_solo_miner::redis::{Redis, RedisUrl};
+use bitcoin_solo_miner::stratum::MiningJob;
+use bitcoin_solo_miner::tasks::{Stage, Tasks};
+use bitcoin_solo_miner::testutil::notify_params;
+use bitcoin_solo_miner::work::{calculate_target, compute_merkle_root_hex, create_block_header};
+use bitcoin_solo_miner::worker::SharedWork;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+
+struct Connection {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl Connection {
+    async fn open(addr: std::net::SocketAddr) -> Self {
+        let (reader, writer) = TcpStream::connect(addr).await.unwrap().into_split();
+        Self { lines: BufReader::new(reader).lines(), writer }
+    }
+
+    async fn send(&mut self, message: WorkerMessage) {
+        let line = serde_json::to_string(&message).unwrap();
+        self.writer.write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+    }
+
+    async fn receive(&mut self) -> CoordinatorMessage {
+        serde_json::from_str(&self.lines.next_line().await.unwrap().unwrap()).unwrap()
+    }
+
+    async fn work(&mut self) -> Assignment {
+        match self.receive().await {
+            CoordinatorMessage::Work { assignment } => *assignment,
+            other => panic!("expected work, got {:?}", other),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Keys {
+    strings: HashMap<String, String>,
+    hashes: HashMap<String, BTreeMap<String, String>>,
+}
+
+/// Just enough of a Redis server for the coordinator, keeping its keys in memory
+async fn fake_redis() -> RedisUrl {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = RedisUrl::parse(&listener.local_addr().unwrap().to_string()).unwrap();
+    let keys = Arc::new(Mutex::new(Keys::default()));
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let keys = Arc::clone(&keys);
+            tokio::spawn(async move {
+                let (reader, mut writer) = stream.into_split();
+                let mut reader = BufReader::new(reader);
+                let mut line = String::new();
+                while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
+                    let count: usize = line.trim()[1..].parse().unwrap();
+                    let mut args = Vec::new();
+                    for _ in 0..count {
+                        line.clear();
+                        reader.read_line(&mut line).await.unwrap();
+                        let mut arg = vec![0; line.trim()[1..].parse::<usize>().unwrap() + 2];
+                        reader.read_exact(&mut arg).await.unwrap();
+                        args.push(String::from_utf8(arg[..arg.len() - 2].to_vec()).unwrap());
+                    }
+                    line.clear();
+                    let bulk = |value: Option<&String>| value.map_or("$-1\r\n".to_string(), |value| format!("${}\r\n{}\r\n", value.len(), value));
+                    let reply = {
+                        let mut keys = keys.lock().unwrap();
+                        match args[0].as_str() {
+                            "GET" => bulk(keys.strings.get(&args[1])),
+                            "SET" => {
+                                keys.strings.insert(args[1].clone(), args[2].clone());
+                                "+OK\r\n".to_string()
+                            }
+                            "DEL" => {
+                                keys.strings.remove(&args[1]);
+                                keys.hashes.remove(&args[1]);
+                                ":1\r\n".to_string()
+                            }
+                            "HSET" => {
+                                keys.hashes.entry(args[1].clone()).or_default().insert(args[2].clone(), args[3].clone());
+                                ":1\r\n".to_string()
+                            }
+                            "HDEL" => {
+                                keys.hashes.entry(args[1].clone()).or_default().remove(&args[2]);
+                                ":1\r\n".to_string()
+                            }
+                            "HGETALL" => {
+                                let hash = keys.hashes.get(&args[1]).cloned().unwrap_or_default();
+                                let items: String = hash.iter().map(|(field, value)| format!("{}{}", bulk(Some(field)), bulk(Some(value)))).collect();
+                                format!("*{}\r\n{}", hash.len() * 2, items)
+                            }
+                            other => format!("-ERR unknown command '{}'\r\n", other),
+                        }
+                    };
+                    if writer.write_all(reply.as_bytes()).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    url
+}
+
+fn hello(name: &str, token: Option<&str>) -> WorkerMessage {
+    WorkerMessage::Hello { name: name.to_string(), threads: 2, token: token.map(str::to_string) }
+}
+
+#[tokio::test]
+async fn hands_out_disjoint_assignments_and_forwards_blocks() {
+    let mut tasks = Tasks::new();
+    let coordinator = cluster::start("127.0.0.1:0".parse().unwrap(), Some("secret".to_string()), None, &mut tasks).await.unwrap();
+    let job = MiningJob::from_params(&notify_params("job1", "207fffff")).unwrap();
+    let target = calculate_target(&job.nbits).unwrap();
+    let (found_tx, mut found_rx) = tokio::sync::mpsc::unbounded_channel();
+    coordinator.publish(Arc::new(SharedWork {
+        job: job.clone(),
+        extranonce1: "f000000f".to_string(),
+        extranonce2_size: 4,
+        target: target.clone(),
+        found: found_tx,
+    }));
+
+    let mut intruder = Connection::open(coordinator.addr()).await;
+    intruder.send(hello("intruder", Some("guess"))).await;
+    assert!(matches!(intruder.receive().await, CoordinatorMessage::Refused { .. }));
+
+    let mut first = Connection::open(coordinator.addr()).await;
+    first.send(hello("pi-01", Some("secret"))).await;
+    assert!(matches!(first.receive().await, CoordinatorMessage::Welcome { .. }));
+    let one = first.work().await;
+    let mut second = Connection::open(coordinator.addr()).await;
+    second.send(hello("pi-02", Some("secret"))).await;
+    assert!(matches!(second.receive().await, CoordinatorMessage::Welcome { .. }));
+    let two = second.work().await;
+    assert_eq!((one.extranonce2.as_str(), one.nonce_start, one.nonce_end), ("00000000", 0, 0x0fff_ffff));
+    assert_eq!((two.extranonce2.as_str(), two.nonce_start), ("00000000", 0x1000_0000));
+
+    first.send(WorkerMessage::Progress { hashes: 5000, hashrate: 500.0, best_difficulty: 0.25 }).await;
+    first.send(WorkerMessage::Finished { assignment: one.id }).await;
+    let three = first.work().await;
+    assert_eq!(three.nonce_start, 0x2000_0000);
+    assert_eq!(coordinator.workers().len(), 2);
+    assert_eq!(coordinator.workers()[0].finished, 1);
+    let stats = coordinator.stats(100.0, 1000, 0.5);
+    assert_eq!((stats.hashrate, stats.hashes, stats.best_share_difficulty), (600.0, 6000, 0.5));
+    assert_eq!(stats.workers.len(), 2);
+
+    // Search the second assignment as its worker would
+    let merkle_root = compute_merkle_root_hex(&job, &two.extranonce1, &two.extranonce2).unwrap();
+    let nonce = (two.nonce_start..=two.nonce_end)
+        .map(|nonce| format!("{:08x}", nonce))
+        .find(|nonce| {
+            let header = create_block_header(&job.version, &job.prevhash, &merkle_root, &job.nbits, &job.ntime, nonce).unwrap();
+            hash_meets_target(&double_sha256(&header), &target)
+        })
+        .unwrap();
+    // Claimed by the wrong worker, it is ignored
+    first.send(WorkerMessage::Found { assignment: two.id, nonce: nonce.clone() }).await;
+    second.send(WorkerMessage::Found { assignment: two.id, nonce: nonce.clone() }).await;
+    let found = found_rx.recv().await.unwrap().unwrap();
+    assert_eq!(found.extranonce2, "00000000");
+    assert_eq!(found.nonce, nonce);
+    assert!(found_rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn peers_without_the_token_or_sending_endless_lines_are_dropped() {
+    let mut tasks = Tasks::new();
+    let coordinator = cluster::start("127.0.0.1:0".parse().unwrap(), Some("secret".to_string()), None, &mut tasks).await.unwrap();
+
+    let mut anonymous = Connection::open(coordinator.addr()).await;
+    anonymous.send(hello("anonymous", None)).await;
+    assert!(matches!(anonymous.receive().await, CoordinatorMessage::Refused { .. }));
+    let mut close = Connection::open(coordinator.addr()).await;
+    close.send(hello("close", Some("secreT"))).await;
+    assert!(matches!(close.receive().await, CoordinatorMessage::Refused { .. }));
+
+    // A megabyte without a newline is cut off rather than buffered
+    let mut flood = Connection::open(coordinator.addr()).await;
+    let _ = flood.writer.write_all(&vec![b'{'; 1024 * 1024]).await;
+    assert!(flood.lines.next_line().await.map_or(true, |line| line.is_none()));
+    assert!(coordinator.workers().is_empty());
+}
+
+#[tokio::test]
+async fn a_lost_workers_unfinished_assignment_goes_to_another_worker() {
+    let mut tasks = Tasks::new();
+    let coordinator = cluster::start("127.0.0.1:0".parse().unwrap(), None, None, &mut tasks).await.unwrap();
+    let job = MiningJob::from_params(&notify_params("job1", "1d00ffff")).unwrap();
+    let (found_tx, _found_rx) = tokio::sync::mpsc::unbounded_channel();
+    coordinator.publish(Arc::new(SharedWork {
+        target: calculate_target(&job.nbits).unwrap(),
+        job,
+        extranonce1: "f000000f".to_string(),
+        extranonce2_size: 4,
+        found: found_tx,
+    }));
+
+    let mut lost = Connection::open(coordinator.addr()).await;
+    lost.send(hello("pi-01", None)).await;
+    assert!(matches!(lost.receive().await, CoordinatorMessage::Welcome { .. }));
+    let unfinished = lost.work().await;
+    let mut healthy = Connection::open(coordinator.addr()).await;
+    healthy.send(hello("pi-02", None)).await;
+    assert!(matches!(healthy.receive().await, CoordinatorMessage::Welcome { .. }));
+    let second = healthy.work().await;
+    drop(lost);
+    while coordinator.workers().len() > 1 {
+        tokio::task::yield_now().await;
+    }
+
+    // The lost slice comes before any new one
+    healthy.send(WorkerMessage::Finished { assignment: second.id }).await;
+    let reissued = healthy.work().await;
+    assert_eq!((reissued.extranonce2.as_str(), reissued.nonce_start), (unfinished.extranonce2.as_str(), unfinished.nonce_start));
+    healthy.send(WorkerMessage::Finished { assignment: reissued.id }).await;
+    assert_eq!(healthy.work().await.nonce_start, 0x2000_0000);
+}
+
+#[tokio::test]
+async fn a_worker_reports_the_block_it_finds() {
+    let mut tasks = Tasks::new();
+    let coordinator = cluster::start("127.0.0.1:0".parse().unwrap(), None, None, &mut tasks).await.unwrap();
+    let job = MiningJob::from_params(&notify_params("job1", "207fffff")).unwrap();
+    let (found_tx, mut found_rx) = tokio::sync::mpsc::unbounded_channel();
+    coordinator.publish(Arc::new(SharedWork {
+        target: calculate_target(&job.nbits).unwrap(),
+        job,
+        extranonce1: "f000000f".to_string(),
+        extranonce2_size: 4,
+        found: found_tx,
+    }));
+
+    let options = WorkerOptions {
+        coordinator: coordinator.addr().to_string(),
+        name: "pi-01".to_string(),
+        threads: 2,
+        token: None,
+    };
+    let worker = tokio::spawn(cluster::run_worker(options, std::future::pending()));
+    let found = found_rx.recv().await.unwrap().unwrap();
+    assert_eq!(found.extranonce2, "00000000");
+    assert!(u32::from_str_radix(&found.nonce, 16).unwrap() < 0x1000_0000);
+    worker.abort();
+}
+
+#[tokio::test]
+async fn a_worker_rejects_assignments_it_cant_search() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let options = WorkerOptions {
+        coordinator: listener.local_addr().unwrap().to_string(),
+        name: "pi-01".to_string(),
+        threads: 2,
+        token: None,
+    };
+    let worker = tokio::spawn(cluster::run_worker(options, std::future::pending()));
+    let (stream, _) = listener.accept().await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut receive = async || serde_json::from_str::<WorkerMessage>(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert!(matches!(receive().await, WorkerMessage::Hello { .. }));
+
+    let job = MiningJob::from_params(&notify_params("job1", "1d00ffff")).unwrap();
+    let assignment = |id, nonce_start, nonce_end, target: String| Assignment {
+        id,
+        job: job.clone(),
+        extranonce1: "f000000f".to_string(),
+        extranonce2: "00000000".to_string(),
+        nonce_start,
+        nonce_end,
+        target,
+    };
+    let target = hex::encode(calculate_target("1d00ffff").unwrap());
+    for work in [assignment(1, 0x2000_0000, 0x1fff_ffff, target), assignment(2, 0, 0x0fff_ffff, "00ff".to_string())] {
+        let line = serde_json::to_string(&CoordinatorMessage::Work { assignment: Box::new(work) }).unwrap();
+        writer.write_all(format!("{}\n", line).as_bytes()).await.unwrap();
+    }
+    let WorkerMessage::Rejected { assignment, reason } = receive().await else { panic!("expected a rejection") };
+    assert_eq!(assignment, 1);
+    assert!(reason.contains("ends before it starts"), "{}", reason);
+    let WorkerMessage::Rejected { assignment, reason } = receive().await else { panic!("expected a rejection") };
+    assert_eq!(assignment, 2);
+    assert!(reason.contains("isn't 32 bytes"), "{}", reason);
+    // Still connected, waiting for work it can search
+    assert!(!worker.is_finished());
+    worker.abort();
+}
+
+#[tokio::test]
+async fn a_rejected_assignment_is_followed_by_the_next_one() {
+    let mut tasks = Tasks::new();
+    let coordinator = cluster::start("127.0.0.1:0".parse().unwrap(), None, None, &mut tasks).await.unwrap();
+    let job = MiningJob::from_params(&notify_params("job1", "1d00ffff")).unwrap();
+    let (found_tx, _found_rx) = tokio::sync::mpsc::unbounded_channel();
+    coordinator.publish(Arc::new(SharedWork {
+        target: calculate_target(&job.nbits).unwrap(),
+        job,
+        extranonce1: "f000000f".to_string(),
+        extranonce2_size: 4,
+        found: found_tx,
+    }));
+
+    let mut worker = Connection::open(coordinator.addr()).await;
+    worker.send(hello("pi-01", None)).await;
+    assert!(matches!(worker.receive().await, CoordinatorMessage::Welcome { .. }));
+    let first = worker.work().await;
+    worker.send(WorkerMessage::Rejected { assignment: first.id, reason: "test".to_string() }).await;
+    let next = worker.work().await;
+    assert!(next.id > first.id);
+    assert_ne!((next.extranonce2.as_str(), next.nonce_start), (first.extranonce2.as_str(), first.nonce_start));
+    // Rejected isn't searched
+    assert_eq!(coordinator.totals().get("pi-01").map_or(0, |totals| totals.finished), 0);
+}
+
+#[tokio::test]
+async fn a_restarted_coordinator_picks_up_its_state_from_redis() {
+    let redis = fake_redis().await;
+    let job = MiningJob::from_params(&notify_params("job1", "1d00ffff")).unwrap();
+    let publish = |coordinator: &cluster::Coordinator| {
+        let (found_tx, _) = tokio::sync::mpsc::unbounded_channel();
+        coordinator.publish(Arc::new(SharedWork {
+            target: calculate_target(&job.nbits).unwrap(),
+            job: job.clone(),
+            extranonce1: "f000000f".to_string(),
+            extranonce2_size: 4,
+            found: found_tx,
+        }));
+    };
+
+    let mut tasks = Tasks::new();
+    let coordinator = cluster::start("127.0.0.1:0".parse().unwrap(), None, Some(redis.clone()), &mut tasks).await.unwrap();
+    publish(&coordinator);
+    let mut worker = Connection::open(coordinator.addr()).await;
+    worker.send(hello("pi-01", None)).await;
+    assert!(matches!(worker.receive().await, CoordinatorMessage::Welcome { .. }));
+    let first = worker.work().await;
+    worker.send(WorkerMessage::Progress { hashes: 1000, hashrate: 100.0, best_difficulty: 0.5 }).await;
+    worker.send(WorkerMessage::Finished { assignment: first.id }).await;
+    let unfinished = worker.work().await;
+    tasks.stop(Stage::Mining).await;
+    drop(worker);
+
+    // The same job after the restart: the unfinished slice comes first, then new ones
+    let mut tasks = Tasks::new();
+    let coordinator = cluster::start("127.0.0.1:0".parse().unwrap(), None, Some(redis), &mut tasks).await.unwrap();
+    let totals = WorkerTotals { hashes: 1000, finished: 1, blocks: 0, best_share_difficulty: 0.5 };
+    assert_eq!(coordinator.totals().get("pi-01"), Some(&totals));
+    publish(&coordinator);
+    let mut worker = Connection::open(coordinator.addr()).await;
+    worker.send(hello("pi-02", None)).await;
+    assert!(matches!(worker.receive().await, CoordinatorMessage::Welcome { .. }));
+    let reissued = worker.work().await;
+    assert!(reissued.id > unfinished.id);
+    assert_eq!((reissued.extranonce2.as_str(), reissued.nonce_start), (unfinished.extranonce2.as_str(), unfinished.nonce_start));
+    worker.send(WorkerMessage::Finished { assignment: reissued.id }).await;
+    assert_eq!(worker.work().await.nonce_start, 0x2000_0000);
+}
+
+#[tokio::test]
+async fn redis_replies_announcing_huge_lengths_are_refused_without_allocating_them() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = RedisUrl::parse(&listener.local_addr().unwrap().to_string()).unwrap();
+    // One reply per connection, after which the server hangs up
+    tokio::spawn(async move {
+        for reply in ["*4000000000000000000\r\n$3\r\nkey\r\n", "$4000000000000000000\r\n", "$100\r\nshort"] {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.read(&mut [0; 64]).await;
+            stream.write_all(reply.as_bytes()).await.unwrap();
+        }
+    });
+
+    // The array grows only as its elements arrive, and ends where the server stops sending
+    let error = Redis::connect(&url).await.unwrap().hgetall("workers").await.unwrap_err();
+    assert!(error.to_string().contains("closed"), "{}", error);
+    let error = Redis::connect(&url).await.unwrap().get("cursor").await.unwrap_err();
+    assert!(error.to_string().contains("limit"), "{}", error);
+    let error = Redis::connect(&url).await.unwrap().get("cursor").await.unwrap_err();
+    assert!(error.to_string().contains("closed"), "{}", error);
+}
+
+#[test]
+fn mdns_answers_lead_workers_to_the_coordinator() {
+    let advert = mdns::Advert { name: "pi.lan".to_string(), port: 3335 };
+    let source: std::net::SocketAddr = "192.168.1.10:5353".parse().unwrap();
+    let answer = mdns::answer(&mdns::query(), &advert, "192.168.1.20".parse().unwrap()).unwrap();
+    let found = mdns::parse_answer(&answer, source);
+    assert_eq!(found, [mdns::Discovered { name: "pi-lan".to_string(), addr: "192.168.1.20:3335".parse().unwrap() }]);
+
+    // Answers aren't answered, and neither are queries for other services
+    assert!(mdns::answer(&answer, &advert, "192.168.1.20".parse().unwrap()).is_none());
+    let mut other = mdns::query();
+    // _btc-solo-cluster becomes _xtc-solo-cluster
+    other[14] = b'x';
+    assert!(mdns::answer(&other, &advert, "192.168.1.20".parse().unwrap()).is_none());
+}
@@ -0,0 +1,63 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use bitcoin_solo_miner::clock::{Clock, ManualClock, RandomSource};
+use bitcoin_solo_miner::payout::{AddressRotation, PayoutAddress, PayoutRotation};
+use bitcoin_solo_miner::stratum::MiningJob;
+use bitcoin_solo_miner::testutil::notify_params;
+use bitcoin_solo_miner::work::{calculate_target, random_extranonce2, HeaderTemplate};
+use bitcoin_solo_miner::worker::{FoundBlock, JobWork, WorkerPool};
+
+async fn find_block(seed: u64, threads: usize) -> FoundBlock {
+    let job = MiningJob::from_params(&notify_params("job1", "207fffff")).unwrap();
+    let work = Arc::new(JobWork {
+        target: calculate_target(&job.nbits).unwrap(),
+        template: HeaderTemplate::new(&job, "f000000f", 4).unwrap(),
+        job,
+        rng: RandomSource::seeded(seed),
+    });
+    let (found_tx, mut found_rx) = tokio::sync::mpsc::unbounded_channel();
+    let workers = WorkerPool::start(threads, work, found_tx).unwrap();
+    let found = found_rx.recv().await.unwrap().unwrap();
+    workers.join();
+    found
+}
+
+#[test]
+fn a_seed_repeats_its_extranonce2_sequence() {
+    let draw = |rng: &RandomSource| (0..5).map(|_| random_extranonce2(rng, 4)).collect::<Vec<_>>();
+    assert_eq!(draw(&RandomSource::seeded(7)), draw(&RandomSource::seeded(7)));
+    assert_ne!(draw(&RandomSource::seeded(7)), draw(&RandomSource::seeded(8)));
+}
+
+#[tokio::test]
+async fn a_seeded_worker_finds_the_same_block() {
+    let first = find_block(42, 1).await;
+    let second = find_block(42, 1).await;
+    assert_eq!(first.extranonce2, second.extranonce2);
+    assert_eq!(first.nonce, second.nonce);
+    assert_eq!(first.hash, second.hash);
+}
+
+#[test]
+fn weighted_rotation_repeats_with_a_seed() {
+    let picks = |seed| {
+        let addresses = vec![
+            PayoutAddress { address: "1BoatSLRHtKNngkdXEeobR76b53LETtpyT".to_string(), weight: 3 },
+            PayoutAddress { address: "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa".to_string(), weight: 1 },
+        ];
+        let mut rotation = PayoutRotation::new(addresses, AddressRotation::Weighted)
+            .unwrap()
+            .with_rng(RandomSource::seeded(seed));
+        (0..20).map(|_| rotation.next_address().to_string()).collect::<Vec<_>>()
+    };
+    assert_eq!(picks(1), picks(1));
+}
+
+#[test]
+fn a_manual_clock_only_moves_when_told() {
+    let clock = ManualClock::new(1_700_000_000);
+    assert_eq!(clock.unix_secs(), 1_700_000_000);
+    clock.advance(Duration::from_secs(90));
+    assert_eq!(clock.unix_secs(), 1_700_000_090);
+}
@@ -0,0 +1,26 @@
+use bitcoin_solo_miner::difficulty_check::{cross_check, DifficultyMismatch};
+
+#[test]
+fn agreeing_difficulties_pass() {
+    assert_eq!(cross_check(83.7e12, Some(83.7e12), Some(10_000.0)), vec![]);
+    // Explorers round what they report
+    assert_eq!(cross_check(83.7e12, Some(83.69e12), None), vec![]);
+    assert_eq!(cross_check(83.7e12, None, None), vec![]);
+}
+
+#[test]
+fn every_disagreement_is_reported() {
+    // Regtest work from a pool that claims to be on mainnet, asking for more than a block
+    assert_eq!(
+        cross_check(4.6e-10, Some(83.7e12), Some(1.0)),
+        vec![
+            DifficultyMismatch::Network { job: 4.6e-10, explorer: 83.7e12 },
+            DifficultyMismatch::PoolAboveNetwork { pool: 1.0, network: 4.6e-10 },
+        ]
+    );
+    assert_eq!(cross_check(83.7e12, None, Some(0.0)), vec![DifficultyMismatch::InvalidPool(0.0)]);
+    assert_eq!(
+        DifficultyMismatch::Network { job: 83.7e12, explorer: 90e12 }.to_string(),
+        "job nbits give network difficulty 83.7T, but the block explorer reports 90T"
+    );
+}
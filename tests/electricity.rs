@@ -0,0 +1,59 @@
+use bitcoin_solo_miner::electricity::{decide, parse_awattar, parse_octopus, parse_tibber, PriceDecision, PriceProvider, PriceSlot};
+use serde_json::json;
+
+/// 2024-01-01T00:00:00Z
+const MIDNIGHT: u64 = 1_704_067_200;
+
+fn slot(hour: u64, price: f64) -> PriceSlot {
+    PriceSlot { start: MIDNIGHT + hour * 3600, end: MIDNIGHT + (hour + 1) * 3600, price }
+}
+
+#[test]
+fn each_provider_is_read_into_prices_per_kwh() {
+    let tibber = json!({ "data": { "viewer": { "homes": [{ "currentSubscription": { "priceInfo": {
+        "today": [
+            { "total": 0.25, "startsAt": "2024-01-01T01:00:00.000+01:00" },
+            { "total": 0.31, "startsAt": "2024-01-01T02:00:00.000+01:00" },
+        ],
+        "tomorrow": [],
+    } } }] } } });
+    assert_eq!(parse_tibber(&tibber).unwrap(), vec![slot(0, 0.25), slot(1, 0.31)]);
+    assert!(parse_tibber(&json!({ "errors": [{ "message": "invalid token" }] })).is_err());
+
+    let octopus = json!({ "results": [
+        { "value_inc_vat": 30.0, "valid_from": "2024-01-01T01:00:00Z", "valid_to": "2024-01-01T02:00:00Z" },
+        { "value_inc_vat": 12.5, "valid_from": "2024-01-01T00:00:00Z", "valid_to": "2024-01-01T01:00:00Z" },
+    ] });
+    assert_eq!(parse_octopus(&octopus).unwrap(), vec![slot(0, 0.125), slot(1, 0.3)]);
+
+    let awattar = json!({ "data": [
+        { "start_timestamp": MIDNIGHT * 1000, "end_timestamp": (MIDNIGHT + 3600) * 1000, "marketprice": 95.0, "unit": "Eur/MWh" },
+    ] });
+    assert_eq!(parse_awattar(&awattar).unwrap(), vec![slot(0, 0.095)]);
+}
+
+#[test]
+fn hashing_pauses_above_the_price_limit_until_it_drops() {
+    let slots = [slot(0, 0.20), slot(1, 0.40), slot(2, 0.50), slot(3, 0.10)];
+    assert_eq!(decide(&slots, MIDNIGHT + 60, 0.30, 100), PriceDecision::Mine { price: 0.20 });
+
+    // Half an hour left at 0.40 and an hour at 0.50, drawing 100 W
+    let PriceDecision::Pause { price, until, projected_savings } = decide(&slots, MIDNIGHT + 5400, 0.30, 100) else {
+        panic!("expected a pause");
+    };
+    assert_eq!((price, until), (0.40, Some(MIDNIGHT + 3 * 3600)));
+    assert!((projected_savings - (0.05 * 0.40 + 0.1 * 0.50)).abs() < 1e-9);
+
+    assert_eq!(decide(&slots, MIDNIGHT + 5 * 3600, 0.30, 100), PriceDecision::Unknown);
+}
+
+#[test]
+fn providers_need_their_settings() {
+    assert_eq!(PriceProvider::parse("", "", "", "").unwrap(), None);
+    assert!(PriceProvider::parse("tibber", "", "", "").is_err());
+    assert!(PriceProvider::parse("octopus", "", "", "").is_err());
+    assert_eq!(
+        PriceProvider::parse("awattar", "", "", "AT").unwrap(),
+        Some(PriceProvider::Awattar { country: "at".into() })
+    );
+}
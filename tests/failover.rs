@@ -0,0 +1,118 @@
+use std::time::{Duration, Instant};
+use bitcoin_solo_miner::failover::{parse_pools, Blacklisting, FailoverSettings, PoolList, SessionOutcome};
+
+const COOLDOWN: Duration = Duration::from_secs(600);
+
+fn pools(backups: &[&str], blacklist_after: u32) -> PoolList {
+    let settings = FailoverSettings {
+        pools: backups.iter().map(|pool| pool.to_string()).collect(),
+        blacklist_after,
+        cooldown: COOLDOWN,
+    };
+    PoolList::new("primary:3333".to_string(), Some(&settings))
+}
+
+#[test]
+fn pools_are_parsed_from_a_comma_separated_list() {
+    assert_eq!(parse_pools(" a:1, b:2 ,,"), vec!["a:1", "b:2"]);
+    assert!(parse_pools("").is_empty());
+}
+
+#[test]
+fn a_failing_pool_is_blacklisted_and_the_next_takes_over() {
+    let now = Instant::now();
+    let mut list = pools(&["backup:3333"], 3);
+    assert_eq!(list.select(), "primary:3333");
+    assert_eq!(list.record(SessionOutcome::NeverWorked, now), None);
+    assert_eq!(list.record(SessionOutcome::RejectedEverything, now), None);
+    assert_eq!(list.select(), "primary:3333");
+    assert_eq!(
+        list.record(SessionOutcome::NeverWorked, now),
+        Some(Blacklisting { pool: "primary:3333".to_string(), failures: 3, cooldown: COOLDOWN })
+    );
+    assert_eq!(list.select(), "backup:3333");
+    assert!(!list.all_blacklisted());
+}
+
+#[test]
+fn a_session_that_works_forgives_earlier_failures() {
+    let now = Instant::now();
+    let mut list = pools(&["backup:3333"], 2);
+    list.select();
+    assert_eq!(list.record(SessionOutcome::NeverWorked, now), None);
+    assert_eq!(list.record(SessionOutcome::Worked, now), None);
+    assert_eq!(list.record(SessionOutcome::NeverWorked, now), None);
+    assert_eq!(list.select(), "primary:3333");
+}
+
+#[test]
+fn a_pool_back_from_its_cooldown_is_blacklisted_on_its_next_failure() {
+    let now = Instant::now();
+    let mut list = pools(&["backup:3333"], 3);
+    list.select();
+    for _ in 0..3 {
+        list.record(SessionOutcome::NeverWorked, now);
+    }
+    assert!(list.release_expired(now + COOLDOWN / 2).is_empty());
+    assert_eq!(list.release_expired(now + COOLDOWN), vec!["primary:3333"]);
+    assert_eq!(list.select(), "primary:3333");
+    assert!(list.record(SessionOutcome::NeverWorked, now + COOLDOWN).is_some());
+}
+
+#[test]
+fn with_every_pool_blacklisted_the_one_back_first_is_used() {
+    let now = Instant::now();
+    let mut list = pools(&["backup:3333"], 1);
+    list.select();
+    list.record(SessionOutcome::NeverWorked, now);
+    assert_eq!(list.select(), "backup:3333");
+    list.record(SessionOutcome::NeverWorked, now + Duration::from_secs(1));
+    assert!(list.all_blacklisted());
+    assert_eq!(list.select(), "primary:3333");
+    // Working there lifts its blacklisting early
+    list.record(SessionOutcome::Worked, now + Duration::from_secs(2));
+    assert!(!list.all_blacklisted());
+}
+
+#[test]
+fn a_lone_pool_or_blacklisting_off_never_blacklists() {
+    let now = Instant::now();
+    let mut lone = PoolList::new("primary:3333".to_string(), None);
+    let mut off = pools(&["backup:3333"], 0);
+    for list in [&mut lone, &mut off] {
+        assert!(!list.can_fail_over());
+        list.select();
+        for _ in 0..10 {
+            assert_eq!(list.record(SessionOutcome::NeverWorked, now), None);
+        }
+        assert_eq!(list.select(), "primary:3333");
+    }
+}
+
+#[test]
+fn the_primary_is_not_listed_twice() {
+    let now = Instant::now();
+    let mut list = pools(&["primary:3333"], 1);
+    assert!(!list.can_fail_over());
+    list.select();
+    assert_eq!(list.record(SessionOutcome::NeverWorked, now), None);
+}
+
+#[test]
+fn a_new_primary_replaces_the_old_one_with_a_clean_record() {
+    let now = Instant::now();
+    let mut list = pools(&["backup:3333", "other:3333"], 1);
+    list.select();
+    list.record(SessionOutcome::NeverWorked, now);
+    assert_eq!(list.select(), "backup:3333");
+
+    list.set_primary("new:3333".to_string());
+    assert_eq!(list.select(), "new:3333");
+    // A backup chosen as the primary isn't kept as a backup too
+    list.set_primary("other:3333".to_string());
+    assert_eq!(list.select(), "other:3333");
+    list.record(SessionOutcome::NeverWorked, now);
+    assert_eq!(list.select(), "backup:3333");
+    list.record(SessionOutcome::NeverWorked, now);
+    assert!(list.all_blacklisted());
+}
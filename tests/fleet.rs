@@ -0,0 +1,64 @@
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use bitcoin_solo_miner::fleet::{self, Fleet, FleetInstance, InstanceStatus};
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+fn mining(hashrate: u64) -> InstanceStatus {
+    InstanceStatus { connected: true, hashrate, ..Default::default() }
+}
+
+#[test]
+fn alerts_once_on_silence_and_again_on_recovery() {
+    let instances = FleetInstance::parse_list("pi-01=10.0.0.1:7777, 10.0.0.3:7777").unwrap();
+    assert_eq!(instances[1].name, "10.0.0.3:7777");
+    let started = Instant::now();
+    let at = |minutes: u64| started + Duration::from_secs(minutes * 60);
+    let mut fleet = Fleet::new(instances, Duration::from_secs(20 * 60), started);
+
+    assert!(fleet.update(vec![Ok(mining(100)), Ok(mining(50))], at(0)).is_empty());
+    assert_eq!(fleet.hashrate(), 150);
+    assert!(fleet.update(vec![Ok(mining(100)), Err(anyhow!("connection refused"))], at(5)).is_empty());
+    assert_eq!((fleet.mining(), fleet.hashrate()), (1, 100));
+    // Silence counts from the last answer
+    let alerts = fleet.update(vec![Ok(mining(100)), Err(anyhow!("connection refused"))], at(20));
+    assert_eq!(alerts, ["10.0.0.3:7777 silent for 20 minutes (connection refused)"]);
+    assert!(fleet.update(vec![Ok(mining(100)), Err(anyhow!("connection refused"))], at(25)).is_empty());
+    assert!(fleet.summary(at(25)).contains("10.0.0.3:7777: silent for 25 minutes (connection refused)"));
+
+    let no_pool = InstanceStatus { connected: false, ..Default::default() };
+    let alerts = fleet.update(vec![Ok(no_pool.clone()), Ok(mining(50))], at(30));
+    assert_eq!(alerts, ["10.0.0.3:7777 is back after 30 minutes"]);
+    let alerts = fleet.update(vec![Ok(no_pool), Ok(mining(50))], at(50));
+    assert_eq!(alerts, ["pi-01 has had no pool connection for 20 minutes"]);
+    let alerts = fleet.update(vec![Ok(mining(100)), Ok(mining(50))], at(51));
+    assert_eq!(alerts, ["pi-01 is connected to its pool again after 21 minutes"]);
+    assert!(fleet.summary(at(51)).starts_with("2/2 mining | 150 h/s"));
+}
+
+#[tokio::test]
+async fn queries_status_with_the_api_token() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let (reader, mut writer) = stream.into_split();
+            let line = BufReader::new(reader).lines().next_line().await.unwrap().unwrap();
+            let request: Value = serde_json::from_str(&line).unwrap();
+            assert_eq!(request["method"], "status");
+            let response = if request["params"]["token"] == "secret" {
+                json!({"jsonrpc": "2.0", "id": request["id"], "result": {"connected": true, "hashrate": 1234, "session": {"hashes": 99}}})
+            } else {
+                json!({"jsonrpc": "2.0", "id": request["id"], "error": {"code": -32001, "message": "missing or wrong API token"}})
+            };
+            writer.write_all(format!("{}\n", response).as_bytes()).await.unwrap();
+        }
+    });
+
+    let status = fleet::query_status(&addr, Some("secret")).await.unwrap();
+    assert_eq!((status.connected, status.hashrate, status.session.hashes), (true, 1234, 99));
+    let refused = fleet::query_status(&addr, None).await.unwrap_err();
+    assert_eq!(refused.to_string(), "missing or wrong API token");
+}
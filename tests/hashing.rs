@@ -0,0 +1,22 @@
+use bitcoin_solo_miner::hashing::{double_sha256, double_sha256_portable, hash_hex};
+
+/// The genesis block header
+const GENESIS_HEADER: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c";
+
+#[test]
+fn the_genesis_header_hashes_to_the_genesis_block() {
+    let header = hex::decode(GENESIS_HEADER).unwrap();
+    assert_eq!(hash_hex(&double_sha256(&header)), "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f");
+    assert_eq!(double_sha256(&header), double_sha256_portable(&header));
+}
+
+#[cfg(sha256_asm)]
+#[test]
+fn the_assembly_implementation_agrees_at_every_padding_length() {
+    use bitcoin_solo_miner::hashing::double_sha256_asm;
+
+    let data: Vec<u8> = (0..=200u8).collect();
+    for len in 0..data.len() {
+        assert_eq!(double_sha256_asm(&data[..len]), double_sha256_portable(&data[..len]), "{} bytes", len);
+    }
+}
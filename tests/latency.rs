@@ -0,0 +1,45 @@
+use std::time::Duration;
+use bitcoin_solo_miner::latency::{LatencyChange, LatencyStats, LatencyTracker};
+
+fn record_ms(tracker: &mut LatencyTracker, ms: u64) -> Option<LatencyChange> {
+    tracker.record(Duration::from_millis(ms))
+}
+
+#[test]
+fn round_trips_are_summarised() {
+    let mut tracker = LatencyTracker::default();
+    assert_eq!(tracker.stats(), LatencyStats::default());
+    assert_eq!(tracker.stats().summary(), None);
+    for ms in [40, 30, 50] {
+        assert_eq!(record_ms(&mut tracker, ms), None);
+    }
+    let stats = tracker.stats();
+    assert_eq!((stats.last_ms, stats.recent_ms), (Some(50.0), Some(40.0)));
+    assert_eq!((stats.min_ms, stats.max_ms, stats.samples), (Some(30.0), Some(50.0), 3));
+    assert_eq!(stats.summary().as_deref(), Some("50 ms (recent 40 ms, 30-50 ms)"));
+}
+
+#[test]
+fn a_slower_link_is_degraded_until_it_recovers() {
+    let mut tracker = LatencyTracker::default();
+    for _ in 0..5 {
+        assert_eq!(record_ms(&mut tracker, 100), None);
+    }
+    // The recent average climbs past three times the 100 ms baseline on the fourth slow trip
+    let changes: Vec<_> = (0..5).filter_map(|_| record_ms(&mut tracker, 400)).collect();
+    assert_eq!(changes, vec![LatencyChange::Degraded { recent_ms: 340.0, baseline_ms: 100.0 }]);
+    assert!(tracker.stats().degraded);
+    assert!(tracker.stats().summary().unwrap().ends_with(", degraded"));
+
+    let changes: Vec<_> = (0..5).filter_map(|_| record_ms(&mut tracker, 100)).collect();
+    assert_eq!(changes, vec![LatencyChange::Recovered { recent_ms: 280.0, baseline_ms: 100.0 }]);
+    assert!(!tracker.stats().degraded);
+}
+
+#[test]
+fn a_fast_link_getting_a_little_slower_is_fine() {
+    let mut tracker = LatencyTracker::default();
+    for ms in [5, 5, 5, 5, 5, 60, 60, 60, 60, 60] {
+        assert_eq!(record_ms(&mut tracker, ms), None);
+    }
+}
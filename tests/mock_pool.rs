@@ -0,0 +1,399 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use bitcoin_solo_miner::backend::{PoolBackend, Solution, StratumV1};
+use bitcoin_solo_miner::bandwidth;
+use bitcoin_solo_miner::events::{self, MinerEvent};
+use bitcoin_solo_miner::exit_code::ExitCode;
+use bitcoin_solo_miner::hooks::Hooks;
+use bitcoin_solo_miner::log_rotation::RotationPolicy;
+use bitcoin_solo_miner::logging::Verbosity;
+use bitcoin_solo_miner::miner::{self, MiningConfig};
+use bitcoin_solo_miner::session::{SessionError, SessionMachine, SessionState};
+use bitcoin_solo_miner::stats::{LifetimeStats, MinerStats};
+use bitcoin_solo_miner::stratum::{StratumClient, StratumError};
+use bitcoin_solo_miner::submit::{self, SubmitPolicy};
+use bitcoin_solo_miner::testutil::{notify_params, Fault, MockPool, MockPoolConfig};
+use bitcoin_solo_miner::timestamp::Timestamps;
+
+const ADDRESS: &str = "1BoatSLRHtKNngkdXEeobR76b53LETtpyT";
+
+fn mining_config(pool: &MockPool) -> Arc<Mutex<MiningConfig>> {
+    let config = MiningConfig::new(
+        ADDRESS.to_string(),
+        pool.url(),
+        1,
+        None,
+        MinerStats::new(LifetimeStats::default()),
+        PathBuf::new(),
+        PathBuf::new(),
+        RotationPolicy::default(),
+        false,
+        false,
+        Verbosity::default(),
+        Timestamps::default(),
+        Hooks::default(),
+    );
+    Arc::new(Mutex::new(config))
+}
+
+fn session(pool: &MockPool) -> SessionMachine {
+    SessionMachine::new(mining_config(pool), pool.url())
+}
+
+/// A job whose coinbase starts with the BIP34 push of `height`
+fn job_at_height(job_id: &str, prevhash: &str, height: u32) -> serde_json::Value {
+    let mut params = notify_params(job_id, "1d00ffff");
+    params[1] = prevhash.into();
+    // The script is the height push, then extranonce1 and extranonce2
+    params[2] = format!("01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0c03{}", &hex::encode(height.to_le_bytes())[..6]).into();
+    params
+}
+
+/// Everything logged on this thread while the returned guard lives
+fn capture_logs() -> (Arc<Mutex<Vec<u8>>>, tracing::subscriber::DefaultGuard) {
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+    impl std::io::Write for Capture {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let logs = Arc::new(Mutex::new(Vec::new()));
+    let writer = Arc::clone(&logs);
+    let subscriber = tracing_subscriber::fmt().with_ansi(false).with_writer(move || Capture(Arc::clone(&writer))).finish();
+    (logs, tracing::subscriber::set_default(subscriber))
+}
+
+async fn connected_client(pool: &MockPool) -> StratumClient {
+    let mut client = StratumClient::connect(pool.addr()).await.unwrap();
+    client.subscribe().await.unwrap();
+    client.authorize(ADDRESS, "x").await.unwrap();
+    client
+}
+
+#[tokio::test]
+async fn subscribes_authorizes_and_receives_jobs() {
+    let pool = MockPool::start(MockPoolConfig {
+        extranonce1: "abcd0123".to_string(),
+        extranonce2_size: 8,
+        jobs: vec![notify_params("job1", "207fffff")],
+        ..Default::default()
+    }).await.unwrap();
+
+    let mut client = StratumClient::connect(pool.addr()).await.unwrap();
+    let subscription = client.subscribe().await.unwrap();
+    assert_eq!(subscription.extranonce1, "abcd0123");
+    assert_eq!(subscription.extranonce2_size, 8);
+    assert!(client.authorize(ADDRESS, "x").await.unwrap().accepted());
+    assert_eq!(client.next_job().await.unwrap().job_id, "job1");
+
+    pool.notify(notify_params("job2", "1d00ffff"));
+    let job = client.next_job().await.unwrap();
+    assert_eq!(job.job_id, "job2");
+    assert_eq!(job.nbits, "1d00ffff");
+
+    let methods: Vec<_> = pool.requests().iter().map(|r| r["method"].as_str().unwrap().to_string()).collect();
+    assert_eq!(methods, ["mining.subscribe", "mining.authorize"]);
+    assert_eq!(pool.requests_for("mining.authorize")[0]["params"][0], ADDRESS);
+}
+
+#[tokio::test]
+async fn counts_the_traffic_on_the_connection() {
+    let pool = MockPool::start(MockPoolConfig {
+        jobs: vec![notify_params("job1", "207fffff")],
+        ..Default::default()
+    }).await.unwrap();
+    let before = bandwidth::totals();
+    let mut client = connected_client(&pool).await;
+    client.next_job().await.unwrap();
+
+    // Other tests may be talking to their pools at the same time, so this is a lower bound
+    let traffic = bandwidth::totals().since(before);
+    let sent: usize = pool.requests().iter().map(|request| request.to_string().len() + 1).sum();
+    assert!(traffic.sent_bytes >= sent as u64);
+    assert!(traffic.received_bytes > notify_params("job1", "207fffff").to_string().len() as u64);
+}
+
+#[tokio::test]
+async fn reassembles_partial_frames() {
+    let pool = MockPool::start(MockPoolConfig {
+        jobs: vec![notify_params("job1", "207fffff")],
+        faults: vec![Fault::PartialFrames { chunk: 7, delay: Duration::from_millis(1) }],
+        ..Default::default()
+    }).await.unwrap();
+
+    let mut client = connected_client(&pool).await;
+    assert_eq!(client.next_job().await.unwrap().job_id, "job1");
+}
+
+#[tokio::test]
+async fn reports_a_dropped_connection() {
+    let pool = MockPool::start(MockPoolConfig {
+        jobs: vec![notify_params("job1", "207fffff")],
+        faults: vec![Fault::DisconnectAfter(3)],
+        ..Default::default()
+    }).await.unwrap();
+
+    let mut client = connected_client(&pool).await;
+    assert_eq!(client.next_job().await.unwrap().job_id, "job1");
+    assert!(matches!(client.next_job().await, Err(StratumError::Closed)));
+}
+
+#[tokio::test]
+async fn reports_a_pool_restart() {
+    let pool = MockPool::start(MockPoolConfig::default()).await.unwrap();
+    let mut client = connected_client(&pool).await;
+    assert_eq!(pool.connections(), 1);
+
+    pool.disconnect_all();
+    assert!(matches!(client.next_notification().await, Err(StratumError::Closed)));
+    assert_eq!(pool.connections(), 0);
+}
+
+#[tokio::test]
+async fn shows_the_pool_operators_messages() {
+    let mut pool_events = events::subscribe();
+    let pool = MockPool::start(MockPoolConfig::default()).await.unwrap();
+    let mut client = connected_client(&pool).await;
+
+    pool.show_message("Maintenance at 12:00 UTC,\n\tback by 12:30");
+    pool.show_message(&"x".repeat(600));
+    pool.notify(notify_params("job1", "207fffff"));
+    // Messages are taken out of the way of jobs
+    assert_eq!(client.next_job().await.unwrap().job_id, "job1");
+    assert!(client.queued_jobs().is_empty());
+
+    let mut messages = Vec::new();
+    while let Ok(event) = pool_events.try_recv() {
+        if let MinerEvent::PoolMessage { message } = event {
+            messages.push(message);
+        }
+    }
+    assert!(messages.contains(&"Maintenance at 12:00 UTC, back by 12:30".to_string()));
+    assert!(messages.contains(&format!("{}…", "x".repeat(500))));
+}
+
+#[tokio::test]
+async fn rejects_messages_that_are_not_json() {
+    let pool = MockPool::start(MockPoolConfig {
+        faults: vec![Fault::Garbage("<html>502 Bad Gateway</html>".to_string())],
+        ..Default::default()
+    }).await.unwrap();
+
+    let mut client = StratumClient::connect(pool.addr()).await.unwrap();
+    let error = client.subscribe().await.unwrap_err();
+    assert!(matches!(error, StratumError::InvalidMessage { ref line, .. } if line.contains("502")));
+}
+
+#[tokio::test]
+async fn passes_on_a_rejected_submission() {
+    let pool = MockPool::start(MockPoolConfig {
+        jobs: vec![notify_params("job1", "207fffff")],
+        reject_submissions: Some("Stale".to_string()),
+        ..Default::default()
+    }).await.unwrap();
+
+    let mut backend = StratumV1::connect(&pool.url(), ADDRESS, &mut session(&pool)).await.unwrap();
+    let work = backend.next_work().await.unwrap();
+    let result = backend.submit(&Solution {
+        job_id: work.job.job_id,
+        extranonce2: "00000000".to_string(),
+        ntime: work.job.ntime,
+        nonce: "00000000".to_string(),
+    }).await.unwrap();
+    assert!(!result.accepted);
+    assert!(result.reason.unwrap().contains("Stale"));
+    assert_eq!(pool.requests_for("mining.submit")[0]["params"][1], "job1");
+}
+
+#[tokio::test]
+async fn resends_a_submission_whose_reply_was_lost() {
+    let pool = MockPool::start(MockPoolConfig {
+        jobs: vec![notify_params("job1", "207fffff")],
+        faults: vec![Fault::IgnoreFirst("mining.submit".to_string(), 1)],
+        ..Default::default()
+    }).await.unwrap();
+
+    let mut backend = StratumV1::connect(&pool.url(), ADDRESS, &mut session(&pool)).await.unwrap();
+    let work = backend.next_work().await.unwrap();
+    let solution = Solution {
+        job_id: work.job.job_id,
+        extranonce2: "00000000".to_string(),
+        ntime: work.job.ntime,
+        nonce: "0000abcd".to_string(),
+    };
+    let policy = SubmitPolicy { timeout: Duration::from_millis(200), retries: 2 };
+    let submitted = submit::submit(&mut backend, &solution, policy).await.unwrap();
+    // The pool got the first attempt, so the resend's duplicate reject acknowledges it
+    assert!(submitted.result.accepted);
+    assert_eq!(submitted.attempts, 2);
+    assert_eq!(pool.requests_for("mining.submit").len(), 2);
+}
+
+#[tokio::test]
+async fn alerts_on_a_submission_the_pool_never_answers() {
+    let mut submit_events = events::subscribe();
+    let pool = MockPool::start(MockPoolConfig {
+        jobs: vec![notify_params("job1", "207fffff")],
+        faults: vec![Fault::Ignore("mining.submit".to_string())],
+        ..Default::default()
+    }).await.unwrap();
+
+    let mut backend = StratumV1::connect(&pool.url(), ADDRESS, &mut session(&pool)).await.unwrap();
+    let work = backend.next_work().await.unwrap();
+    let solution = Solution {
+        job_id: work.job.job_id,
+        extranonce2: "00000000".to_string(),
+        ntime: work.job.ntime,
+        nonce: "0000dcba".to_string(),
+    };
+    let policy = SubmitPolicy { timeout: Duration::from_millis(100), retries: 1 };
+    let error = submit::submit(&mut backend, &solution, policy).await.unwrap_err();
+    assert!(error.to_string().contains("2 attempt(s)"));
+    assert_eq!(pool.requests_for("mining.submit").len(), 2);
+
+    let mut unacknowledged = None;
+    while let Ok(event) = submit_events.try_recv() {
+        if let MinerEvent::SubmitUnacknowledged { nonce, attempts, .. } = event {
+            if nonce == "0000dcba" {
+                unacknowledged = Some(attempts);
+            }
+        }
+    }
+    assert_eq!(unacknowledged, Some(2));
+}
+
+#[tokio::test]
+async fn connects_through_the_session_states() {
+    let pool = MockPool::start(MockPoolConfig::default()).await.unwrap();
+    let mut session = session(&pool);
+    let mut backend = StratumV1::connect(&pool.url(), ADDRESS, &mut session).await.unwrap();
+    assert_eq!(session.state(), SessionState::Authorized);
+
+    // Authorizing again is the keepalive, and must not disturb the session
+    backend.keepalive().await.unwrap();
+    assert_eq!(pool.requests_for("mining.authorize").len(), 2);
+    assert!(matches!(
+        session.transition(SessionState::Subscribed),
+        Err(SessionError::InvalidTransition { from: SessionState::Authorized, to: SessionState::Subscribed })
+    ));
+}
+
+#[tokio::test]
+async fn stops_on_a_rejected_authorization() {
+    let pool = MockPool::start(MockPoolConfig {
+        reject_authorization: Some("Unauthorized worker".to_string()),
+        ..Default::default()
+    }).await.unwrap();
+
+    let mut session = session(&pool);
+    let error = StratumV1::connect(&pool.url(), ADDRESS, &mut session).await.err().unwrap();
+    assert_eq!(ExitCode::of(&error), ExitCode::AuthorizationRejected);
+    assert_eq!(session.state(), SessionState::Subscribed);
+}
+
+#[tokio::test(start_paused = true)]
+async fn times_out_a_pool_that_never_answers() {
+    let pool = MockPool::start(MockPoolConfig {
+        faults: vec![Fault::Ignore("mining.subscribe".to_string())],
+        ..Default::default()
+    }).await.unwrap();
+
+    let error = StratumV1::connect(&pool.url(), ADDRESS, &mut session(&pool)).await.err().unwrap();
+    assert!(matches!(
+        error.downcast_ref::<SessionError>(),
+        Some(SessionError::Timeout { state: SessionState::Connecting, .. })
+    ));
+}
+
+#[tokio::test]
+async fn switches_jobs_on_the_same_connection() {
+    let (logs, _guard) = capture_logs();
+    let mut jobs = events::subscribe();
+    let pool = MockPool::start(MockPoolConfig {
+        jobs: vec![job_at_height("job1", &"11".repeat(32), 870001)],
+        ..Default::default()
+    }).await.unwrap();
+    let config = mining_config(&pool);
+    {
+        let mut config = config.lock().unwrap();
+        // The address the test coinbase pays, and no explorer to cross-check the difficulty against
+        config.address = "1111111111111111111114oLvT2".to_string();
+        config.low_bandwidth = true;
+    }
+    let mut session = SessionMachine::new(Arc::clone(&config), pool.url());
+
+    let mut next_received = async || loop {
+        if let Ok(MinerEvent::JobReceived { job_id, height, .. }) = jobs.recv().await {
+            return (job_id, height);
+        }
+    };
+    let drive = async {
+        // The heights come from the coinbases, one below the block each job builds
+        assert_eq!(next_received().await, ("job1".to_string(), 870000));
+        pool.notify(job_at_height("job2", &"22".repeat(32), 870002));
+        assert_eq!(next_received().await, ("job2".to_string(), 870001));
+        assert_eq!(pool.connections(), 1);
+        config.lock().unwrap().restart_requested = true;
+    };
+    let miner = miner::bitcoin_miner(Arc::clone(&config), &mut session);
+    tokio::pin!(miner);
+    tokio::select! {
+        result = &mut miner => panic!("The session ended early: {:?}", result),
+        () = drive => {}
+    }
+    miner.await.unwrap();
+
+    assert_eq!(pool.requests_for("mining.subscribe").len(), 1);
+    // The second job was read while the first was hashed, not waited for
+    let logs = String::from_utf8(logs.lock().unwrap().clone()).unwrap();
+    assert_eq!(logs.matches("waiting_for_job").count(), 1, "{}", logs);
+}
+
+#[tokio::test]
+async fn submits_an_extranonce2_of_the_size_the_pool_announced() {
+    let mut job = job_at_height("job1", &"11".repeat(32), 870001);
+    // ckpool's 8-byte extranonce2 after the height push and the 4-byte extranonce1
+    job[2] = job[2].as_str().unwrap().replace("ffffffff0c03", "ffffffff1003").into();
+    job[6] = "207fffff".into();
+    let pool = MockPool::start(MockPoolConfig {
+        extranonce2_size: 8,
+        jobs: vec![job],
+        ..Default::default()
+    }).await.unwrap();
+    let blocks_dir = std::env::temp_dir().join(format!("miner-extranonce2-{}", std::process::id()));
+    let config = mining_config(&pool);
+    {
+        let mut config = config.lock().unwrap();
+        config.address = "1111111111111111111114oLvT2".to_string();
+        config.low_bandwidth = true;
+        config.blocks_dir = blocks_dir.clone();
+    }
+    let mut session = SessionMachine::new(Arc::clone(&config), pool.url());
+
+    let drive = async {
+        while pool.requests_for("mining.submit").is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // The miner only looks at the restart request while it has a job to work on
+        config.lock().unwrap().restart_requested = true;
+        pool.notify(job_at_height("job2", &"22".repeat(32), 870002));
+    };
+    let miner = miner::bitcoin_miner(Arc::clone(&config), &mut session);
+    tokio::pin!(miner);
+    tokio::select! {
+        result = &mut miner => panic!("The session ended early: {:?}", result),
+        () = drive => {}
+    }
+    miner.await.unwrap();
+    let _ = std::fs::remove_dir_all(&blocks_dir);
+
+    let extranonce2 = pool.requests_for("mining.submit")[0]["params"][2].as_str().unwrap().to_string();
+    assert_eq!(extranonce2.len(), 16);
+    assert!(hex::decode(&extranonce2).is_ok());
+}
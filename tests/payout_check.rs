@@ -0,0 +1,64 @@
+use bitcoin_solo_miner::address;
+use bitcoin_solo_miner::coinbase;
+use bitcoin_solo_miner::coinbase::{format_btc, Reward};
+use bitcoin_solo_miner::payout::coinbase_reward;
+use bitcoin_solo_miner::stratum::MiningJob;
+use bitcoin_solo_miner::testutil::notify_params;
+
+const ADDRESS: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+/// A job whose coinbase pays 6.25 BTC to `script` and nothing to a witness commitment
+fn job_paying(script: &[u8]) -> MiningJob {
+    let mut params = notify_params("job1", "1d00ffff");
+    // Input script: the 4-byte extranonce1 and 4-byte extranonce2
+    params[2] = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff08".into();
+    params[3] = format!(
+        "ffffffff0240be402500000000{:02x}{}0000000000000000266a24aa21a9ed{}00000000",
+        script.len(),
+        hex::encode(script),
+        "00".repeat(32)
+    ).into();
+    MiningJob::from_params(&params).unwrap()
+}
+
+#[test]
+fn addresses_turn_into_their_output_scripts() {
+    assert_eq!(hex::encode(address::script_pubkey(ADDRESS).unwrap()), "0014751e76e8199196d454941c45d1b3a323f1433bd6");
+    assert_eq!(
+        hex::encode(address::script_pubkey("1BoatSLRHtKNngkdXEeobR76b53LETtpyT").unwrap()),
+        "76a9147680adec8eabcabac676be9e83854ade0bd22cdb88ac"
+    );
+    assert_eq!(
+        hex::encode(address::script_pubkey("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0").unwrap()),
+        "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+    );
+}
+
+#[test]
+fn the_coinbase_must_pay_the_address() {
+    let job = job_paying(&address::script_pubkey(ADDRESS).unwrap());
+    let outputs = coinbase::outputs(&job, "f000000f", 4).unwrap();
+    assert_eq!(outputs.len(), 2);
+    assert_eq!(outputs[0].value, 625_000_000);
+    assert_eq!(coinbase_reward(&job, "f000000f", 4, ADDRESS).unwrap(), Reward { total: 625_000_000, to_address: 625_000_000 });
+    assert_eq!(
+        coinbase_reward(&job, "f000000f", 4, "1BoatSLRHtKNngkdXEeobR76b53LETtpyT").unwrap(),
+        Reward { total: 625_000_000, to_address: 0 }
+    );
+
+    // An extranonce2 size the coinbase wasn't built for doesn't parse
+    assert!(coinbase_reward(&job, "f000000f", 8, ADDRESS).is_err());
+}
+
+#[test]
+fn the_reward_adds_up_every_output_and_the_ones_paying_the_address() {
+    let script = address::script_pubkey(ADDRESS).unwrap();
+    let outputs = [
+        coinbase::TxOut { value: 300_000_000, script_pubkey: script.clone() },
+        coinbase::TxOut { value: 12_500_000, script_pubkey: vec![0x51] },
+        coinbase::TxOut { value: 10_000, script_pubkey: script.clone() },
+    ];
+    assert_eq!(Reward::of(&outputs, &script), Reward { total: 312_510_000, to_address: 300_010_000 });
+    assert_eq!(format_btc(312_510_000), "3.12510000 BTC");
+    assert_eq!(format_btc(546), "0.00000546 BTC");
+}
@@ -0,0 +1,62 @@
+use bitcoin_solo_miner::backend::Solution;
+use bitcoin_solo_miner::pool_check::{predict, PoolRejection, PoolState, SubmittedShares};
+use bitcoin_solo_miner::stratum::MiningJob;
+use bitcoin_solo_miner::testutil::notify_params;
+
+fn job(job_id: &str) -> MiningJob {
+    MiningJob::from_params(&notify_params(job_id, "207fffff")).unwrap()
+}
+
+fn solution(job: &MiningJob) -> Solution {
+    Solution { job_id: job.job_id.clone(), extranonce2: "00000001".into(), ntime: job.ntime.clone(), nonce: "0000beef".into() }
+}
+
+fn pool<'a>(newer_jobs: &'a [MiningJob], submitted: &'a SubmittedShares) -> PoolState<'a> {
+    PoolState { newer_jobs, extranonce2_size: 4, difficulty: Some(10.0), submitted }
+}
+
+#[test]
+fn a_well_formed_new_share_is_predicted_to_be_accepted() {
+    let job = job("job1");
+    let submitted = SubmittedShares::default();
+    assert_eq!(predict(&job, &solution(&job), 20.0, pool(&[], &submitted)), vec![]);
+
+    // A newer job on the same block doesn't make this one stale
+    let mut newer = self::job("job2");
+    newer.clean_jobs = false;
+    assert_eq!(predict(&job, &solution(&job), 20.0, pool(&[newer], &submitted)), vec![]);
+}
+
+#[test]
+fn shares_the_pool_would_refuse_are_predicted_rejects() {
+    let job = job("job1");
+    let mut submitted = SubmittedShares::default();
+
+    let mut unknown = solution(&job);
+    unknown.job_id = "job9".into();
+    assert_eq!(predict(&job, &unknown, 20.0, pool(&[], &submitted)), vec![PoolRejection::UnknownJob("job9".into())]);
+    assert_eq!(
+        predict(&job, &solution(&job), 20.0, pool(&[self::job("job2")], &submitted)),
+        vec![PoolRejection::StaleJob("job1".into())]
+    );
+
+    let job_ntime = u32::from_str_radix(&job.ntime, 16).unwrap();
+    let mut malformed = solution(&job);
+    malformed.extranonce2 = "0001".into();
+    malformed.ntime = format!("{:08x}", job_ntime + 7001);
+    malformed.nonce = "beef".into();
+    assert_eq!(
+        predict(&job, &malformed, 5.0, pool(&[], &submitted)),
+        vec![
+            PoolRejection::Extranonce2Length { extranonce2: "0001".into(), expected: 4 },
+            PoolRejection::NtimeOutOfRange { ntime: job_ntime + 7001, job_ntime },
+            PoolRejection::InvalidNonce("beef".into()),
+            PoolRejection::LowDifficulty { difficulty: 5.0, required: 10.0 },
+        ]
+    );
+
+    submitted.record(&solution(&job));
+    let mut upper_case = solution(&job);
+    upper_case.nonce = "0000BEEF".into();
+    assert_eq!(predict(&job, &upper_case, 20.0, pool(&[], &submitted)), vec![PoolRejection::Duplicate]);
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+use bitcoin_solo_miner::power::{counter_delta, estimated_joules, Rapl};
+use bitcoin_solo_miner::stats::{LifetimeStats, MinerStats};
+
+#[test]
+fn estimates_count_only_hashing_threads() {
+    assert_eq!(estimated_joules(12.5, 4, Duration::from_secs(10)), 500.0);
+    assert_eq!(estimated_joules(12.5, 0, Duration::from_secs(10)), 0.0);
+}
+
+#[test]
+fn rapl_counters_wrap_at_their_range() {
+    assert_eq!(counter_delta(1_000, 5_000, 10_000), 4_000);
+    assert_eq!(counter_delta(9_000, 500, 10_000), 1_500);
+}
+
+#[test]
+fn rapl_adds_up_the_packages_but_not_their_subzones() {
+    let powercap = std::env::temp_dir().join(format!("miner-powercap-{}", std::process::id()));
+    let write = |path: &str, contents: &str| {
+        let path = powercap.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    };
+    write("intel-rapl:0/energy_uj", "1000000\n");
+    write("intel-rapl:0/max_energy_range_uj", "262143328850\n");
+    write("intel-rapl:0:0/energy_uj", "400000\n");
+    write("intel-rapl:1/energy_uj", "262143000000\n");
+    write("intel-rapl:1/max_energy_range_uj", "262143328850\n");
+
+    let mut rapl = Rapl::open(&powercap).unwrap();
+    write("intel-rapl:0/energy_uj", "3500000\n");
+    write("intel-rapl:0:0/energy_uj", "9000000\n");
+    // Package 1 wrapped around
+    write("intel-rapl:1/energy_uj", "671150\n");
+    let joules = rapl.joules().unwrap();
+    std::fs::remove_dir_all(&powercap).unwrap();
+    assert!((joules - 3.5).abs() < 1e-9, "{}", joules);
+}
+
+#[test]
+fn energy_and_its_cost_are_reported_per_session_and_per_day() {
+    let mut stats = MinerStats::new(LifetimeStats::default());
+    assert_eq!(stats.session_summary().energy_line(), None);
+    // 1 kWh at 0.30, then 0.5 kWh before the price was known
+    stats.record_energy(3.6e6, Some(0.30));
+    stats.record_energy(1.8e6, None);
+    let summary = stats.session_summary();
+    assert_eq!(summary.energy_kwh, 1.5);
+    assert!((summary.energy_cost.unwrap() - 0.30).abs() < 1e-9);
+    assert_eq!(summary.per_day(1.0), 86_400.0 / summary.duration_secs.max(1) as f64);
+    assert!(summary.energy_line().unwrap().starts_with("1.500 kWh ("));
+    assert!(summary.summary_line().contains(", cost 0.30 ("));
+}
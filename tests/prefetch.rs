@@ -0,0 +1,61 @@
+use bitcoin_solo_miner::backend::WorkTemplate;
+use bitcoin_solo_miner::coinbase::WitnessCheck;
+use bitcoin_solo_miner::prefetch::PreparedJob;
+use bitcoin_solo_miner::stratum::MiningJob;
+use bitcoin_solo_miner::testutil::notify_params;
+use bitcoin_solo_miner::work::{calculate_target, network_difficulty};
+
+/// The address the test job's coinbase pays: P2PKH of an all-zero key hash
+const ADDRESS: &str = "1111111111111111111114oLvT2";
+
+fn work(params: serde_json::Value) -> WorkTemplate {
+    WorkTemplate {
+        job: MiningJob::from_params(&params).unwrap(),
+        extranonce1: "f000000f".to_string(),
+        extranonce2_size: 28,
+    }
+}
+
+#[test]
+fn a_prepared_job_carries_what_mining_it_needs() {
+    let prepared = PreparedJob::prepare(work(notify_params("job1", "1d00ffff")), ADDRESS);
+    assert_eq!(prepared.target.unwrap(), calculate_target("1d00ffff").unwrap());
+    assert_eq!(prepared.difficulty.unwrap(), network_difficulty("1d00ffff").unwrap());
+    let reward = prepared.reward.unwrap();
+    assert_eq!((reward.total, reward.to_address), (50_0000_0000, 50_0000_0000));
+    assert_eq!(prepared.witness.unwrap(), WitnessCheck::Absent);
+    let header = prepared.template.unwrap().with_extranonce2(&"00".repeat(28)).unwrap();
+    assert_eq!(header.bits.to_consensus(), 0x1d00ffff);
+    // The test coinbase has no BIP34 height push
+    assert!(prepared.height.is_err());
+}
+
+#[test]
+fn a_bad_job_is_prepared_with_its_errors() {
+    let prepared = PreparedJob::prepare(work(notify_params("job1", "zz")), ADDRESS);
+    assert!(prepared.target.is_err());
+    assert!(prepared.difficulty.is_err());
+    assert!(prepared.template.is_err());
+}
+
+#[test]
+fn jobs_replace_the_current_one_on_a_new_block_or_when_cleaned() {
+    let current = work(notify_params("job1", "1d00ffff")).job;
+
+    let mut update = notify_params("job2", "1d00ffff");
+    update[8] = false.into();
+    let update = PreparedJob::prepare(work(update), ADDRESS);
+    assert!(!update.new_block(&current));
+    assert!(!update.replaces(&current));
+
+    let cleaned = PreparedJob::prepare(work(notify_params("job3", "1d00ffff")), ADDRESS);
+    assert!(!cleaned.new_block(&current));
+    assert!(cleaned.replaces(&current));
+
+    let mut next_block = notify_params("job4", "1d00ffff");
+    next_block[1] = "11".repeat(32).into();
+    next_block[8] = false.into();
+    let next_block = PreparedJob::prepare(work(next_block), ADDRESS);
+    assert!(next_block.new_block(&current));
+    assert!(next_block.replaces(&current));
+}
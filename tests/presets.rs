@@ -0,0 +1,38 @@
+use std::time::Duration;
+use bitcoin_solo_miner::presets::{self, CKPOOL_PRESETS};
+
+#[test]
+fn presets_are_found_by_name() {
+    assert_eq!(presets::find("ckpool-eu").unwrap().address, "eusolo.ckpool.org:3333");
+    assert_eq!(presets::find(" CKPOOL-AU ").unwrap().address, "ausolo.ckpool.org:3333");
+    assert!(presets::find("ckpool").is_none());
+    assert!(presets::find("pool.example.com:3333").is_none());
+}
+
+#[test]
+fn a_pool_setting_stands_for_an_address() {
+    assert_eq!(presets::address("ckpool-main"), "solo.ckpool.org:3333");
+    assert_eq!(presets::address("ckpool"), "solo.ckpool.org:3333");
+    assert_eq!(presets::address("pool.example.com:3333"), "pool.example.com:3333");
+}
+
+#[test]
+fn the_fastest_server_that_answered_is_picked() {
+    let [main, eu, au] = [&CKPOOL_PRESETS[0], &CKPOOL_PRESETS[1], &CKPOOL_PRESETS[2]];
+    let probes = [
+        (main, Some(Duration::from_millis(120))),
+        (eu, None),
+        (au, Some(Duration::from_millis(40))),
+    ];
+    assert_eq!(presets::fastest(&probes), Some((au, Duration::from_millis(40))));
+    assert_eq!(presets::fastest(&[(main, None), (eu, None)]), None);
+}
+
+#[tokio::test]
+async fn probing_times_the_connection() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    assert!(presets::probe(&address).await.is_some());
+    drop(listener);
+    assert_eq!(presets::probe(&address).await, None);
+}
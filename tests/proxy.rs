@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use bitcoin_solo_miner::hashing::{double_sha256, hash_meets_target};
+use bitcoin_solo_miner::proxy;
+use bitcoin_solo_miner::stratum::{MiningJob, StratumClient};
+use bitcoin_solo_miner::tasks::Tasks;
+use bitcoin_solo_miner::testutil::notify_params;
+use bitcoin_solo_miner::work::{calculate_target, compute_merkle_root_hex, create_block_header};
+use bitcoin_solo_miner::worker::SharedWork;
+
+#[tokio::test]
+async fn splits_extranonce_space_and_forwards_blocks() {
+    let mut tasks = Tasks::new();
+    let proxy = proxy::start("127.0.0.1:0".parse().unwrap(), 0, &mut tasks).await.unwrap();
+    let job = MiningJob::from_params(&notify_params("job1", "207fffff")).unwrap();
+    let target = calculate_target(&job.nbits).unwrap();
+    let (found_tx, mut found_rx) = tokio::sync::mpsc::unbounded_channel();
+    proxy.publish(Arc::new(SharedWork {
+        job: job.clone(),
+        extranonce1: "f000000f".to_string(),
+        extranonce2_size: 8,
+        target: target.clone(),
+        found: found_tx,
+    }));
+
+    let mut first = StratumClient::connect(proxy.addr()).await.unwrap();
+    let mut second = StratumClient::connect(proxy.addr()).await.unwrap();
+    let subscription = first.subscribe().await.unwrap();
+    assert_eq!(subscription.extranonce1, "f000000f0001");
+    assert_eq!(subscription.extranonce2_size, 6);
+    assert_eq!(second.subscribe().await.unwrap().extranonce1, "f000000f0002");
+
+    assert!(first.authorize("lan-miner", "x").await.unwrap().accepted());
+    assert_eq!(first.next_job().await.unwrap().job_id, "job1");
+
+    // Search as the first miner would until a header meets the network target
+    let extranonce2 = "000000000000";
+    let merkle_root = compute_merkle_root_hex(&job, "f000000f0001", extranonce2).unwrap();
+    let nonce = (0u32..)
+        .map(|nonce| format!("{:08x}", nonce))
+        .find(|nonce| {
+            let header = create_block_header(&job.version, &job.prevhash, &merkle_root, &job.nbits, &job.ntime, nonce).unwrap();
+            hash_meets_target(&double_sha256(&header), &target)
+        })
+        .unwrap();
+
+    assert!(first.submit("lan-miner", "job1", extranonce2, &job.ntime, &nonce).await.unwrap().accepted());
+    let found = found_rx.recv().await.unwrap().unwrap();
+    assert_eq!(found.extranonce2, "0001000000000000");
+    assert_eq!(found.nonce, nonce);
+
+    let duplicate = first.submit("lan-miner", "job1", extranonce2, &job.ntime, &nonce).await.unwrap();
+    assert_eq!(duplicate.error[0], 22);
+    let stale = first.submit("lan-miner", "job0", extranonce2, &job.ntime, &nonce).await.unwrap();
+    assert_eq!(stale.error[0], 21);
+}
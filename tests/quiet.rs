@@ -0,0 +1,38 @@
+use bitcoin_solo_miner::quiet::{parse_windows, QuietSchedule, QuietWindow};
+use bitcoin_solo_miner::timestamp::TimeZone;
+
+#[test]
+fn windows_parse_from_a_comma_separated_list() {
+    let windows = parse_windows("22:00-07:00, 12:00-13:30").unwrap();
+    assert_eq!(windows, vec![QuietWindow { start: 22 * 60, end: 7 * 60 }, QuietWindow { start: 12 * 60, end: 13 * 60 + 30 }]);
+    assert_eq!(windows[1].to_string(), "12:00-13:30");
+    assert!(parse_windows("").unwrap().is_empty());
+    assert!(parse_windows("22:00").is_err());
+    assert!(parse_windows("25:00-07:00").is_err());
+    assert!(parse_windows("08:00-08:00").is_err());
+}
+
+#[test]
+fn a_window_ending_before_it_starts_runs_past_midnight() {
+    let night = QuietWindow::parse("22:00-07:00").unwrap();
+    assert!(night.contains(23 * 60));
+    assert!(night.contains(0));
+    assert!(night.contains(7 * 60 - 1));
+    assert!(!night.contains(7 * 60));
+    assert!(!night.contains(12 * 60));
+
+    let lunch = QuietWindow::parse("12:00-13:00").unwrap();
+    assert!(lunch.contains(12 * 60));
+    assert!(!lunch.contains(13 * 60));
+    assert!(!lunch.contains(11 * 60 + 59));
+}
+
+#[test]
+fn the_time_of_day_is_taken_in_the_configured_zone() {
+    // 2024-01-01 21:30 UTC
+    let secs = 1_704_144_600;
+    let schedule = |zone| QuietSchedule { windows: parse_windows("22:00-07:00").unwrap(), zone };
+    assert_eq!(schedule(TimeZone::Utc).window_at(secs), None);
+    let plus_two = TimeZone::parse("+02:00").unwrap();
+    assert_eq!(schedule(plus_two).window_at(secs), Some(QuietWindow { start: 22 * 60, end: 7 * 60 }));
+}
@@ -0,0 +1,39 @@
+// The recorder is process-wide, so these tests get their own test binary
+use bitcoin_solo_miner::recording;
+use bitcoin_solo_miner::stratum::{StratumClient, StratumError};
+use bitcoin_solo_miner::testutil::{notify_params, Fault, MockPool, MockPoolConfig};
+
+const ADDRESS: &str = "1BoatSLRHtKNngkdXEeobR76b53LETtpyT";
+
+#[tokio::test]
+async fn replays_a_recorded_session() {
+    let path = std::env::temp_dir().join(format!("miner-recording-{}.ndjson", std::process::id()));
+    recording::start(&path).unwrap();
+
+    let pool = MockPool::start(MockPoolConfig {
+        extranonce1: "abcd0123".to_string(),
+        jobs: vec![notify_params("job1", "207fffff"), notify_params("job2", "1d00ffff")],
+        faults: vec![Fault::DisconnectAfter(4)],
+        ..Default::default()
+    }).await.unwrap();
+    let mut client = StratumClient::connect(pool.addr()).await.unwrap();
+    client.subscribe().await.unwrap();
+    client.authorize(ADDRESS, "x").await.unwrap();
+    client.next_job().await.unwrap();
+    client.next_job().await.unwrap();
+    assert!(matches!(client.next_job().await, Err(StratumError::Closed)));
+    drop(pool);
+
+    let (addr, finished) = recording::replay(&path).await.unwrap();
+    let mut client = StratumClient::connect(addr).await.unwrap();
+    assert_eq!(client.subscribe().await.unwrap().extranonce1, "abcd0123");
+    assert!(client.authorize(ADDRESS, "x").await.unwrap().accepted());
+    assert_eq!(client.next_job().await.unwrap().job_id, "job1");
+    assert_eq!(client.next_job().await.unwrap().nbits, "1d00ffff");
+    assert!(matches!(client.next_job().await, Err(StratumError::Closed)));
+
+    // Connecting past the end of the recording finishes the replay
+    let _ = StratumClient::connect(addr).await;
+    finished.await.unwrap();
+    let _ = std::fs::remove_file(&path);
+}
@@ -0,0 +1,6 @@
+use bitcoin_solo_miner::self_test;
+
+#[tokio::test]
+async fn the_hashing_pipeline_reproduces_the_known_blocks() {
+    assert_eq!(self_test::run().await.unwrap(), 4);
+}
@@ -0,0 +1,70 @@
+use std::path::{Path, PathBuf};
+use bitcoin_solo_miner::sensors::{read_from, SensorReadings};
+
+/// A scratch sysfs tree, removed when dropped
+struct FakeSys(PathBuf);
+
+impl FakeSys {
+    fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("miner-sensors-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        Self(root)
+    }
+
+    fn write(&self, path: &str, contents: &str) {
+        let path = self.0.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for FakeSys {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn readings_come_from_hwmon_and_cpufreq() {
+    let sys = FakeSys::new("hwmon");
+    sys.write("class/hwmon/hwmon0/name", "coretemp\n");
+    sys.write("class/hwmon/hwmon0/temp1_input", "62000\n");
+    sys.write("class/hwmon/hwmon0/temp2_input", "71500\n");
+    sys.write("class/hwmon/hwmon0/temp1_max", "100000\n");
+    // A drive's temperature isn't the CPU's, but a board controller's fans count
+    sys.write("class/hwmon/hwmon1/name", "nvme\n");
+    sys.write("class/hwmon/hwmon1/temp1_input", "90000\n");
+    sys.write("class/hwmon/hwmon2/name", "nct6775\n");
+    sys.write("class/hwmon/hwmon2/fan1_input", "1450\n");
+    sys.write("class/hwmon/hwmon2/fan2_input", "0\n");
+    sys.write("devices/system/cpu/cpu0/cpufreq/scaling_cur_freq", "3400000\n");
+    sys.write("devices/system/cpu/cpu1/cpufreq/scaling_cur_freq", "2600000\n");
+    sys.write("devices/system/cpu/cpufreq/policy0/scaling_cur_freq", "1000\n");
+
+    let readings = read_from(sys.path());
+    assert_eq!(readings, SensorReadings { cpu_temp_c: Some(71.5), fan_rpm: Some(1450), cpu_mhz: Some(3000.0) });
+    assert_eq!(readings.summary().as_deref(), Some("CPU 71.5°C | fan 1450 RPM | 3000 MHz"));
+}
+
+#[test]
+fn thermal_zones_stand_in_without_a_cpu_hwmon_driver() {
+    let sys = FakeSys::new("thermal");
+    sys.write("class/thermal/thermal_zone0/type", "x86_pkg_temp\n");
+    sys.write("class/thermal/thermal_zone0/temp", "55000\n");
+    sys.write("class/thermal/thermal_zone1/type", "iwlwifi_1\n");
+    sys.write("class/thermal/thermal_zone1/temp", "80000\n");
+
+    let readings = read_from(sys.path());
+    assert_eq!(readings, SensorReadings { cpu_temp_c: Some(55.0), ..Default::default() });
+}
+
+#[test]
+fn a_machine_without_sensors_has_no_readings() {
+    let sys = FakeSys::new("none");
+    assert_eq!(read_from(sys.path()), SensorReadings::default());
+    assert_eq!(SensorReadings::default().summary(), None);
+}
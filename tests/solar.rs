@@ -0,0 +1,22 @@
+use bitcoin_solo_miner::solar::{parse_surplus, threads_for};
+
+#[test]
+fn the_surplus_is_read_as_a_bare_number_or_from_json() {
+    assert_eq!(parse_surplus("1250.5\n", None).unwrap(), 1250.5);
+    assert_eq!(parse_surplus(r#"{"grid":{"export_w":830}}"#, Some("/grid/export_w")).unwrap(), 830.0);
+    assert_eq!(parse_surplus(r#"{"surplus":"412"}"#, Some("/surplus")).unwrap(), 412.0);
+    assert!(parse_surplus(r#"{"grid":{}}"#, Some("/grid/export_w")).is_err());
+    assert!(parse_surplus("n/a", None).is_err());
+}
+
+#[test]
+fn threads_follow_the_surplus_including_what_the_miner_already_draws() {
+    // 25 W per thread, up to 8 threads
+    assert_eq!(threads_for(60.0, 0, 25, 8), 2);
+    // Two threads running and 60 W still going to the grid: room for two more
+    assert_eq!(threads_for(60.0, 2, 25, 8), 4);
+    // Importing 30 W with four running: drop to two
+    assert_eq!(threads_for(-30.0, 4, 25, 8), 2);
+    assert_eq!(threads_for(-500.0, 4, 25, 8), 0);
+    assert_eq!(threads_for(5000.0, 4, 25, 8), 8);
+}
@@ -0,0 +1,47 @@
+use bitcoin_solo_miner::events::MinerEvent;
+use bitcoin_solo_miner::sensors::SensorReadings;
+use bitcoin_solo_miner::stats::{format_closest_call, LifetimeStats, MinerStats};
+
+#[test]
+fn the_closest_call_weighs_each_share_against_its_own_job() {
+    let mut stats = MinerStats::new(LifetimeStats { closest_call: 0.001, ..Default::default() });
+    stats.record_share_difficulty(0.5, 1000.0);
+    // A lower difficulty, but on an easier job
+    stats.record_share_difficulty(0.2, 100.0);
+    let summary = stats.session_summary();
+    assert_eq!(summary.best_share_difficulty, 0.5);
+    assert_eq!(summary.closest_call, 0.002);
+    assert_eq!(stats.totals().closest_call, 0.002);
+
+    stats.record_event(&MinerEvent::BlockFound {
+        job_id: "job1".into(),
+        hash: "00".repeat(32),
+        target: "00".repeat(32),
+        nonce: "00000000".into(),
+        address: String::new(),
+        network_difficulty: 100.0,
+        hash_difficulty: 150.0,
+        reward_sats: None,
+        payout_sats: None,
+    });
+    assert_eq!(stats.session_summary().best_share_difficulty, 150.0);
+    assert_eq!(stats.session_summary().closest_call, 1.5);
+
+    assert_eq!(format_closest_call(0.002), "1/500");
+    assert_eq!(format_closest_call(1e-9), "1/1G");
+    assert_eq!(format_closest_call(1.5), "a block");
+    assert_eq!(format_closest_call(0.0), "none");
+}
+
+#[test]
+fn sensor_readings_keep_the_latest_and_the_peak_temperature() {
+    let mut stats = MinerStats::new(LifetimeStats::default());
+    assert_eq!(stats.session_summary().peak_cpu_temp_c, None);
+    stats.record_sensors(SensorReadings { cpu_temp_c: Some(81.0), fan_rpm: Some(2100), cpu_mhz: None });
+    stats.record_sensors(SensorReadings { cpu_temp_c: Some(64.5), ..Default::default() });
+    assert_eq!(stats.sensors().cpu_temp_c, Some(64.5));
+    assert_eq!(stats.sensors().fan_rpm, None);
+    let summary = stats.session_summary();
+    assert_eq!(summary.peak_cpu_temp_c, Some(81.0));
+    assert!(summary.summary_line().ends_with("| peak CPU temperature 81.0°C"));
+}
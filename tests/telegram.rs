@@ -0,0 +1,38 @@
+use bitcoin_solo_miner::notify::{parse_telegram_updates, TelegramConfig};
+use serde_json::json;
+
+fn telegram() -> TelegramConfig {
+    TelegramConfig {
+        bot_token: "123:abc".to_string(),
+        user_id: "42".to_string(),
+        notify_summary: false,
+        notify_weekly: false,
+        commands: true,
+        notify_pool_messages: false,
+        notify_network_blocks: false,
+    }
+}
+
+fn update(chat_id: i64, sender_id: i64) -> serde_json::Value {
+    json!({"update_id": 7, "message": {"chat": {"id": chat_id}, "from": {"id": sender_id}, "date": 1_700_000_000, "text": "/setpool evil.example:3333"}})
+}
+
+#[test]
+fn commands_are_taken_only_from_the_configured_user_in_their_own_chat() {
+    let body = json!({"ok": true, "result": [update(42, 42), update(42, 666), update(-100, 42), update(666, 666)]});
+    let updates = parse_telegram_updates(&body);
+    assert_eq!(updates.len(), 4);
+    assert_eq!((updates[1].chat_id.as_str(), updates[1].sender_id.as_str()), ("42", "666"));
+    assert_eq!(updates[1].text, "/setpool evil.example:3333");
+
+    let accepted: Vec<bool> = updates.iter().map(|update| telegram().accepts_command(update)).collect();
+    // The right chat but someone else sending, a group the user is in, and a stranger
+    assert_eq!(accepted, [true, false, false, false]);
+}
+
+#[test]
+fn a_message_without_a_sender_is_not_a_command() {
+    let body = json!({"result": [{"update_id": 8, "message": {"chat": {"id": 42}, "date": 1_700_000_000, "text": "/pause"}}]});
+    let updates = parse_telegram_updates(&body);
+    assert!(!telegram().accepts_command(&updates[0]));
+}
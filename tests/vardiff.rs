@@ -0,0 +1,35 @@
+use bitcoin_solo_miner::vardiff::{DifficultyChange, DifficultyWatch};
+
+#[test]
+fn small_vardiff_steps_go_unreported() {
+    let mut watch = DifficultyWatch::default();
+    assert_eq!(watch.observe(10_000.0, 4.0), None);
+    assert_eq!(watch.observe(20_000.0, 4.0), None);
+    assert_eq!(watch.observe(10_000.0, 4.0), None);
+    assert_eq!(watch.observe(10_000.0, 4.0), None);
+}
+
+#[test]
+fn a_jump_either_way_is_reported() {
+    let mut watch = DifficultyWatch::default();
+    watch.observe(10_000.0, 4.0);
+    let change = watch.observe(50_000.0, 4.0).unwrap();
+    assert_eq!(change, DifficultyChange { from: 10_000.0, to: 50_000.0 });
+    assert_eq!(change.ratio(), 5.0);
+    assert_eq!(change.to_string(), "from 10k to 50k (5.0x higher)");
+    // A reset to the pool's starting difficulty after a reconnect
+    let reset = watch.observe(1.0, 4.0).unwrap();
+    assert_eq!(reset, DifficultyChange { from: 50_000.0, to: 1.0 });
+    assert_eq!(reset.to_string(), "from 50k to 1.0000 (50000.0x lower)");
+}
+
+#[test]
+fn reporting_can_be_turned_off_and_nonsense_is_ignored() {
+    let mut watch = DifficultyWatch::default();
+    watch.observe(10_000.0, 0.0);
+    assert_eq!(watch.observe(1.0, 0.0), None);
+    assert_eq!(watch.observe(0.0, 4.0), None);
+    assert_eq!(watch.observe(f64::NAN, 4.0), None);
+    // The last real difficulty is still the one compared with
+    assert_eq!(watch.observe(3.0, 4.0), None);
+}
@@ -0,0 +1,79 @@
+use bitcoin_solo_miner::stats::{LifetimeStats, MinerStats};
+use bitcoin_solo_miner::weekly::{expected_blocks, WeeklyStats, WEEK_SECS};
+
+const START: u64 = 1_700_000_000;
+
+#[test]
+fn the_first_week_starts_on_the_first_check() {
+    let mut stats = MinerStats::new(LifetimeStats::default());
+    assert_eq!(stats.roll_week(START), None);
+    assert_eq!(stats.week().started_at, START);
+    assert_eq!(stats.roll_week(START + WEEK_SECS - 1), None);
+}
+
+#[test]
+fn a_finished_week_is_handed_over_and_the_next_starts_empty() {
+    let mut stats = MinerStats::new(LifetimeStats::default());
+    stats.roll_week(START);
+    stats.record_hashes(1_000);
+    stats.record_expected_blocks(1_000, 1.0);
+    stats.record_share();
+    stats.record_share_difficulty(0.5, 1000.0);
+    stats.record_energy(3.6e6, Some(0.25));
+
+    let week = stats.roll_week(START + WEEK_SECS).unwrap();
+    assert_eq!(week.started_at, START);
+    assert_eq!(week.hashes, 1_000);
+    assert_eq!(week.blocks_submitted, 1);
+    assert_eq!(week.best_share_difficulty, 0.5);
+    assert_eq!(week.energy_kwh, 1.0);
+    assert_eq!(week.energy_cost, Some(0.25));
+    assert!(week.expected_blocks > 0.0);
+
+    let next = stats.week();
+    assert_eq!(next.started_at, START + WEEK_SECS);
+    assert_eq!(next.hashes, 0);
+    assert_eq!(next.best_share_difficulty, 0.0);
+    // The lifetime totals go on, and carry the new week to the state file
+    assert_eq!(stats.totals().total_hashes, 1_000);
+    assert_eq!(stats.totals().week.started_at, START + WEEK_SECS);
+}
+
+#[test]
+fn a_restart_carries_on_with_the_saved_week() {
+    let saved = LifetimeStats {
+        week: WeeklyStats { started_at: START, hashes: 5_000, uptime_secs: 100, ..Default::default() },
+        ..Default::default()
+    };
+    let mut stats = MinerStats::new(saved);
+    stats.record_hashes(1_000);
+    assert_eq!(stats.roll_week(START + 60), None);
+    assert_eq!(stats.week().hashes, 6_000);
+    assert!(stats.week().uptime_secs >= 100);
+}
+
+#[test]
+fn the_report_sets_what_was_found_against_the_odds() {
+    // 2^32 hashes find a block at difficulty 1 on average
+    assert_eq!(expected_blocks(4_294_967_296, 1.0), 1.0);
+    assert_eq!(expected_blocks(1_000, 0.0), 0.0);
+
+    let week = WeeklyStats {
+        started_at: START,
+        hashes: 6_048_000_000,
+        uptime_secs: 604_800,
+        connected_secs: 599_000,
+        disconnected_secs: 1_000,
+        best_share_difficulty: 12.5,
+        expected_blocks: expected_blocks(6_048_000_000, 83.7e12),
+        ..Default::default()
+    };
+    assert_eq!(week.average_hashrate(), 10_000.0);
+    assert!((week.availability_percent() - 99.8333).abs() < 0.001);
+    assert_eq!(week.odds_line(), "0 found, 1.68e-14 expected (1 in 59.4T)");
+    assert_eq!(week.energy_line(), None);
+    assert_eq!(
+        week.summary_line(),
+        "6048000000 hashes | avg 10000 h/s | uptime 168.0h | availability 99.83% | best share difficulty 12.5000 | blocks 0 found, 1.68e-14 expected (1 in 59.4T)"
+    );
+}
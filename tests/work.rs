@@ -0,0 +1,134 @@
+use bitcoin_solo_miner::hashing::{double_sha256, hash_below, hash_hex, hash_meets_target};
+use bitcoin_solo_miner::stats::{format_difficulty, hash_difficulty};
+use bitcoin_solo_miner::stratum::MiningJob;
+use bitcoin_solo_miner::testutil::notify_params;
+use bitcoin_solo_miner::work::{calculate_target, compute_merkle_root_hex, create_block_header, network_difficulty, stratum_prevhash, HeaderTemplate};
+
+/// The genesis block's coinbase, its only transaction
+const GENESIS_COINBASE: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+const GENESIS_MERKLE_ROOT: &str = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+const GENESIS_HASH: &str = "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f";
+
+#[test]
+fn the_genesis_block_header_is_rebuilt_from_its_fields() {
+    let mut params = notify_params("genesis", "1d00ffff");
+    params[2] = GENESIS_COINBASE.into();
+    params[3] = "".into();
+    let job = MiningJob::from_params(&params).unwrap();
+    assert_eq!(compute_merkle_root_hex(&job, "", "").unwrap(), GENESIS_MERKLE_ROOT);
+
+    let header = create_block_header("00000001", &"00".repeat(32), GENESIS_MERKLE_ROOT, "1d00ffff", "495fab29", "7c2bac1d").unwrap();
+    assert_eq!(header.len(), 80);
+    // Little-endian fields, the merkle root in internal byte order and time before bits
+    assert_eq!(&header[..4], &[1, 0, 0, 0]);
+    assert_eq!(hex::encode(&header[36..40]), "3ba3edfd");
+    assert_eq!(hex::encode(&header[68..80]), "29ab5f49ffff001d1dac2b7c");
+
+    let hash = double_sha256(&header);
+    assert_eq!(hash_hex(&hash), GENESIS_HASH);
+    assert!(hash_meets_target(&hash, &calculate_target("1d00ffff").unwrap()));
+    // Difficulty 1 is all the genesis block needed, but its hash happened to do far better
+    assert!(!hash_meets_target(&hash, &calculate_target("1a00ffff").unwrap()));
+    assert!(hash_difficulty(&hash) > 2000.0);
+}
+
+#[test]
+fn targets_come_from_the_compact_encoding() {
+    assert_eq!(hex::encode(calculate_target("1d00ffff").unwrap()), format!("00000000ffff{}", "00".repeat(26)));
+    assert_eq!(hex::encode(calculate_target("207fffff").unwrap()), format!("7fffff{}", "00".repeat(29)));
+    assert!(calculate_target("1d00ff").is_err());
+    assert!(calculate_target("2100ffff").is_err());
+}
+
+#[test]
+fn hashes_compare_as_little_endian_numbers() {
+    let mut low = [0xff; 32];
+    low[31] = 0;
+    low[30] = 0x7f;
+    let mut high = [0; 32];
+    high[31] = 1;
+    assert!(hash_below(&low, &high));
+    assert!(!hash_below(&high, &low));
+    assert!(hash_difficulty(&low) > hash_difficulty(&high));
+}
+
+/// Block 1 as a pool would send it: the genesis hash as its word-swapped prevhash
+#[test]
+fn the_stratum_prevhash_is_word_swapped_back_into_the_header() {
+    let prevhash = "0a8ce26f72b3f1b646a2a6c14ff763ae65831e939c085ae10019d66800000000";
+    assert_eq!(stratum_prevhash(prevhash).unwrap().to_string(), GENESIS_HASH);
+
+    let merkle_root = "0e3e2357e806b6cdb1f70b54c3a3a17b6714ee1f0e68bebb44a74b1efd512098";
+    let header = create_block_header("00000001", prevhash, merkle_root, "1d00ffff", "4966bc61", "9962e301").unwrap();
+    assert_eq!(hash_hex(&double_sha256(&header)), "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048");
+
+    assert!(stratum_prevhash("00").is_err());
+}
+
+/// Block 170 as a pool would notify it, its coinbase's scriptSig split around extranonce1
+/// `01` and extranonce2 `02`, and the first bitcoin transfer as the one-hash merkle branch
+const BLOCK_170_NOTIFY: &str = r#"{"id":null,"method":"mining.notify","params":["170","0a84bd55d08a7978683f85da183d4f97dbd12b3e1f2c846a2a22cfee00000000","01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff0704ffff001d","ffffffff0100f2052a01000000434104d46c4968bde02899d2aa0963367c7a6ce34eec332b32e42e5f3407e052d64ac625da6f0718e7b302140434bd725706957c092db53805b821a85b23a7ac61725bac00000000",["169e1e83e930853391bc6f35f605c6754cfead57cf8387639d3b4096c54f18f4"],"00000001","1d00ffff","496ab951",true]}"#;
+
+#[test]
+fn a_notified_block_with_a_merkle_branch_hashes_to_the_real_block() {
+    let notify: serde_json::Value = serde_json::from_str(BLOCK_170_NOTIFY).unwrap();
+    let job = MiningJob::from_params(&notify["params"]).unwrap();
+    let merkle_root = compute_merkle_root_hex(&job, "01", "02").unwrap();
+    assert_eq!(merkle_root, "7dac2c5666815c17a3b36427de37bb9d2e2c5ccec3f8633eb91a4205cb4c10ff");
+
+    let header = create_block_header(&job.version, &job.prevhash, &merkle_root, &job.nbits, &job.ntime, "709e3e28").unwrap();
+    let hash = double_sha256(&header);
+    assert_eq!(hash_hex(&hash), "00000000d1145790a8694403d4063f323d499e655c83426834d4ce2f8dd4a2ee");
+    assert!(hash_meets_target(&hash, &calculate_target(&job.nbits).unwrap()));
+
+    // Workers build the same header from the job's template
+    let mut header = HeaderTemplate::new(&job, "01", 1).unwrap().with_extranonce2("02").unwrap();
+    header.nonce = 0x709e3e28;
+    assert_eq!(header.block_hash().to_string(), hash_hex(&hash));
+}
+
+#[test]
+fn every_extranonce2_gets_its_own_merkle_root_in_the_header() {
+    let mut params = notify_params("job1", "1d00ffff");
+    params[4] = serde_json::json!(["ab".repeat(32), "cd".repeat(32)]);
+    let job = MiningJob::from_params(&params).unwrap();
+    let template = HeaderTemplate::new(&job, "f000000f", 4).unwrap();
+
+    let first = template.with_extranonce2("00000001").unwrap();
+    let second = template.with_extranonce2("00000002").unwrap();
+    assert_ne!(first.merkle_root, second.merkle_root);
+    for (extranonce2, header) in [("00000001", first), ("00000002", second)] {
+        let root = compute_merkle_root_hex(&job, "f000000f", extranonce2).unwrap();
+        assert_eq!(header.merkle_root.to_string(), root);
+        // The header carries the root in internal byte order, the reverse of how it's shown
+        let serialized = bitcoin::consensus::serialize(&header);
+        let mut internal = hex::decode(&root).unwrap();
+        internal.reverse();
+        assert_eq!(&serialized[36..68], &internal[..]);
+        assert_eq!(serialized, create_block_header(&job.version, &job.prevhash, &root, &job.nbits, &job.ntime, "00000000").unwrap());
+    }
+}
+
+#[test]
+fn an_extranonce2_of_the_wrong_size_is_refused() {
+    let job = MiningJob::from_params(&notify_params("job1", "1d00ffff")).unwrap();
+    let template = HeaderTemplate::new(&job, "f000000f", 8).unwrap();
+    assert_eq!(template.extranonce2_size(), 8);
+    assert!(template.with_extranonce2("0000000000000001").is_ok());
+    // A short extranonce2 would leave the coinbase shorter than its scriptSig length says
+    assert!(template.with_extranonce2("00000001").is_err());
+    assert!(template.with_extranonce2("000000000000000001").is_err());
+}
+
+#[test]
+fn difficulties_read_like_explorers_show_them() {
+    assert_eq!(network_difficulty("1d00ffff").unwrap(), 1.0);
+    // Block 100000
+    assert!((network_difficulty("1b04864c").unwrap() - 14484.162361225399).abs() < 1e-6);
+    assert!(network_difficulty("1d00ff").is_err());
+
+    assert_eq!(format_difficulty(0.01234), "0.0123");
+    assert_eq!(format_difficulty(10_000.0), "10k");
+    assert_eq!(format_difficulty(83_675_000_000_000.0), "83.7T");
+    assert_eq!(format_difficulty(999_960.0), "1M");
+}
@@ -0,0 +1,54 @@
+use std::io::Read;
+use std::net::TcpListener;
+use std::time::{Duration, Instant};
+
+use bitcoin_solo_miner::error_report;
+use bitcoin_solo_miner::exit_code::{catch_panic, exit_on_panic};
+
+// In a test binary of its own: the exit hook would end any other test that panics
+#[test]
+fn caught_panics_dont_exit_the_process() {
+    exit_on_panic();
+    assert_eq!(catch_panic(|| 7), Ok(7));
+    assert_eq!(catch_panic(|| -> u32 { panic!("nonce {} overflowed", 3) }), Err("nonce 3 overflowed".to_string()));
+    // Nested: the inner catch mustn't re-arm the exit for the rest of the outer one
+    let outer = catch_panic(|| {
+        let inner = catch_panic(|| panic!("inner"));
+        assert_eq!(inner, Err("inner".to_string()));
+        panic!("outer");
+    });
+    assert_eq!(outer, Err::<(), _>("outer".to_string()));
+}
+
+#[test]
+fn caught_panics_are_reported_as_errors_without_waiting_for_sentry() {
+    // A Sentry that takes the report but never answers
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let dsn = format!("http://key@{}/1", listener.local_addr().unwrap());
+    let received = std::thread::spawn(move || loop {
+        let (mut stream, _) = listener.accept().unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        while !String::from_utf8_lossy(&request).contains("\"recovered\"") {
+            let read = stream.read(&mut buffer).unwrap();
+            assert!(read > 0, "connection closed before the report arrived");
+            request.extend_from_slice(&buffer[..read]);
+        }
+        // The other test's panics may be reported too
+        let request = String::from_utf8_lossy(&request).into_owned();
+        if request.contains("worker 0 tripped") {
+            break request;
+        }
+    });
+    error_report::init(&dsn, "pool", "cpu-portable").unwrap();
+    exit_on_panic();
+
+    let started = Instant::now();
+    assert_eq!(catch_panic(|| -> u32 { panic!("worker 0 tripped") }), Err("worker 0 tripped".to_string()));
+    assert!(started.elapsed() < Duration::from_secs(2), "the panic waited {:?} on the report", started.elapsed());
+
+    let request = received.join().unwrap();
+    assert!(request.contains("\"level\":\"error\""), "{}", request);
+    assert!(request.contains("panic: worker 0 tripped"), "{}", request);
+}